@@ -1,125 +1,4491 @@
 //! SVG to PDF conversion with TRUE vector fidelity via svg2pdf.
 
-use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use flate2::{read::GzDecoder, write::ZlibEncoder, Compression};
+use crate::eps;
+use crate::job::{Clock, SystemClock};
+use crate::links::{self, Link};
+use crate::markup;
+use crate::resources;
+use crate::sink::{LocalSink, OutputSink};
+use pdf_writer::types::{ActionType, AnnotationType, NumberingStyle, OutputIntentSubtype};
+use pdf_writer::writers::{OutputIntent, PageLabel};
+use pdf_writer::{Content, Date, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
 use std::fs;
-use tracing::info;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{info, warn};
+use usvg::fontdb;
+
+pub use crate::resources::ResourcePolicy;
+
+/// Points per millimetre (PDF user space is 1/72 inch).
+const PT_PER_MM: f32 = 72.0 / 25.4;
+
+/// Creation date written by deterministic conversions whose metadata sets
+/// none (the Unix epoch).
+pub const DETERMINISTIC_CREATION_DATE: DateTime<Utc> = DateTime::UNIX_EPOCH;
+
+/// Resolution of pages rasterized for EPS output, in pixels per point.
+const EPS_RASTER_SCALE: f32 = 150.0 / 72.0;
+
+/// Stroke width of trim marks, in points.
+const TRIM_MARK_WIDTH: f32 = 0.25;
+
+/// Width of the slug area added outside the bleed for trim marks, in
+/// points; also the length of each mark.
+const TRIM_MARK_SLUG: f32 = 18.0;
+
+/// First bytes of a gzip stream, identifying `.svgz` input.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Default limit on the size of a single SVG document (50 MiB).
+pub const DEFAULT_MAX_SVG_BYTES: usize = 50 * 1024 * 1024;
+
+/// Default limit on the number of pages in one conversion.
+pub const DEFAULT_MAX_PAGES: usize = 500;
+
+/// Errors produced while converting SVG content.
+#[derive(Debug, Error)]
+pub enum ConvertError {
+    /// The SVG could not be parsed; `location` points at the problem when
+    /// the SVG is malformed XML.
+    #[error(
+        "Failed to parse SVG content: {error}{}",
+        ParseLocation::in_element(.location.as_ref())
+    )]
+    Parse {
+        error: usvg::Error,
+        location: Option<ParseLocation>,
+    },
+
+    /// The SVG document is larger than the configured limit.
+    #[error("SVG too large: {size} bytes exceeds the {limit}-byte limit")]
+    TooLarge { size: usize, limit: usize },
+
+    /// A multi-page conversion has more pages than the configured limit.
+    #[error("Too many pages: {pages} exceeds the {limit}-page limit")]
+    TooManyPages { pages: usize, limit: usize },
+
+    /// The SVG declares XML entities, which could pull in external files
+    /// (XXE) or expand without bound.
+    #[error("Unsafe SVG: {0}")]
+    Security(String),
+
+    /// Raw SVG input was neither valid UTF-8 nor a readable gzip stream.
+    #[error("Failed to decode SVG data: {0}")]
+    Decode(String),
+
+    /// The SVG has a zero, negative or missing size.
+    #[error("Invalid SVG dimensions: {0}")]
+    InvalidDimensions(String),
+
+    /// Rendering the parsed SVG failed.
+    #[error("{0}")]
+    Render(String),
+
+    /// Writing the output file failed.
+    #[error("Failed to write {path}: {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// Writing the output to a caller's writer failed.
+    #[error("Failed to write output: {0}")]
+    Write(#[source] std::io::Error),
+
+    /// The conversion did not finish before its deadline.
+    #[error("Conversion timeout: exceeded {}ms", .0.as_millis())]
+    Timeout(Duration),
+
+    /// A page of a multi-page conversion failed.
+    #[error("Failed to convert page {page}: {source}")]
+    Page {
+        page: usize,
+        #[source]
+        source: Box<ConvertError>,
+    },
+}
+
+impl From<usvg::Error> for ConvertError {
+    fn from(error: usvg::Error) -> Self {
+        match error {
+            usvg::Error::InvalidSize => ConvertError::InvalidDimensions(error.to_string()),
+            error => ConvertError::Parse {
+                error,
+                location: None,
+            },
+        }
+    }
+}
+
+/// Where in an SVG document parsing failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLocation {
+    /// Line number, from 1.
+    pub line: u32,
+    /// Column in characters, from 1.
+    pub column: u32,
+    /// Name of the start tag the error is inside, such as `rect` for a
+    /// `<rect>` with a duplicated attribute.
+    pub element: Option<String>,
+}
+
+impl ParseLocation {
+    /// Displays ` (in <rect>)` for a location inside a tag, and nothing
+    /// otherwise; the line and column are already in the XML error.
+    fn in_element(location: Option<&ParseLocation>) -> InElement<'_> {
+        InElement(location.and_then(|location| location.element.as_deref()))
+    }
+}
+
+/// Display helper returned by [`ParseLocation::in_element`].
+struct InElement<'a>(Option<&'a str>);
+
+impl fmt::Display for InElement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(element) => write!(f, " (in <{}>)", element),
+            None => Ok(()),
+        }
+    }
+}
+
+impl ConvertError {
+    /// Returns `true` if writing the output failed because the disk is full
+    /// (`ENOSPC`), which retrying on the same worker won't fix.
+    pub fn is_disk_full(&self) -> bool {
+        matches!(
+            self.root(),
+            ConvertError::Io { source, .. } | ConvertError::Write(source)
+                if source.kind() == io::ErrorKind::StorageFull
+        )
+    }
+
+    /// Returns the underlying error, looking through [`ConvertError::Page`].
+    pub fn root(&self) -> &ConvertError {
+        match self {
+            ConvertError::Page { source, .. } => source.root(),
+            other => other,
+        }
+    }
+}
+
+type Result<T, E = ConvertError> = std::result::Result<T, E>;
+
+/// Converts SVG content to an in-memory PDF with a default
+/// [`SvgToPdfConverter`], for one-off conversions outside the worker.
+///
+/// ```
+/// use worker_export::{render_svg_to_pdf, ConverterOptions};
+///
+/// let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+///     <rect width="100" height="100" fill="red"/>
+/// </svg>"#;
+/// let pdf = render_svg_to_pdf(svg, ConverterOptions::default()).unwrap();
+/// assert!(pdf.starts_with(b"%PDF"));
+/// ```
+///
+/// # Errors
+///
+/// Fails on the same size, parse and dimension errors as
+/// [`SvgToPdfConverter::convert_to_bytes_with_options`].
+pub fn render_svg_to_pdf(svg_content: &str, options: ConverterOptions) -> Result<Vec<u8>> {
+    SvgToPdfConverter::new().convert_to_bytes_with_options(svg_content, &options)
+}
+
+/// Like [`render_svg_to_pdf`], but writes the PDF to `output_path`.
+///
+/// ```
+/// use worker_export::{render_svg_to_file, ConverterOptions};
+///
+/// let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"/>"#;
+/// let path = std::env::temp_dir().join("render_svg_to_file.pdf");
+/// render_svg_to_file(svg, path.to_str().unwrap(), ConverterOptions::default()).unwrap();
+/// assert!(std::fs::metadata(&path).unwrap().len() > 0);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// As [`render_svg_to_pdf`], plus I/O errors writing the file.
+pub fn render_svg_to_file(
+    svg_content: &str,
+    output_path: &str,
+    options: ConverterOptions,
+) -> Result<()> {
+    SvgToPdfConverter::new().convert_with_options(svg_content, output_path, &options)
+}
+
+/// Decodes raw SVG input, decompressing it first if it starts with the gzip
+/// magic bytes (`.svgz`).
+///
+/// Decompression stops once the output exceeds `max_svg_bytes`, so a small
+/// gzip bomb can't exhaust memory.
+///
+/// # Errors
+///
+/// Returns [`ConvertError::Decode`] for a corrupt gzip stream or content
+/// that isn't UTF-8, and [`ConvertError::TooLarge`] if the decoded SVG
+/// exceeds `max_svg_bytes`.
+pub fn decode_svg(data: &[u8], max_svg_bytes: usize) -> Result<Cow<'_, str>> {
+    if !data.starts_with(&GZIP_MAGIC) {
+        let svg = std::str::from_utf8(data)
+            .map_err(|e| ConvertError::Decode(format!("SVG is not valid UTF-8: {}", e)))?;
+        check_svg_size(svg, max_svg_bytes)?;
+        return Ok(Cow::Borrowed(svg));
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(data)
+        .take(max_svg_bytes as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| ConvertError::Decode(format!("Invalid gzip stream: {}", e)))?;
+    if decompressed.len() > max_svg_bytes {
+        return Err(ConvertError::TooLarge {
+            size: decompressed.len(),
+            limit: max_svg_bytes,
+        });
+    }
+    String::from_utf8(decompressed)
+        .map(Cow::Owned)
+        .map_err(|e| ConvertError::Decode(format!("Decompressed SVG is not valid UTF-8: {}", e)))
+}
+
+/// Fails with [`ConvertError::TooLarge`] if `svg_content` exceeds
+/// `max_svg_bytes`.
+pub fn check_svg_size(svg_content: &str, max_svg_bytes: usize) -> Result<()> {
+    if svg_content.len() > max_svg_bytes {
+        return Err(ConvertError::TooLarge {
+            size: svg_content.len(),
+            limit: max_svg_bytes,
+        });
+    }
+    Ok(())
+}
+
+/// Fails with [`ConvertError::TooManyPages`] if `pages` exceeds
+/// `max_pages`.
+pub fn check_page_count(pages: usize, max_pages: usize) -> Result<()> {
+    if pages > max_pages {
+        return Err(ConvertError::TooManyPages {
+            pages,
+            limit: max_pages,
+        });
+    }
+    Ok(())
+}
+
+/// Fails with [`ConvertError::Security`] if `svg_content` declares XML
+/// entities.
+///
+/// Entities, external ones especially, have no place in an SVG we render:
+/// they can point the parser at local files or URLs (XXE), or expand
+/// exponentially. A plain `<!DOCTYPE svg PUBLIC ...>` without an internal
+/// subset is common in exported files and is allowed; the DTD it names is
+/// never loaded. Only the DOCTYPE's internal subset is searched, so the
+/// document's own text may mention `<!ENTITY`.
+pub fn check_svg_entities(svg_content: &str) -> Result<()> {
+    let Some(doctype) = doctype(svg_content) else {
+        return Ok(());
+    };
+    if declares_entities(doctype) {
+        return Err(ConvertError::Security(
+            "SVG declares XML entities in its DOCTYPE".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Strips what some clients put before the markup: byte order marks
+/// (occasionally more than one, from re-encoding) and whitespace. An XML
+/// declaration is only valid at the very start of a document, so a
+/// template that leaves a newline before `<?xml ...?>` otherwise fails to
+/// parse.
+fn trim_svg_start(svg_content: &str) -> &str {
+    svg_content.trim_start_matches(|c: char| c == '\u{feff}' || c.is_whitespace())
+}
+
+/// Wraps a usvg parse failure, locating it in `svg_content` if the SVG is
+/// malformed XML.
+///
+/// usvg parses the document after it's been trimmed and resized, so the
+/// caller's text is parsed again to report positions in it.
+fn parse_error(error: usvg::Error, svg_content: &str) -> ConvertError {
+    if !matches!(error, usvg::Error::ParsingFailed(_)) {
+        return error.into();
+    }
+    let svg = blank_svg_start(svg_content);
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    let Err(xml_error) = roxmltree::Document::parse_with_options(&svg, options) else {
+        return error.into();
+    };
+
+    // These report 1:1 rather than a real position
+    let positioned = !matches!(
+        xml_error,
+        roxmltree::Error::NoRootNode
+            | roxmltree::Error::UnclosedRootNode
+            | roxmltree::Error::UnexpectedEndOfStream
+            | roxmltree::Error::DtdDetected
+            | roxmltree::Error::NodesLimitReached
+            | roxmltree::Error::AttributesLimitReached
+            | roxmltree::Error::NamespacesLimitReached
+    );
+    let location = positioned.then(|| {
+        let pos = xml_error.pos();
+        ParseLocation {
+            line: pos.row,
+            column: pos.col,
+            element: markup::enclosing_tag(&svg, text_offset(&svg, pos)),
+        }
+    });
+    ConvertError::Parse {
+        error: usvg::Error::ParsingFailed(xml_error),
+        location,
+    }
+}
+
+/// `svg_content` with what [`trim_svg_start`] strips, and an XML
+/// declaration after it, blanked out rather than removed, so it parses
+/// with its lines and columns where they are in the caller's text. Byte
+/// order marks are dropped, as editors don't show them.
+fn blank_svg_start(svg_content: &str) -> String {
+    let trimmed = trim_svg_start(svg_content);
+    let prefix = &svg_content[..svg_content.len() - trimmed.len()];
+    let declaration_len = trimmed
+        .strip_prefix("<?xml")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .and_then(|rest| rest.find("?>"))
+        .map_or(0, |end| "<?xml".len() + end + "?>".len());
+    let (declaration, rest) = trimmed.split_at(declaration_len);
+
+    let mut svg: String = prefix
+        .chars()
+        .chain(declaration.chars())
+        .filter(|&c| c != '\u{feff}')
+        .map(|c| if c == '\n' { c } else { ' ' })
+        .collect();
+    svg.push_str(rest);
+    svg
+}
+
+/// Byte offset of a 1-based line and character column in `text`.
+fn text_offset(text: &str, pos: roxmltree::TextPos) -> usize {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(pos.row.saturating_sub(1) as usize)
+        .map(str::len)
+        .sum();
+    text[line_start..]
+        .char_indices()
+        .nth(pos.col.saturating_sub(1) as usize)
+        .map_or(text.len(), |(i, _)| line_start + i)
+}
+
+/// Returns the document from its `<!DOCTYPE` on, if it has one, skipping
+/// the XML declaration, processing instructions and comments before it.
+fn doctype(svg_content: &str) -> Option<&str> {
+    let mut rest = svg_content.trim_start_matches('\u{feff}');
+    loop {
+        rest = rest.trim_start();
+        if let Some(pi) = rest.strip_prefix("<?") {
+            rest = &pi[pi.find("?>")? + 2..];
+        } else if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = &comment[comment.find("-->")? + 3..];
+        } else if rest.get(..9).is_some_and(|start| start.eq_ignore_ascii_case("<!DOCTYPE")) {
+            return Some(rest);
+        } else {
+            return None;
+        }
+    }
+}
+
+/// Returns `true` if the DOCTYPE at the start of `doctype` has an `<!ENTITY`
+/// declaration in its internal subset, from its `[` to the matching `]`.
+/// Quoted literals, comments and processing instructions are skipped over,
+/// and nothing after the DOCTYPE's closing `>` is searched.
+fn declares_entities(doctype: &str) -> bool {
+    // Past the name and any quoted external ID, to the internal subset
+    let mut rest = &doctype["<!DOCTYPE".len()..];
+    loop {
+        let Some(i) = rest.find(['[', '>', '"', '\'']) else {
+            return false;
+        };
+        let (delimiter, after) = rest[i..].split_at(1);
+        match delimiter {
+            "[" => {
+                rest = after;
+                break;
+            }
+            ">" => return false,
+            quote => match after.find(quote) {
+                Some(end) => rest = &after[end + 1..],
+                None => return false,
+            },
+        }
+    }
+
+    // Through the internal subset, to its closing `]`
+    loop {
+        let Some(i) = rest.find(['<', ']', '"', '\'']) else {
+            return false;
+        };
+        rest = &rest[i..];
+        if rest.starts_with(']') {
+            return false;
+        }
+        if rest.get(..8).is_some_and(|start| start.eq_ignore_ascii_case("<!ENTITY")) {
+            return true;
+        }
+        let close = if rest.starts_with("<!--") {
+            "-->"
+        } else if rest.starts_with("<?") {
+            "?>"
+        } else if rest.starts_with('<') {
+            // Any other declaration; its quoted literals are skipped in turn
+            rest = &rest[1..];
+            continue;
+        } else {
+            &rest[..1]
+        };
+        let body = &rest[1..];
+        match body.find(close) {
+            Some(end) => rest = &body[end + close.len()..],
+            None => return false,
+        }
+    }
+}
+
+/// Target page size for PDF output.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PageSize {
+    /// Use the SVG's intrinsic dimensions (one SVG user unit per point).
+    #[default]
+    Intrinsic,
+    /// ISO A3 (297 × 420 mm).
+    A3,
+    /// ISO A4 (210 × 297 mm).
+    A4,
+    /// ISO A5 (148 × 210 mm).
+    A5,
+    /// US Letter (8.5 × 11 in).
+    Letter,
+    /// US Legal (8.5 × 14 in).
+    Legal,
+    /// Custom page size in points, given in portrait orientation.
+    Custom { width: f32, height: f32 },
+}
+
+impl PageSize {
+    /// Returns the portrait `(width, height)` in points, or `None` for
+    /// [`PageSize::Intrinsic`].
+    pub fn dimensions_pt(&self) -> Option<(f32, f32)> {
+        match *self {
+            PageSize::Intrinsic => None,
+            PageSize::A3 => Some((297.0 * PT_PER_MM, 420.0 * PT_PER_MM)),
+            PageSize::A4 => Some((210.0 * PT_PER_MM, 297.0 * PT_PER_MM)),
+            PageSize::A5 => Some((148.0 * PT_PER_MM, 210.0 * PT_PER_MM)),
+            PageSize::Letter => Some((612.0, 792.0)),
+            PageSize::Legal => Some((612.0, 1008.0)),
+            PageSize::Custom { width, height } => Some((width, height)),
+        }
+    }
+}
+
+/// Error returned when a string is not a recognized page size.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid page size: {0:?} (expected intrinsic, a3, a4, a5, letter, legal or WIDTHxHEIGHT in points)")]
+pub struct ParsePageSizeError(String);
+
+impl FromStr for PageSize {
+    type Err = ParsePageSizeError;
+
+    /// Parses a paper name (`a4`, `letter`, ...) case-insensitively, or a
+    /// custom `WIDTHxHEIGHT` in points such as `600x800`.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let page_size = match s.to_ascii_lowercase().as_str() {
+            "intrinsic" => PageSize::Intrinsic,
+            "a3" => PageSize::A3,
+            "a4" => PageSize::A4,
+            "a5" => PageSize::A5,
+            "letter" => PageSize::Letter,
+            "legal" => PageSize::Legal,
+            custom => {
+                let invalid = || ParsePageSizeError(s.to_string());
+                let (width, height) = custom.split_once('x').ok_or_else(invalid)?;
+                let dimension = |value: &str| {
+                    value
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|v| v.is_finite() && *v > 0.0)
+                        .ok_or_else(invalid)
+                };
+                PageSize::Custom {
+                    width: dimension(width)?,
+                    height: dimension(height)?,
+                }
+            }
+        };
+        Ok(page_size)
+    }
+}
+
+/// Page orientation applied to fixed page sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Orientation {
+    #[default]
+    Portrait,
+    Landscape,
+}
+
+/// How SVG content is placed on a fixed-size page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitMode {
+    /// Scale the content (up or down) to fit the page, preserving aspect ratio.
+    #[default]
+    Contain,
+    /// Keep the content at its intrinsic size, scaling down only if it
+    /// would not otherwise fit on the page.
+    ActualSize,
+}
+
+/// Blank space around the content, in points.
+///
+/// On fixed-size pages the margins are inset from the page edges and the
+/// content is fitted into the remaining area; with
+/// [`PageSize::Intrinsic`] they are added around the content, growing the
+/// page.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Margins {
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub left: f32,
+}
+
+impl Margins {
+    /// The same margin on all four sides.
+    pub const fn uniform(margin: f32) -> Self {
+        Self {
+            top: margin,
+            right: margin,
+            bottom: margin,
+            left: margin,
+        }
+    }
+}
+
+/// A region of the SVG canvas, in SVG user units with the y axis pointing
+/// down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CropRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CropRect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+/// How `<text>` ends up in the PDF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextMode {
+    /// Real text with the used fonts embedded, so it can be selected,
+    /// searched and read by screen readers.
+    #[default]
+    Embed,
+    /// Glyphs converted to vector paths. The output needs no fonts, but its
+    /// text can no longer be selected or searched.
+    Outline,
+}
+
+/// How SVG content is turned into PDF drawing operations.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RenderMode {
+    /// Convert everything to PDF vector operations.
+    #[default]
+    Vector,
+    /// Convert to vectors, but rasterize pages that use features svg2pdf
+    /// can't reproduce faithfully (filters, non-normal blend modes) at `dpi`
+    /// and embed the bitmap instead.
+    VectorWithRasterFallback { dpi: f32 },
+}
+
+/// How hard PDF streams are compressed.
+///
+/// Applies to svg2pdf's content streams, page content streams and raster
+/// fallback images, all of which are Flate-encoded unless compression is
+/// [`None`](CompressionLevel::None).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionLevel {
+    /// Write streams uncompressed, e.g. for inspecting output by hand.
+    None,
+    /// Favor speed over size.
+    Fast,
+    /// Balance speed and size.
+    #[default]
+    Default,
+    /// Favor size over speed.
+    Best,
+}
+
+impl CompressionLevel {
+    /// Returns the zlib level for this setting, or `None` if streams are
+    /// left uncompressed.
+    fn flate(self) -> Option<Compression> {
+        match self {
+            CompressionLevel::None => None,
+            CompressionLevel::Fast => Some(Compression::fast()),
+            CompressionLevel::Default => Some(Compression::default()),
+            CompressionLevel::Best => Some(Compression::best()),
+        }
+    }
+}
+
+/// Color space the output is meant for.
+///
+/// Artwork colors are never converted: svg2pdf always writes ICC-tagged
+/// sRGB. [`CmykIntent`](ColorSpace::CmykIntent) only declares a CMYK
+/// output intent for print. Every page is composited in a DeviceCMYK
+/// transparency group, the page background and trim marks are painted in
+/// CMYK, and the document names a PDF/X output condition, so a print RIP
+/// separates the RGB artwork into CMYK with the configured ICC profile (or
+/// the registered SWOP condition when none is given).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Screen output; no output intent is written.
+    #[default]
+    Rgb,
+    /// sRGB artwork with a CMYK output intent, for the RIP to separate.
+    CmykIntent,
+}
+
+/// Output condition declared for CMYK output without an ICC profile: SWOP
+/// coated, which print RIPs know without an embedded profile.
+const DEFAULT_CMYK_CONDITION: &str = "CGATS TR 001";
+
+/// Registry in which [`DEFAULT_CMYK_CONDITION`] is defined.
+const ICC_REGISTRY: &str = "http://www.color.org";
+
+/// An opaque sRGB color, e.g. for [`ConverterOptions::background`].
+///
+/// Parses from `#RRGGBB`, `#RGB` or one of a handful of CSS color names
+/// (`white`, `black`, `red`, ...), case-insensitively.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+
+    /// Creates a color from 8-bit channels.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Returns the channels scaled to the 0.0–1.0 range PDF expects.
+    fn to_pdf(self) -> (f32, f32, f32) {
+        (
+            f32::from(self.r) / 255.0,
+            f32::from(self.g) / 255.0,
+            f32::from(self.b) / 255.0,
+        )
+    }
+
+    /// Returns the color as CMYK components (0.0–1.0), by the naive
+    /// device-independent formula rather than through an ICC profile.
+    fn to_cmyk(self) -> (f32, f32, f32, f32) {
+        let (r, g, b) = self.to_pdf();
+        let k = 1.0 - r.max(g).max(b);
+        if k >= 1.0 {
+            return (0.0, 0.0, 0.0, 1.0);
+        }
+        (
+            (1.0 - r - k) / (1.0 - k),
+            (1.0 - g - k) / (1.0 - k),
+            (1.0 - b - k) / (1.0 - k),
+            k,
+        )
+    }
+}
+
+/// Error returned when a string is not a recognized color.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid color: {0:?}")]
+pub struct ParseColorError(String);
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let invalid = || ParseColorError(s.to_string());
+
+        if let Some(hex) = s.strip_prefix('#') {
+            if !hex.is_ascii() {
+                return Err(invalid());
+            }
+            let channel = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| invalid());
+            return match hex.len() {
+                6 => Ok(Color::rgb(
+                    channel(&hex[0..2])?,
+                    channel(&hex[2..4])?,
+                    channel(&hex[4..6])?,
+                )),
+                // #RGB is shorthand for #RRGGBB
+                3 => Ok(Color::rgb(
+                    channel(&hex[0..1])? * 17,
+                    channel(&hex[1..2])? * 17,
+                    channel(&hex[2..3])? * 17,
+                )),
+                _ => Err(invalid()),
+            };
+        }
+
+        let color = match s.to_ascii_lowercase().as_str() {
+            "white" => Color::WHITE,
+            "black" => Color::BLACK,
+            "red" => Color::rgb(255, 0, 0),
+            "green" => Color::rgb(0, 128, 0),
+            "blue" => Color::rgb(0, 0, 255),
+            "yellow" => Color::rgb(255, 255, 0),
+            "gray" | "grey" => Color::rgb(128, 128, 128),
+            "lightgray" | "lightgrey" => Color::rgb(211, 211, 211),
+            "silver" => Color::rgb(192, 192, 192),
+            _ => return Err(invalid()),
+        };
+        Ok(color)
+    }
+}
+
+/// Document information written to the PDF Info dictionary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub creator: Option<String>,
+    /// Defaults to the conversion time when unset, or to
+    /// [`DETERMINISTIC_CREATION_DATE`] in deterministic mode.
+    pub creation_date: Option<DateTime<Utc>>,
+}
+
+/// Parse-time defaults handed to usvg, for SVGs that leave them unspecified.
+///
+/// [`Default`] matches `usvg::Options::default()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    /// Resolution used to convert physical units (`mm`, `in`, ...) to
+    /// pixels.
+    pub dpi: f32,
+    /// Font family used for text without a `font-family`.
+    pub font_family: String,
+    /// Font size used for text without a `font-size`.
+    pub font_size: f32,
+    /// Languages matched against `systemLanguage` attributes, e.g. `en-US`.
+    pub languages: Vec<String>,
+    /// Default `shape-rendering` hint.
+    pub shape_rendering: usvg::ShapeRendering,
+    /// Default `text-rendering` hint.
+    pub text_rendering: usvg::TextRendering,
+    /// Default `image-rendering` hint.
+    pub image_rendering: usvg::ImageRendering,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        let defaults = usvg::Options::default();
+        Self {
+            dpi: defaults.dpi,
+            font_family: defaults.font_family,
+            font_size: defaults.font_size,
+            languages: defaults.languages,
+            shape_rendering: defaults.shape_rendering,
+            text_rendering: defaults.text_rendering,
+            image_rendering: defaults.image_rendering,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Sets the resolution used for physical units.
+    pub fn with_dpi(mut self, dpi: f32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    /// Sets the fallback font family.
+    pub fn with_font_family(mut self, font_family: impl Into<String>) -> Self {
+        self.font_family = font_family.into();
+        self
+    }
+
+    /// Sets the fallback font size.
+    pub fn with_font_size(mut self, font_size: f32) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    /// Sets the languages matched against `systemLanguage`.
+    pub fn with_languages(mut self, languages: Vec<String>) -> Self {
+        self.languages = languages;
+        self
+    }
+}
+
+/// Summary of a parsed SVG, returned by [`SvgToPdfConverter::validate`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvgInfo {
+    /// Width in SVG user units.
+    pub width: f32,
+    /// Height in SVG user units.
+    pub height: f32,
+    /// Number of nodes (groups, paths, images and text) in the tree.
+    pub node_count: usize,
+}
+
+/// Something in an SVG that the PDF does not reproduce. Conversion still
+/// succeeds, but the output differs from what the SVG describes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConversionWarning {
+    /// Elements usvg does not support (scripts, animation, embedded HTML)
+    /// were skipped.
+    UnsupportedElement {
+        page: usize,
+        element: String,
+        count: usize,
+    },
+    /// Text elements were dropped, usually because no font matched.
+    TextDropped { page: usize, count: usize },
+    /// Images that could not be loaded or decoded were dropped.
+    ImageDropped { page: usize, count: usize },
+    /// The page was rasterized by the raster fallback.
+    Rasterized { page: usize, dpi: f32 },
+}
+
+impl fmt::Display for ConversionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionWarning::UnsupportedElement { page, element, count } => write!(
+                f,
+                "page {}: skipped {} unsupported <{}> element(s)",
+                page, count, element
+            ),
+            ConversionWarning::TextDropped { page, count } => write!(
+                f,
+                "page {}: dropped {} text element(s), likely because no font matched",
+                page, count
+            ),
+            ConversionWarning::ImageDropped { page, count } => write!(
+                f,
+                "page {}: dropped {} image(s) that could not be loaded",
+                page, count
+            ),
+            ConversionWarning::Rasterized { page, dpi } => write!(
+                f,
+                "page {}: rasterized at {} DPI because it uses filters or blend modes",
+                page, dpi
+            ),
+        }
+    }
+}
+
+/// Outcome of a successful conversion, from
+/// [`SvgToPdfConverter::convert_with_report`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConversionReport {
+    /// Size of the written PDF.
+    pub output_bytes: usize,
+    /// Hex SHA-256 of the PDF bytes, so the file can be verified after it
+    /// is downloaded.
+    pub output_sha256: String,
+    /// Content that was skipped or degraded, in page order.
+    pub warnings: Vec<ConversionWarning>,
+}
+
+/// Options controlling PDF output.
+#[derive(Debug, Clone)]
+pub struct ConverterOptions {
+    /// Target page size.
+    pub page_size: PageSize,
+    /// Page orientation (ignored for [`PageSize::Intrinsic`]).
+    pub orientation: Orientation,
+    /// Content placement on fixed-size pages.
+    pub fit: FitMode,
+    /// Space between the trim edge and the content.
+    pub margins: Margins,
+    /// Region of the SVG to render. The SVG's viewBox is replaced by this
+    /// rectangle, so the page is sized (or the content fitted) as if the SVG
+    /// were only this large. `None` renders the whole SVG.
+    pub crop: Option<CropRect>,
+    /// Printer bleed in points, added outside the trim edge on every side.
+    /// When non-zero, the page declares trim and bleed boxes and grows by a
+    /// further slug margin outside the bleed, where trim marks are drawn so
+    /// they never print over the bleed.
+    pub bleed: f32,
+    /// Document information (title, author, ...). Omitted when `None`.
+    pub metadata: Option<PdfMetadata>,
+    /// Vector-only or vector with raster fallback.
+    pub render_mode: RenderMode,
+    /// Stream compression. Also decides whether svg2pdf compresses the
+    /// streams it writes.
+    pub compression: CompressionLevel,
+    /// Whether text stays selectable text or is converted to outlines.
+    pub text_mode: TextMode,
+    /// Embeds only the glyphs a document uses from each font. When off,
+    /// fonts are embedded whole, e.g. so the PDF can be edited with
+    /// characters the artwork doesn't use; fonts from a collection (`.ttc`)
+    /// are always subset.
+    pub subset_fonts: bool,
+    /// Pixels per SVG user unit used by svg2pdf for effects it has to
+    /// rasterize, such as filters. Unrelated to the whole-page fallback of
+    /// [`RenderMode::VectorWithRasterFallback`].
+    pub raster_scale: f32,
+    /// Color painted behind the content on every page. `None` leaves the
+    /// page transparent.
+    pub background: Option<Color>,
+    /// RGB for screens, or a CMYK output intent for print.
+    pub color_space: ColorSpace,
+    /// CMYK output profile embedded as the output intent in
+    /// [`ColorSpace::CmykIntent`] mode. Ignored for RGB output.
+    pub icc_profile: Option<PathBuf>,
+    /// Turns SVG `<a>` elements into clickable PDF link annotations.
+    pub preserve_links: bool,
+    /// Produces byte-identical output for identical input, for reproducible
+    /// builds and golden-file tests: a creation date left unset in
+    /// `metadata` is fixed at [`DETERMINISTIC_CREATION_DATE`] instead of the
+    /// conversion time.
+    pub deterministic: bool,
+    /// Labels PDF viewers show for the pages instead of their numbers, e.g.
+    /// artboard names, in page order. Pages past the end of the list, or
+    /// given an empty label, are numbered by position. Empty writes no page
+    /// labels.
+    pub page_labels: Vec<String>,
+}
+
+impl Default for ConverterOptions {
+    fn default() -> Self {
+        Self {
+            page_size: PageSize::default(),
+            orientation: Orientation::default(),
+            fit: FitMode::default(),
+            margins: Margins::default(),
+            crop: None,
+            bleed: 0.0,
+            metadata: None,
+            render_mode: RenderMode::default(),
+            compression: CompressionLevel::default(),
+            text_mode: TextMode::default(),
+            subset_fonts: true,
+            raster_scale: svg2pdf::ConversionOptions::default().raster_scale,
+            background: None,
+            color_space: ColorSpace::default(),
+            icc_profile: None,
+            preserve_links: true,
+            deterministic: false,
+            page_labels: Vec::new(),
+        }
+    }
+}
+
+impl ConverterOptions {
+    /// Sets the space between the trim edge and the content.
+    pub fn with_margins(mut self, margins: Margins) -> Self {
+        self.margins = margins;
+        self
+    }
+
+    /// Renders only `crop` of the SVG.
+    pub fn with_crop(mut self, crop: CropRect) -> Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    /// Adds `bleed` points of printer bleed around the page, with trim marks.
+    pub fn with_bleed(mut self, bleed: f32) -> Self {
+        self.bleed = bleed;
+        self
+    }
+
+    /// Sets the document information written into the PDF.
+    pub fn with_metadata(mut self, metadata: PdfMetadata) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+
+    /// Sets how SVG content is rendered.
+    pub fn with_render_mode(mut self, render_mode: RenderMode) -> Self {
+        self.render_mode = render_mode;
+        self
+    }
+
+    /// Sets how PDF streams are compressed.
+    pub fn with_compression(mut self, compression: CompressionLevel) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets whether text stays text or is converted to outlines.
+    pub fn with_text_mode(mut self, text_mode: TextMode) -> Self {
+        self.text_mode = text_mode;
+        self
+    }
+
+    /// Sets whether embedded fonts are subset to the glyphs used.
+    pub fn with_subset_fonts(mut self, subset_fonts: bool) -> Self {
+        self.subset_fonts = subset_fonts;
+        self
+    }
+
+    /// Sets the scale at which svg2pdf rasterizes effects such as filters.
+    pub fn with_raster_scale(mut self, raster_scale: f32) -> Self {
+        self.raster_scale = raster_scale;
+        self
+    }
+
+    /// Paints `color` behind the content, flattening any transparency.
+    pub fn with_background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Sets the color space the output is meant for.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Embeds the CMYK ICC profile at `path` as the CMYK output intent.
+    pub fn with_icc_profile(mut self, path: impl Into<PathBuf>) -> Self {
+        self.icc_profile = Some(path.into());
+        self
+    }
+
+    /// Sets whether SVG hyperlinks become PDF link annotations.
+    pub fn with_preserve_links(mut self, preserve_links: bool) -> Self {
+        self.preserve_links = preserve_links;
+        self
+    }
+
+    /// Sets whether output is reproducible byte for byte.
+    pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Labels the pages with `labels`, in page order.
+    pub fn with_page_labels(mut self, labels: Vec<String>) -> Self {
+        self.page_labels = labels;
+        self
+    }
+
+    /// Maps these options onto svg2pdf's.
+    fn svg2pdf_options(&self) -> svg2pdf::ConversionOptions {
+        svg2pdf::ConversionOptions {
+            compress: self.compression.flate().is_some(),
+            raster_scale: self.raster_scale,
+            embed_text: self.text_mode == TextMode::Embed,
+            subset_fonts: self.subset_fonts,
+        }
+    }
+
+    /// Computes the trim size and content placement for an SVG of the given
+    /// size.
+    ///
+    /// Returns `(page_width, page_height, [w, 0, 0, h, x, y])`, where the
+    /// transform maps the unit-square XObject produced by svg2pdf onto the
+    /// page. The page size sets the trim size; margins are taken out of it
+    /// (or added to the SVG size for [`PageSize::Intrinsic`]) and content is
+    /// centered in what remains. Bleed is not included: it is added around
+    /// the result when rendering.
+    fn layout(&self, svg_width: f32, svg_height: f32) -> (f32, f32, [f32; 6]) {
+        let margins = self.margins;
+        let Some((width, height)) = self.page_size.dimensions_pt() else {
+            return (
+                svg_width + margins.left + margins.right,
+                svg_height + margins.top + margins.bottom,
+                [svg_width, 0.0, 0.0, svg_height, margins.left, margins.bottom],
+            );
+        };
+
+        let (page_width, page_height) = match self.orientation {
+            Orientation::Portrait => (width.min(height), width.max(height)),
+            Orientation::Landscape => (width.max(height), width.min(height)),
+        };
+
+        let available_width = (page_width - margins.left - margins.right).max(0.0);
+        let available_height = (page_height - margins.top - margins.bottom).max(0.0);
+        let fit_scale = (available_width / svg_width).min(available_height / svg_height);
+        let scale = match self.fit {
+            FitMode::Contain => fit_scale,
+            FitMode::ActualSize => fit_scale.min(1.0),
+        };
+
+        let content_width = svg_width * scale;
+        let content_height = svg_height * scale;
+        let x = margins.left + (available_width - content_width) / 2.0;
+        let y = margins.bottom + (available_height - content_height) / 2.0;
+
+        (
+            page_width,
+            page_height,
+            [content_width, 0.0, 0.0, content_height, x, y],
+        )
+    }
+}
 
 /// SVG to PDF converter using svg2pdf for true vector fidelity.
 ///
 /// This converter uses the svg2pdf crate which converts SVG to PDF
 /// maintaining complete vector graphics (no rasterization).
-pub struct SvgToPdfConverter;
+///
+/// Text is rendered using the fonts in the converter's font database, which
+/// starts out empty. Use [`with_font_dirs`](Self::with_font_dirs) or
+/// [`with_font_data`](Self::with_font_data) to make fonts available.
+///
+/// SVGs larger than [`DEFAULT_MAX_SVG_BYTES`] (or the limit set with
+/// [`with_max_svg_bytes`](Self::with_max_svg_bytes)) are rejected before
+/// parsing.
+///
+/// Output files are written through an [`OutputSink`], by default
+/// [`LocalSink`] (see [`with_output_sink`](Self::with_output_sink)).
+///
+/// Cloning is cheap: the font database, the default
+/// [`ConverterOptions`] (see [`reload_config`](Self::reload_config)) and the
+/// image cache (see [`with_resource_cache`](Self::with_resource_cache)) are
+/// shared between clones.
+#[derive(Clone)]
+pub struct SvgToPdfConverter {
+    /// Fonts available to `<text>` elements, shared across conversions.
+    fontdb: Arc<fontdb::Database>,
+    /// Images already resolved by this converter, when caching is on.
+    image_cache: Option<Arc<ImageCache>>,
+    /// Largest SVG document accepted, in bytes.
+    max_svg_bytes: usize,
+    /// Most pages rendered in one conversion.
+    max_pages: usize,
+    /// Pixel budget for embedded raster images; larger ones are downsampled.
+    max_image_pixels: Option<u64>,
+    /// Which `<image>` hrefs are loaded.
+    resource_policy: ResourcePolicy,
+    /// Directory relative `<image>` paths are read from, if any.
+    resources_dir: Option<PathBuf>,
+    /// Defaults for DPI, fonts, languages and rendering hints.
+    parse_options: ParseOptions,
+    /// Renders without writing output files.
+    dry_run: bool,
+    /// Set once the caller of a [`ConversionTask`] running this converter
+    /// has stopped waiting for it, so nothing is written after all.
+    abandoned: Option<Arc<AtomicBool>>,
+    /// Page options for conversions that aren't given their own,
+    /// replaceable while conversions are running.
+    options: Arc<RwLock<ConverterOptions>>,
+    /// Where output files are written.
+    output_sink: Arc<dyn OutputSink>,
+    /// Source of the creation date of PDFs whose metadata sets none.
+    clock: Arc<dyn Clock>,
+}
+
+impl SvgToPdfConverter {
+    /// Creates a new converter with default options.
+    pub fn new() -> Self {
+        Self {
+            fontdb: Arc::new(fontdb::Database::new()),
+            image_cache: None,
+            max_svg_bytes: DEFAULT_MAX_SVG_BYTES,
+            max_pages: DEFAULT_MAX_PAGES,
+            max_image_pixels: None,
+            resource_policy: ResourcePolicy::default(),
+            resources_dir: None,
+            parse_options: ParseOptions::default(),
+            dry_run: false,
+            abandoned: None,
+            options: Arc::default(),
+            output_sink: Arc::new(LocalSink),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the largest SVG document accepted, in bytes.
+    pub fn with_max_svg_bytes(mut self, max_svg_bytes: usize) -> Self {
+        self.max_svg_bytes = max_svg_bytes;
+        self
+    }
+
+    /// Sets the most pages a multi-page conversion may have. Longer ones
+    /// are rejected before any page is parsed.
+    pub fn with_max_pages(mut self, max_pages: usize) -> Self {
+        self.max_pages = max_pages;
+        self
+    }
+
+    /// Caps embedded raster images (`<image>` PNG, JPEG and GIF data) at
+    /// `max_pixels` pixels.
+    ///
+    /// Larger images are downsampled, preserving aspect ratio, before they
+    /// are placed into the PDF, so a huge photo drawn at thumbnail size no
+    /// longer bloats the output. Images are drawn at the size the SVG gives
+    /// them, so only `<image>` elements without a `width`/`height` change
+    /// size on the page.
+    pub fn with_max_image_pixels(mut self, max_pixels: u64) -> Self {
+        self.max_image_pixels = Some(max_pixels);
+        self
+    }
+
+    /// Sets which `<image>` hrefs are loaded. The default,
+    /// [`ResourcePolicy::DataUriOnly`], loads only images embedded in the
+    /// SVG, plus local files under the [resources
+    /// directory](Self::with_resources_dir) when one is set.
+    pub fn with_resource_policy(mut self, policy: ResourcePolicy) -> Self {
+        self.resource_policy = policy;
+        self
+    }
+
+    /// Reads `<image>`s with relative paths from `dir`, typically the
+    /// directory of the SVG file being converted. Paths that are absolute
+    /// or lead outside `dir` are never read. Without a resources directory
+    /// (the default) no local files are read at all.
+    pub fn with_resources_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.resources_dir = Some(dir.into());
+        self
+    }
+
+    /// Makes the converter remember the `<image>`s it resolves, so every
+    /// conversion through it (or its clones) loads, decodes and downsamples
+    /// each distinct image only once.
+    ///
+    /// Meant as a context for the pages of a single job that reuse the same
+    /// images: the cache is never evicted, so drop the converter when the
+    /// job is done. Multi-page conversions already share a cache between
+    /// their pages for the duration of the call. Fonts need no cache; they
+    /// are loaded once per converter.
+    pub fn with_resource_cache(mut self) -> Self {
+        self.image_cache = Some(Arc::new(ImageCache::default()));
+        self
+    }
+
+    /// Runs conversions in full but skips writing the output file, for
+    /// debugging the pipeline in production. The returned size (and page
+    /// count) is that of the output that would have been written.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Writes output files through `sink` instead of to the local
+    /// filesystem. Output paths are passed to the sink unchanged.
+    pub fn with_output_sink(mut self, sink: Arc<dyn OutputSink>) -> Self {
+        self.output_sink = sink;
+        self
+    }
+
+    /// Reads the creation date of PDFs whose metadata sets none from
+    /// `clock` instead of the [`SystemClock`].
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Sets the defaults usvg applies while parsing, such as the DPI and the
+    /// font used for text without an explicit `font-family`.
+    pub fn with_parse_options(mut self, parse_options: ParseOptions) -> Self {
+        self.parse_options = parse_options;
+        self
+    }
+
+    /// Sets the page options used by the conversions that don't take their
+    /// own, such as [`convert`](Self::convert).
+    pub fn with_options(mut self, options: ConverterOptions) -> Self {
+        self.options = Arc::new(RwLock::new(options));
+        self
+    }
+
+    /// Returns a copy of the current default page options.
+    pub fn options(&self) -> ConverterOptions {
+        self.options.read().unwrap_or_else(PoisonError::into_inner).clone()
+    }
+
+    /// Replaces the default page options of this converter and its clones.
+    ///
+    /// Safe to call while conversions are running: each conversion works on
+    /// the options it started with, and those that start afterwards get
+    /// `options`.
+    pub fn reload_config(&self, options: ConverterOptions) {
+        *self.options.write().unwrap_or_else(PoisonError::into_inner) = options;
+        info!("Converter options reloaded");
+    }
+
+    /// Loads every font found (recursively) in the given directories.
+    ///
+    /// Directories that do not exist are skipped with a warning.
+    pub fn with_font_dirs(mut self, paths: Vec<PathBuf>) -> Self {
+        let db = Arc::make_mut(&mut self.fontdb);
+        for path in paths {
+            if !path.is_dir() {
+                warn!("Font directory not found: {}", path.display());
+                continue;
+            }
+            db.load_fonts_dir(&path);
+        }
+        info!("Font database loaded: {} faces", db.len());
+        self
+    }
+
+    /// Loads a font from in-memory TrueType/OpenType data.
+    pub fn with_font_data(mut self, data: Vec<u8>) -> Self {
+        Arc::make_mut(&mut self.fontdb).load_font_data(data);
+        self
+    }
+
+    /// Builds usvg parse options backed by this converter's font database.
+    fn usvg_options(&self) -> usvg::Options<'static> {
+        let image_href_resolver = resources::image_resolver(self.resource_policy.clone());
+        let image_href_resolver = match self.max_image_pixels {
+            Some(max_pixels) => capped_image_resolver(image_href_resolver, max_pixels),
+            None => image_href_resolver,
+        };
+        let image_href_resolver = match &self.image_cache {
+            Some(cache) => cached_image_resolver(image_href_resolver, cache.clone()),
+            None => image_href_resolver,
+        };
+        let parse = self.parse_options.clone();
+        usvg::Options {
+            dpi: parse.dpi,
+            font_family: parse.font_family,
+            font_size: parse.font_size,
+            languages: parse.languages,
+            shape_rendering: parse.shape_rendering,
+            text_rendering: parse.text_rendering,
+            image_rendering: parse.image_rendering,
+            fontdb: self.fontdb.clone(),
+            image_href_resolver,
+            resources_dir: self.resources_dir.clone(),
+            ..usvg::Options::default()
+        }
+    }
+
+    /// Converts SVG content to PDF and writes to the specified output path.
+    ///
+    /// The page follows the converter's [`options`](Self::options), which by
+    /// default match the SVG's intrinsic size. Use
+    /// [`convert_with_options`](Self::convert_with_options) to pass options
+    /// for a single conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `svg_content` - UTF-8 SVG XML string
+    /// * `output_path` - Filesystem path for PDF output
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` on success, or an error if parsing or rendering fails.
+    ///
+    /// # Errors
+    ///
+    /// - SVG parsing errors (malformed XML, unsupported features)
+    /// - File I/O errors (permissions, disk full)
+    /// - Rendering errors (out of memory, invalid dimensions)
+    pub fn convert(&self, svg_content: &str, output_path: &str) -> Result<()> {
+        self.convert_with_options(svg_content, output_path, &self.options())
+    }
+
+    /// Converts SVG content to PDF using the given page options and writes
+    /// to the specified output path.
+    ///
+    /// # Arguments
+    ///
+    /// * `svg_content` - UTF-8 SVG XML string
+    /// * `output_path` - Filesystem path for PDF output
+    /// * `options` - Page size, orientation and fit mode
+    ///
+    /// # Errors
+    ///
+    /// Same as [`convert`](Self::convert).
+    pub fn convert_with_options(
+        &self,
+        svg_content: &str,
+        output_path: &str,
+        options: &ConverterOptions,
+    ) -> Result<()> {
+        info!("Converting SVG to PDF (VECTOR): output={}", output_path);
+
+        let pdf_data = self.convert_to_bytes_with_options(svg_content, options)?;
+
+        // Write PDF to file
+        if self.dry_run {
+            info!("Dry run, not writing {} bytes to {}", pdf_data.len(), output_path);
+        } else {
+            self.write_output(output_path, &pdf_data)?;
+        }
+
+        info!("PDF export complete (VECTOR): {} bytes", pdf_data.len());
+        Ok(())
+    }
+
+    /// Like [`convert`](Self::convert), also reporting content that was
+    /// skipped or degraded along the way (see [`ConversionWarning`]).
+    ///
+    /// # Errors
+    ///
+    /// Same as [`convert`](Self::convert). Warnings never fail the
+    /// conversion.
+    pub fn convert_with_report(
+        &self,
+        svg_content: &str,
+        output_path: &str,
+    ) -> Result<ConversionReport> {
+        self.convert_multi_with_report(
+            &[svg_content],
+            output_path,
+            &self.options(),
+            |_, _| {},
+        )
+    }
+
+    /// Like [`convert`](Self::convert), for raw input that may be
+    /// gzip-compressed (`.svgz`). See [`decode_svg`].
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`ConvertError::Decode`] if the input can't be decoded, in
+    /// addition to the errors of [`convert`](Self::convert).
+    pub fn convert_raw(&self, data: &[u8], output_path: &str) -> Result<()> {
+        let svg_content = decode_svg(data, self.max_svg_bytes)?;
+        self.convert(&svg_content, output_path)
+    }
+
+    /// Converts SVG content to PDF on a blocking thread, failing if the
+    /// conversion does not finish within `timeout`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a "conversion timeout" error once the deadline passes, in
+    /// addition to the errors of [`convert`](Self::convert).
+    pub async fn convert_with_timeout(
+        &self,
+        svg_content: &str,
+        output_path: &str,
+        timeout: Duration,
+    ) -> Result<()> {
+        let svg_content = svg_content.to_string();
+        let output_path = output_path.to_string();
+        self.run_with_timeout(timeout, move |converter| {
+            converter.convert(&svg_content, &output_path)
+        })
+        .await
+    }
+
+    /// Runs a conversion closure on tokio's blocking pool with a deadline.
+    ///
+    /// Conversion is synchronous CPU work, so it cannot be cancelled once
+    /// started: on timeout, or if this future is dropped, the blocking
+    /// thread is left to finish in the background without writing any
+    /// output, and its result is discarded, but the caller is released
+    /// immediately. Use [`spawn_conversion`](Self::spawn_conversion) to
+    /// wait for the thread as well.
+    pub async fn run_with_timeout<T, F>(&self, timeout: Duration, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SvgToPdfConverter) -> Result<T> + Send + 'static,
+    {
+        self.spawn_conversion(f).wait(timeout).await
+    }
+
+    /// Starts a conversion closure on tokio's blocking pool, returning a
+    /// handle to wait for it with.
+    pub fn spawn_conversion<T, F>(&self, f: F) -> ConversionTask<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SvgToPdfConverter) -> Result<T> + Send + 'static,
+    {
+        let abandoned = Arc::new(AtomicBool::new(false));
+        let mut converter = self.clone();
+        converter.abandoned = Some(abandoned.clone());
+        // Keep the caller's span (e.g. the job's) around the conversion's logs
+        let span = tracing::Span::current();
+        let handle = tokio::task::spawn_blocking(move || span.in_scope(|| f(&converter)));
+        ConversionTask {
+            handle: Some(handle),
+            abandoned,
+        }
+    }
+
+    /// Converts SVG content to an in-memory PDF without touching the
+    /// filesystem.
+    ///
+    /// Useful when the PDF is streamed elsewhere (e.g. object storage) rather
+    /// than written to local disk.
+    ///
+    /// # Errors
+    ///
+    /// - SVG parsing errors (malformed XML, unsupported features)
+    /// - Rendering errors (invalid dimensions)
+    pub fn convert_to_bytes(&self, svg_content: &str) -> Result<Vec<u8>> {
+        self.convert_to_bytes_with_options(svg_content, &self.options())
+    }
+
+    /// Converts SVG content to a PDF written to `writer`, such as an HTTP
+    /// response body or a compressing encoder, which is flushed at the end.
+    ///
+    /// The PDF is currently rendered in full before any of it is written,
+    /// so nothing reaches `writer` if the conversion fails; callers should
+    /// not rely on that, as output may be streamed as it is produced.
+    ///
+    /// # Errors
+    ///
+    /// As [`convert_to_bytes`](Self::convert_to_bytes), plus
+    /// [`ConvertError::Write`] if writing to `writer` fails.
+    pub fn convert_to_writer<W: Write>(&self, svg_content: &str, mut writer: W) -> Result<()> {
+        let pdf_data = self.convert_to_bytes(svg_content)?;
+        writer
+            .write_all(&pdf_data)
+            .and_then(|()| writer.flush())
+            .map_err(ConvertError::Write)
+    }
+
+    /// Converts SVG content to an in-memory PDF using the given page options.
+    pub fn convert_to_bytes_with_options(
+        &self,
+        svg_content: &str,
+        options: &ConverterOptions,
+    ) -> Result<Vec<u8>> {
+        self.convert_multi_to_bytes_with_options(&[svg_content], options)
+    }
+
+    /// Converts several SVG documents into a single PDF with one page per
+    /// SVG, in order, and writes it to the specified output path.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `pages` is empty or any page fails to parse; no file is
+    /// written in that case.
+    pub fn convert_multi(&self, pages: &[&str], output_path: &str) -> Result<()> {
+        self.convert_multi_with_options(pages, output_path, &self.options())
+    }
+
+    /// Multi-page variant of [`convert_with_options`](Self::convert_with_options).
+    pub fn convert_multi_with_options(
+        &self,
+        pages: &[&str],
+        output_path: &str,
+        options: &ConverterOptions,
+    ) -> Result<()> {
+        self.convert_multi_with_progress(pages, output_path, options, |_, _| {})
+    }
+
+    /// Like [`convert_multi_with_options`](Self::convert_multi_with_options),
+    /// calling `on_page(done, total)` after each page is rendered.
+    pub fn convert_multi_with_progress(
+        &self,
+        pages: &[&str],
+        output_path: &str,
+        options: &ConverterOptions,
+        on_page: impl FnMut(usize, usize),
+    ) -> Result<()> {
+        self.convert_multi_with_report(pages, output_path, options, on_page)
+            .map(|_| ())
+    }
+
+    /// Like [`convert_multi_with_progress`](Self::convert_multi_with_progress),
+    /// also reporting content that was skipped or degraded.
+    pub fn convert_multi_with_report(
+        &self,
+        pages: &[&str],
+        output_path: &str,
+        options: &ConverterOptions,
+        on_page: impl FnMut(usize, usize),
+    ) -> Result<ConversionReport> {
+        info!(
+            "Converting {} SVG page(s) to PDF (VECTOR): output={}",
+            pages.len(),
+            output_path
+        );
+
+        let (pdf_data, warnings) = self.render_with_warnings(pages, options, on_page)?;
+
+        if self.dry_run {
+            info!("Dry run, not writing {} bytes to {}", pdf_data.len(), output_path);
+        } else {
+            self.write_output(output_path, &pdf_data)?;
+        }
+
+        info!("PDF export complete (VECTOR): {} bytes", pdf_data.len());
+        for warning in &warnings {
+            warn!("{}", warning);
+        }
+        Ok(ConversionReport {
+            output_bytes: pdf_data.len(),
+            output_sha256: Sha256::digest(&pdf_data)
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            warnings,
+        })
+    }
+
+    /// Multi-page variant of
+    /// [`convert_to_bytes_with_options`](Self::convert_to_bytes_with_options).
+    pub fn convert_multi_to_bytes_with_options(
+        &self,
+        pages: &[&str],
+        options: &ConverterOptions,
+    ) -> Result<Vec<u8>> {
+        self.convert_multi_to_bytes_with_progress(pages, options, |_, _| {})
+    }
+
+    /// Like
+    /// [`convert_multi_to_bytes_with_options`](Self::convert_multi_to_bytes_with_options),
+    /// calling `on_page(done, total)` after each page is rendered.
+    pub fn convert_multi_to_bytes_with_progress(
+        &self,
+        pages: &[&str],
+        options: &ConverterOptions,
+        on_page: impl FnMut(usize, usize),
+    ) -> Result<Vec<u8>> {
+        self.render_with_warnings(pages, options, on_page)
+            .map(|(pdf_data, _)| pdf_data)
+    }
+
+    /// Renders `pages` into an in-memory PDF, collecting a
+    /// [`ConversionWarning`] for each page's skipped or degraded content.
+    fn render_with_warnings(
+        &self,
+        pages: &[&str],
+        options: &ConverterOptions,
+        mut on_page: impl FnMut(usize, usize),
+    ) -> Result<(Vec<u8>, Vec<ConversionWarning>)> {
+        if pages.is_empty() {
+            return Err(ConvertError::Render("No pages to convert".to_string()));
+        }
+        check_page_count(pages.len(), self.max_pages)?;
+
+        // Pages of one document tend to reuse the same images
+        let cached;
+        let converter = if self.image_cache.is_none() && pages.len() > 1 {
+            cached = self.clone().with_resource_cache();
+            &cached
+        } else {
+            self
+        };
+
+        let (parsed, warnings): (Vec<_>, Vec<_>) = pages
+            .iter()
+            .enumerate()
+            .map(|(index, svg)| {
+                let (tree, links, source) = converter
+                    .parse_with_links(svg, options.preserve_links, options.crop)
+                    .map_err(|e| ConvertError::Page {
+                        page: index + 1,
+                        source: Box::new(e),
+                    })?;
+                let warnings = scan_warnings(index + 1, source.as_ref(), &tree, options);
+                Ok(((tree, links), warnings))
+            })
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        let warnings = warnings.into_iter().flatten().collect();
+
+        // Convert to PDF using svg2pdf (true vector conversion)
+        let pdf_data = render_pdf(&parsed, options, self.clock.now(), &mut on_page)?;
+        Ok((pdf_data, warnings))
+    }
+
+    /// Rasterizes SVG content to a PNG file, returning its size in bytes.
+    ///
+    /// `scale` is the number of pixels per SVG user unit, so `2.0` renders a
+    /// 100×50 SVG at 200×100 pixels.
+    ///
+    /// # Errors
+    ///
+    /// - SVG parsing errors (malformed XML, unsupported features)
+    /// - Rendering errors (invalid dimensions or scale)
+    /// - File I/O errors (permission denied, disk full)
+    pub fn convert_to_png(&self, svg_content: &str, output_path: &str, scale: f32) -> Result<usize> {
+        info!("Converting SVG to PNG: output={}, scale={}", output_path, scale);
+
+        let png_data = self.convert_to_png_bytes(svg_content, scale)?;
+
+        if self.dry_run {
+            info!("Dry run, not writing {} bytes to {}", png_data.len(), output_path);
+        } else {
+            self.write_output(output_path, &png_data)?;
+        }
+
+        info!("PNG export complete: {} bytes", png_data.len());
+        Ok(png_data.len())
+    }
+
+    /// Rasterizes SVG content to an in-memory PNG.
+    pub fn convert_to_png_bytes(&self, svg_content: &str, scale: f32) -> Result<Vec<u8>> {
+        let tree = self.parse(svg_content)?;
+        rasterize(&tree, scale)?
+            .encode_png()
+            .map_err(|e| ConvertError::Render(format!("Failed to encode PNG: {}", e)))
+    }
+
+    /// Converts SVG content to an Encapsulated PostScript file, returning
+    /// its size in bytes.
+    ///
+    /// The EPS is sized like the SVG, one point per user unit. See
+    /// [`convert_to_eps_bytes`](Self::convert_to_eps_bytes) for which SVGs
+    /// stay vector.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`convert_to_png`](Self::convert_to_png).
+    pub fn convert_to_eps(&self, svg_content: &str, output_path: &str) -> Result<usize> {
+        info!("Converting SVG to EPS: output={}", output_path);
+
+        let eps_data = self.convert_to_eps_bytes(svg_content)?;
+
+        if self.dry_run {
+            info!("Dry run, not writing {} bytes to {}", eps_data.len(), output_path);
+        } else {
+            self.write_output(output_path, &eps_data)?;
+        }
+
+        info!("EPS export complete: {} bytes", eps_data.len());
+        Ok(eps_data.len())
+    }
+
+    /// Converts SVG content to an in-memory EPS.
+    ///
+    /// Solid-colored, opaque shapes and text are written as PostScript
+    /// paths. PostScript has no gradients, transparency or clipping to
+    /// match SVG's, so an SVG using any of those, filters, masks, blend
+    /// modes or raster images is rasterized at 150 DPI and embedded as a
+    /// single image instead (composited over white).
+    pub fn convert_to_eps_bytes(&self, svg_content: &str) -> Result<Vec<u8>> {
+        let tree = self.parse(svg_content)?;
+        if let Some(eps) = eps::vector(&tree) {
+            return Ok(eps);
+        }
+
+        info!("SVG uses features without PostScript support, rasterizing EPS");
+        let pixmap = rasterize(&tree, EPS_RASTER_SCALE)?;
+        let data = deflate(&eps::opaque_rgb(&pixmap), Compression::default())?;
+        let size = tree.size();
+        Ok(eps::raster(pixmap.width(), pixmap.height(), size.width(), size.height(), &data))
+    }
+
+    /// Checks that SVG content would convert, without rendering or writing
+    /// anything.
+    ///
+    /// # Errors
+    ///
+    /// Fails on the same size, parse and dimension errors as
+    /// [`convert`](Self::convert).
+    pub fn validate(&self, svg_content: &str) -> Result<SvgInfo> {
+        let tree = self.parse(svg_content)?;
+        let size = tree.size();
+        Ok(SvgInfo {
+            width: size.width(),
+            height: size.height(),
+            node_count: count_nodes(tree.root()),
+        })
+    }
+
+    /// Like [`validate`](Self::validate), for raw input that may be
+    /// gzip-compressed (`.svgz`). See [`decode_svg`].
+    pub fn validate_raw(&self, data: &[u8]) -> Result<SvgInfo> {
+        let svg_content = decode_svg(data, self.max_svg_bytes)?;
+        self.validate(&svg_content)
+    }
+
+    /// Parses SVG content into a usvg tree and validates its dimensions.
+    fn parse(&self, svg_content: &str) -> Result<usvg::Tree> {
+        self.parse_with_links(svg_content, false, None)
+            .map(|(tree, _, _)| tree)
+    }
+
+    /// Like [`parse`](Self::parse), also collecting the SVG's hyperlinks
+    /// when `preserve_links` is set and limiting the SVG to `crop`.
+    ///
+    /// The source is parsed once, for every rewrite before usvg and for the
+    /// [`SourceElements`] returned to compare with the tree, which are
+    /// `None` if it isn't well-formed XML.
+    fn parse_with_links(
+        &self,
+        svg_content: &str,
+        preserve_links: bool,
+        crop: Option<CropRect>,
+    ) -> Result<(usvg::Tree, Vec<Link>, Option<SourceElements>)> {
+        let original = svg_content;
+        // Refuse oversized input before usvg allocates for it
+        check_svg_size(svg_content, self.max_svg_bytes)?;
+        let svg_content = trim_svg_start(svg_content);
+        check_svg_entities(svg_content)?;
+
+        let doc = markup::parse(svg_content);
+        let mut edits = match crop {
+            Some(crop) => crop_svg(doc.as_ref(), crop)?,
+            None => size_svg(doc.as_ref(), self.parse_options.dpi)?,
+        };
+        let anchors = match &doc {
+            Some(doc) if preserve_links => {
+                let (anchor_edits, anchors) = links::tag_anchors(doc);
+                edits.extend(anchor_edits);
+                anchors
+            }
+            _ => Vec::new(),
+        };
+        let source = doc.as_ref().map(SourceElements::count);
+        let svg_content = markup::apply_edits(svg_content, edits);
+
+        // Parse SVG to usvg tree
+        let tree = usvg::Tree::from_str(&svg_content, &self.usvg_options())
+            .map_err(|error| parse_error(error, original))?;
+
+        // Validate tree has valid dimensions
+        let size = tree.size();
+        if size.width() <= 0.0 || size.height() <= 0.0 {
+            return Err(ConvertError::InvalidDimensions(format!(
+                "{}x{}",
+                size.width(),
+                size.height()
+            )));
+        }
+
+        info!(
+            "SVG parsed successfully: {}x{} units",
+            size.width(),
+            size.height()
+        );
+
+        let links = links::resolve(&tree, &anchors);
+        Ok((tree, links, source))
+    }
+}
+
+/// A root `width` or `height`, as far as sizing the page is concerned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RootLength {
+    /// An absolute length, in pixels.
+    Absolute(f32),
+    /// Absent, `auto` or a percentage, none of which mean anything without
+    /// a viewport.
+    Relative,
+    /// A font-relative or unrecognized length, left for usvg to resolve.
+    Other,
+}
+
+impl RootLength {
+    fn parse(value: Option<&str>, dpi: f32) -> Self {
+        let Some(value) = value.map(str::trim) else {
+            return RootLength::Relative;
+        };
+        if value.is_empty() || value == "auto" || value.ends_with('%') {
+            return RootLength::Relative;
+        }
+        let units = [
+            ("px", 1.0),
+            ("in", dpi),
+            ("cm", dpi / 2.54),
+            ("mm", dpi / 25.4),
+            ("pt", dpi / 72.0),
+            ("pc", dpi / 6.0),
+        ];
+        let (number, scale) = units
+            .iter()
+            .find_map(|&(unit, scale)| Some((value.strip_suffix(unit)?, scale)))
+            .unwrap_or((value, 1.0));
+        match number.parse::<f32>() {
+            Ok(number) if number.is_finite() => RootLength::Absolute(number * scale),
+            _ => RootLength::Other,
+        }
+    }
+}
+
+/// Gives the root element of `svg` an absolute size derived from its
+/// viewBox when its `width` or `height` is missing, `auto` or a percentage.
+/// Given one of them, the other follows from the viewBox's aspect ratio;
+/// given neither, the page is the viewBox's size. Lengths in physical units
+/// are converted at `dpi`.
+///
+/// Returns the edits to `doc`'s source that do so. Unparsable input, and
+/// roots sized in font-relative units, are left unchanged for usvg to
+/// handle.
+///
+/// # Errors
+///
+/// Returns [`ConvertError::InvalidDimensions`] if the root lacks an
+/// absolute size and has no viewBox of positive size to take one from.
+fn size_svg(doc: Option<&roxmltree::Document>, dpi: f32) -> Result<Vec<markup::Edit>> {
+    let Some(doc) = doc else {
+        return Ok(Vec::new());
+    };
+    let root = doc.root_element();
+    let attribute = |name: &str| {
+        root.attributes()
+            .find(|attr| attr.namespace().is_none() && attr.name() == name)
+    };
+
+    let width = RootLength::parse(attribute("width").map(|attr| attr.value()), dpi);
+    let height = RootLength::parse(attribute("height").map(|attr| attr.value()), dpi);
+    match (width, height) {
+        (RootLength::Absolute(_), RootLength::Absolute(_)) => return Ok(Vec::new()),
+        (RootLength::Other, _) | (_, RootLength::Other) => return Ok(Vec::new()),
+        _ => {}
+    }
+
+    let view_box = attribute("viewBox").and_then(|attr| {
+        let numbers: Vec<f32> = attr
+            .value()
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|n| !n.is_empty())
+            .map(str::parse)
+            .collect::<std::result::Result<_, _>>()
+            .ok()?;
+        match numbers[..] {
+            [_, _, width, height] if width > 0.0 && height > 0.0 && (width * height).is_finite() => {
+                Some((width, height))
+            }
+            _ => None,
+        }
+    });
+    let Some((view_width, view_height)) = view_box else {
+        return Err(ConvertError::InvalidDimensions(
+            "SVG has no width and height or viewBox to size the page from".to_string(),
+        ));
+    };
+    let (width, height) = match (width, height) {
+        (RootLength::Absolute(width), _) => (width, width * view_height / view_width),
+        (_, RootLength::Absolute(height)) => (height * view_width / view_height, height),
+        _ => (view_width, view_height),
+    };
+
+    // Replace the root's own size with the derived one
+    Ok(markup::replace_attributes(
+        root,
+        &["width", "height"],
+        &format!("width=\"{}\" height=\"{}\"", width, height),
+    ))
+}
+
+/// Returns edits to the root element of `doc`'s source that make its
+/// viewBox `crop` and its size the crop's size, leaving everything outside
+/// the crop off the canvas.
+///
+/// Unparsable input is left unchanged for usvg to report.
+fn crop_svg(doc: Option<&roxmltree::Document>, crop: CropRect) -> Result<Vec<markup::Edit>> {
+    let CropRect {
+        x,
+        y,
+        width,
+        height,
+    } = crop;
+    if ![x, y, width, height].iter().all(|n| n.is_finite()) || width <= 0.0 || height <= 0.0 {
+        return Err(ConvertError::InvalidDimensions(format!(
+            "crop {}x{} at {},{}",
+            width, height, x, y
+        )));
+    }
+
+    let Some(doc) = doc else {
+        return Ok(Vec::new());
+    };
+
+    // Replace the root's own sizing attributes with the crop's
+    Ok(markup::replace_attributes(
+        doc.root_element(),
+        &["width", "height", "viewBox", "preserveAspectRatio"],
+        &format!(
+            "width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\"",
+            width, height, x, y, width, height
+        ),
+    ))
+}
+
+/// Renders parsed trees into a standalone PDF, one page per tree.
+///
+/// Each SVG is converted to a Form XObject via `svg2pdf::to_chunk` and placed
+/// on a page sized according to `options`, so the vector content is never
+/// rasterized regardless of the page size. With
+/// [`RenderMode::VectorWithRasterFallback`], pages using unsupported features
+/// are instead rasterized and embedded as an image XObject in the same place.
+/// A background color, if set, is filled across the bleed box first (the
+/// whole page without bleed).
+/// Each page's links are added as link annotations over their content, and
+/// `options.page_labels` become the document's page labels.
+/// Streams are compressed according to `options.compression`; objects are
+/// written individually, as pdf-writer has no object stream support.
+/// `now` is the creation date written unless the metadata sets one or the
+/// output is deterministic. `on_page(done, total)` is called after each
+/// page.
+fn render_pdf(
+    pages: &[(usvg::Tree, Vec<Link>)],
+    options: &ConverterOptions,
+    now: DateTime<Utc>,
+    on_page: &mut dyn FnMut(usize, usize),
+) -> Result<Vec<u8>> {
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let page_tree_id = alloc.bump();
+    let info_id = alloc.bump();
+    let svg_name = Name(b"S1");
+    let flate = options.compression.flate();
+    let svg_options = options.svg2pdf_options();
+
+    let cmyk = options.color_space == ColorSpace::CmykIntent;
+    let icc_profile = match (&options.icc_profile, cmyk) {
+        (Some(path), true) => Some(read_cmyk_profile(path)?),
+        _ => None,
+    };
+
+    let mut pdf = Pdf::new();
+    let mut page_ids = Vec::with_capacity(pages.len());
+
+    for (tree, links) in pages {
+        let page_id = alloc.bump();
+        let content_id = alloc.bump();
+
+        let svg_id = match options.render_mode {
+            RenderMode::VectorWithRasterFallback { dpi } if needs_raster_fallback(tree) => {
+                info!("SVG uses features without vector support, rasterizing at {} DPI", dpi);
+                write_raster_image(&mut pdf, &mut alloc, tree, dpi, flate)?
+            }
+            _ => {
+                // Convert the SVG into an independent chunk and renumber it
+                // into our reference space.
+                let (svg_chunk, svg_ref) = svg2pdf::to_chunk(tree, svg_options);
+                let mut map = HashMap::new();
+                let svg_chunk =
+                    svg_chunk.renumber(|old| *map.entry(old).or_insert_with(|| alloc.bump()));
+                pdf.extend(&svg_chunk);
+                map[&svg_ref]
+            }
+        };
+
+        let size = tree.size();
+        let (trim_width, trim_height, mut transform) =
+            options.layout(size.width(), size.height());
+        let bleed = options.bleed.max(0.0);
+        let slug = if bleed > 0.0 { TRIM_MARK_SLUG } else { 0.0 };
+        let offset = slug + bleed;
+        let trim_box = Rect::new(offset, offset, offset + trim_width, offset + trim_height);
+        let bleed_box = Rect::new(
+            slug,
+            slug,
+            offset + trim_width + bleed,
+            offset + trim_height + bleed,
+        );
+        let media_box = Rect::new(0.0, 0.0, bleed_box.x2 + slug, bleed_box.y2 + slug);
+        transform[4] += offset;
+        transform[5] += offset;
+
+        let annotation_ids: Vec<Ref> = links.iter().map(|_| alloc.bump()).collect();
+        for (link, &annotation_id) in links.iter().zip(&annotation_ids) {
+            let [x1, y1, x2, y2] = link.page_rect(size, transform);
+            let mut annotation = pdf.annotation(annotation_id);
+            annotation
+                .subtype(AnnotationType::Link)
+                .rect(Rect::new(x1, y1, x2, y2))
+                .border(0.0, 0.0, 0.0, None);
+            annotation
+                .action()
+                .action_type(ActionType::Uri)
+                .uri(Str(link.uri.as_bytes()));
+        }
+
+        let mut page = pdf.page(page_id);
+        page.media_box(media_box);
+        if bleed > 0.0 {
+            page.bleed_box(bleed_box);
+            page.trim_box(trim_box);
+        }
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(svg_name, svg_id);
+        if cmyk {
+            page.group().transparency().color_space().device_cmyk();
+        }
+        if !annotation_ids.is_empty() {
+            page.annotations(annotation_ids);
+        }
+        page.finish();
+
+        let mut content = Content::new();
+        if let Some(color) = options.background {
+            content.save_state();
+            if cmyk {
+                let (c, m, y, k) = color.to_cmyk();
+                content.set_fill_cmyk(c, m, y, k);
+            } else {
+                let (r, g, b) = color.to_pdf();
+                content.set_fill_rgb(r, g, b);
+            }
+            content.rect(
+                bleed_box.x1,
+                bleed_box.y1,
+                bleed_box.x2 - bleed_box.x1,
+                bleed_box.y2 - bleed_box.y1,
+            );
+            content.fill_nonzero();
+            content.restore_state();
+        }
+        content.save_state();
+        content.transform(transform);
+        content.x_object(svg_name);
+        content.restore_state();
+        if bleed > 0.0 {
+            draw_trim_marks(&mut content, media_box, bleed_box, trim_box, cmyk);
+        }
+        let content = content.finish();
+        match flate {
+            Some(level) => {
+                let compressed = deflate(&content, level)?;
+                pdf.stream(content_id, &compressed).filter(Filter::FlateDecode);
+            }
+            None => {
+                pdf.stream(content_id, &content);
+            }
+        }
+
+        page_ids.push(page_id);
+        on_page(page_ids.len(), pages.len());
+    }
+
+    let profile_id = match &icc_profile {
+        Some(profile) => {
+            let profile_id = alloc.bump();
+            match flate {
+                Some(level) => {
+                    let compressed = deflate(profile, level)?;
+                    pdf.icc_profile(profile_id, &compressed)
+                        .n(4)
+                        .filter(Filter::FlateDecode);
+                }
+                None => {
+                    pdf.icc_profile(profile_id, profile).n(4);
+                }
+            }
+            Some(profile_id)
+        }
+        None => None,
+    };
+
+    let label_ranges = page_label_ranges(&options.page_labels, page_ids.len());
+    let label_ids: Vec<Ref> = label_ranges.iter().map(|_| alloc.bump()).collect();
+
+    let mut catalog = pdf.catalog(catalog_id);
+    catalog.pages(page_tree_id);
+    if !label_ids.is_empty() {
+        let mut labels = catalog.page_labels();
+        let mut nums = labels.nums();
+        for (&(start, _), &label_id) in label_ranges.iter().zip(&label_ids) {
+            nums.insert(start as i32, label_id);
+        }
+    }
+    if cmyk {
+        let mut intents = catalog.insert(Name(b"OutputIntents")).array();
+        let mut intent = intents.push().start::<OutputIntent>();
+        intent.subtype(OutputIntentSubtype::PDFX);
+        match (profile_id, &options.icc_profile) {
+            (Some(profile_id), Some(path)) => {
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy())
+                    .unwrap_or_default();
+                intent
+                    .output_condition_identifier(TextStr("Custom"))
+                    .info(TextStr(&name))
+                    .dest_output_profile(profile_id);
+            }
+            _ => {
+                intent
+                    .output_condition_identifier(TextStr(DEFAULT_CMYK_CONDITION))
+                    .registry_name(TextStr(ICC_REGISTRY));
+            }
+        }
+    }
+    catalog.finish();
+
+    for (&(start, label), &label_id) in label_ranges.iter().zip(&label_ids) {
+        let mut page_label = pdf.indirect(label_id).start::<PageLabel>();
+        match label {
+            Some(label) => {
+                page_label.prefix(TextStr(label));
+            }
+            None => {
+                page_label.style(NumberingStyle::Arabic).offset(start as i32 + 1);
+            }
+        }
+    }
+
+    pdf.pages(page_tree_id)
+        .count(page_ids.len() as i32)
+        .kids(page_ids);
+
+    write_document_info(&mut pdf, info_id, options.metadata.as_ref(), options.deterministic, now);
+    Ok(pdf.finish())
+}
+
+/// Splits `page_count` pages into the ranges of a page-label tree, as
+/// `(first page index, label)`: one range per labelled page, and one per run
+/// of unlabelled pages, which keep their page numbers. Empty if no page has
+/// a label.
+fn page_label_ranges(labels: &[String], page_count: usize) -> Vec<(usize, Option<&str>)> {
+    if labels.iter().all(String::is_empty) {
+        return Vec::new();
+    }
+
+    let mut ranges: Vec<(usize, Option<&str>)> = Vec::new();
+    for index in 0..page_count {
+        let label = labels.get(index).map(String::as_str).filter(|label| !label.is_empty());
+        let continues_run = label.is_none() && matches!(ranges.last(), Some((_, None)));
+        if !continues_run {
+            ranges.push((index, label));
+        }
+    }
+    ranges
+}
+
+/// Draws trim marks at the corners of `trim`, continuing the trim lines in
+/// the slug between `bleed` and the edge of `media` so they stay clear of
+/// the bleed. Marks use registration color in CMYK so they appear on every
+/// separation.
+fn draw_trim_marks(content: &mut Content, media: Rect, bleed: Rect, trim: Rect, cmyk: bool) {
+    content.save_state();
+    if cmyk {
+        content.set_stroke_cmyk(1.0, 1.0, 1.0, 1.0);
+    } else {
+        content.set_stroke_rgb(0.0, 0.0, 0.0);
+    }
+    content.set_line_width(TRIM_MARK_WIDTH);
+    for (x, x_bleed, x_edge) in [(trim.x1, bleed.x1, media.x1), (trim.x2, bleed.x2, media.x2)] {
+        for (y, y_bleed, y_edge) in [(trim.y1, bleed.y1, media.y1), (trim.y2, bleed.y2, media.y2)] {
+            content.move_to(x_edge, y);
+            content.line_to(x_bleed, y);
+            content.move_to(x, y_edge);
+            content.line_to(x, y_bleed);
+        }
+    }
+    content.stroke();
+    content.restore_state();
+}
+
+/// Reads the ICC profile at `path`, rejecting anything but a CMYK profile
+/// since it becomes the document's CMYK output intent.
+fn read_cmyk_profile(path: &Path) -> Result<Vec<u8>> {
+    let profile = fs::read(path).map_err(|e| {
+        ConvertError::Render(format!("Failed to read ICC profile {}: {}", path.display(), e))
+    })?;
+    // The data color space signature sits at bytes 16..20 of the header
+    if profile.get(16..20) != Some(b"CMYK".as_slice()) {
+        return Err(ConvertError::Render(format!(
+            "ICC profile {} is not a CMYK profile",
+            path.display()
+        )));
+    }
+    Ok(profile)
+}
+
+/// Images resolved by a converter, keyed by their source.
+///
+/// Failed resolutions are cached too, so a missing file is only looked for
+/// once.
+#[derive(Default)]
+struct ImageCache {
+    images: Mutex<HashMap<ImageKey, Option<usvg::ImageKind>>>,
+}
+
+/// Source of an `<image>`: the SHA-256 of a data URL's MIME type and
+/// content, or any other `href` as written together with the directory
+/// relative paths resolve against, since the same `href` names different
+/// files for different documents.
+#[derive(PartialEq, Eq, Hash)]
+enum ImageKey {
+    Data([u8; 32]),
+    Href(Option<PathBuf>, String),
+}
+
+impl ImageCache {
+    /// Returns the cached image for `key`, resolving and caching it first if
+    /// needed. The lock isn't held while resolving, since nested SVG images
+    /// resolve their own images through the same cache, and a conversion
+    /// that panicked while holding it leaves the cache usable.
+    fn get_or_resolve(
+        &self,
+        key: ImageKey,
+        resolve: impl FnOnce() -> Option<usvg::ImageKind>,
+    ) -> Option<usvg::ImageKind> {
+        let images = || self.images.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(kind) = images().get(&key) {
+            return kind.clone();
+        }
+        let kind = resolve();
+        images().insert(key, kind.clone());
+        kind
+    }
+}
+
+/// Wraps `resolver` so each image it resolves is kept in `cache`.
+fn cached_image_resolver(
+    resolver: usvg::ImageHrefResolver<'static>,
+    cache: Arc<ImageCache>,
+) -> usvg::ImageHrefResolver<'static> {
+    let usvg::ImageHrefResolver {
+        resolve_data,
+        resolve_string,
+    } = resolver;
+    let data_cache = cache.clone();
+    usvg::ImageHrefResolver {
+        resolve_data: Box::new(move |mime, data, options| {
+            let digest = Sha256::new()
+                .chain_update(mime)
+                .chain_update([0])
+                .chain_update(data.as_slice())
+                .finalize();
+            data_cache.get_or_resolve(ImageKey::Data(digest.into()), || {
+                resolve_data(mime, data, options)
+            })
+        }),
+        resolve_string: Box::new(move |href, options| {
+            let key = ImageKey::Href(options.resources_dir.clone(), href.to_string());
+            cache.get_or_resolve(key, || resolve_string(href, options))
+        }),
+    }
+}
+
+/// Wraps `resolver` so raster images over `max_pixels` are downsampled.
+fn capped_image_resolver(
+    resolver: usvg::ImageHrefResolver<'static>,
+    max_pixels: u64,
+) -> usvg::ImageHrefResolver<'static> {
+    let usvg::ImageHrefResolver {
+        resolve_data,
+        resolve_string,
+    } = resolver;
+    usvg::ImageHrefResolver {
+        resolve_data: Box::new(move |mime, data, options| {
+            resolve_data(mime, data, options).map(|kind| cap_image(kind, max_pixels))
+        }),
+        resolve_string: Box::new(move |href, options| {
+            resolve_string(href, options).map(|kind| cap_image(kind, max_pixels))
+        }),
+    }
+}
+
+/// Downsamples a raster image to at most `max_pixels` pixels. JPEGs stay
+/// JPEGs; PNGs and GIFs become PNGs. Images within budget, nested SVGs and
+/// data that fails to decode are returned unchanged.
+fn cap_image(kind: usvg::ImageKind, max_pixels: u64) -> usvg::ImageKind {
+    let (data, format) = match &kind {
+        usvg::ImageKind::JPEG(data) => (data, image::ImageFormat::Jpeg),
+        usvg::ImageKind::PNG(data) => (data, image::ImageFormat::Png),
+        usvg::ImageKind::GIF(data) => (data, image::ImageFormat::Gif),
+        usvg::ImageKind::SVG(_) => return kind,
+    };
+
+    // Read just the header so images within budget are never decoded
+    let reader = image::ImageReader::with_format(std::io::Cursor::new(data.as_slice()), format);
+    let (width, height) = match reader.into_dimensions() {
+        Ok(dimensions) => dimensions,
+        Err(e) => {
+            warn!("Failed to read embedded image dimensions: {}", e);
+            return kind;
+        }
+    };
+    let pixels = u64::from(width) * u64::from(height);
+    if pixels <= max_pixels {
+        return kind;
+    }
+
+    let decoded = match image::load_from_memory_with_format(data, format) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            warn!("Failed to decode embedded image for downsampling: {}", e);
+            return kind;
+        }
+    };
+
+    let scale = (max_pixels as f64 / pixels as f64).sqrt();
+    let new_width = ((f64::from(width) * scale).floor() as u32).max(1);
+    let new_height = ((f64::from(height) * scale).floor() as u32).max(1);
+    let resized = decoded.resize_exact(new_width, new_height, image::imageops::FilterType::Triangle);
+
+    let jpeg = format == image::ImageFormat::Jpeg;
+    let mut encoded = std::io::Cursor::new(Vec::new());
+    let result = if jpeg {
+        // JPEG has no alpha channel
+        image::DynamicImage::ImageRgb8(resized.to_rgb8())
+            .write_to(&mut encoded, image::ImageFormat::Jpeg)
+    } else {
+        resized.write_to(&mut encoded, image::ImageFormat::Png)
+    };
+    if let Err(e) = result {
+        warn!("Failed to re-encode downsampled image: {}", e);
+        return kind;
+    }
+
+    info!(
+        "Downsampled embedded image from {}x{} to {}x{}",
+        width, height, new_width, new_height
+    );
+    let data = Arc::new(encoded.into_inner());
+    if jpeg {
+        usvg::ImageKind::JPEG(data)
+    } else {
+        usvg::ImageKind::PNG(data)
+    }
+}
+
+/// Counts the nodes below `group`, recursively.
+fn count_nodes(group: &usvg::Group) -> usize {
+    group
+        .children()
+        .iter()
+        .map(|node| match node {
+            usvg::Node::Group(group) => 1 + count_nodes(group),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Elements usvg skips entirely.
+const UNSUPPORTED_ELEMENTS: &[&str] = &[
+    "animate",
+    "animateColor",
+    "animateMotion",
+    "animateTransform",
+    "audio",
+    "canvas",
+    "foreignObject",
+    "iframe",
+    "script",
+    "set",
+    "video",
+];
+
+/// Elements whose content is only drawn where referenced, if at all.
+const NON_RENDERED_CONTAINERS: &[&str] =
+    &["clipPath", "defs", "marker", "mask", "pattern", "symbol"];
+
+/// Elements counted in an SVG's source, to compare with the tree usvg
+/// parsed from it.
+#[derive(Debug, Default)]
+struct SourceElements {
+    /// Each [`UNSUPPORTED_ELEMENTS`] entry found, with its count.
+    unsupported: Vec<(&'static str, usize)>,
+    /// Rendered `<text>` elements with any non-blank content.
+    texts: usize,
+    /// Rendered `<image>` elements.
+    images: usize,
+}
+
+impl SourceElements {
+    fn count(doc: &roxmltree::Document) -> Self {
+        let mut counted = SourceElements::default();
+        for node in doc.descendants().filter(|n| n.is_element()) {
+            let name = node.tag_name().name();
+            if let Some(&element) = UNSUPPORTED_ELEMENTS.iter().find(|&&e| e == name) {
+                match counted.unsupported.iter_mut().find(|(e, _)| *e == element) {
+                    Some((_, count)) => *count += 1,
+                    None => counted.unsupported.push((element, 1)),
+                }
+            } else if is_rendered(node) {
+                match name {
+                    "text" if has_text(node) => counted.texts += 1,
+                    "image" => counted.images += 1,
+                    _ => {}
+                }
+            }
+        }
+        counted
+    }
+}
+
+/// Compares an SVG's source elements, if it could be scanned, with the tree
+/// usvg parsed from it, reporting unsupported elements, text and images
+/// usvg dropped, and pages the raster fallback will rasterize.
+fn scan_warnings(
+    page: usize,
+    source: Option<&SourceElements>,
+    tree: &usvg::Tree,
+    options: &ConverterOptions,
+) -> Vec<ConversionWarning> {
+    let mut warnings = Vec::new();
+
+    if let Some(source) = source {
+        warnings.extend(source.unsupported.iter().map(|&(element, count)| {
+            ConversionWarning::UnsupportedElement {
+                page,
+                element: element.to_string(),
+                count,
+            }
+        }));
+
+        let (texts, images) = count_texts_and_images(tree.root());
+        if texts < source.texts {
+            warnings.push(ConversionWarning::TextDropped {
+                page,
+                count: source.texts - texts,
+            });
+        }
+        if images < source.images {
+            warnings.push(ConversionWarning::ImageDropped {
+                page,
+                count: source.images - images,
+            });
+        }
+    }
+
+    if let RenderMode::VectorWithRasterFallback { dpi } = options.render_mode {
+        if needs_raster_fallback(tree) {
+            warnings.push(ConversionWarning::Rasterized { page, dpi });
+        }
+    }
+
+    warnings
+}
+
+/// Returns `true` if `node` is drawn in place: not hidden with
+/// `display="none"` and not inside a container such as `<defs>`.
+fn is_rendered(node: roxmltree::Node) -> bool {
+    node.ancestors().filter(|n| n.is_element()).all(|n| {
+        n.attribute("display") != Some("none")
+            && !NON_RENDERED_CONTAINERS.contains(&n.tag_name().name())
+    })
+}
+
+/// Returns `true` if a `<text>` element has any non-blank content.
+fn has_text(node: roxmltree::Node) -> bool {
+    node.descendants()
+        .any(|n| n.text().is_some_and(|text| !text.trim().is_empty()))
+}
+
+/// Counts text and image nodes drawn by `group` and its descendants.
+fn count_texts_and_images(group: &usvg::Group) -> (usize, usize) {
+    group
+        .children()
+        .iter()
+        .fold((0, 0), |(texts, images), node| match node {
+            usvg::Node::Group(group) => {
+                let (group_texts, group_images) = count_texts_and_images(group);
+                (texts + group_texts, images + group_images)
+            }
+            usvg::Node::Text(_) => (texts + 1, images),
+            usvg::Node::Image(_) => (texts, images + 1),
+            usvg::Node::Path(_) => (texts, images),
+        })
+}
+
+/// Returns `true` if the tree uses features svg2pdf doesn't render
+/// faithfully: filters and non-normal blend modes.
+fn needs_raster_fallback(tree: &usvg::Tree) -> bool {
+    fn uses_blend_mode(group: &usvg::Group) -> bool {
+        group.blend_mode() != usvg::BlendMode::Normal
+            || group.children().iter().any(|node| match node {
+                usvg::Node::Group(group) => uses_blend_mode(group),
+                usvg::Node::Text(text) => uses_blend_mode(text.flattened()),
+                _ => false,
+            })
+    }
+
+    !tree.filters().is_empty() || uses_blend_mode(tree.root())
+}
+
+/// Rasterizes the tree at `dpi` and writes it as an RGB image XObject with
+/// an alpha soft mask, Flate-encoded at `flate` if set. Returns the image's
+/// reference.
+///
+/// Like the svg2pdf XObject, the image occupies the unit square, so it is
+/// placed with the same layout transform.
+fn write_raster_image(
+    pdf: &mut Pdf,
+    alloc: &mut Ref,
+    tree: &usvg::Tree,
+    dpi: f32,
+    flate: Option<Compression>,
+) -> Result<Ref> {
+    // One SVG user unit is one point
+    let pixmap = rasterize(tree, dpi / 72.0)?;
+    let (width, height) = (pixmap.width(), pixmap.height());
+
+    let pixel_count = (width * height) as usize;
+    let mut rgb = Vec::with_capacity(pixel_count * 3);
+    let mut alpha = Vec::with_capacity(pixel_count);
+    for pixel in pixmap.pixels() {
+        let color = pixel.demultiply();
+        rgb.extend_from_slice(&[color.red(), color.green(), color.blue()]);
+        alpha.push(color.alpha());
+    }
+
+    if let Some(level) = flate {
+        rgb = deflate(&rgb, level)?;
+        alpha = deflate(&alpha, level)?;
+    }
+    let image_id = alloc.bump();
+    let mask_id = alloc.bump();
+
+    let mut image = pdf.image_xobject(image_id, &rgb);
+    if flate.is_some() {
+        image.filter(Filter::FlateDecode);
+    }
+    image.width(width as i32);
+    image.height(height as i32);
+    image.color_space().device_rgb();
+    image.bits_per_component(8);
+    image.s_mask(mask_id);
+    image.finish();
+
+    let mut mask = pdf.image_xobject(mask_id, &alpha);
+    if flate.is_some() {
+        mask.filter(Filter::FlateDecode);
+    }
+    mask.width(width as i32);
+    mask.height(height as i32);
+    mask.color_space().device_gray();
+    mask.bits_per_component(8);
+    mask.finish();
+
+    Ok(image_id)
+}
+
+/// Renders the tree into a pixmap at `scale` pixels per SVG user unit.
+fn rasterize(tree: &usvg::Tree, scale: f32) -> Result<resvg::tiny_skia::Pixmap> {
+    let size = tree.size();
+    let width = (size.width() * scale).ceil() as u32;
+    let height = (size.height() * scale).ceil() as u32;
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height).ok_or_else(|| {
+        ConvertError::Render(format!(
+            "Cannot rasterize {}x{} px at scale {}",
+            width, height, scale
+        ))
+    })?;
+    resvg::render(
+        tree,
+        resvg::tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+    Ok(pixmap)
+}
+
+/// Compresses stream data for a `FlateDecode` stream.
+fn deflate(data: &[u8], level: Compression) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), level);
+    encoder
+        .write_all(data)
+        .and_then(|()| encoder.finish())
+        .map_err(|e| ConvertError::Render(format!("Failed to compress PDF stream: {}", e)))
+}
+
+/// Fills a temporary file next to `path` using `write`, syncs it, then
+/// renames it over `path`. If `write` fails (or the process dies) the
+/// temporary file is discarded and `path` is left untouched.
+pub(crate) fn write_atomic(path: &Path, write: impl FnOnce(&mut fs::File) -> io::Result<()>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut temp = tempfile::Builder::new()
+        .prefix(".export-")
+        .suffix(".tmp")
+        .tempfile_in(dir)?;
+    // Exports are readable by everyone rather than owner-only like other
+    // temporary files. Set explicitly so the process umask doesn't matter.
+    #[cfg(unix)]
+    temp.as_file()
+        .set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o644))?;
+
+    write(temp.as_file_mut())?;
+    temp.as_file().sync_all()?;
+
+    temp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
+
+/// Writes the PDF Info dictionary, including any caller-supplied metadata.
+fn write_document_info(
+    pdf: &mut Pdf,
+    id: Ref,
+    metadata: Option<&PdfMetadata>,
+    deterministic: bool,
+    now: DateTime<Utc>,
+) {
+    let producer = concat!("WireTuner worker-export ", env!("CARGO_PKG_VERSION"));
+    let mut info = pdf.document_info(id);
+    info.producer(TextStr(producer));
+
+    let Some(metadata) = metadata else {
+        return;
+    };
+
+    if let Some(title) = &metadata.title {
+        info.title(TextStr(title));
+    }
+    if let Some(author) = &metadata.author {
+        info.author(TextStr(author));
+    }
+    if let Some(subject) = &metadata.subject {
+        info.subject(TextStr(subject));
+    }
+    if let Some(creator) = &metadata.creator {
+        info.creator(TextStr(creator));
+    }
+
+    let created = match metadata.creation_date {
+        Some(created) => created,
+        None if deterministic => DETERMINISTIC_CREATION_DATE,
+        None => now,
+    };
+    info.creation_date(
+        Date::new(created.year() as u16)
+            .month(created.month() as u8)
+            .day(created.day() as u8)
+            .hour(created.hour() as u8)
+            .minute(created.minute() as u8)
+            .second(created.second() as u8)
+            .utc_offset_hour(0),
+    );
+}
+
+/// A conversion running on tokio's blocking pool, started with
+/// [`SvgToPdfConverter::spawn_conversion`].
+///
+/// The conversion can't be interrupted, so one that is abandoned (or whose
+/// handle is dropped) runs to its end, but skips writing its output as if
+/// it were a dry run.
+pub struct ConversionTask<T> {
+    /// The blocking thread, until it has been waited for to the end.
+    handle: Option<tokio::task::JoinHandle<Result<T>>>,
+    abandoned: Arc<AtomicBool>,
+}
+
+impl<T: Send + 'static> ConversionTask<T> {
+    /// Waits up to `timeout` for the conversion's result, abandoning it
+    /// once the deadline passes.
+    ///
+    /// Dropping the returned future, e.g. in a `select!`, leaves the
+    /// conversion running; it can be waited for again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConvertError::Timeout`] once the deadline passes, in
+    /// addition to the conversion's own errors.
+    pub async fn wait(&mut self, timeout: Duration) -> Result<T> {
+        let Some(handle) = self.handle.as_mut() else {
+            return Err(ConvertError::Render("Conversion already finished".to_string()));
+        };
+        match tokio::time::timeout(timeout, handle).await {
+            Ok(joined) => {
+                self.handle = None;
+                joined.unwrap_or_else(|e| {
+                    Err(ConvertError::Render(format!("Conversion task failed: {}", e)))
+                })
+            }
+            Err(_) => {
+                // Its job is failed (or retried) from here on
+                self.abandoned.store(true, Ordering::Relaxed);
+                warn!("Conversion exceeded timeout of {:?}", timeout);
+                Err(ConvertError::Timeout(timeout))
+            }
+        }
+    }
+
+    /// Stops the conversion from writing its output, returning the thread
+    /// still running it, if any, to wait for.
+    pub fn abandon(mut self) -> Option<AbandonedConversion> {
+        self.abandoned.store(true, Ordering::Relaxed);
+        let handle = self.handle.take()?;
+        Some(AbandonedConversion(Box::pin(async move {
+            let _ = handle.await;
+        })))
+    }
+}
+
+impl<T> Drop for ConversionTask<T> {
+    fn drop(&mut self) {
+        self.abandoned.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The thread of an abandoned [`ConversionTask`], which writes nothing
+/// but holds on to its CPU until the conversion returns.
+pub struct AbandonedConversion(Pin<Box<dyn Future<Output = ()> + Send>>);
+
+impl AbandonedConversion {
+    /// Waits for the conversion to return.
+    pub async fn finished(self) {
+        self.0.await;
+    }
+}
+
+impl SvgToPdfConverter {
+    /// Writes converted output to `path` through the output sink, unless
+    /// the conversion has been abandoned.
+    fn write_output(&self, path: &str, data: &[u8]) -> Result<()> {
+        if self
+            .abandoned
+            .as_ref()
+            .is_some_and(|abandoned| abandoned.load(Ordering::Relaxed))
+        {
+            warn!("Conversion abandoned, not writing {} bytes to {}", data.len(), path);
+            return Ok(());
+        }
+        self.output_sink.write(path, data).map_err(|source| ConvertError::Io {
+            path: path.to_string(),
+            source,
+        })
+    }
+}
+
+impl Default for SvgToPdfConverter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ManualClock;
+    use tempfile::NamedTempFile;
+
+    /// Returns the offset of the first occurrence of `needle` in `haystack`.
+    fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+        haystack.windows(needle.len()).position(|window| window == needle)
+    }
+
+    #[test]
+    fn test_convert_simple_svg() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect x="10" y="10" width="80" height="80" fill="blue"/>
+        </svg>"#;
+
+        let temp = NamedTempFile::new().unwrap();
+        let result = converter.convert(svg, temp.path().to_str().unwrap());
+
+        assert!(result.is_ok());
+        assert!(temp.path().exists());
+
+        // Verify PDF is not empty
+        let metadata = std::fs::metadata(temp.path()).unwrap();
+        assert!(metadata.len() > 0);
+    }
+
+    #[test]
+    fn test_convert_to_bytes() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect x="10" y="10" width="80" height="80" fill="blue"/>
+        </svg>"#;
+
+        let pdf = converter.convert_to_bytes(svg).unwrap();
+
+        assert!(pdf.starts_with(b"%PDF"));
+    }
+
+    #[test]
+    fn test_convert_to_writer() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect x="10" y="10" width="80" height="80" fill="blue"/>
+        </svg>"#;
+
+        let mut cursor = io::Cursor::new(Vec::new());
+        converter.convert_to_writer(svg, &mut cursor).unwrap();
+        assert!(cursor.get_ref().starts_with(b"%PDF"));
+        assert_eq!(cursor.get_ref().len(), converter.convert_to_bytes(svg).unwrap().len());
+
+        // Writer failures surface as write errors; parse errors write nothing
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from(io::ErrorKind::StorageFull))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let err = converter.convert_to_writer(svg, Full).unwrap_err();
+        assert!(matches!(err, ConvertError::Write(_)));
+        assert!(err.is_disk_full());
+
+        let mut cursor = io::Cursor::new(Vec::new());
+        assert!(converter.convert_to_writer("<svg", &mut cursor).is_err());
+        assert!(cursor.get_ref().is_empty());
+    }
+
+    #[test]
+    fn test_invalid_svg() {
+        let converter = SvgToPdfConverter::new();
+        let invalid_svg = "not an svg";
+
+        let temp = NamedTempFile::new().unwrap();
+        let result = converter.convert(invalid_svg, temp.path().to_str().unwrap());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zero_dimensions() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="0" height="0"></svg>"#;
+
+        let temp = NamedTempFile::new().unwrap();
+        let result = converter.convert(svg, temp.path().to_str().unwrap());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_view_box_sizes_unsized_svg() {
+        let converter = SvgToPdfConverter::new();
+        let size = |svg: &str| {
+            let info = converter.validate(svg).unwrap();
+            (info.width, info.height)
+        };
+        let svg = |attrs: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" {attrs}><rect width="10" height="10"/></svg>"#
+            )
+        };
+
+        // viewBox only, or with sizes that need a viewport
+        assert_eq!(size(&svg(r#"viewBox="0 0 320 200""#)), (320.0, 200.0));
+        assert_eq!(size(&svg(r#"viewBox="10,20,320,200" width="100%" height="auto""#)), (320.0, 200.0));
+        // One absolute side keeps the viewBox's aspect ratio
+        assert_eq!(size(&svg(r#"viewBox="0 0 320 200" width="640""#)), (640.0, 400.0));
+        assert_eq!(size(&svg(r#"viewBox="0 0 320 200" height="1in""#)), (153.6, 96.0));
+        // Explicit sizes win over the viewBox
+        assert_eq!(size(&svg(r#"viewBox="0 0 320 200" width="50" height="60""#)), (50.0, 60.0));
+
+        let pdf = converter.convert_to_bytes(&svg(r#"viewBox="0 0 320 200""#)).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/MediaBox [0 0 320 200]"));
+
+        // Nothing to size the page from
+        for attrs in ["", r#"width="100%""#, r#"width="100""#, r#"viewBox="0 0 0 0""#, r#"viewBox="0 0 320""#] {
+            let err = converter.convert_to_bytes(&svg(attrs)).unwrap_err();
+            assert!(
+                matches!(err.root(), ConvertError::InvalidDimensions(_)),
+                "{:?} gave {:?}",
+                attrs,
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn test_root_length_parsing() {
+        let dpi = 96.0;
+        assert_eq!(RootLength::parse(Some("12"), dpi), RootLength::Absolute(12.0));
+        assert_eq!(RootLength::parse(Some(" 12px "), dpi), RootLength::Absolute(12.0));
+        assert_eq!(RootLength::parse(Some("1e2"), dpi), RootLength::Absolute(100.0));
+        assert_eq!(RootLength::parse(Some("72pt"), dpi), RootLength::Absolute(96.0));
+        assert_eq!(RootLength::parse(Some("25.4mm"), 72.0), RootLength::Absolute(72.0));
+        assert_eq!(RootLength::parse(None, dpi), RootLength::Relative);
+        assert_eq!(RootLength::parse(Some("50%"), dpi), RootLength::Relative);
+        assert_eq!(RootLength::parse(Some("auto"), dpi), RootLength::Relative);
+        assert_eq!(RootLength::parse(Some("2em"), dpi), RootLength::Other);
+        assert_eq!(RootLength::parse(Some("3ex"), dpi), RootLength::Other);
+        assert_eq!(RootLength::parse(Some("wide"), dpi), RootLength::Other);
+    }
+
+    #[test]
+    fn test_page_size_dimensions() {
+        let (width, height) = PageSize::Letter.dimensions_pt().unwrap();
+        assert_eq!((width, height), (612.0, 792.0));
+
+        let (width, height) = PageSize::A4.dimensions_pt().unwrap();
+        assert!((width - 595.28).abs() < 0.01);
+        assert!((height - 841.89).abs() < 0.01);
+
+        assert!(PageSize::Intrinsic.dimensions_pt().is_none());
+    }
+
+    #[test]
+    fn test_page_size_parsing() {
+        assert_eq!("A4".parse(), Ok(PageSize::A4));
+        assert_eq!("letter".parse(), Ok(PageSize::Letter));
+        assert_eq!("intrinsic".parse(), Ok(PageSize::Intrinsic));
+        assert_eq!(
+            "600x800".parse(),
+            Ok(PageSize::Custom {
+                width: 600.0,
+                height: 800.0
+            })
+        );
+
+        for invalid in ["", "b5", "600", "0x800", "600x-1", "axb"] {
+            assert!(invalid.parse::<PageSize>().is_err(), "{:?} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_layout_landscape_swaps_dimensions() {
+        let options = ConverterOptions {
+            page_size: PageSize::Letter,
+            orientation: Orientation::Landscape,
+            fit: FitMode::Contain,
+            ..Default::default()
+        };
+
+        let (page_width, page_height, _) = options.layout(100.0, 100.0);
+        assert_eq!((page_width, page_height), (792.0, 612.0));
+    }
+
+    #[test]
+    fn test_layout_contain_scales_and_centers() {
+        let options = ConverterOptions {
+            page_size: PageSize::Letter,
+            orientation: Orientation::Portrait,
+            fit: FitMode::Contain,
+            ..Default::default()
+        };
+
+        // A 2000x1000 SVG scales down to the page width, keeping 2:1 aspect.
+        let (_, _, [width, _, _, height, x, y]) = options.layout(2000.0, 1000.0);
+        assert_eq!(width, 612.0);
+        assert_eq!(height, 306.0);
+        assert_eq!(x, 0.0);
+        assert_eq!(y, (792.0 - 306.0) / 2.0);
+    }
+
+    #[test]
+    fn test_layout_actual_size_only_scales_down() {
+        let options = ConverterOptions {
+            page_size: PageSize::Letter,
+            orientation: Orientation::Portrait,
+            fit: FitMode::ActualSize,
+            ..Default::default()
+        };
+
+        // Business card stays at its intrinsic size, centered.
+        let (_, _, [width, _, _, height, x, y]) = options.layout(252.0, 144.0);
+        assert_eq!((width, height), (252.0, 144.0));
+        assert_eq!((x, y), (180.0, 324.0));
+
+        // Oversized content still shrinks to fit.
+        let (_, _, [width, _, _, height, _, _]) = options.layout(1224.0, 792.0);
+        assert_eq!((width, height), (612.0, 396.0));
+    }
+
+    #[test]
+    fn test_layout_margins() {
+        let options = ConverterOptions::default().with_margins(Margins {
+            top: 10.0,
+            right: 20.0,
+            bottom: 30.0,
+            left: 40.0,
+        });
+
+        // Intrinsic pages grow by the margins
+        let (page_width, page_height, [_, _, _, _, x, y]) = options.layout(100.0, 50.0);
+        assert_eq!((page_width, page_height), (160.0, 90.0));
+        assert_eq!((x, y), (40.0, 30.0));
+
+        // Fixed pages keep their size and fit the content inside the margins
+        let options = ConverterOptions {
+            page_size: PageSize::Letter,
+            ..options
+        };
+        let (page_width, _, [width, _, _, _, x, _]) = options.layout(2000.0, 1000.0);
+        assert_eq!(page_width, 612.0);
+        assert_eq!(width, 552.0);
+        assert_eq!(x, 40.0);
+    }
+
+    #[test]
+    fn test_convert_with_report_lists_warnings() {
+        // No fonts are loaded, so the text can't be shaped
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <rect width="100" height="50" fill="blue"/>
+            <foreignObject width="100" height="50"><div xmlns="http://www.w3.org/1999/xhtml">HTML</div></foreignObject>
+            <text x="10" y="20">Hello</text>
+        </svg>"#;
+
+        let temp = NamedTempFile::new().unwrap();
+        let report = converter
+            .convert_with_report(svg, temp.path().to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(report.output_bytes as u64, fs::metadata(temp.path()).unwrap().len());
+        let digest = Sha256::digest(fs::read(temp.path()).unwrap());
+        assert_eq!(report.output_sha256, format!("{:x}", digest));
+        assert_eq!(
+            report.warnings,
+            vec![
+                ConversionWarning::UnsupportedElement {
+                    page: 1,
+                    element: "foreignObject".to_string(),
+                    count: 1,
+                },
+                ConversionWarning::TextDropped { page: 1, count: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_convert_with_report_clean_svg() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <defs><text id="unused">Hidden</text></defs>
+            <rect width="100" height="50" fill="blue"/>
+        </svg>"#;
+
+        let temp = NamedTempFile::new().unwrap();
+        let report = converter
+            .convert_with_report(svg, temp.path().to_str().unwrap())
+            .unwrap();
+
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_convert_gzipped_svg() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="blue"/></svg>"#;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(svg.as_bytes()).unwrap();
+        let svgz = encoder.finish().unwrap();
+
+        let plain = NamedTempFile::new().unwrap();
+        let gzipped = NamedTempFile::new().unwrap();
+        converter.convert(svg, plain.path().to_str().unwrap()).unwrap();
+        converter
+            .convert_raw(&svgz, gzipped.path().to_str().unwrap())
+            .unwrap();
+
+        assert_eq!(fs::read(plain.path()).unwrap(), fs::read(gzipped.path()).unwrap());
+        assert_eq!(converter.validate_raw(&svgz).unwrap().width, 100.0);
+        assert_eq!(converter.validate_raw(svg.as_bytes()).unwrap().width, 100.0);
+
+        // The size limit applies to the decompressed SVG
+        let limited = SvgToPdfConverter::new().with_max_svg_bytes(16);
+        assert!(matches!(
+            limited.validate_raw(&svgz),
+            Err(ConvertError::TooLarge { limit: 16, .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_gzip_is_a_decode_error() {
+        let converter = SvgToPdfConverter::new();
+        let truncated = [0x1f, 0x8b, 0x08, 0x00, 0x00];
+
+        let err = converter.validate_raw(&truncated).unwrap_err();
+        assert!(matches!(err, ConvertError::Decode(_)));
+        assert!(err.to_string().contains("Invalid gzip stream"));
+    }
+
+    #[test]
+    fn test_deterministic_output_is_byte_identical() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="blue"/></svg>"#;
+        let options = ConverterOptions::default()
+            .with_metadata(PdfMetadata {
+                title: Some("Golden".to_string()),
+                ..Default::default()
+            })
+            .with_deterministic(true);
+
+        let start = DateTime::parse_from_rfc3339("2025-11-11T12:00:00Z").unwrap().with_timezone(&Utc);
+        let clock = Arc::new(ManualClock::new(start));
+        let converter = converter.with_clock(clock.clone());
+
+        let first = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        clock.advance(chrono::Duration::seconds(90));
+        let second = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        assert_eq!(first, second);
+
+        // Otherwise the creation date is the clock's
+        let dated = converter
+            .convert_to_bytes_with_options(svg, &options.with_deterministic(false))
+            .unwrap();
+        assert!(String::from_utf8_lossy(&dated).contains("D:20251111120130"));
+    }
+
+    #[test]
+    fn test_bleed_expands_media_box() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="blue"/></svg>"#;
+        let bleed = 3.0 * PT_PER_MM;
+        let options = ConverterOptions::default()
+            .with_compression(CompressionLevel::None)
+            .with_bleed(bleed);
+
+        let pdf = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        let page_box = |name: &str| -> Vec<f32> {
+            pdf.split(&format!("/{name} ["))
+                .nth(1)
+                .and_then(|rest| rest.split(']').next())
+                .unwrap()
+                .split_whitespace()
+                .map(|n| n.parse().unwrap())
+                .collect()
+        };
+        let close = |actual: &[f32], expected: [f32; 4]| {
+            actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 0.01)
+        };
+        // Trim marks get a slug of their own outside the bleed
+        let slug = TRIM_MARK_SLUG;
+        let outer = slug + bleed;
+        assert!(close(
+            &page_box("MediaBox"),
+            [0.0, 0.0, 100.0 + 2.0 * outer, 50.0 + 2.0 * outer]
+        ));
+        assert!(close(
+            &page_box("BleedBox"),
+            [slug, slug, 100.0 + 2.0 * bleed + slug, 50.0 + 2.0 * bleed + slug]
+        ));
+        assert!(close(&page_box("TrimBox"), [outer, outer, 100.0 + outer, 50.0 + outer]));
+        // Each mark runs from the media edge to the bleed edge only
+        let mark = format!("0 {outer} m\n{slug} {outer} l");
+        assert!(pdf.contains(&mark), "{}", pdf);
+
+        let pdf = converter
+            .convert_to_bytes_with_options(svg, &options.with_bleed(0.0))
+            .unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/MediaBox [0 0 100 50]"));
+        assert!(!pdf.contains("/TrimBox"));
+    }
+
+    #[test]
+    fn test_convert_with_page_size() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="252" height="144">
+            <rect width="252" height="144" fill="green"/>
+        </svg>"#;
+        let options = ConverterOptions {
+            page_size: PageSize::Letter,
+            ..Default::default()
+        };
+
+        let temp = NamedTempFile::new().unwrap();
+        converter
+            .convert_with_options(svg, temp.path().to_str().unwrap(), &options)
+            .unwrap();
+
+        let pdf = std::fs::read(temp.path()).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/MediaBox [0 0 612 792]"));
+    }
+
+    #[test]
+    fn test_reload_config_during_conversions() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="252" height="144">
+            <rect width="252" height="144" fill="green"/>
+        </svg>"#;
+        let intrinsic = "/MediaBox [0 0 252 144]";
+        let letter = "/MediaBox [0 0 612 792]";
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let converter = converter.clone();
+                std::thread::spawn(move || {
+                    (0..10)
+                        .map(|_| {
+                            let pdf = converter.convert_to_bytes(svg).unwrap();
+                            String::from_utf8_lossy(&pdf).into_owned()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for i in 0..20 {
+            let page_size = if i % 2 == 0 { PageSize::Letter } else { PageSize::Intrinsic };
+            converter.reload_config(ConverterOptions {
+                page_size,
+                ..Default::default()
+            });
+        }
+        converter.reload_config(ConverterOptions {
+            page_size: PageSize::Letter,
+            ..Default::default()
+        });
+
+        // Every conversion saw one whole set of options or the other
+        for worker in workers {
+            for pdf in worker.join().unwrap() {
+                assert!(pdf.contains(intrinsic) != pdf.contains(letter));
+            }
+        }
+        assert_eq!(converter.options().page_size, PageSize::Letter);
+        let pdf = converter.convert_to_bytes(svg).unwrap();
+        assert!(String::from_utf8_lossy(&pdf).contains(letter));
+    }
+
+    #[tokio::test]
+    async fn test_convert_with_timeout_fires() {
+        let converter = SvgToPdfConverter::new();
+        let mut svg = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="1000">"#);
+        for i in 0..20_000 {
+            svg.push_str(&format!(
+                r#"<g><rect x="{}" y="{}" width="5" height="5" fill="red"/></g>"#,
+                i % 1000,
+                i / 20
+            ));
+        }
+        svg.push_str("</svg>");
+
+        let temp = NamedTempFile::new().unwrap();
+        let result = converter
+            .convert_with_timeout(&svg, temp.path().to_str().unwrap(), Duration::from_millis(1))
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("Conversion timeout"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_conversion_writes_nothing() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abandoned.pdf");
+
+        let (start, started) = std::sync::mpsc::channel::<()>();
+        let output_path = path.to_string_lossy().into_owned();
+        let task = converter.spawn_conversion(move |converter| {
+            // Still converting when the caller gives up
+            let _ = started.recv();
+            converter.convert(svg, &output_path)
+        });
+        let abandoned = task.abandon().expect("the conversion is still running");
+        start.send(()).unwrap();
+        abandoned.finished().await;
+        assert!(!path.exists());
+
+        // A conversion that is waited for writes as usual
+        let output_path = path.to_string_lossy().into_owned();
+        let mut task = converter.spawn_conversion(move |converter| converter.convert(svg, &output_path));
+        task.wait(Duration::from_secs(30)).await.unwrap();
+        assert!(task.abandon().is_none());
+        assert!(path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_conversion_writes_nothing() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("timed-out.pdf");
+
+        let (start, started) = std::sync::mpsc::channel::<()>();
+        let (done, finished) = std::sync::mpsc::channel();
+        let output_path = path.to_string_lossy().into_owned();
+        let result = converter
+            .run_with_timeout(Duration::from_millis(10), move |converter| {
+                // Only finishes once the deadline has passed
+                let _ = started.recv();
+                let result = converter.convert(svg, &output_path);
+                done.send(()).unwrap();
+                result
+            })
+            .await;
+        assert!(matches!(result, Err(ConvertError::Timeout(_))));
+
+        start.send(()).unwrap();
+        tokio::task::spawn_blocking(move || finished.recv().unwrap())
+            .await
+            .unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_convert_with_timeout_completes() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect width="100" height="100" fill="blue"/>
+        </svg>"#;
+
+        let temp = NamedTempFile::new().unwrap();
+        converter
+            .convert_with_timeout(svg, temp.path().to_str().unwrap(), Duration::from_secs(30))
+            .await
+            .unwrap();
+
+        assert!(std::fs::metadata(temp.path()).unwrap().len() > 0);
+    }
+
+    #[test]
+    fn test_convert_multi_produces_one_page_per_svg() {
+        let converter = SvgToPdfConverter::new();
+        let pages = [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"><rect width="100" height="100" fill="red"/></svg>"#,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100"><rect width="200" height="100" fill="green"/></svg>"#,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="300"><rect width="100" height="300" fill="blue"/></svg>"#,
+        ];
+
+        let temp = NamedTempFile::new().unwrap();
+        converter
+            .convert_multi(&pages, temp.path().to_str().unwrap())
+            .unwrap();
+
+        let pdf = std::fs::read(temp.path()).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Count 3"));
+        assert_eq!(pdf.matches("/Type /Page\n").count(), 3);
+        assert!(pdf.contains("/MediaBox [0 0 200 100]"));
+        assert!(pdf.contains("/MediaBox [0 0 100 300]"));
+        assert!(!pdf.contains("/PageLabels"));
+    }
+
+    #[test]
+    fn test_convert_multi_writes_page_labels() {
+        let converter = SvgToPdfConverter::new();
+        let page = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"/>"#;
+        let pages = [page; 5];
+        let options = ConverterOptions::default().with_page_labels(vec![
+            "Cover".to_string(),
+            "Hero (mobile)".to_string(),
+            String::new(),
+        ]);
+
+        let pdf = converter.convert_multi_to_bytes_with_options(&pages, &options).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/PageLabels <<"));
+        assert!(pdf.contains("/Nums [0 "));
+        assert!(pdf.contains("/P (Cover)"));
+        assert!(pdf.contains("/P (Hero (mobile))"));
+        // The unlabelled pages after them are numbered 3 and 4
+        assert_eq!(pdf.matches("/Type /PageLabel\n").count(), 3);
+        assert!(pdf.contains("/S /D"));
+        assert!(pdf.contains("/St 3"));
+
+        assert_eq!(
+            page_label_ranges(&["a".to_string(), String::new(), "c".to_string()], 4),
+            [(0, Some("a")), (1, None), (2, Some("c")), (3, None)]
+        );
+        assert!(page_label_ranges(&[String::new()], 2).is_empty());
+    }
+
+    #[test]
+    fn test_raster_fallback_embeds_bitmap_for_filters() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="400">
+            <defs><filter id="blur"><feGaussianBlur stdDeviation="8"/></filter></defs>
+            <circle cx="200" cy="200" r="120" fill="teal" filter="url(#blur)"/>
+            <rect x="40" y="40" width="80" height="80" fill="orange"/>
+        </svg>"#;
+
+        let vector = converter
+            .convert_to_bytes_with_options(svg, &ConverterOptions::default())
+            .unwrap();
+        let fallback = converter
+            .convert_to_bytes_with_options(
+                svg,
+                &ConverterOptions::default()
+                    .with_render_mode(RenderMode::VectorWithRasterFallback { dpi: 300.0 }),
+            )
+            .unwrap();
+
+        assert!(
+            fallback.len() > vector.len(),
+            "fallback {} bytes, vector {} bytes",
+            fallback.len(),
+            vector.len()
+        );
+        let fallback = String::from_utf8_lossy(&fallback);
+        assert!(fallback.contains("/Subtype /Image"));
+        assert!(fallback.contains("/SMask"));
+    }
+
+    #[test]
+    fn test_raster_fallback_keeps_plain_svg_vector() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"><rect width="50" height="50" fill="red"/></svg>"#;
+
+        let pdf = converter
+            .convert_to_bytes_with_options(
+                svg,
+                &ConverterOptions::default()
+                    .with_render_mode(RenderMode::VectorWithRasterFallback { dpi: 150.0 }),
+            )
+            .unwrap();
+
+        assert!(!String::from_utf8_lossy(&pdf).contains("/Subtype /Image"));
+    }
+
+    #[test]
+    fn test_max_image_pixels_downsamples_embedded_image() {
+        use base64::Engine;
+
+        // A noisy 800x800 PNG that compresses poorly
+        let image = image::RgbImage::from_fn(800, 800, |x, y| {
+            let v = x.wrapping_mul(2_654_435_761).wrapping_add(y.wrapping_mul(40_503));
+            image::Rgb([v as u8, (v >> 8) as u8, (v >> 16) as u8])
+        });
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100"><image width="100" height="100" xlink:href="data:image/png;base64,{}"/></svg>"#,
+            base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+        );
+
+        let full = SvgToPdfConverter::new().convert_to_bytes(&svg).unwrap();
+        let capped = SvgToPdfConverter::new()
+            .with_max_image_pixels(100 * 100)
+            .convert_to_bytes(&svg)
+            .unwrap();
+
+        assert!(
+            capped.len() * 10 < full.len(),
+            "capped {} bytes vs full {} bytes",
+            capped.len(),
+            full.len()
+        );
+    }
+
+    #[test]
+    fn test_resource_cache_matches_independent_conversions() {
+        use base64::Engine;
+
+        let image = image::RgbImage::from_fn(400, 400, |x, y| image::Rgb([x as u8, y as u8, 0]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+        let href = format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png.into_inner())
+        );
+        let pages: Vec<String> = (0..3)
+            .map(|i| {
+                format!(
+                    r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="100" height="100"><image x="{}" width="50" height="50" xlink:href="{}"/></svg>"#,
+                    i * 10,
+                    href
+                )
+            })
+            .collect();
+        let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+        let options = ConverterOptions::default().with_deterministic(true);
+        let converter = SvgToPdfConverter::new().with_max_image_pixels(100 * 100);
+
+        let shared = converter.clone().with_resource_cache();
+        for page in &pages {
+            assert_eq!(
+                shared.convert_to_bytes_with_options(page, &options).unwrap(),
+                converter.convert_to_bytes_with_options(page, &options).unwrap()
+            );
+        }
+        // The image was decoded and downsampled once for all three pages
+        assert_eq!(shared.image_cache.as_ref().unwrap().images.lock().unwrap().len(), 1);
+
+        let independent: Vec<usvg::Tree> = pages.iter().map(|page| converter.parse(page).unwrap()).collect();
+        let independent: Vec<_> = independent.into_iter().map(|tree| (tree, Vec::new())).collect();
+        assert_eq!(
+            shared.convert_multi_to_bytes_with_options(&pages, &options).unwrap(),
+            render_pdf(&independent, &options, Utc::now(), &mut |_, _| {}).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_image_cache_keys_hrefs_by_base_directory() {
+        let resolver = usvg::ImageHrefResolver {
+            resolve_data: Box::new(|_, _, _| None),
+            resolve_string: Box::new(|href, options| {
+                let dir = options.resources_dir.as_ref()?;
+                Some(usvg::ImageKind::PNG(Arc::new(
+                    dir.join(href).to_string_lossy().into_owned().into_bytes(),
+                )))
+            }),
+        };
+        let cache = Arc::new(ImageCache::default());
+        let resolver = cached_image_resolver(resolver, cache.clone());
 
-impl SvgToPdfConverter {
-    /// Creates a new converter with default options.
-    pub fn new() -> Self {
-        Self
+        let resolve = |dir: Option<&str>| {
+            let options = usvg::Options {
+                resources_dir: dir.map(PathBuf::from),
+                ..usvg::Options::default()
+            };
+            match (resolver.resolve_string)("logo.png", &options) {
+                Some(usvg::ImageKind::PNG(data)) => Some(String::from_utf8(data.to_vec()).unwrap()),
+                _ => None,
+            }
+        };
+        // The same href in two documents' directories is two images
+        assert_eq!(resolve(Some("/docs/a")).as_deref(), Some("/docs/a/logo.png"));
+        assert_eq!(resolve(Some("/docs/b")).as_deref(), Some("/docs/b/logo.png"));
+        assert_eq!(resolve(None), None);
+        assert_eq!(resolve(Some("/docs/a")).as_deref(), Some("/docs/a/logo.png"));
+        assert_eq!(cache.images.lock().unwrap().len(), 3);
     }
 
-    /// Converts SVG content to PDF and writes to the specified output path.
-    ///
-    /// # Arguments
-    ///
-    /// * `svg_content` - UTF-8 SVG XML string
-    /// * `output_path` - Filesystem path for PDF output
-    ///
-    /// # Returns
-    ///
-    /// Returns `Ok(())` on success, or an error if parsing or rendering fails.
-    ///
-    /// # Errors
-    ///
-    /// - SVG parsing errors (malformed XML, unsupported features)
-    /// - File I/O errors (permissions, disk full)
-    /// - Rendering errors (out of memory, invalid dimensions)
-    pub fn convert(&self, svg_content: &str, output_path: &str) -> Result<()> {
-        info!("Converting SVG to PDF (VECTOR): output={}", output_path);
+    #[test]
+    fn test_image_cache_survives_a_poisoned_lock() {
+        let cache = Arc::new(ImageCache::default());
+        let poisoner = cache.clone();
+        let _ = std::thread::spawn(move || {
+            let _images = poisoner.images.lock().unwrap();
+            panic!("conversion panicked");
+        })
+        .join();
+        assert!(cache.images.is_poisoned());
 
-        // Parse SVG to usvg tree
-        let tree = usvg::Tree::from_str(svg_content, &usvg::Options::default())
-            .context("Failed to parse SVG content")?;
+        let key = || ImageKey::Href(None, "logo.png".to_string());
+        let png = || Some(usvg::ImageKind::PNG(Arc::new(vec![1, 2, 3])));
+        assert!(cache.get_or_resolve(key(), png).is_some());
+        // Served from the cache
+        assert!(cache.get_or_resolve(key(), || None).is_some());
+    }
 
-        // Validate tree has valid dimensions
-        let size = tree.size();
-        if size.width() <= 0.0 || size.height() <= 0.0 {
-            anyhow::bail!(
-                "Invalid SVG dimensions: {}x{}",
-                size.width(),
-                size.height()
-            );
+    /// Returns `true` if `svg` parses to a tree with an `<image>` in it.
+    fn has_image(converter: &SvgToPdfConverter, svg: &str) -> bool {
+        fn contains_image(group: &usvg::Group) -> bool {
+            group.children().iter().any(|node| match node {
+                usvg::Node::Image(_) => true,
+                usvg::Node::Group(group) => contains_image(group),
+                _ => false,
+            })
         }
+        contains_image(converter.parse(svg).unwrap().root())
+    }
 
-        info!(
-            "SVG parsed successfully: {}x{} units",
-            size.width(),
-            size.height()
+    #[test]
+    fn test_svg_data_uri_image_renders() {
+        use base64::Engine;
+
+        let inner = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><rect width="10" height="10" fill="red"/></svg>"#;
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20"><image width="20" height="20" href="data:image/svg+xml;base64,{}"/></svg>"#,
+            base64::engine::general_purpose::STANDARD.encode(inner)
         );
 
-        // Convert to PDF using svg2pdf (true vector conversion)
-        let pdf_data = svg2pdf::to_pdf(
-            &tree,
-            svg2pdf::ConversionOptions::default(),
-            svg2pdf::PageOptions::default()
+        // Allowed by the default policy
+        let converter = SvgToPdfConverter::new();
+        assert!(has_image(&converter, &svg));
+        let png = converter.convert_to_png_bytes(&svg, 1.0).unwrap();
+        let pixmap = resvg::tiny_skia::Pixmap::decode_png(&png).unwrap();
+        let pixel = pixmap.pixel(10, 10).unwrap();
+        assert_eq!((pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()), (255, 0, 0, 255));
+
+        let denied = SvgToPdfConverter::new().with_resource_policy(ResourcePolicy::Deny);
+        assert!(!has_image(&denied, &svg));
+    }
+
+    #[test]
+    fn test_remote_image_blocked_unless_allowlisted() {
+        use std::io::{BufRead, BufReader};
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::from_pixel(4, 4, image::Rgb([0, 0, 255]))
+            .write_to(&mut png, image::ImageFormat::Png)
+            .unwrap();
+        let png = png.into_inner();
+
+        // A minimal image server counting the requests it gets
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                served.fetch_add(1, Ordering::SeqCst);
+                let header = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    png.len()
+                );
+                stream.write_all(header.as_bytes()).unwrap();
+                stream.write_all(&png).unwrap();
+            }
+        });
+
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20"><image width="20" height="20" href="http://127.0.0.1:{}/logo.png"/></svg>"#,
+            port
         );
+        let allow = |host: &str| {
+            SvgToPdfConverter::new().with_resource_policy(ResourcePolicy::AllowRemote {
+                allowlist: vec![host.to_string()],
+            })
+        };
 
-        // Write PDF to file
-        fs::write(output_path, &pdf_data)
-            .with_context(|| format!("Failed to write PDF to {}", output_path))?;
+        // Not fetched under the default policy or for hosts off the allowlist
+        assert!(!has_image(&SvgToPdfConverter::new(), &svg));
+        assert!(!has_image(&allow("assets.example.com"), &svg));
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
 
-        info!("PDF export complete (VECTOR): {} bytes", pdf_data.len());
-        Ok(())
+        assert!(has_image(&allow("127.0.0.1"), &svg));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
     }
 
-}
+    #[test]
+    fn test_local_images_are_read_only_from_resources_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let resources = dir.path().join("doc");
+        fs::create_dir(&resources).unwrap();
+        let logo = image::RgbImage::from_pixel(4, 4, image::Rgb([0, 0, 255]));
+        logo.save(resources.join("logo.png")).unwrap();
+        logo.save(dir.path().join("secret.png")).unwrap();
+        let svg = |href: &str| {
+            format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="20"><image width="20" height="20" href="{}"/></svg>"#,
+                href
+            )
+        };
+        let absolute = svg(&resources.join("logo.png").display().to_string());
 
-impl Default for SvgToPdfConverter {
-    fn default() -> Self {
-        Self::new()
+        // Without a resources directory no file is read
+        assert!(!has_image(&SvgToPdfConverter::new(), &absolute));
+        assert!(!has_image(&SvgToPdfConverter::new(), &svg("logo.png")));
+        let remote = SvgToPdfConverter::new().with_resource_policy(ResourcePolicy::AllowRemote {
+            allowlist: vec!["localhost".to_string()],
+        });
+        assert!(!has_image(&remote, &absolute));
+
+        // With one, relative paths inside it are
+        let converter = SvgToPdfConverter::new().with_resources_dir(&resources);
+        assert!(has_image(&converter, &svg("logo.png")));
+        assert!(has_image(&converter, &svg("./logo.png")));
+        assert!(!has_image(&converter, &absolute));
+        assert!(!has_image(&converter, &svg("../secret.png")));
+        assert!(!has_image(&converter, &svg("missing.png")));
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path().join("secret.png"), resources.join("link.png")).unwrap();
+            assert!(!has_image(&converter, &svg("link.png")));
+        }
+        let denied = converter.with_resource_policy(ResourcePolicy::Deny);
+        assert!(!has_image(&denied, &svg("logo.png")));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::NamedTempFile;
+    #[test]
+    fn test_compression_shrinks_output() {
+        let converter = SvgToPdfConverter::new();
+        let mut svg = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="500" height="500">"#);
+        for i in 0..500 {
+            svg.push_str(&format!(
+                r##"<path d="M{} {} L{} {} Q250 250 {} {} Z" fill="#{:06x}" stroke="black"/>"##,
+                i % 500,
+                i / 2,
+                (i * 7) % 500,
+                (i * 3) % 500,
+                (i * 11) % 500,
+                (i * 13) % 500,
+                i * 2048
+            ));
+        }
+        svg.push_str("</svg>");
+
+        let convert = |compression| {
+            converter
+                .convert_to_bytes_with_options(
+                    &svg,
+                    &ConverterOptions::default().with_compression(compression),
+                )
+                .unwrap()
+        };
+        let uncompressed = convert(CompressionLevel::None);
+        let compressed = convert(CompressionLevel::Default);
+        let best = convert(CompressionLevel::Best);
+
+        assert!(
+            compressed.len() * 2 < uncompressed.len(),
+            "compressed {} bytes vs uncompressed {} bytes",
+            compressed.len(),
+            uncompressed.len()
+        );
+        assert!(best.len() <= compressed.len());
+        assert!(String::from_utf8_lossy(&compressed).contains("/FlateDecode"));
+    }
 
     #[test]
-    fn test_convert_simple_svg() {
+    fn test_color_parsing() {
+        assert_eq!("#ff8000".parse(), Ok(Color::rgb(255, 128, 0)));
+        assert_eq!("#F80".parse(), Ok(Color::rgb(255, 136, 0)));
+        assert_eq!("White".parse(), Ok(Color::WHITE));
+        assert_eq!(" black ".parse(), Ok(Color::BLACK));
+
+        for invalid in ["", "#12345", "#ggg", "#ééé", "chartreuse-ish"] {
+            assert!(invalid.parse::<Color>().is_err(), "{:?} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_background_flattens_transparency() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"><circle cx="50" cy="50" r="40" fill="red" fill-opacity="0.5"/></svg>"#;
+        let options = ConverterOptions::default().with_compression(CompressionLevel::None);
+
+        let transparent = converter
+            .convert_to_bytes_with_options(svg, &options)
+            .unwrap();
+        let white = converter
+            .convert_to_bytes_with_options(
+                svg,
+                &options.clone().with_background("#ffffff".parse().unwrap()),
+            )
+            .unwrap();
+
+        assert_ne!(transparent, white);
+        let white = String::from_utf8_lossy(&white);
+        assert!(white.contains("1 1 1 rg\n0 0 100 100 re\nf"));
+        assert!(!String::from_utf8_lossy(&transparent).contains(" re\nf"));
+    }
+
+    #[test]
+    fn test_crop_sizes_page_to_region() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="400" height="300" viewBox="0 0 800 600">
+            <rect width="800" height="600" fill="white"/>
+            <a href="https://example.com"><rect x="120" y="60" width="20" height="10" fill="blue"/></a>
+        </svg>"#;
+        let options = ConverterOptions::default()
+            .with_compression(CompressionLevel::None)
+            .with_crop(CropRect::new(100.0, 50.0, 200.0, 80.0));
+
+        let pdf = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/MediaBox [0 0 200 80]"));
+        // Links are placed relative to the crop
+        assert!(pdf.contains("/Rect [20 60 40 70]"));
+
+        let result = converter.convert_to_bytes_with_options(
+            svg,
+            &options.with_crop(CropRect::new(0.0, 0.0, 0.0, 80.0)),
+        );
+        assert!(matches!(result.unwrap_err().root(), ConvertError::InvalidDimensions(_)));
+    }
+
+    #[test]
+    fn test_links_become_link_annotations() {
         let converter = SvgToPdfConverter::new();
         let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
-            <rect x="10" y="10" width="80" height="80" fill="blue"/>
+            <a href="https://example.com/docs"><rect x="10" y="10" width="20" height="30" fill="blue"/></a>
         </svg>"#;
+        let options = ConverterOptions::default().with_compression(CompressionLevel::None);
 
-        let temp = NamedTempFile::new().unwrap();
-        let result = converter.convert(svg, temp.path().to_str().unwrap());
+        let pdf = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/Subtype /Link"));
+        assert!(pdf.contains("/URI (https://example.com/docs)"));
+        // SVG's top-left origin is flipped to PDF's bottom-left one
+        assert!(pdf.contains("/Rect [10 60 30 90]"));
 
-        assert!(result.is_ok());
-        assert!(temp.path().exists());
+        let pdf = converter
+            .convert_to_bytes_with_options(svg, &options.with_preserve_links(false))
+            .unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(!pdf.contains("/Link"));
+        assert!(!pdf.contains("/Annots"));
+    }
 
-        // Verify PDF is not empty
-        let metadata = temp.as_file().metadata().unwrap();
-        assert!(metadata.len() > 0);
+    #[test]
+    fn test_cmyk_output_declares_output_intent() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"><rect width="50" height="50" fill="red"/></svg>"#;
+        let options = ConverterOptions::default()
+            .with_compression(CompressionLevel::None)
+            .with_background(Color::rgb(255, 0, 0));
+
+        let rgb = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        let rgb = String::from_utf8_lossy(&rgb);
+        assert!(!rgb.contains("/DeviceCMYK"));
+        assert!(!rgb.contains("/OutputIntents"));
+
+        let cmyk = converter
+            .convert_to_bytes_with_options(svg, &options.with_color_space(ColorSpace::CmykIntent))
+            .unwrap();
+        let cmyk = String::from_utf8_lossy(&cmyk);
+        assert!(cmyk.contains("/CS /DeviceCMYK"));
+        // The artwork itself stays sRGB for the RIP to separate
+        assert!(cmyk.contains("1 0 0 scn"));
+        assert!(cmyk.contains("/OutputIntents"));
+        assert!(cmyk.contains("/S /GTS_PDFX"));
+        assert!(cmyk.contains("(CGATS TR 001)"));
+        // The background is painted in process colors
+        assert!(cmyk.contains("0 1 1 0 k\n0 0 100 100 re\nf"));
     }
 
     #[test]
-    fn test_invalid_svg() {
+    fn test_cmyk_embeds_icc_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut header = vec![0u8; 128];
+        header[16..20].copy_from_slice(b"CMYK");
+        let cmyk_profile = dir.path().join("CoatedFOGRA39.icc");
+        fs::write(&cmyk_profile, &header).unwrap();
+        header[16..20].copy_from_slice(b"RGB ");
+        let rgb_profile = dir.path().join("sRGB.icc");
+        fs::write(&rgb_profile, &header).unwrap();
+
         let converter = SvgToPdfConverter::new();
-        let invalid_svg = "not an svg";
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+        let options = ConverterOptions::default()
+            .with_compression(CompressionLevel::None)
+            .with_color_space(ColorSpace::CmykIntent);
+
+        let pdf = converter
+            .convert_to_bytes_with_options(svg, &options.clone().with_icc_profile(&cmyk_profile))
+            .unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+        assert!(pdf.contains("/DestOutputProfile"));
+        assert!(pdf.contains("/Info (CoatedFOGRA39)"));
+        assert!(pdf.contains("/N 4"));
+
+        let err = converter
+            .convert_to_bytes_with_options(svg, &options.clone().with_icc_profile(&rgb_profile))
+            .unwrap_err();
+        assert!(err.to_string().contains("is not a CMYK profile"), "{}", err);
+
+        // Without CMYK output the profile is ignored
+        let rgb = converter
+            .convert_to_bytes_with_options(
+                svg,
+                &ConverterOptions::default().with_icc_profile(&rgb_profile),
+            )
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&rgb).contains("/OutputIntents"));
+    }
+
+    #[test]
+    fn test_color_to_cmyk() {
+        assert_eq!(Color::WHITE.to_cmyk(), (0.0, 0.0, 0.0, 0.0));
+        assert_eq!(Color::BLACK.to_cmyk(), (0.0, 0.0, 0.0, 1.0));
+        assert_eq!(Color::rgb(255, 0, 0).to_cmyk(), (0.0, 1.0, 1.0, 0.0));
+        let (c, m, y, k) = Color::rgb(0, 0, 128).to_cmyk();
+        assert_eq!((c, m, y), (1.0, 1.0, 0.0));
+        assert!((k - 0.498).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_validate_valid_svg() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="120" height="80">
+            <rect width="10" height="10" fill="red"/>
+            <circle cx="40" cy="40" r="10" fill="blue"/>
+        </svg>"#;
+
+        let info = converter.validate(svg).unwrap();
+        assert_eq!(info.width, 120.0);
+        assert_eq!(info.height, 80.0);
+        assert_eq!(info.node_count, 2);
+    }
+
+    #[test]
+    fn test_bom_and_prolog_prefixed_svgs_convert() {
+        let converter = SvgToPdfConverter::new();
+        let body = r#"<svg xmlns="http://www.w3.org/2000/svg" width="120" height="80">
+            <a href="https://example.com/"><rect width="10" height="10" fill="red"/></a>
+        </svg>"#;
+        let options = ConverterOptions::default().with_deterministic(true);
+        let clean = converter.convert_to_bytes_with_options(body, &options).unwrap();
+
+        for prefix in [
+            "\u{feff}",
+            "\u{feff}\u{feff}",
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "\u{feff}<?xml version=\"1.0\" standalone=\"no\"?>\r\n",
+            "\n  <?xml version=\"1.0\"?>\n",
+            "\u{feff}\r\n<?xml version='1.0'?>\n<!-- Generator: WireTuner -->\n",
+        ] {
+            let svg = format!("{}{}", prefix, body);
+            let info = converter.validate(&svg).unwrap();
+            assert_eq!((info.width, info.height), (120.0, 80.0), "prefix {:?}", prefix);
+            let pdf = converter.convert_to_bytes_with_options(&svg, &options).unwrap();
+            assert_eq!(pdf, clean, "prefix {:?}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_validate_malformed_svg() {
+        let converter = SvgToPdfConverter::new();
+        assert!(converter.validate("<svg><rect").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_points_at_malformed_element() {
+        let converter = SvgToPdfConverter::new();
+        let svg = "\n<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"100\" height=\"100\">\n  <circle cx=\"50\" cy=\"50\" r=\"40\"/>\n  <rect x=\"1\" y=\"2\" x=\"3\" width=\"10\" height=\"10\"/>\n</svg>";
+
+        let err = converter.convert_to_bytes(svg).unwrap_err();
+        let ConvertError::Parse { location, .. } = err.root() else {
+            panic!("expected a parse error, got {:?}", err);
+        };
+        assert_eq!(
+            location.as_ref(),
+            Some(&ParseLocation {
+                line: 4,
+                column: 21,
+                element: Some("rect".to_string()),
+            })
+        );
+        let message = err.to_string();
+        assert!(message.contains("attribute 'x' at 4:21"), "{}", message);
+        assert!(message.contains("(in <rect>)"), "{}", message);
+
+        // Lines stay counted from the caller's first line past an XML
+        // declaration, which usvg only accepts with the whitespace before it
+        // trimmed
+        let declared = format!("\n<?xml version=\"1.0\"?>{}", svg);
+        let err = converter.convert_to_bytes(&declared).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            ConvertError::Parse { location: Some(ParseLocation { line: 5, column: 21, .. }), .. }
+        ));
+
+        // Truncated documents have no position to point at
+        let err = converter.convert_to_bytes("<svg><rect").unwrap_err();
+        assert!(matches!(err.root(), ConvertError::Parse { location: None, .. }));
+    }
+
+    #[test]
+    fn test_validate_zero_size_svg() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="0" height="0"/>"#;
+        assert!(converter.validate(svg).is_err());
+    }
+
+    #[test]
+    fn test_oversized_svg_rejected_before_parsing() {
+        let converter = SvgToPdfConverter::new().with_max_svg_bytes(64);
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">{}</svg>"#,
+            "<g/>".repeat(32)
+        );
+
+        let err = converter.convert_to_bytes(&svg).unwrap_err();
+        assert!(matches!(err.root(), ConvertError::TooLarge { limit: 64, .. }));
+        assert!(err.to_string().contains("SVG too large"));
+        assert!(converter.validate(&svg).is_err());
+
+        // The same SVG is fine under the default limit
+        assert!(SvgToPdfConverter::new().validate(&svg).is_ok());
+    }
+
+    #[test]
+    fn test_entity_declarations_are_rejected() {
+        let converter = SvgToPdfConverter::new();
+        let benign = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- Exported by an editor -->
+<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><text>&lt;!ENTITY&gt;</text></svg>"#;
+        assert!(check_svg_entities(benign).is_ok());
+        assert!(converter.convert_to_bytes(benign).is_ok());
+
+        let xxe = r#"<?xml version="1.0"?>
+<!DOCTYPE svg [ <!ENTITY secret SYSTEM "file:///etc/passwd"> ]>
+<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><text>&secret;</text></svg>"#;
+        let err = converter.convert_to_bytes(xxe).unwrap_err();
+        assert!(matches!(err.root(), ConvertError::Security(_)), "unexpected error: {}", err);
+        assert_eq!(crate::job::ErrorKind::from(&err), crate::job::ErrorKind::Security);
+
+        // Internal entities and lowercase declarations are refused too
+        let laughs = r#"<!doctype svg [<!entity lol "lol"><!ENTITY lol2 "&lol;&lol;">]><svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+        assert!(matches!(check_svg_entities(laughs), Err(ConvertError::Security(_))));
+
+        // Declarations after quoted `]`s and comments in the subset are found
+        let hidden = r#"<!DOCTYPE svg [<!ATTLIST svg a CDATA "]>"><!-- ]> --><!ENTITY x "x">]><svg/>"#;
+        assert!(matches!(check_svg_entities(hidden), Err(ConvertError::Security(_))));
+    }
+
+    #[test]
+    fn test_entity_text_outside_the_doctype_is_allowed() {
+        let converter = SvgToPdfConverter::new();
+        for svg in [
+            r#"<!DOCTYPE svg PUBLIC "-//W3C//DTD SVG 1.1//EN" "http://www.w3.org/Graphics/SVG/1.1/DTD/svg11.dtd">
+<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><!-- <!ENTITY x "x"> --><text><![CDATA[<!ENTITY y "y">]]></text></svg>"#,
+            r#"<!DOCTYPE svg [<!-- <!ENTITY in a comment --><!ELEMENT svg ANY>]>
+<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"><text><![CDATA[<!ENTITY y "y">]]></text></svg>"#,
+        ] {
+            assert!(check_svg_entities(svg).is_ok(), "{}", svg);
+            assert!(converter.convert_to_bytes(svg).is_ok(), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_convert_to_png() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="blue"/></svg>"#;
 
         let temp = NamedTempFile::new().unwrap();
-        let result = converter.convert(invalid_svg, temp.path().to_str().unwrap());
+        converter
+            .convert_to_png(svg, temp.path().to_str().unwrap(), 2.0)
+            .unwrap();
 
-        assert!(result.is_err());
+        let png = std::fs::read(temp.path()).unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // IHDR width and height follow the signature and chunk header
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+        assert_eq!((width, height), (200, 100));
     }
 
     #[test]
-    fn test_zero_dimensions() {
+    fn test_convert_to_eps() {
         let converter = SvgToPdfConverter::new();
-        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="0" height="0"></svg>"#;
+        let vector = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <rect width="100" height="50" fill="blue"/>
+            <path d="M10 10 Q 50 40 90 10" fill="none" stroke="red" stroke-width="2" stroke-dasharray="4 2"/>
+        </svg>"#;
 
         let temp = NamedTempFile::new().unwrap();
-        let result = converter.convert(svg, temp.path().to_str().unwrap());
+        let written = converter
+            .convert_to_eps(vector, temp.path().to_str().unwrap())
+            .unwrap();
+        let eps = std::fs::read_to_string(temp.path()).unwrap();
+        assert_eq!(written, eps.len());
+        assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n"));
+        assert!(eps.contains("%%BoundingBox: 0 0 100 50\n"));
+        assert!(eps.contains("0 0 1 setrgbcolor"));
+        assert!(eps.contains("curveto") && eps.contains("[4 2] 0 setdash"));
+        assert!(!eps.contains(" image"));
+        assert!(eps.ends_with("%%EOF\n"));
+
+        // Transparency can't be expressed in PostScript
+        let translucent = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50">
+            <rect width="100" height="50" fill="blue" opacity="0.5"/>
+        </svg>"#;
+        let eps = String::from_utf8(converter.convert_to_eps_bytes(translucent).unwrap()).unwrap();
+        assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n"));
+        assert!(eps.contains("/Width 209 /Height 105"));
+        assert!(eps.contains("/FlateDecode filter >> image"));
+        assert!(eps.ends_with("%%EOF\n"));
+    }
+
+    #[test]
+    fn test_dry_run_reports_size_without_writing() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="blue"/></svg>"#;
+        let dir = tempfile::tempdir().unwrap();
+        let options = ConverterOptions::default().with_deterministic(true);
+        let converter = SvgToPdfConverter::new().with_dry_run(true);
+
+        let pdf_path = dir.path().join("out.pdf");
+        let report = converter
+            .convert_multi_with_report(&[svg, svg], pdf_path.to_str().unwrap(), &options, |_, _| {})
+            .unwrap();
+        let pdf = converter
+            .convert_multi_to_bytes_with_options(&[svg, svg], &options)
+            .unwrap();
+        assert_eq!(report.output_bytes, pdf.len());
+        assert_eq!(report.output_sha256, format!("{:x}", Sha256::digest(&pdf)));
+        assert!(!pdf_path.exists());
+
+        let png_path = dir.path().join("out.png");
+        let png_bytes = converter
+            .convert_to_png(svg, png_path.to_str().unwrap(), 2.0)
+            .unwrap();
+        assert_eq!(png_bytes, converter.convert_to_png_bytes(svg, 2.0).unwrap().len());
+        assert!(!png_path.exists());
+
+        let single_path = dir.path().join("single.pdf");
+        converter.convert(svg, single_path.to_str().unwrap()).unwrap();
+        assert!(!single_path.exists());
+    }
+
+    #[test]
+    fn test_convert_multi_reports_progress_per_page() {
+        let converter = SvgToPdfConverter::new();
+        let page = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+
+        let mut reported = Vec::new();
+        converter
+            .convert_multi_to_bytes_with_progress(
+                &[page, page, page],
+                &ConverterOptions::default(),
+                |done, total| reported.push((done, total)),
+            )
+            .unwrap();
+
+        assert_eq!(reported, vec![(1, 3), (2, 3), (3, 3)]);
+    }
+
+    #[test]
+    fn test_convert_multi_rejects_empty_and_invalid_pages() {
+        let converter = SvgToPdfConverter::new();
+        let options = ConverterOptions::default();
+
+        assert!(converter.convert_multi_to_bytes_with_options(&[], &options).is_err());
+
+        let pages = [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"/>"#,
+            "not an svg",
+        ];
+        let err = converter
+            .convert_multi_to_bytes_with_options(&pages, &options)
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("page 2"));
+    }
+
+    #[test]
+    fn test_metadata_embedded_in_pdf() {
+        let converter = SvgToPdfConverter::new();
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">
+            <rect width="100" height="100" fill="red"/>
+        </svg>"#;
+        let options = ConverterOptions::default().with_metadata(PdfMetadata {
+            title: Some("doc-123".to_string()),
+            author: Some("user-42".to_string()),
+            creator: Some("WireTuner 0.1.0".to_string()),
+            creation_date: Some("2025-11-11T12:00:00Z".parse().unwrap()),
+            ..Default::default()
+        });
+
+        let pdf = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.contains("/Title (doc-123)"));
+        assert!(pdf.contains("/Author (user-42)"));
+        assert!(pdf.contains("/Creator (WireTuner 0.1.0)"));
+        assert!(pdf.contains("/CreationDate (D:20251111120000"));
+    }
+
+    #[test]
+    fn test_custom_font_dir_embeds_glyphs() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="50">
+            <text x="10" y="30" font-family="Tuffy" font-size="20">Hello fonts</text>
+        </svg>"#;
+        let fonts_dir = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/test/fixtures/fonts"));
+
+        let without_fonts = NamedTempFile::new().unwrap();
+        SvgToPdfConverter::new()
+            .convert(svg, without_fonts.path().to_str().unwrap())
+            .unwrap();
+
+        let with_fonts = NamedTempFile::new().unwrap();
+        SvgToPdfConverter::new()
+            .with_font_dirs(vec![fonts_dir])
+            .convert(svg, with_fonts.path().to_str().unwrap())
+            .unwrap();
+
+        let without_len = std::fs::metadata(without_fonts.path()).unwrap().len();
+        let with_len = std::fs::metadata(with_fonts.path()).unwrap().len();
+        assert!(
+            with_len > without_len,
+            "embedded font should grow the PDF ({} <= {})",
+            with_len,
+            without_len
+        );
+    }
+
+    #[test]
+    fn test_font_data_matches_font_dir() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="50">
+            <text x="10" y="30" font-family="Tuffy" font-size="20">Hello fonts</text>
+        </svg>"#;
+        let font_path = concat!(env!("CARGO_MANIFEST_DIR"), "/test/fixtures/fonts/Tuffy.ttf");
+
+        let converter = SvgToPdfConverter::new().with_font_data(std::fs::read(font_path).unwrap());
+        let temp = NamedTempFile::new().unwrap();
+        converter.convert(svg, temp.path().to_str().unwrap()).unwrap();
+
+        let pdf = std::fs::read(temp.path()).unwrap();
+        assert!(String::from_utf8_lossy(&pdf).contains("Tuffy"));
+    }
+
+    #[test]
+    fn test_outline_text_mode_embeds_no_fonts() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="50">
+            <text x="10" y="30" font-family="Tuffy" font-size="20">Hello fonts</text>
+        </svg>"#;
+        let font_path = concat!(env!("CARGO_MANIFEST_DIR"), "/test/fixtures/fonts/Tuffy.ttf");
+        let converter = SvgToPdfConverter::new().with_font_data(std::fs::read(font_path).unwrap());
+        let options = ConverterOptions::default().with_compression(CompressionLevel::None);
+
+        let embedded = converter
+            .convert_to_bytes_with_options(svg, &options.clone().with_text_mode(TextMode::Embed))
+            .unwrap();
+        let outlined = converter
+            .convert_to_bytes_with_options(svg, &options.with_text_mode(TextMode::Outline))
+            .unwrap();
+
+        assert_ne!(embedded, outlined);
+        let embedded = String::from_utf8_lossy(&embedded);
+        let outlined = String::from_utf8_lossy(&outlined);
+        assert!(embedded.contains("/Type /Font") && embedded.contains("/FontFile"));
+        assert!(!outlined.contains("/Type /Font") && !outlined.contains("/FontFile"));
+    }
+
+    #[test]
+    fn test_subset_fonts_shrinks_embedded_fonts() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="50">
+            <text x="10" y="30" font-family="Tuffy" font-size="20">Hi there</text>
+        </svg>"#;
+        let font_path = concat!(env!("CARGO_MANIFEST_DIR"), "/test/fixtures/fonts/Tuffy.ttf");
+        let font = std::fs::read(font_path).unwrap();
+        let converter = SvgToPdfConverter::new().with_font_data(font.clone());
+        let options = ConverterOptions::default();
+        assert!(options.subset_fonts);
+
+        let subset = converter.convert_to_bytes_with_options(svg, &options).unwrap();
+        let full = converter
+            .convert_to_bytes_with_options(svg, &options.clone().with_subset_fonts(false))
+            .unwrap();
+        assert!(
+            subset.len() * 2 < full.len(),
+            "subset fonts should be much smaller ({} vs {} bytes)",
+            subset.len(),
+            full.len()
+        );
+        assert!(String::from_utf8_lossy(&subset).contains("+Tuffy"));
+        assert!(!String::from_utf8_lossy(&full).contains("+Tuffy"));
+
+        // Uncompressed, the whole font file is embedded as is
+        let uncompressed = converter
+            .convert_to_bytes_with_options(
+                svg,
+                &options.with_subset_fonts(false).with_compression(CompressionLevel::None),
+            )
+            .unwrap();
+        assert!(find(&uncompressed, &font).is_some());
+        assert!(String::from_utf8_lossy(&uncompressed).contains(&format!("/Length1 {}", font.len())));
+    }
 
+    #[test]
+    fn test_parse_options_default_font_family() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="50">
+            <text x="10" y="30" font-size="20">Hello fonts</text>
+        </svg>"#;
+        let font_path = concat!(env!("CARGO_MANIFEST_DIR"), "/test/fixtures/fonts/Tuffy.ttf");
+        let converter = SvgToPdfConverter::new().with_font_data(std::fs::read(font_path).unwrap());
+
+        let default_family = converter.convert_to_bytes(svg).unwrap();
+        let tuffy = converter
+            .with_parse_options(ParseOptions::default().with_font_family("Tuffy"))
+            .convert_to_bytes(svg)
+            .unwrap();
+
+        assert_ne!(default_family, tuffy);
+        assert!(!String::from_utf8_lossy(&default_family).contains("Tuffy"));
+        assert!(String::from_utf8_lossy(&tuffy).contains("Tuffy"));
+    }
+
+    #[test]
+    fn test_render_svg_helpers_match_converter() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50"><rect width="100" height="50" fill="blue"/></svg>"#;
+        let options = ConverterOptions {
+            page_size: PageSize::A4,
+            ..ConverterOptions::default()
+        };
+
+        let pdf = render_svg_to_pdf(svg, options.clone()).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+        assert!(String::from_utf8_lossy(&pdf).contains("/MediaBox [0 0 595.27"));
+
+        let temp = NamedTempFile::new().unwrap();
+        render_svg_to_file(svg, temp.path().to_str().unwrap(), options).unwrap();
+        assert!(std::fs::read(temp.path()).unwrap().starts_with(b"%PDF"));
+
+        assert!(render_svg_to_pdf("not svg", ConverterOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_interrupted_write_leaves_destination_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.pdf");
+
+        // Fails halfway through, as if the worker died mid-write
+        let result = write_atomic(&path, |file| {
+            file.write_all(b"%PDF-1.7 partial")?;
+            Err(io::Error::new(io::ErrorKind::Interrupted, "killed"))
+        });
+        assert!(result.is_err());
+        assert!(!path.exists());
+
+        // An existing file is only ever replaced by a complete one
+        LocalSink.write(path.to_str().unwrap(), b"complete").unwrap();
+        let result = write_atomic(&path, |file| {
+            file.write_all(b"trunc")?;
+            Err(io::Error::new(io::ErrorKind::Interrupted, "killed"))
+        });
         assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"complete");
+
+        // No temporary files are left behind
+        let entries: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_full_write_is_not_retryable() {
+        const ENOSPC: i32 = 28;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.pdf");
+
+        /// Runs out of space partway through every write.
+        struct FullDisk(io::ErrorKind);
+
+        impl OutputSink for FullDisk {
+            fn write(&self, path: &str, bytes: &[u8]) -> io::Result<()> {
+                write_atomic(Path::new(path), |file| {
+                    file.write_all(&bytes[..4])?;
+                    Err(match self.0 {
+                        io::ErrorKind::StorageFull => io::Error::from_raw_os_error(ENOSPC),
+                        kind => io::Error::new(kind, "killed"),
+                    })
+                })
+            }
+        }
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+        let output = path.to_str().unwrap();
+        let error = SvgToPdfConverter::new()
+            .with_output_sink(Arc::new(FullDisk(io::ErrorKind::StorageFull)))
+            .convert(svg, output)
+            .unwrap_err();
+
+        assert!(error.is_disk_full());
+        let kind = crate::job::ErrorKind::from(&error);
+        assert_eq!(kind, crate::job::ErrorKind::DiskFull);
+        assert!(!kind.is_retryable());
+        assert!(!path.exists());
+
+        let other = SvgToPdfConverter::new()
+            .with_output_sink(Arc::new(FullDisk(io::ErrorKind::Interrupted)))
+            .convert(svg, output)
+            .unwrap_err();
+        assert!(!other.is_disk_full());
+        assert_eq!(crate::job::ErrorKind::from(&other), crate::job::ErrorKind::Io);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_atomic_write_keeps_readable_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("out.pdf");
+        LocalSink.write(path.to_str().unwrap(), b"%PDF").unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o644);
     }
 }
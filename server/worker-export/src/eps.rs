@@ -0,0 +1,260 @@
+//! Encapsulated PostScript output.
+//!
+//! Pages drawn only with solid-colored, fully opaque fills and strokes are
+//! written as PostScript paths, one `concat` per path for its transform.
+//! Anything PostScript can't express directly (gradients, patterns,
+//! transparency, clipping, masks, filters, blend modes and raster images)
+//! makes [`vector`] decline the page, and the caller embeds a rasterized
+//! page with [`raster`] instead. Text is drawn from usvg's outlines.
+
+use std::fmt::Write;
+use usvg::tiny_skia_path::PathSegment;
+use usvg::{FillRule, LineCap, LineJoin, Node, Paint, PaintOrder, Transform};
+
+/// Writes `tree` as a vector EPS, or returns `None` if it uses features
+/// that need rasterizing.
+pub(crate) fn vector(tree: &usvg::Tree) -> Option<Vec<u8>> {
+    if !is_vector(tree.root()) {
+        return None;
+    }
+
+    let size = tree.size();
+    let mut out = header(size.width(), size.height(), 1);
+    // SVG's y axis points down
+    let _ = writeln!(out, "0 {} translate 1 -1 scale", fmt(size.height()));
+    write_group(&mut out, tree.root());
+    out.push_str(TRAILER);
+    Some(out.into_bytes())
+}
+
+/// Writes an EPS of `width` × `height` points filled by a `w` × `h` pixel
+/// image, given as Flate-compressed RGB samples (see [`opaque_rgb`]).
+pub(crate) fn raster(w: u32, h: u32, width: f32, height: f32, data: &[u8]) -> Vec<u8> {
+    let mut out = header(width, height, 3);
+    let _ = writeln!(out, "{} {} scale", fmt(width), fmt(height));
+    let _ = writeln!(
+        out,
+        "/DeviceRGB setcolorspace\n<< /ImageType 1 /Width {w} /Height {h} /BitsPerComponent 8 \
+         /Decode [0 1 0 1 0 1] /ImageMatrix [{w} 0 0 -{h} 0 {h}] \
+         /DataSource currentfile /ASCIIHexDecode filter /FlateDecode filter >> image"
+    );
+    for line in data.chunks(39) {
+        for byte in line {
+            let _ = write!(out, "{byte:02x}");
+        }
+        out.push('\n');
+    }
+    out.push_str(">\n");
+    out.push_str(TRAILER);
+    out.into_bytes()
+}
+
+/// Returns the RGB samples of `pixmap` composited over white, since EPS
+/// images have no alpha.
+pub(crate) fn opaque_rgb(pixmap: &resvg::tiny_skia::Pixmap) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(pixmap.data().len() / 4 * 3);
+    for pixel in pixmap.data().chunks_exact(4) {
+        // Premultiplied, so adding the uncovered share of white composites
+        let uncovered = 255 - pixel[3];
+        rgb.extend(pixel[..3].iter().map(|&c| c.saturating_add(uncovered)));
+    }
+    rgb
+}
+
+const TRAILER: &str = "grestore\n%%EOF\n";
+
+/// Opening comments and prolog for a `width` × `height` point EPS.
+fn header(width: f32, height: f32, language_level: u8) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "%!PS-Adobe-3.0 EPSF-3.0");
+    let _ = writeln!(
+        out,
+        "%%BoundingBox: 0 0 {} {}",
+        width.ceil() as i64,
+        height.ceil() as i64
+    );
+    let _ = writeln!(out, "%%HiResBoundingBox: 0 0 {} {}", fmt(width), fmt(height));
+    let _ = writeln!(out, "%%Creator: WireTuner worker-export");
+    let _ = writeln!(out, "%%LanguageLevel: {language_level}");
+    let _ = writeln!(out, "%%Pages: 1");
+    let _ = writeln!(out, "%%EndComments");
+    let _ = writeln!(out, "gsave");
+    out
+}
+
+/// Returns `true` if everything in `group` can be drawn as PostScript
+/// paths.
+fn is_vector(group: &usvg::Group) -> bool {
+    if group.opacity().get() < 1.0
+        || group.clip_path().is_some()
+        || group.mask().is_some()
+        || !group.filters().is_empty()
+        || group.blend_mode() != usvg::BlendMode::Normal
+    {
+        return false;
+    }
+    group.children().iter().all(|node| match node {
+        Node::Group(group) => is_vector(group),
+        Node::Path(path) => {
+            path.fill().is_none_or(|fill| {
+                matches!(fill.paint(), Paint::Color(_)) && fill.opacity().get() >= 1.0
+            }) && path.stroke().is_none_or(|stroke| {
+                matches!(stroke.paint(), Paint::Color(_)) && stroke.opacity().get() >= 1.0
+            })
+        }
+        Node::Image(_) => false,
+        Node::Text(text) => is_vector(text.flattened()),
+    })
+}
+
+fn write_group(out: &mut String, group: &usvg::Group) {
+    for node in group.children() {
+        match node {
+            Node::Group(group) => write_group(out, group),
+            Node::Path(path) if path.is_visible() => write_path(out, path),
+            Node::Path(_) | Node::Image(_) => {}
+            Node::Text(text) => write_group(out, text.flattened()),
+        }
+    }
+}
+
+fn write_path(out: &mut String, path: &usvg::Path) {
+    out.push_str("gsave\n");
+    write_transform(out, path.abs_transform());
+
+    let fill = |out: &mut String| {
+        if let Some(fill) = path.fill() {
+            write_color(out, fill.paint());
+            write_segments(out, path.data());
+            out.push_str(match fill.rule() {
+                FillRule::NonZero => "fill\n",
+                FillRule::EvenOdd => "eofill\n",
+            });
+        }
+    };
+    let stroke = |out: &mut String| {
+        if let Some(stroke) = path.stroke() {
+            write_color(out, stroke.paint());
+            let _ = writeln!(out, "{} setlinewidth", fmt(stroke.width().get()));
+            let cap = match stroke.linecap() {
+                LineCap::Butt => 0,
+                LineCap::Round => 1,
+                LineCap::Square => 2,
+            };
+            let join = match stroke.linejoin() {
+                LineJoin::Miter | LineJoin::MiterClip => 0,
+                LineJoin::Round => 1,
+                LineJoin::Bevel => 2,
+            };
+            let _ = writeln!(
+                out,
+                "{cap} setlinecap {join} setlinejoin {} setmiterlimit",
+                fmt(stroke.miterlimit().get().max(1.0))
+            );
+            if let Some(dashes) = stroke.dasharray() {
+                let dashes: Vec<String> = dashes.iter().map(|&d| fmt(d)).collect();
+                let _ = writeln!(out, "[{}] {} setdash", dashes.join(" "), fmt(stroke.dashoffset()));
+            }
+            write_segments(out, path.data());
+            out.push_str("stroke\n");
+        }
+    };
+    match path.paint_order() {
+        PaintOrder::FillAndStroke => {
+            fill(out);
+            stroke(out);
+        }
+        PaintOrder::StrokeAndFill => {
+            stroke(out);
+            fill(out);
+        }
+    }
+    out.push_str("grestore\n");
+}
+
+fn write_transform(out: &mut String, ts: Transform) {
+    if !ts.is_identity() {
+        let _ = writeln!(
+            out,
+            "[{} {} {} {} {} {}] concat",
+            fmt(ts.sx),
+            fmt(ts.ky),
+            fmt(ts.kx),
+            fmt(ts.sy),
+            fmt(ts.tx),
+            fmt(ts.ty)
+        );
+    }
+}
+
+fn write_color(out: &mut String, paint: &Paint) {
+    if let Paint::Color(color) = paint {
+        let channel = |c: u8| fmt(f32::from(c) / 255.0);
+        let _ = writeln!(
+            out,
+            "{} {} {} setrgbcolor",
+            channel(color.red),
+            channel(color.green),
+            channel(color.blue)
+        );
+    }
+}
+
+fn write_segments(out: &mut String, data: &usvg::tiny_skia_path::Path) {
+    out.push_str("newpath\n");
+    let mut current = (0.0, 0.0);
+    for segment in data.segments() {
+        match segment {
+            PathSegment::MoveTo(p) => {
+                let _ = writeln!(out, "{} {} moveto", fmt(p.x), fmt(p.y));
+                current = (p.x, p.y);
+            }
+            PathSegment::LineTo(p) => {
+                let _ = writeln!(out, "{} {} lineto", fmt(p.x), fmt(p.y));
+                current = (p.x, p.y);
+            }
+            PathSegment::QuadTo(c, p) => {
+                // PostScript only has cubics; raise the quadratic's degree
+                let (x0, y0) = current;
+                let c1 = (x0 + 2.0 / 3.0 * (c.x - x0), y0 + 2.0 / 3.0 * (c.y - y0));
+                let c2 = (p.x + 2.0 / 3.0 * (c.x - p.x), p.y + 2.0 / 3.0 * (c.y - p.y));
+                let _ = writeln!(
+                    out,
+                    "{} {} {} {} {} {} curveto",
+                    fmt(c1.0),
+                    fmt(c1.1),
+                    fmt(c2.0),
+                    fmt(c2.1),
+                    fmt(p.x),
+                    fmt(p.y)
+                );
+                current = (p.x, p.y);
+            }
+            PathSegment::CubicTo(c1, c2, p) => {
+                let _ = writeln!(
+                    out,
+                    "{} {} {} {} {} {} curveto",
+                    fmt(c1.x),
+                    fmt(c1.y),
+                    fmt(c2.x),
+                    fmt(c2.y),
+                    fmt(p.x),
+                    fmt(p.y)
+                );
+                current = (p.x, p.y);
+            }
+            PathSegment::Close => out.push_str("closepath\n"),
+        }
+    }
+}
+
+/// Formats a number the way PostScript reads it, without exponents and
+/// trailing zeros.
+fn fmt(value: f32) -> String {
+    let s = format!("{:.4}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    match s {
+        "" | "-0" => "0".to_string(),
+        s => s.to_string(),
+    }
+}
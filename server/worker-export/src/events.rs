@@ -0,0 +1,150 @@
+//! Job lifecycle events.
+//!
+//! The queue and the worker report each step of a job's life (enqueued,
+//! started, retried, completed, failed) to an [`EventSink`], so they can be
+//! forwarded elsewhere, e.g. to Kafka or a webhook. The default
+//! [`TracingSink`] only logs them. Set a sink with
+//! [`JobQueue::with_event_sink`](crate::queue::JobQueue::with_event_sink).
+
+use crate::job::{ErrorKind, PdfExportJob};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt;
+use tracing::info;
+
+/// Step of a job's lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobEventKind {
+    /// The job was written to the queue (or scheduled for later).
+    Enqueued,
+    /// A worker started converting the job.
+    Started,
+    /// The job failed and was scheduled to run again.
+    Retried,
+    /// The output was written.
+    Completed,
+    /// The job failed for good, either with an error that retrying won't
+    /// fix or with no retries left.
+    Failed,
+}
+
+impl fmt::Display for JobEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JobEventKind::Enqueued => "enqueued",
+            JobEventKind::Started => "started",
+            JobEventKind::Retried => "retried",
+            JobEventKind::Completed => "completed",
+            JobEventKind::Failed => "failed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A lifecycle event, with the job's state when it happened.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobEvent {
+    pub kind: JobEventKind,
+    pub job_id: String,
+    pub document_id: String,
+    /// Retries scheduled so far, including the one a
+    /// [`Retried`](JobEventKind::Retried) event reports.
+    pub retry_count: u8,
+    /// Last error, for retried and failed jobs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+    pub at: DateTime<Utc>,
+}
+
+impl JobEvent {
+    /// Creates an event of `kind` for `job`, as of now on the job's clock.
+    pub fn new(kind: JobEventKind, job: &PdfExportJob) -> Self {
+        Self {
+            kind,
+            job_id: job.job_id.clone(),
+            document_id: job.document_id.clone(),
+            retry_count: job.retry_count,
+            error: job.error.clone(),
+            error_kind: job.error_kind,
+            at: job.now(),
+        }
+    }
+}
+
+/// Receives job lifecycle events.
+///
+/// `emit` is called inline by the queue and the worker, so it should hand
+/// events off (to a channel, say) rather than block on slow I/O.
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &JobEvent);
+}
+
+/// Logs each event at info level. The default sink.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+impl EventSink for TracingSink {
+    fn emit(&self, event: &JobEvent) {
+        info!(
+            "Job event: kind={}, job_id={}, document_id={}, retry_count={}, error_kind={:?}",
+            event.kind, event.job_id, event.document_id, event.retry_count, event.error_kind
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::JobMetadata;
+    use crate::testing::ManualClock;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_event_serializes_job_state() {
+        let mut job = PdfExportJob::new(
+            "doc-123".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let json = serde_json::to_value(JobEvent::new(JobEventKind::Enqueued, &job)).unwrap();
+        assert_eq!(json["kind"], "enqueued");
+        assert_eq!(json["job_id"], job.job_id.as_str());
+        assert!(json.get("error").is_none());
+
+        job.mark_failed("disk unplugged".to_string());
+        job.error_kind = Some(ErrorKind::Io);
+        let json = serde_json::to_value(JobEvent::new(JobEventKind::Failed, &job)).unwrap();
+        assert_eq!(json["kind"], "failed");
+        assert_eq!(json["error"], "disk unplugged");
+        assert_eq!(json["error_kind"], "io");
+        assert_eq!(JobEventKind::Retried.to_string(), "retried");
+    }
+
+    #[test]
+    fn test_event_time_matches_job_history() {
+        let start = DateTime::parse_from_rfc3339("2025-11-11T12:00:00Z").unwrap().with_timezone(&Utc);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut job = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg("<svg></svg>")
+            .output_path("/tmp/test.pdf")
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+
+        clock.advance(chrono::Duration::seconds(5));
+        job.start_processing();
+        let event = JobEvent::new(JobEventKind::Started, &job);
+        assert_eq!(event.at, start + chrono::Duration::seconds(5));
+        assert_eq!(event.at, job.history.last().unwrap().at);
+    }
+}
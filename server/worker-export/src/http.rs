@@ -0,0 +1,66 @@
+//! Blocking HTTP requests from conversion threads.
+//!
+//! Conversions, and the sinks they write through, run on blocking threads
+//! that may belong to a Tokio runtime that can't be re-entered. Requests
+//! made from there run on one runtime shared by the whole process, through
+//! one [`hyper::Client`], so connections are pooled across requests and no
+//! thread or runtime is started per request.
+
+use hyper::client::HttpConnector;
+use std::future::Future;
+use std::sync::{mpsc, OnceLock};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// Runtime and client shared by every request.
+struct Shared {
+    runtime: Runtime,
+    client: hyper::Client<HttpConnector>,
+}
+
+static SHARED: OnceLock<Result<Shared, String>> = OnceLock::new();
+
+fn shared() -> Result<&'static Shared, String> {
+    SHARED
+        .get_or_init(|| {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .worker_threads(1)
+                .thread_name("http-client")
+                .enable_all()
+                .build()
+                .map_err(|e| format!("Failed to start HTTP runtime: {}", e))?;
+            Ok(Shared {
+                runtime,
+                client: hyper::Client::new(),
+            })
+        })
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// Runs the request built by `request` on the shared runtime, blocking the
+/// calling thread until it finishes.
+///
+/// Fails with a message if the runtime can't be started, the request
+/// panics or it takes longer than `timeout`. Must not be called from the
+/// shared runtime itself.
+pub(crate) fn block_on<F>(
+    timeout: Duration,
+    request: impl FnOnce(hyper::Client<HttpConnector>) -> F,
+) -> Result<F::Output, String>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let shared = shared()?;
+    let future = request(shared.client.clone());
+    let (tx, rx) = mpsc::sync_channel(1);
+    shared.runtime.spawn(async move {
+        let result = tokio::time::timeout(timeout, future)
+            .await
+            .map_err(|_| format!("timed out after {:?}", timeout));
+        let _ = tx.send(result);
+    });
+    rx.recv()
+        .unwrap_or_else(|_| Err("request task panicked".to_string()))
+}
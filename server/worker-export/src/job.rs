@@ -1,40 +1,350 @@
 //! Job models and state management for PDF export queue.
 
+use crate::converter::{self, ConvertError};
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use rand::Rng;
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
 use uuid::Uuid;
 
+/// Retries allowed when a job doesn't specify its own limit.
+pub const DEFAULT_MAX_RETRIES: u8 = 3;
+
+fn default_max_retries() -> u8 {
+    DEFAULT_MAX_RETRIES
+}
+
+/// Backoff before the first retry; doubles with each subsequent retry.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+
+/// Upper bound on the backoff between retries.
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// Most state transitions kept in a job's history; older ones are dropped.
+pub const MAX_HISTORY: usize = 32;
+
+/// Priority for batch work that may wait behind everything else.
+pub const PRIORITY_LOW: u8 = 0;
+
+/// Default job priority.
+pub const PRIORITY_NORMAL: u8 = 1;
+
+/// Priority for interactive exports a user is waiting on.
+pub const PRIORITY_HIGH: u8 = 2;
+
+/// File format produced by a job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Pdf,
+    /// Raster image of the first page, e.g. for thumbnails.
+    Png,
+    /// Encapsulated PostScript of the first page, for print tooling that
+    /// doesn't take PDF.
+    Eps,
+}
+
+impl OutputFormat {
+    /// File extension for the format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Pdf => "pdf",
+            OutputFormat::Png => "png",
+            OutputFormat::Eps => "eps",
+        }
+    }
+}
+
+/// A job's `output_path` doesn't carry its output format's extension.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Output path {path} does not end in .{} for {format:?} output", .format.extension())]
+pub struct OutputExtensionError {
+    pub path: String,
+    pub format: OutputFormat,
+}
+
+/// Where a job's SVG comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SvgSource {
+    /// Carried in the job itself, as `svg_content`.
+    Inline(String),
+    /// Read from this Redis string key when the job is processed.
+    RedisKey(String),
+    /// Read from this file, on the worker's filesystem, when the job is
+    /// processed. Only files under the worker's input root are read (see
+    /// [`OutputConfig::with_input_root`](crate::output::OutputConfig::with_input_root)).
+    FilePath(PathBuf),
+}
+
+/// Source of the current time for a job's timestamps.
+///
+/// Jobs use the [`SystemClock`] unless built with another
+/// ([`PdfExportJobBuilder::clock`]), which lets tests pin creation and
+/// transition times and assert durations exactly.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// The clock a job reads; not serialized, so jobs read back from Redis use
+/// the system clock.
+#[derive(Clone)]
+struct JobClock(Arc<dyn Clock>);
+
+impl JobClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.0.now()
+    }
+}
+
+impl Default for JobClock {
+    fn default() -> Self {
+        Self(Arc::new(SystemClock))
+    }
+}
+
+impl fmt::Debug for JobClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("JobClock")
+    }
+}
+
 /// PDF export job request.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PdfExportJob {
     pub job_id: String,
     pub document_id: String,
     pub svg_content: String,
+    /// Reference to SVG stored outside the job. When set, `svg_content` is
+    /// empty and the worker loads the SVG at process time, so neither the
+    /// queued payload nor status keys carry it. Never [`SvgSource::Inline`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub svg_source: Option<SvgSource>,
+    /// Per-artboard SVGs for multi-page exports. When non-empty, each entry
+    /// becomes one PDF page and `svg_content` is ignored.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artboards: Vec<ArtboardSvg>,
     pub output_path: String,
+    #[serde(default)]
+    pub output_format: OutputFormat,
     pub metadata: JobMetadata,
+    /// Scheduling priority; values above [`PRIORITY_HIGH`] are treated as high.
+    /// Unset, it is chosen from `metadata.export_scope` when the job is
+    /// enqueued (see [`ScopePriorities`](crate::queue::ScopePriorities)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
     pub status: JobStatus,
+    /// Conversion progress in percent, once processing has started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<u8>,
     pub retry_count: u8,
+    /// Number of retries allowed before the job fails permanently.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u8,
+    /// Times the job was replayed from the dead-letter list by hand.
+    #[serde(default)]
+    pub dlq_replay_count: u8,
+    /// Earliest time a retried job may be picked up again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// How long the job's status stays queryable, in seconds. Defaults to
+    /// 24 hours when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_ttl: Option<u64>,
+    /// Deadline after which the job isn't worth starting, e.g. an
+    /// interactive export the user has stopped waiting for. Workers mark
+    /// jobs still queued by then as expired instead of converting them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
+    /// When the job last became ready to run, if later than `created_at`:
+    /// the end of a retry backoff or a scheduled job's run time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enqueued_at: Option<DateTime<Utc>>,
+    /// Time spent waiting in the queue before the current attempt started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_wait_ms: Option<i64>,
+    /// Time the last successful attempt spent converting and writing the
+    /// output, without the queue wait, retries or status updates that
+    /// [`processing_duration_ms`](Self::processing_duration_ms) includes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub render_duration_ms: Option<i64>,
+    /// Consumer id of the worker that last started the job, so a job stuck
+    /// in `processing` can be traced to its worker.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<String>,
     pub updated_at: DateTime<Utc>,
+    /// Status changes since the job was created, oldest first, capped at the
+    /// latest [`MAX_HISTORY`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<StateTransition>,
     pub error: Option<String>,
+    /// Category of the last failure, used to decide whether to retry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<ErrorKind>,
+    /// Size of the written output file, once complete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_bytes: Option<u64>,
+    /// Number of pages in the output, once complete.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<u32>,
+    /// Hex SHA-256 of the output file, once complete, so clients can verify
+    /// the file they download. Only set for PDF output.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_sha256: Option<String>,
+    /// Content the conversion skipped or degraded (see
+    /// [`ConversionWarning`](crate::converter::ConversionWarning)), once
+    /// complete.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// SHA-256 of the SVG content (see [`content_checksum`](Self::content_checksum)),
+    /// stamped at enqueue so corrupted payloads are caught at dequeue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+    #[serde(skip)]
+    clock: JobClock,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A status change recorded in a job's [`history`](PdfExportJob::history).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateTransition {
+    /// Status the job moved to.
+    pub status: JobStatus,
+    pub at: DateTime<Utc>,
+}
+
+/// A single artboard rendered as one page of a multi-page export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtboardSvg {
+    pub artboard_id: String,
+    pub svg_content: String,
+    /// Display name of the artboard, used as its page label in the PDF
+    /// instead of `artboard_id`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct JobMetadata {
     pub artboard_ids: Vec<String>,
     pub export_scope: String,
     pub client_version: String,
     pub user_id: Option<String>,
+    /// Client-supplied key that makes repeated enqueues of the same export
+    /// resolve to a single job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotency_key: Option<String>,
+    /// W3C trace context (`traceparent`, optionally `tracestate`) of the
+    /// request that created the job, so worker spans join its trace.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_sorted"
+    )]
+    pub trace_context: Option<HashMap<String, String>>,
+    /// Service tier of the requesting user, selecting which enqueue rate
+    /// limit applies (see [`RateLimits`](crate::rate_limit::RateLimits)).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_tier: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Serializes a map with its keys in order, so a job always encodes to the
+/// same bytes and its queue entry can be found again from its status.
+fn serialize_sorted<S: Serializer>(
+    map: &Option<HashMap<String, String>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    map.as_ref()
+        .map(|map| map.iter().collect::<BTreeMap<_, _>>())
+        .serialize(serializer)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum JobStatus {
     Queued,
     Processing,
     Complete,
     Failed,
+    Cancelled,
+    /// Its `expires_at` passed before a worker started it.
+    Expired,
+}
+
+/// Category of a job failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    TooLarge,
+    Parse,
+    InvalidDimensions,
+    Render,
+    Io,
+    Timeout,
+    /// The queued payload was truncated or failed its checksum.
+    Corrupted,
+    /// The output disk ran out of space.
+    DiskFull,
+    /// The output path's extension doesn't match the output format.
+    OutputExtension,
+    /// The output path names object storage this worker can't write to, or
+    /// the storage server refused the upload (a 4xx response).
+    OutputLocation,
+    /// The SVG declares XML entities (see
+    /// [`check_svg_entities`](crate::converter::check_svg_entities)).
+    Security,
+    /// The job's [`SvgSource`] names a Redis key that doesn't exist or a
+    /// file the worker may not read.
+    SourceUnavailable,
+}
+
+impl ErrorKind {
+    /// Returns `true` if the failure may be transient, so retrying the job
+    /// could succeed. Problems with the SVG itself fail the same way every
+    /// time.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, ErrorKind::Io | ErrorKind::Timeout)
+    }
+}
+
+impl From<&ConvertError> for ErrorKind {
+    fn from(error: &ConvertError) -> Self {
+        match error.root() {
+            ConvertError::TooLarge { .. } | ConvertError::TooManyPages { .. } => {
+                ErrorKind::TooLarge
+            }
+            ConvertError::Security(_) => ErrorKind::Security,
+            ConvertError::Parse { .. } | ConvertError::Decode(_) => ErrorKind::Parse,
+            ConvertError::InvalidDimensions(_) => ErrorKind::InvalidDimensions,
+            ConvertError::Render(_) | ConvertError::Page { .. } => ErrorKind::Render,
+            ConvertError::Io { .. } | ConvertError::Write(_) if error.is_disk_full() => {
+                ErrorKind::DiskFull
+            }
+            ConvertError::Io { source, .. } if crate::sink::is_permanent_failure(source) => {
+                ErrorKind::OutputLocation
+            }
+            ConvertError::Io { .. } | ConvertError::Write(_) => ErrorKind::Io,
+            ConvertError::Timeout(_) => ErrorKind::Timeout,
+        }
+    }
 }
 
 impl fmt::Display for JobStatus {
@@ -44,50 +354,281 @@ impl fmt::Display for JobStatus {
             JobStatus::Processing => write!(f, "processing"),
             JobStatus::Complete => write!(f, "complete"),
             JobStatus::Failed => write!(f, "failed"),
+            JobStatus::Cancelled => write!(f, "cancelled"),
+            JobStatus::Expired => write!(f, "expired"),
         }
     }
 }
 
 impl PdfExportJob {
+    /// Returns a builder for a job, as an alternative to the positional
+    /// constructors.
+    pub fn builder() -> PdfExportJobBuilder {
+        PdfExportJobBuilder::default()
+    }
+
     pub fn new(document_id: String, svg_content: String, output_path: String, metadata: JobMetadata) -> Self {
-        let now = Utc::now();
-        Self {
-            job_id: Uuid::new_v4().to_string(),
-            document_id,
-            svg_content,
-            output_path,
-            metadata,
-            status: JobStatus::Queued,
-            retry_count: 0,
-            created_at: now,
-            updated_at: now,
-            error: None,
+        Self::builder()
+            .document_id(document_id)
+            .svg(svg_content)
+            .output_path(output_path)
+            .metadata(metadata)
+            .assemble()
+            .expect("every required field is set")
+    }
+
+    /// Creates a single-page job whose SVG comes from `source`.
+    ///
+    /// Inline sources behave exactly like [`new`](Self::new); references are
+    /// resolved by the worker when the job runs.
+    pub fn from_source(
+        document_id: String,
+        source: SvgSource,
+        output_path: String,
+        metadata: JobMetadata,
+    ) -> Self {
+        Self::builder()
+            .document_id(document_id)
+            .svg_source(source)
+            .output_path(output_path)
+            .metadata(metadata)
+            .assemble()
+            .expect("every required field is set")
+    }
+
+    /// Creates a multi-page job with one page per artboard, in order.
+    pub fn new_multi_page(
+        document_id: String,
+        artboards: Vec<ArtboardSvg>,
+        output_path: String,
+        metadata: JobMetadata,
+    ) -> Self {
+        let mut job = Self::new(document_id, String::new(), output_path, metadata);
+        job.artboards = artboards;
+        job
+    }
+
+    /// Sets the output file format.
+    pub fn with_output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Sets the scheduling priority.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Returns the priority the job is scheduled with: its own, or
+    /// [`PRIORITY_NORMAL`] if it was never given one.
+    pub fn effective_priority(&self) -> u8 {
+        self.priority.unwrap_or(PRIORITY_NORMAL)
+    }
+
+    /// Sets how long the job's status is kept, in seconds, e.g. minutes for
+    /// throwaway previews or longer for audited exports.
+    pub fn with_status_ttl(mut self, status_ttl: u64) -> Self {
+        self.status_ttl = Some(status_ttl);
+        self
+    }
+
+    /// Sets the deadline after which the job is skipped as expired.
+    pub fn with_expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Returns the current time on the job's clock, which its timestamps
+    /// and history are taken from.
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Returns `true` if the job has an `expires_at` that has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|expires_at| self.clock.now() >= expires_at)
+    }
+
+    /// Sets the number of retries allowed before the job fails permanently.
+    pub fn with_max_retries(mut self, max_retries: u8) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Returns the inline SVG for each page to render, in page order.
+    ///
+    /// A job with an external [`svg_source`](Self::svg_source) has one empty
+    /// page here; the worker loads the real content when processing it.
+    pub fn pages(&self) -> Vec<&str> {
+        if self.artboards.is_empty() {
+            vec![self.svg_content.as_str()]
+        } else {
+            self.artboards.iter().map(|a| a.svg_content.as_str()).collect()
+        }
+    }
+
+    /// Returns the label each page is shown with in PDF viewers, in page
+    /// order: an artboard's `name`, or its `artboard_id` if it has none.
+    /// Single-page jobs are labelled with their first `artboard_ids` entry,
+    /// if any.
+    pub fn page_labels(&self) -> Vec<String> {
+        if self.artboards.is_empty() {
+            return self.metadata.artboard_ids.iter().take(1).cloned().collect();
+        }
+        self.artboards
+            .iter()
+            .map(|artboard| artboard.name.clone().unwrap_or_else(|| artboard.artboard_id.clone()))
+            .collect()
+    }
+
+    /// Checks that no page's SVG exceeds `max_svg_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConvertError::TooLarge`] for the first oversized page.
+    pub fn check_svg_size(&self, max_svg_bytes: usize) -> Result<(), ConvertError> {
+        self.pages()
+            .into_iter()
+            .try_for_each(|svg| converter::check_svg_size(svg, max_svg_bytes))
+    }
+
+    /// Checks that the job has no more than `max_pages` pages.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConvertError::TooManyPages`] otherwise.
+    pub fn check_page_count(&self, max_pages: usize) -> Result<(), ConvertError> {
+        converter::check_page_count(self.pages().len(), max_pages)
+    }
+
+    /// Checks that `output_path` ends in the extension of `output_format`
+    /// (in any case), so one format is never written under another's name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OutputExtensionError`] if the extension is missing or
+    /// belongs to another format.
+    pub fn check_output_extension(&self) -> Result<(), OutputExtensionError> {
+        let matches = Path::new(&self.output_path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case(self.output_format.extension()));
+        if matches {
+            Ok(())
+        } else {
+            Err(OutputExtensionError {
+                path: self.output_path.clone(),
+                format: self.output_format,
+            })
         }
     }
 
+    /// Returns the hex SHA-256 of every page's SVG, in order, and of the
+    /// external SVG reference if there is one.
+    pub fn content_checksum(&self) -> String {
+        let mut hasher = Sha256::new();
+        for svg in self.pages() {
+            // Length-prefix pages so moving bytes between them changes the hash
+            hasher.update((svg.len() as u64).to_le_bytes());
+            hasher.update(svg.as_bytes());
+        }
+        if let Some(source) = &self.svg_source {
+            hasher.update(serde_json::to_vec(source).unwrap_or_default());
+        }
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Returns `false` if the job carries a checksum that doesn't match its
+    /// content. Jobs enqueued without one always pass.
+    pub fn verify_checksum(&self) -> bool {
+        self.checksum
+            .as_ref()
+            .is_none_or(|checksum| *checksum == self.content_checksum())
+    }
+
+    /// Marks the job as processing and records how long it waited since it
+    /// became ready (see [`enqueued_at`](Self::enqueued_at)).
     pub fn start_processing(&mut self) {
-        self.status = JobStatus::Processing;
-        self.updated_at = Utc::now();
+        let now = self.clock.now();
+        let ready_at = self.enqueued_at.unwrap_or(self.created_at);
+        self.transition(JobStatus::Processing, now);
+        self.progress = Some(0);
+        self.queue_wait_ms = Some(now.signed_duration_since(ready_at).num_milliseconds().max(0));
+        self.render_duration_ms = None;
     }
 
-    pub fn mark_complete(&mut self) {
-        self.status = JobStatus::Complete;
-        self.updated_at = Utc::now();
+    /// Marks the job complete, recording the output file's size in bytes,
+    /// its page count and its SHA-256 (if known) so pollers needn't fetch
+    /// the file to learn them.
+    pub fn mark_complete(&mut self, output_bytes: u64, page_count: u32, output_sha256: Option<String>) {
+        self.transition(JobStatus::Complete, self.clock.now());
+        self.progress = Some(100);
+        self.output_bytes = Some(output_bytes);
+        self.page_count = Some(page_count);
+        self.output_sha256 = output_sha256;
         self.error = None;
+        self.error_kind = None;
     }
 
     pub fn mark_failed(&mut self, error: String) {
-        self.status = JobStatus::Failed;
-        self.updated_at = Utc::now();
+        self.transition(JobStatus::Failed, self.clock.now());
         self.error = Some(error);
     }
 
+    /// Marks the job failed with a conversion error, recording its kind.
+    pub fn mark_conversion_failed(&mut self, error: &ConvertError) {
+        self.mark_failed(error.to_string());
+        self.error_kind = Some(ErrorKind::from(error));
+    }
+
+    pub fn mark_cancelled(&mut self) {
+        self.transition(JobStatus::Cancelled, self.clock.now());
+    }
+
+    /// Marks a job that passed its `expires_at` before it was started.
+    pub fn mark_expired(&mut self) {
+        self.transition(JobStatus::Expired, self.clock.now());
+    }
+
+    /// Returns the job to `Queued` without consuming a retry, e.g. when a
+    /// worker shuts down (or is found dead) before finishing it.
+    pub fn requeue(&mut self) {
+        self.transition(JobStatus::Queued, self.clock.now());
+        self.progress = None;
+        self.worker_id = None;
+        self.enqueued_at = Some(self.updated_at);
+    }
+
+    /// Returns a dead-lettered job to `Queued` for a manual replay, clearing
+    /// the error that put it there and counting the replay.
+    pub fn requeue_from_dead_letter(&mut self) {
+        self.requeue();
+        self.error = None;
+        self.error_kind = None;
+        self.dlq_replay_count = self.dlq_replay_count.saturating_add(1);
+    }
+
+    /// Consumes a retry and schedules the next attempt after an
+    /// exponential backoff (see [`retry_backoff`]).
+    ///
+    /// Returns `false` and marks the job failed once `max_retries` is
+    /// exhausted, or straight away if the last error isn't retryable.
     pub fn retry(&mut self) -> bool {
-        const MAX_RETRIES: u8 = 3;
-        if self.retry_count < MAX_RETRIES {
+        if self.error_kind.is_some_and(|kind| !kind.is_retryable()) {
+            self.transition(JobStatus::Failed, self.clock.now());
+            false
+        } else if self.retry_count < self.max_retries {
             self.retry_count += 1;
-            self.status = JobStatus::Queued;
-            self.updated_at = Utc::now();
+            self.transition(JobStatus::Queued, self.clock.now());
+            self.progress = None;
+            let delay = chrono::Duration::from_std(retry_backoff(self.retry_count))
+                .unwrap_or_else(|_| chrono::Duration::zero());
+            self.next_attempt_at = Some(self.updated_at + delay);
+            self.enqueued_at = self.next_attempt_at;
             true
         } else {
             self.mark_failed("Max retries exceeded".to_string());
@@ -95,11 +636,839 @@ impl PdfExportJob {
         }
     }
 
+    /// Sets the status, recording the change in the history unless the job
+    /// already had that status.
+    fn transition(&mut self, status: JobStatus, at: DateTime<Utc>) {
+        self.status = status;
+        self.updated_at = at;
+        if self.history.last().is_some_and(|last| last.status == status) {
+            return;
+        }
+        self.history.push(StateTransition { status, at });
+        if self.history.len() > MAX_HISTORY {
+            let excess = self.history.len() - MAX_HISTORY;
+            self.history.drain(..excess);
+        }
+    }
+
+    /// Returns the job's end-to-end time, from creation to its last status
+    /// change, once it has finished. Compare
+    /// [`render_duration_ms`](Self::render_duration_ms).
     pub fn processing_duration_ms(&self) -> Option<i64> {
-        if self.status == JobStatus::Complete || self.status == JobStatus::Failed {
+        if matches!(
+            self.status,
+            JobStatus::Complete | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Expired
+        ) {
             Some(self.updated_at.signed_duration_since(self.created_at).num_milliseconds())
         } else {
             None
         }
     }
 }
+
+/// Error returned by [`PdfExportJobBuilder::build`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum JobBuildError {
+    /// A required field was never set.
+    #[error("Missing required field: {0}")]
+    MissingField(&'static str),
+
+    /// `output_path` doesn't end in the output format's extension.
+    #[error(transparent)]
+    OutputExtension(#[from] OutputExtensionError),
+}
+
+/// SVG content of a job being built.
+#[derive(Debug, Clone)]
+enum JobContent {
+    Source(SvgSource),
+    Artboards(Vec<ArtboardSvg>),
+}
+
+/// Builder for [`PdfExportJob`], from [`PdfExportJob::builder`].
+///
+/// `document_id`, `output_path` and the SVG (via [`svg`](Self::svg),
+/// [`svg_source`](Self::svg_source) or [`artboards`](Self::artboards), the
+/// last call winning) are required. Everything else has the same defaults
+/// as [`PdfExportJob::new`]; `export_scope` defaults to `"all"` and
+/// `client_version` to `"unknown"`.
+///
+/// ```
+/// use worker_export::job::{PdfExportJob, PRIORITY_HIGH};
+///
+/// let job = PdfExportJob::builder()
+///     .document_id("doc-123")
+///     .svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#)
+///     .output_path("doc-123.pdf")
+///     .artboard_ids(vec!["artboard-1".to_string()])
+///     .priority(PRIORITY_HIGH)
+///     .build()
+///     .unwrap();
+/// assert_eq!(job.priority, Some(PRIORITY_HIGH));
+/// ```
+#[derive(Debug, Clone)]
+pub struct PdfExportJobBuilder {
+    document_id: Option<String>,
+    content: Option<JobContent>,
+    output_path: Option<String>,
+    output_format: OutputFormat,
+    metadata: JobMetadata,
+    priority: Option<u8>,
+    max_retries: u8,
+    status_ttl: Option<u64>,
+    expires_at: Option<DateTime<Utc>>,
+    clock: JobClock,
+}
+
+impl Default for PdfExportJobBuilder {
+    fn default() -> Self {
+        Self {
+            document_id: None,
+            content: None,
+            output_path: None,
+            output_format: OutputFormat::Pdf,
+            metadata: JobMetadata {
+                artboard_ids: Vec::new(),
+                export_scope: "all".to_string(),
+                client_version: "unknown".to_string(),
+                user_id: None,
+                idempotency_key: None,
+                trace_context: None,
+                user_tier: None,
+            },
+            priority: None,
+            max_retries: DEFAULT_MAX_RETRIES,
+            status_ttl: None,
+            expires_at: None,
+            clock: JobClock::default(),
+        }
+    }
+}
+
+impl PdfExportJobBuilder {
+    /// Sets the document being exported. Required.
+    pub fn document_id(mut self, document_id: impl Into<String>) -> Self {
+        self.document_id = Some(document_id.into());
+        self
+    }
+
+    /// Sets inline SVG content for a single-page job.
+    pub fn svg(mut self, svg_content: impl Into<String>) -> Self {
+        self.content = Some(JobContent::Source(SvgSource::Inline(svg_content.into())));
+        self
+    }
+
+    /// Sets where the SVG for a single-page job comes from.
+    pub fn svg_source(mut self, source: SvgSource) -> Self {
+        self.content = Some(JobContent::Source(source));
+        self
+    }
+
+    /// Renders one page per artboard, in order.
+    pub fn artboards(mut self, artboards: Vec<ArtboardSvg>) -> Self {
+        self.content = Some(JobContent::Artboards(artboards));
+        self
+    }
+
+    /// Sets where the output is written. Required.
+    pub fn output_path(mut self, output_path: impl Into<String>) -> Self {
+        self.output_path = Some(output_path.into());
+        self
+    }
+
+    /// Sets the output file format.
+    pub fn output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Replaces all metadata at once. Later metadata setters modify it.
+    pub fn metadata(mut self, metadata: JobMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets the ids of the artboards being exported.
+    pub fn artboard_ids(mut self, artboard_ids: Vec<String>) -> Self {
+        self.metadata.artboard_ids = artboard_ids;
+        self
+    }
+
+    /// Sets the export scope, e.g. `"all"`, `"current"` or `"selected"`.
+    pub fn export_scope(mut self, export_scope: impl Into<String>) -> Self {
+        self.metadata.export_scope = export_scope.into();
+        self
+    }
+
+    /// Sets the version of the client that requested the export.
+    pub fn client_version(mut self, client_version: impl Into<String>) -> Self {
+        self.metadata.client_version = client_version.into();
+        self
+    }
+
+    /// Sets the requesting user.
+    pub fn user_id(mut self, user_id: impl Into<String>) -> Self {
+        self.metadata.user_id = Some(user_id.into());
+        self
+    }
+
+    /// Sets the service tier of the requesting user.
+    pub fn user_tier(mut self, user_tier: impl Into<String>) -> Self {
+        self.metadata.user_tier = Some(user_tier.into());
+        self
+    }
+
+    /// Sets the key that deduplicates repeated enqueues.
+    pub fn idempotency_key(mut self, idempotency_key: impl Into<String>) -> Self {
+        self.metadata.idempotency_key = Some(idempotency_key.into());
+        self
+    }
+
+    /// Sets the scheduling priority. Without one, the queue picks it from
+    /// the export scope.
+    pub fn priority(mut self, priority: u8) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Sets the number of retries allowed before the job fails permanently.
+    pub fn max_retries(mut self, max_retries: u8) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets how long the job's status is kept, in seconds.
+    pub fn status_ttl(mut self, status_ttl: u64) -> Self {
+        self.status_ttl = Some(status_ttl);
+        self
+    }
+
+    /// Sets the deadline after which the job is skipped as expired.
+    pub fn expires_at(mut self, expires_at: DateTime<Utc>) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Sets the clock the job's timestamps are read from, from creation on.
+    /// Defaults to the [`SystemClock`].
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = JobClock(clock);
+        self
+    }
+
+    /// Builds the job with a fresh id, status `queued`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JobBuildError::MissingField`] if `document_id`,
+    /// `output_path` or the SVG was not set, or `artboards` was empty, and
+    /// [`JobBuildError::OutputExtension`] if `output_path` doesn't end in
+    /// the output format's extension.
+    pub fn build(self) -> Result<PdfExportJob, JobBuildError> {
+        let job = self.assemble()?;
+        job.check_output_extension()?;
+        Ok(job)
+    }
+
+    /// Builds the job without checking `output_path` against the output
+    /// format, for the positional constructors, which set the format later.
+    fn assemble(self) -> Result<PdfExportJob, JobBuildError> {
+        let document_id = self
+            .document_id
+            .ok_or(JobBuildError::MissingField("document_id"))?;
+        let output_path = self
+            .output_path
+            .ok_or(JobBuildError::MissingField("output_path"))?;
+        let (svg_content, svg_source, artboards) = match self.content {
+            Some(JobContent::Source(SvgSource::Inline(svg_content))) => (svg_content, None, Vec::new()),
+            Some(JobContent::Source(source)) => (String::new(), Some(source), Vec::new()),
+            Some(JobContent::Artboards(artboards)) if !artboards.is_empty() => {
+                (String::new(), None, artboards)
+            }
+            _ => return Err(JobBuildError::MissingField("svg")),
+        };
+
+        let now = self.clock.now();
+        Ok(PdfExportJob {
+            job_id: Uuid::new_v4().to_string(),
+            document_id,
+            svg_content,
+            svg_source,
+            artboards,
+            output_path,
+            output_format: self.output_format,
+            metadata: self.metadata,
+            priority: self.priority,
+            status: JobStatus::Queued,
+            progress: None,
+            retry_count: 0,
+            dlq_replay_count: 0,
+            max_retries: self.max_retries,
+            next_attempt_at: None,
+            status_ttl: self.status_ttl,
+            expires_at: self.expires_at,
+            created_at: now,
+            enqueued_at: None,
+            queue_wait_ms: None,
+            render_duration_ms: None,
+            worker_id: None,
+            history: Vec::new(),
+            updated_at: now,
+            error: None,
+            error_kind: None,
+            output_bytes: None,
+            output_sha256: None,
+            page_count: None,
+            warnings: Vec::new(),
+            checksum: None,
+            clock: self.clock,
+        })
+    }
+}
+
+/// Returns the delay before retry number `retry_count` (starting at 1).
+///
+/// The delay doubles with each retry from [`RETRY_BASE_DELAY`], capped at
+/// [`RETRY_MAX_DELAY`], and is randomised to between half and all of that
+/// value so jobs that failed together don't retry in lockstep.
+pub fn retry_backoff(retry_count: u8) -> Duration {
+    let exponent = u32::from(retry_count.saturating_sub(1)).min(16);
+    let ceiling = RETRY_BASE_DELAY
+        .saturating_mul(1 << exponent)
+        .min(RETRY_MAX_DELAY);
+    let floor = ceiling / 2;
+    floor + ceiling.mul_f64(rand::thread_rng().gen_range(0.0..=0.5))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ManualClock;
+
+    fn test_job() -> PdfExportJob {
+        PdfExportJob::new(
+            "doc-123".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_builder_sets_fields() {
+        let job = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg("<svg></svg>")
+            .output_path("/tmp/test.png")
+            .artboard_ids(vec!["artboard-1".to_string()])
+            .export_scope("selected")
+            .client_version("0.2.0")
+            .user_id("user-42")
+            .priority(PRIORITY_HIGH)
+            .max_retries(5)
+            .status_ttl(600)
+            .output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+
+        assert_eq!(job.document_id, "doc-123");
+        assert_eq!(job.svg_content, "<svg></svg>");
+        assert_eq!(job.output_path, "/tmp/test.png");
+        assert_eq!(job.metadata.artboard_ids, vec!["artboard-1"]);
+        assert_eq!(job.metadata.export_scope, "selected");
+        assert_eq!(job.metadata.client_version, "0.2.0");
+        assert_eq!(job.metadata.user_id.as_deref(), Some("user-42"));
+        assert_eq!(job.priority, Some(PRIORITY_HIGH));
+        assert_eq!(job.max_retries, 5);
+        assert_eq!(job.status_ttl, Some(600));
+        assert_eq!(job.output_format, OutputFormat::Png);
+        assert_eq!(job.status, JobStatus::Queued);
+
+        // External sources leave svg_content empty, like from_source
+        let job = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg_source(SvgSource::RedisKey("svg:doc-123".to_string()))
+            .output_path("/tmp/test.pdf")
+            .build()
+            .unwrap();
+        assert!(job.svg_content.is_empty());
+        assert_eq!(job.svg_source, Some(SvgSource::RedisKey("svg:doc-123".to_string())));
+    }
+
+    #[test]
+    fn test_builder_requires_fields() {
+        let err = PdfExportJob::builder()
+            .document_id("doc-123")
+            .output_path("/tmp/test.pdf")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, JobBuildError::MissingField("svg"));
+
+        let err = PdfExportJob::builder()
+            .svg("<svg></svg>")
+            .output_path("/tmp/test.pdf")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, JobBuildError::MissingField("document_id"));
+        assert_eq!(err.to_string(), "Missing required field: document_id");
+
+        let err = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg("<svg></svg>")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, JobBuildError::MissingField("output_path"));
+
+        let err = PdfExportJob::builder()
+            .document_id("doc-123")
+            .artboards(vec![])
+            .output_path("/tmp/test.pdf")
+            .build()
+            .unwrap_err();
+        assert_eq!(err, JobBuildError::MissingField("svg"));
+    }
+
+    #[test]
+    fn test_retry_backoff_grows_exponentially() {
+        let expected = [(1, 1_000, 2_000), (2, 2_000, 4_000), (3, 4_000, 8_000)];
+        for _ in 0..100 {
+            for (retry_count, min_ms, max_ms) in expected {
+                let delay = retry_backoff(retry_count).as_millis();
+                assert!(
+                    (min_ms..=max_ms).contains(&delay),
+                    "retry {} delay {}ms outside {}..={}ms",
+                    retry_count,
+                    delay,
+                    min_ms,
+                    max_ms
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_retries_limits_retry() {
+        let mut job = test_job().with_max_retries(1);
+
+        assert!(job.retry());
+        assert!(!job.retry());
+        assert_eq!(job.status, JobStatus::Failed);
+        assert_eq!(job.retry_count, 1);
+    }
+
+    #[test]
+    fn test_max_retries_defaults_for_old_payloads() {
+        let mut value = serde_json::to_value(test_job()).unwrap();
+        value.as_object_mut().unwrap().remove("max_retries");
+
+        let job: PdfExportJob = serde_json::from_value(value).unwrap();
+        assert_eq!(job.max_retries, DEFAULT_MAX_RETRIES);
+    }
+
+    #[test]
+    fn test_output_format_serialization() {
+        let job = test_job().with_output_format(OutputFormat::Png);
+        let value = serde_json::to_value(&job).unwrap();
+        assert_eq!(value["output_format"], "png");
+
+        let mut old = serde_json::to_value(test_job()).unwrap();
+        old.as_object_mut().unwrap().remove("output_format");
+        let job: PdfExportJob = serde_json::from_value(old).unwrap();
+        assert_eq!(job.output_format, OutputFormat::Pdf);
+    }
+
+    #[test]
+    fn test_output_path_must_match_format_extension() {
+        let job = test_job().with_output_format(OutputFormat::Png);
+        let err = job.check_output_extension().unwrap_err();
+        assert_eq!(err.format, OutputFormat::Png);
+        assert_eq!(
+            err.to_string(),
+            format!("Output path {} does not end in .png for Png output", job.output_path)
+        );
+
+        let mut job = job;
+        for path in ["/tmp/thumb.png", "/tmp/THUMB.PNG"] {
+            job.output_path = path.to_string();
+            assert!(job.check_output_extension().is_ok(), "{} was rejected", path);
+        }
+        for path in ["/tmp/thumb", "/tmp/png", "/tmp/thumb.png.pdf"] {
+            job.output_path = path.to_string();
+            assert!(job.check_output_extension().is_err(), "{} was accepted", path);
+        }
+        assert!(test_job().with_output_format(OutputFormat::Pdf).check_output_extension().is_ok());
+
+        let err = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg("<svg></svg>")
+            .output_path("/tmp/test.pdf")
+            .output_format(OutputFormat::Png)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, JobBuildError::OutputExtension(_)));
+    }
+
+    #[test]
+    fn test_error_kind_from_convert_error() {
+        let converter = crate::converter::SvgToPdfConverter::new();
+        let kind = |svg: &str| ErrorKind::from(&converter.convert_to_bytes(svg).unwrap_err());
+
+        assert_eq!(kind("<svg"), ErrorKind::Parse);
+        assert_eq!(
+            kind(r#"<svg xmlns="http://www.w3.org/2000/svg" width="0" height="0"/>"#),
+            ErrorKind::InvalidDimensions
+        );
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+        let io = converter
+            .convert(svg, "/nonexistent-dir/out.pdf")
+            .unwrap_err();
+        assert_eq!(ErrorKind::from(&io), ErrorKind::Io);
+
+        let render = converter
+            .convert_to_png_bytes(svg, 0.0)
+            .unwrap_err();
+        assert_eq!(ErrorKind::from(&render), ErrorKind::Render);
+
+        let page = converter
+            .convert_multi_to_bytes_with_options(&[svg, "<svg"], &Default::default())
+            .unwrap_err();
+        assert_eq!(ErrorKind::from(&page), ErrorKind::Parse);
+
+        let too_large = converter
+            .clone()
+            .with_max_svg_bytes(8)
+            .convert_to_bytes(svg)
+            .unwrap_err();
+        assert_eq!(ErrorKind::from(&too_large), ErrorKind::TooLarge);
+        assert!(!ErrorKind::TooLarge.is_retryable());
+
+        let too_many_pages = converter
+            .clone()
+            .with_max_pages(1)
+            .convert_multi_to_bytes_with_options(&[svg, svg], &Default::default())
+            .unwrap_err();
+        assert_eq!(ErrorKind::from(&too_many_pages), ErrorKind::TooLarge);
+
+        let timeout = ConvertError::Timeout(Duration::from_secs(1));
+        assert_eq!(ErrorKind::from(&timeout), ErrorKind::Timeout);
+    }
+
+    #[test]
+    fn test_retry_skips_non_retryable_errors() {
+        let mut bad_svg = test_job();
+        bad_svg.mark_conversion_failed(&ConvertError::InvalidDimensions("0x0".to_string()));
+        assert!(!bad_svg.retry());
+        assert_eq!(bad_svg.status, JobStatus::Failed);
+        assert_eq!(bad_svg.retry_count, 0);
+        assert!(bad_svg.error.unwrap().contains("Invalid SVG dimensions"));
+
+        let mut malformed = test_job();
+        let parse_error = crate::converter::SvgToPdfConverter::new()
+            .convert_to_bytes("<svg>\n<path d='M0 0' d='M1 1'/></svg>")
+            .unwrap_err();
+        malformed.mark_conversion_failed(&parse_error);
+        assert!(!malformed.retry());
+        let error = malformed.error.unwrap();
+        assert!(error.contains("at 2:16"), "{}", error);
+        assert!(error.contains("(in <path>)"), "{}", error);
+
+        let mut disk_full = test_job();
+        disk_full.mark_conversion_failed(&ConvertError::Io {
+            path: "/exports/out.pdf".to_string(),
+            source: std::io::Error::from(std::io::ErrorKind::StorageFull),
+        });
+        assert_eq!(disk_full.error_kind, Some(ErrorKind::DiskFull));
+        assert!(!disk_full.retry());
+        assert_eq!(disk_full.status, JobStatus::Failed);
+        assert_eq!(disk_full.retry_count, 0);
+
+        let mut timed_out = test_job();
+        timed_out.mark_conversion_failed(&ConvertError::Timeout(Duration::from_secs(1)));
+        assert!(timed_out.retry());
+        assert_eq!(timed_out.retry_count, 1);
+    }
+
+    #[test]
+    fn test_check_svg_size_rejects_oversized_page() {
+        let mut job = test_job();
+        let limit = job.svg_content.len();
+        assert!(job.check_svg_size(limit).is_ok());
+        assert!(matches!(
+            job.check_svg_size(limit - 1),
+            Err(ConvertError::TooLarge { .. })
+        ));
+
+        // Artboards replace svg_content, so each one is checked instead
+        job.artboards = vec![
+            ArtboardSvg {
+                artboard_id: "ab-1".to_string(),
+                svg_content: "<svg/>".to_string(),
+                name: None,
+            },
+            ArtboardSvg {
+                artboard_id: "ab-2".to_string(),
+                svg_content: "<svg>".repeat(100),
+                name: None,
+            },
+        ];
+        let err = job.check_svg_size(100).unwrap_err();
+        assert_eq!(err.to_string(), "SVG too large: 500 bytes exceeds the 100-byte limit");
+    }
+
+    #[test]
+    fn test_page_labels_prefer_artboard_names() {
+        let mut job = test_job();
+        assert!(job.page_labels().is_empty());
+        job.metadata.artboard_ids = vec!["ab-1".to_string()];
+        assert_eq!(job.page_labels(), ["ab-1"]);
+
+        job.artboards = vec![
+            ArtboardSvg {
+                artboard_id: "ab-1".to_string(),
+                svg_content: "<svg/>".to_string(),
+                name: Some("Cover".to_string()),
+            },
+            ArtboardSvg {
+                artboard_id: "ab-2".to_string(),
+                svg_content: "<svg/>".to_string(),
+                name: None,
+            },
+        ];
+        assert_eq!(job.page_labels(), ["Cover", "ab-2"]);
+    }
+
+    #[test]
+    fn test_mark_cancelled_is_terminal() {
+        let mut job = test_job();
+        job.start_processing();
+        job.mark_cancelled();
+
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert_eq!(job.status.to_string(), "cancelled");
+        assert!(job.processing_duration_ms().is_some());
+    }
+
+    #[test]
+    fn test_clock_sets_timestamps_and_durations() {
+        let start = DateTime::parse_from_rfc3339("2025-11-11T12:00:00Z").unwrap().with_timezone(&Utc);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut job = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg("<svg></svg>")
+            .output_path("/tmp/test.pdf")
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        assert_eq!(job.created_at, start);
+        assert_eq!(job.updated_at, start);
+
+        clock.advance(chrono::Duration::milliseconds(250));
+        job.start_processing();
+        assert_eq!(job.queue_wait_ms, Some(250));
+        assert_eq!(job.processing_duration_ms(), None);
+
+        clock.advance(chrono::Duration::milliseconds(1_500));
+        job.mark_complete(1024, 1, None);
+        assert_eq!(job.updated_at, start + chrono::Duration::milliseconds(1_750));
+        assert_eq!(job.processing_duration_ms(), Some(1_750));
+        assert_eq!(job.history.last().unwrap().at, job.updated_at);
+
+        // The clock isn't serialized; a job read back uses the real one
+        let json = serde_json::to_string(&job).unwrap();
+        let mut decoded: PdfExportJob = serde_json::from_str(&json).unwrap();
+        decoded.requeue();
+        assert!(decoded.updated_at > start);
+    }
+
+    #[test]
+    fn test_job_expires_at_its_deadline() {
+        let start = DateTime::parse_from_rfc3339("2025-11-11T12:00:00Z").unwrap().with_timezone(&Utc);
+        let clock = Arc::new(ManualClock::new(start));
+        let mut job = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg("<svg></svg>")
+            .output_path("/tmp/test.pdf")
+            .expires_at(start + chrono::Duration::seconds(30))
+            .clock(clock.clone())
+            .build()
+            .unwrap();
+        assert!(!job.is_expired());
+        assert!(!test_job().is_expired());
+
+        clock.advance(chrono::Duration::seconds(30));
+        assert!(job.is_expired());
+        job.mark_expired();
+        assert_eq!(job.status, JobStatus::Expired);
+        assert_eq!(job.processing_duration_ms(), Some(30_000));
+        assert_eq!(job.status.to_string(), "expired");
+        assert_eq!(serde_json::to_value(job.status).unwrap(), "expired");
+    }
+
+    #[test]
+    fn test_retry_backoff_is_capped() {
+        assert!(retry_backoff(u8::MAX) <= RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn test_retry_schedules_next_attempt() {
+        let mut job = test_job();
+        assert!(job.next_attempt_at.is_none());
+
+        assert!(job.retry());
+        let next = job.next_attempt_at.unwrap();
+        assert!(next > job.updated_at);
+        assert_eq!(job.status, JobStatus::Queued);
+    }
+
+    #[test]
+    fn test_start_processing_records_queue_wait() {
+        let mut job = test_job();
+        job.created_at -= chrono::Duration::seconds(5);
+        job.start_processing();
+        assert!(job.queue_wait_ms.unwrap() >= 5_000);
+
+        // A retry waits from the end of its backoff, not from creation
+        job.mark_failed("boom".to_string());
+        assert!(job.retry());
+        assert_eq!(job.enqueued_at, job.next_attempt_at);
+        job.start_processing();
+        assert_eq!(job.queue_wait_ms, Some(0));
+    }
+
+    #[test]
+    fn test_history_records_transitions_in_order() {
+        let mut job = test_job().with_max_retries(MAX_HISTORY as u8);
+        assert!(job.history.is_empty());
+
+        job.start_processing();
+        job.mark_failed("boom".to_string());
+        assert!(job.retry());
+        job.start_processing();
+        job.mark_complete(1024, 1, None);
+
+        let statuses: Vec<JobStatus> = job.history.iter().map(|t| t.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                JobStatus::Processing,
+                JobStatus::Failed,
+                JobStatus::Queued,
+                JobStatus::Processing,
+                JobStatus::Complete,
+            ]
+        );
+        assert!(job.history.windows(2).all(|pair| pair[0].at <= pair[1].at));
+        assert_eq!(job.history.last().unwrap().at, job.updated_at);
+
+        // Failing again without a status change adds nothing
+        let mut failed = test_job().with_max_retries(0);
+        failed.start_processing();
+        failed.mark_failed("boom".to_string());
+        assert!(!failed.retry());
+        assert_eq!(failed.history.len(), 2);
+
+        // Only the most recent transitions are kept
+        for _ in 0..MAX_HISTORY {
+            job.start_processing();
+            job.mark_failed("boom".to_string());
+        }
+        assert_eq!(job.history.len(), MAX_HISTORY);
+        assert_eq!(job.history.last().unwrap().status, JobStatus::Failed);
+    }
+
+    #[test]
+    fn test_mark_complete_records_output_stats() {
+        let mut job = test_job();
+        let json = serde_json::to_string(&job).unwrap();
+        assert!(!json.contains("output_bytes"));
+        assert!(!json.contains("page_count"));
+        assert!(!json.contains("output_sha256"));
+
+        let sha256 = "ab".repeat(32);
+        job.start_processing();
+        job.mark_complete(48_213, 3, Some(sha256.clone()));
+        assert_eq!(job.status, JobStatus::Complete);
+        assert_eq!(job.output_bytes, Some(48_213));
+        assert_eq!(job.page_count, Some(3));
+        assert_eq!(job.output_sha256, Some(sha256.clone()));
+
+        let restored: PdfExportJob =
+            serde_json::from_str(&serde_json::to_string(&job).unwrap()).unwrap();
+        assert_eq!(restored.output_bytes, Some(48_213));
+        assert_eq!(restored.page_count, Some(3));
+        assert_eq!(restored.output_sha256, Some(sha256));
+    }
+
+    #[test]
+    fn test_checksum_detects_modified_content() {
+        let mut job = test_job();
+        assert!(job.verify_checksum());
+
+        job.checksum = Some(job.content_checksum());
+        assert_eq!(job.checksum.as_ref().unwrap().len(), 64);
+        assert!(job.verify_checksum());
+
+        job.svg_content.push_str("<!-- tampered -->");
+        assert!(!job.verify_checksum());
+
+        // Multi-page jobs hash every artboard
+        let mut job = PdfExportJob::new_multi_page(
+            "doc-123".to_string(),
+            vec![
+                ArtboardSvg {
+                    artboard_id: "ab-1".to_string(),
+                    svg_content: "<svg>a</svg>".to_string(),
+                    name: None,
+                },
+                ArtboardSvg {
+                    artboard_id: "ab-2".to_string(),
+                    svg_content: "<svg>b</svg>".to_string(),
+                    name: None,
+                },
+            ],
+            "/tmp/test.pdf".to_string(),
+            test_job().metadata,
+        );
+        job.checksum = Some(job.content_checksum());
+        job.artboards[1].svg_content = "<svg>c</svg>".to_string();
+        assert!(!job.verify_checksum());
+    }
+
+    #[test]
+    fn test_svg_source_serialization() {
+        let metadata = test_job().metadata;
+        let inline = PdfExportJob::from_source(
+            "doc-123".to_string(),
+            SvgSource::Inline("<svg></svg>".to_string()),
+            "/tmp/test.pdf".to_string(),
+            metadata.clone(),
+        );
+        assert_eq!(inline.svg_content, "<svg></svg>");
+        assert!(inline.svg_source.is_none());
+        assert!(!serde_json::to_string(&inline).unwrap().contains("svg_source"));
+
+        let referenced = PdfExportJob::from_source(
+            "doc-123".to_string(),
+            SvgSource::RedisKey("wiretuner:svg:doc-123".to_string()),
+            "/tmp/test.pdf".to_string(),
+            metadata,
+        );
+        assert!(referenced.svg_content.is_empty());
+        let json = serde_json::to_string(&referenced).unwrap();
+        assert!(json.contains(r#""svg_source":{"redis_key":"wiretuner:svg:doc-123"}"#));
+
+        let restored: PdfExportJob = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.svg_source, referenced.svg_source);
+
+        // Repointing the reference invalidates the checksum
+        let mut tampered = referenced.clone();
+        tampered.checksum = Some(referenced.content_checksum());
+        tampered.svg_source = Some(SvgSource::FilePath("/etc/passwd".into()));
+        assert!(!tampered.verify_checksum());
+    }
+}
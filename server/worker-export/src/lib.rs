@@ -6,16 +6,36 @@
 //! ## Module Overview
 //!
 //! - `converter`: SVG to PDF conversion using resvg
+//! - `events`: Job lifecycle events and the sinks they are sent to
 //! - `job`: Job models and state management
+//! - `metrics`: Prometheus counters, histogram and `/metrics` endpoint
+//! - `output`: Output root confinement for job output paths
+//! - `payload`: JSON and MessagePack encoding of queued jobs
 //! - `queue`: Redis-based job queue operations
+//! - `rate_limit`: Per-document and per-user enqueue rate limits
 //! - `telemetry`: OpenTelemetry integration and structured logging
+//! - `testing`: In-memory `MockQueue` for tests without Redis (`testing` feature)
+//! - `worker`: Worker loop, job processing and graceful shutdown
+//!
+//! ## In-Process Conversion
+//!
+//! Embedders that don't need the Redis queue can convert directly with
+//! [`render_svg_to_pdf`] and [`render_svg_to_file`]:
+//!
+//! ```rust
+//! use worker_export::{render_svg_to_pdf, ConverterOptions};
+//!
+//! let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#;
+//! let pdf = render_svg_to_pdf(svg, ConverterOptions::default()).unwrap();
+//! assert!(pdf.starts_with(b"%PDF"));
+//! ```
 //!
 //! ## Example Usage
 //!
 //! ```rust,no_run
 //! use worker_export::{
 //!     converter::SvgToPdfConverter,
-//!     job::{PdfExportJob, JobMetadata},
+//!     job::PdfExportJob,
 //!     queue::JobQueue,
 //! };
 //!
@@ -25,17 +45,15 @@
 //!     let converter = SvgToPdfConverter::new();
 //!
 //!     // Create job
-//!     let job = PdfExportJob::new(
-//!         "doc-123".to_string(),
-//!         "<svg></svg>".to_string(),
-//!         "/tmp/output.pdf".to_string(),
-//!         JobMetadata {
-//!             artboard_ids: vec!["ab-1".to_string()],
-//!             export_scope: "current".to_string(),
-//!             client_version: "0.1.0".to_string(),
-//!             user_id: None,
-//!         },
-//!     );
+//!     let job = PdfExportJob::builder()
+//!         .document_id("doc-123")
+//!         .svg("<svg></svg>")
+//!         .output_path("/tmp/output.pdf")
+//!         .artboard_ids(vec!["ab-1".to_string()])
+//!         .export_scope("current")
+//!         .client_version("0.1.0")
+//!         .build()
+//!         .expect("required fields are set");
 //!
 //!     // Convert SVG to PDF
 //!     let result = converter.convert(&job.svg_content, &job.output_path);
@@ -44,6 +62,22 @@
 //! ```
 
 pub mod converter;
+mod eps;
+pub mod events;
+mod http;
 pub mod job;
+mod links;
+mod markup;
+pub mod metrics;
+pub mod output;
+pub mod payload;
 pub mod queue;
+pub mod rate_limit;
+mod resources;
+pub mod sink;
 pub mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod worker;
+
+pub use converter::{render_svg_to_file, render_svg_to_pdf, ConvertError, ConverterOptions};
@@ -0,0 +1,165 @@
+//! Hyperlink extraction for PDF link annotations.
+//!
+//! usvg turns `<a>` elements into plain groups and drops their `href`, so
+//! links are collected from the raw SVG before parsing. Each anchor is
+//! tagged with an id (unless it already has one) so its group can be found
+//! in the parsed tree and its bounding box used as the clickable area.
+
+use crate::markup::{tag_name_end, Edit};
+
+const SVG_NS: &str = "http://www.w3.org/2000/svg";
+const XLINK_NS: &str = "http://www.w3.org/1999/xlink";
+
+/// Prefix of the ids given to anchors that have none.
+const LINK_ID_PREFIX: &str = "wiretuner-link-";
+
+/// URI schemes a link may use. Anything else (`javascript:`, `file:`,
+/// `data:`, relative paths) is dropped rather than handed to PDF viewers.
+const LINK_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// An anchor found in the SVG source, not yet located in the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Anchor {
+    /// Element id used to find the anchor's group after parsing.
+    id: String,
+    /// Link target.
+    uri: String,
+}
+
+/// A hyperlink with its clickable area in SVG canvas coordinates.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Link {
+    pub(crate) rect: usvg::Rect,
+    pub(crate) uri: String,
+}
+
+impl Link {
+    /// Maps the link area onto a page where the SVG's unit-square XObject is
+    /// placed with `transform` (`[w, 0, 0, h, x, y]`), flipping the y axis
+    /// from SVG's top-left origin to PDF's bottom-left one.
+    pub(crate) fn page_rect(&self, size: usvg::Size, transform: [f32; 6]) -> [f32; 4] {
+        let [w, _, _, h, x, y] = transform;
+        let (width, height) = (size.width(), size.height());
+        [
+            x + w * self.rect.left() / width,
+            y + h * (height - self.rect.bottom()) / height,
+            x + w * self.rect.right() / width,
+            y + h * (height - self.rect.top()) / height,
+        ]
+    }
+}
+
+/// Finds the external links in `doc`, returning edits to its source that
+/// add an id to every anchor that lacks one.
+///
+/// Fragment links (`#id`) and anchors inside `<text>` are skipped: the former
+/// have no destination outside the drawing and the latter are not kept as
+/// groups by usvg. So are links whose scheme isn't in [`LINK_SCHEMES`].
+pub(crate) fn tag_anchors(doc: &roxmltree::Document) -> (Vec<Edit>, Vec<Anchor>) {
+    let mut anchors = Vec::new();
+    let mut edits = Vec::new();
+    for node in doc.descendants() {
+        if !is_svg_element(node, "a") || node.ancestors().any(|n| is_svg_element(n, "text")) {
+            continue;
+        }
+        let href = node
+            .attribute((XLINK_NS, "href"))
+            .or_else(|| node.attribute("href"))
+            .map(str::trim)
+            .unwrap_or_default();
+        if !has_allowed_scheme(href) {
+            continue;
+        }
+
+        let id = match node.attribute("id") {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => {
+                let id = format!("{}{}", LINK_ID_PREFIX, anchors.len());
+                let at = tag_name_end(doc.input_text(), node.range().start);
+                edits.push((at..at, format!(" id=\"{}\"", id)));
+                id
+            }
+        };
+        anchors.push(Anchor {
+            id,
+            uri: href.to_string(),
+        });
+    }
+    (edits, anchors)
+}
+
+/// Looks up each anchor in the parsed tree, dropping anchors that usvg
+/// removed or that enclose nothing visible.
+pub(crate) fn resolve(tree: &usvg::Tree, anchors: &[Anchor]) -> Vec<Link> {
+    anchors
+        .iter()
+        .filter_map(|anchor| {
+            let rect = tree.node_by_id(&anchor.id)?.abs_bounding_box();
+            (rect.width() > 0.0 || rect.height() > 0.0).then(|| Link {
+                rect,
+                uri: anchor.uri.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Returns `true` if `href` is an absolute URI with one of the
+/// [`LINK_SCHEMES`].
+fn has_allowed_scheme(href: &str) -> bool {
+    let Some((scheme, _)) = href.split_once(':') else {
+        return false;
+    };
+    LINK_SCHEMES
+        .iter()
+        .any(|allowed| scheme.eq_ignore_ascii_case(allowed))
+}
+
+fn is_svg_element(node: roxmltree::Node, name: &str) -> bool {
+    node.is_element()
+        && node.tag_name().name() == name
+        && node.tag_name().namespace() == Some(SVG_NS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markup;
+
+    #[test]
+    fn test_tag_anchors_adds_missing_ids() {
+        let svg = r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink">
+            <a href="https://example.com"><rect width="10" height="10"/></a>
+            <a id="docs" xlink:href="https://example.com/docs"><rect width="5" height="5"/></a>
+            <a href="#local"><rect width="5" height="5"/></a>
+            <a href="javascript:alert(1)"><rect width="5" height="5"/></a>
+            <a href="JavaScript:alert(1)"><rect width="5" height="5"/></a>
+            <a href="file:///etc/passwd"><rect width="5" height="5"/></a>
+            <a href="data:text/html,hi"><rect width="5" height="5"/></a>
+            <a href="page.html"><rect width="5" height="5"/></a>
+            <a href="MAILTO:team@example.com"><rect width="5" height="5"/></a>
+        </svg>"##;
+
+        let doc = markup::parse(svg).unwrap();
+        let (edits, anchors) = tag_anchors(&doc);
+        let tagged = markup::apply_edits(svg, edits);
+
+        assert!(tagged.contains(r#"<a id="wiretuner-link-0" href="https://example.com">"#));
+        assert_eq!(
+            anchors,
+            vec![
+                Anchor {
+                    id: "wiretuner-link-0".to_string(),
+                    uri: "https://example.com".to_string(),
+                },
+                Anchor {
+                    id: "docs".to_string(),
+                    uri: "https://example.com/docs".to_string(),
+                },
+                Anchor {
+                    id: "wiretuner-link-2".to_string(),
+                    uri: "MAILTO:team@example.com".to_string(),
+                },
+            ]
+        );
+    }
+}
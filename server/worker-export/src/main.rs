@@ -9,41 +9,319 @@
 //! - **Status**: Redis keys (`wiretuner:export:pdf:status:{job_id}`)
 //! - **Converter**: resvg-based SVG→PDF pipeline
 //! - **Telemetry**: OpenTelemetry OTLP export
+//! - **Metrics**: Prometheus `/metrics` endpoint and `/ready` probe
+//!
+//! ## Usage
+//!
+//! - `worker-export [worker]`: Run the queue worker daemon (the default)
+//! - `worker-export convert INPUT.svg OUTPUT.pdf [--page-size a4] [--background white]`:
+//!   Convert one file locally, without Redis
+//! - `worker-export batch INPUT_DIR OUTPUT_DIR [--concurrency 4]`: Convert every
+//!   `.svg` in a directory to a matching `.pdf`, without Redis
 //!
 //! ## Configuration
 //!
-//! Environment variables:
+//! Environment variables (`convert` and `batch` only read `FONT_DIRS`, `MAX_SVG_BYTES`,
+//! `MAX_PAGES`, `MAX_IMAGE_PIXELS` and `REMOTE_IMAGE_HOSTS`):
 //! - `REDIS_URL`: Redis connection string (default: redis://127.0.0.1/)
+//! - `QUEUE_NAMESPACE`: Prefix for every Redis key the worker uses; producers must use the same `KeyNamespace` (default: wiretuner:export:pdf)
 //! - `OTEL_EXPORTER_OTLP_ENDPOINT`: OTLP collector endpoint
+//! - `PDF_EXPORT_SLOW_THRESHOLD_MS`: Job duration logged and counted as slow (default: 5000; `SLOW_JOB_THRESHOLD_MS` is still read as a fallback)
+//! - `WORKER_INSTANCE_ID`: Stable id for this worker's processing list (default: `$HOSTNAME`)
 //! - `WORKER_CONCURRENCY`: Number of concurrent workers (default: 4)
+//! - `MAX_WORKER_CONCURRENCY`: Upper bound for the concurrency target set in Redis at runtime (default: 64)
+//! - `CONVERSION_TIMEOUT_SECS`: Per-job conversion deadline in seconds (default: 60)
+//! - `MAX_JOBS_PER_DOCUMENT`: Most jobs of one document processed at once (default: no limit)
+//! - `SHUTDOWN_GRACE_SECS`: Time to let in-flight jobs finish on shutdown, at most the conversion timeout (default: 30)
+//! - `BLPOP_TIMEOUT_SECS`: How long each dequeue blocks waiting for a job (default: 5)
+//! - `QUEUE_SERIALIZATION`: `json` or `msgpack`, the format jobs this worker re-queues are written in (default: json)
+//! - `DEAD_LETTER_MAX_LEN`: Most payloads kept on the dead-letter list, oldest trimmed first; 0 for no limit (default: 10000)
+//! - `DEAD_LETTER_TTL_SECS`: Expire the dead-letter list this long after its last payload (default: never)
+//! - `SCOPE_PRIORITIES`: Priorities of jobs enqueued without one, by export scope, e.g. `current=high,all=normal` (default: that; unlisted scopes are normal)
+//! - `MAX_DLQ_REPLAYS`: Manual replays from the dead-letter list allowed per job before they must be forced (default: 3)
+//! - `ERROR_BACKOFF_SECS`: Wait before retrying after a Redis error, doubling while Redis is unreachable (default: 5)
+//! - `OUTPUT_ROOT`: Directory exports are confined to; job paths outside it are replaced. Set it when upgrading from a worker that wrote anywhere (default: /exports)
+//! - `OUTPUT_TEMPLATE`: Server-side output naming such as `{document_id}/{artboard_id}-{timestamp}.{ext}`, replacing job paths (default: none)
+//! - `SVG_INPUT_ROOT`: Directory jobs may reference SVG files under with `svg_source.file_path` (default: none, file sources fail)
+//! - `MINIO_BUCKET`: MinIO bucket jobs may write to with `s3://bucket/key` output paths (default: none, `s3://` paths fail)
+//! - `MINIO_ENDPOINT`: MinIO server URL, plain http only, e.g. `http://minio:9000` (required with `MINIO_BUCKET`)
+//! - `MINIO_ACCESS_KEY_ID` / `MINIO_SECRET_ACCESS_KEY`: Credentials for `MINIO_BUCKET` (required with it)
+//! - `MINIO_REGION`: Region requests are signed for (default: us-east-1)
+//! - `MINIO_KEY_PREFIX`: Prefix added to every object key written (default: none)
+//! - `MAX_SVG_BYTES`: Largest SVG accepted per page, at enqueue and before parsing (default: 52428800)
+//! - `MAX_PAGES`: Most pages (artboards) per job, at enqueue and before converting (default: 500)
+//! - `MAX_IMAGE_PIXELS`: Pixel budget for embedded raster images; larger ones are downsampled (default: no limit)
+//! - `REMOTE_IMAGE_HOSTS`: Comma-separated hosts `<image>` hrefs may be fetched from over http (default: none, only data URIs load)
+//! - `FONT_DIRS`: Extra font directories for SVG text, `:`-separated (default: none)
+//! - `DRY_RUN`: `true` or `1` to convert jobs in full without writing their output (default: off)
+//! - `METRICS_PORT`: Port for the Prometheus `/metrics` endpoint (default: 9090)
+//! - `STATS_INTERVAL_SECS`: How often queue stats are refreshed for metrics (default: 15)
 //! - `RUST_LOG`: Log level (default: info)
 
-mod converter;
-mod job;
-mod queue;
-mod telemetry;
-
 use anyhow::{Context, Result};
-use converter::SvgToPdfConverter;
-use job::PdfExportJob;
-use queue::JobQueue;
+use clap::{Args, Parser, Subcommand};
 use redis::Client;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
-use tokio::sync::Semaphore;
-use tracing::{error, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use worker_export::{
+    converter::{
+        decode_svg, Color, ConverterOptions, PageSize, ResourcePolicy, SvgToPdfConverter,
+        DEFAULT_MAX_PAGES, DEFAULT_MAX_SVG_BYTES,
+    },
+    metrics,
+    output::{OutputConfig, DEFAULT_OUTPUT_ROOT},
+    queue::{JobQueue, KeyNamespace, QueueConfig},
+    sink::S3Config,
+    telemetry,
+    worker::{
+        concurrency_loop, heartbeat_loop, promoter_loop, worker_loop, ConcurrencyLimit,
+        panic_message, InFlightJobs, WorkerConfig, CONCURRENCY_POLL_INTERVAL, HEARTBEAT_INTERVAL, HEARTBEAT_TTL,
+        PROMOTE_INTERVAL,
+    },
+};
+
+/// WireTuner PDF export worker.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Consume export jobs from Redis (the default).
+    Worker,
+    /// Convert a single SVG file to PDF, bypassing Redis.
+    Convert(ConvertArgs),
+    /// Convert every SVG in a directory to PDF, bypassing Redis.
+    Batch(BatchArgs),
+}
+
+#[derive(Args)]
+struct ConvertArgs {
+    /// SVG file to convert, plain or gzip-compressed (.svgz).
+    input: PathBuf,
+    /// Where to write the PDF.
+    output: PathBuf,
+    #[command(flatten)]
+    page: PageArgs,
+}
 
+#[derive(Args)]
+struct BatchArgs {
+    /// Directory of .svg (and .svgz) files to convert; subdirectories are skipped.
+    input_dir: PathBuf,
+    /// Directory the PDFs are written to, one per SVG with the same file stem.
+    output_dir: PathBuf,
+    /// Files converted at once (default: number of CPUs).
+    #[arg(long, short = 'j')]
+    concurrency: Option<NonZeroUsize>,
+    #[command(flatten)]
+    page: PageArgs,
+}
+
+/// Page settings shared by `convert` and `batch`.
+#[derive(Args)]
+struct PageArgs {
+    /// Page size: intrinsic, a3, a4, a5, letter, legal or WIDTHxHEIGHT in points.
+    #[arg(long, default_value = "intrinsic")]
+    page_size: PageSize,
+    /// Background color (#RRGGBB, #RGB or a name such as white); transparent when omitted.
+    #[arg(long)]
+    background: Option<Color>,
+}
+
+impl PageArgs {
+    fn options(&self) -> ConverterOptions {
+        let mut options = ConverterOptions {
+            page_size: self.page_size,
+            ..ConverterOptions::default()
+        };
+        if let Some(color) = self.background {
+            options = options.with_background(color);
+        }
+        options
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Worker) {
+        Command::Worker => run_worker(),
+        Command::Convert(args) => convert(args),
+        Command::Batch(args) => batch(args),
+    }
+}
+
+/// Converts one file with the same converter settings the worker uses.
+fn convert(args: ConvertArgs) -> Result<()> {
+    init_tracing("warn");
+    convert_file(
+        &build_converter(),
+        &args.input,
+        &args.output,
+        &args.page.options(),
+    )
+}
+
+/// Converts every SVG in `input_dir` into `output_dir`, printing a summary.
+///
+/// A file that fails to convert, or panics the converter, doesn't stop the
+/// others; the command exits with an error once all are done if any failed.
+/// Files whose names differ only in extension (`logo.svg` and `logo.svgz`)
+/// would write the same PDF, so neither is converted.
+fn batch(args: BatchArgs) -> Result<()> {
+    init_tracing("warn");
+
+    let inputs = svg_files(&args.input_dir)?;
+    std::fs::create_dir_all(&args.output_dir)
+        .with_context(|| format!("Failed to create {}", args.output_dir.display()))?;
+
+    let outputs: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| batch_output_path(input, &args.output_dir))
+        .collect();
+    let mut writers: HashMap<&Path, usize> = HashMap::new();
+    for output in &outputs {
+        *writers.entry(output.as_path()).or_default() += 1;
+    }
+
+    let converter = build_converter();
+    let options = args.page.options();
+    let concurrency = args
+        .concurrency
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+        .min(inputs.len());
+
+    // Workers pull the next unclaimed file until none are left.
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<(usize, Result<()>)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..concurrency)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(index) else {
+                            break results;
+                        };
+                        let output = &outputs[index];
+                        let result = if writers[output.as_path()] > 1 {
+                            Err(anyhow::anyhow!(
+                                "Another input file would also write {}",
+                                output.display()
+                            ))
+                        } else {
+                            panic::catch_unwind(AssertUnwindSafe(|| {
+                                convert_file(&converter, input, output, &options)
+                            }))
+                            .unwrap_or_else(|payload| {
+                                Err(anyhow::anyhow!(
+                                    "Converter panicked: {}",
+                                    panic_message(&*payload)
+                                ))
+                            })
+                        };
+                        results.push((index, result));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap_or_default())
+            .collect()
+    });
+    results.sort_by_key(|(index, _)| *index);
+
+    let mut converted = 0;
+    for (index, result) in &results {
+        match result {
+            Ok(()) => {
+                converted += 1;
+                println!("ok      {}", inputs[*index].display());
+            }
+            Err(e) => println!("FAILED  {}: {:#}", inputs[*index].display(), e),
+        }
+    }
+    let failed = inputs.len() - converted;
+    println!(
+        "Converted {} of {} files ({} failed)",
+        converted,
+        inputs.len(),
+        failed
+    );
+
+    if failed > 0 {
+        anyhow::bail!("{} of {} files failed to convert", failed, inputs.len());
+    }
+    Ok(())
+}
+
+/// The `.svg` and `.svgz` files directly inside `dir`, sorted by path.
+fn svg_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .path();
+        let is_svg = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg") || ext.eq_ignore_ascii_case("svgz"));
+        if is_svg && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `output_dir/<stem>.pdf` for an input SVG.
+fn batch_output_path(input: &Path, output_dir: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or(input.as_os_str());
+    output_dir.join(stem).with_extension("pdf")
+}
+
+/// Reads, decodes and converts one SVG file to `output`.
+fn convert_file(
+    converter: &SvgToPdfConverter,
+    input: &Path,
+    output: &Path,
+    options: &ConverterOptions,
+) -> Result<()> {
+    let data =
+        std::fs::read(input).with_context(|| format!("Failed to read {}", input.display()))?;
+    let svg = decode_svg(&data, max_svg_bytes())
+        .with_context(|| format!("Failed to read {}", input.display()))?;
+    let output = output.to_str().context("Output path is not valid UTF-8")?;
+
+    // Relative <image> paths are next to the SVG
+    let dir = input.parent().filter(|dir| !dir.as_os_str().is_empty());
+    let converter = converter.clone().with_resources_dir(dir.unwrap_or(Path::new(".")));
+    converter
+        .convert_with_options(&svg, output, options)
+        .with_context(|| format!("Failed to convert {}", input.display()))?;
+    Ok(())
+}
+
+/// Runs the queue worker until SIGTERM or Ctrl+C.
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+async fn run_worker() -> Result<()> {
+    init_tracing("info");
 
     // Initialize OpenTelemetry
     if let Err(e) = telemetry::init_telemetry() {
@@ -55,16 +333,109 @@ async fn main() -> Result<()> {
     // Load configuration
     let redis_url = std::env::var("REDIS_URL")
         .unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
+    let instance_id = std::env::var("WORKER_INSTANCE_ID")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "default".to_string());
+    let namespace = std::env::var("QUEUE_NAMESPACE")
+        .map(KeyNamespace::new)
+        .unwrap_or_default();
     let concurrency: usize = std::env::var("WORKER_CONCURRENCY")
         .ok()
         .and_then(|v| v.parse().ok())
         .unwrap_or(4);
+    let max_concurrency: usize = std::env::var("MAX_WORKER_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(64)
+        .max(concurrency);
+    let conversion_timeout = Duration::from_secs(
+        std::env::var("CONVERSION_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(60),
+    );
+    let shutdown_grace = Duration::from_secs(
+        std::env::var("SHUTDOWN_GRACE_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30),
+    );
+    let output_root = std::env::var_os("OUTPUT_ROOT").unwrap_or_else(|| {
+        // Deployments from before output confinement wrote wherever jobs asked
+        warn!(
+            "OUTPUT_ROOT is not set; exports are confined to {} and paths outside it are replaced",
+            DEFAULT_OUTPUT_ROOT
+        );
+        DEFAULT_OUTPUT_ROOT.into()
+    });
+    let mut output = OutputConfig::new(output_root);
+    if let Ok(template) = std::env::var("OUTPUT_TEMPLATE") {
+        output = output.with_template(template.parse().context("Invalid OUTPUT_TEMPLATE")?);
+    }
+    if let Some(input_root) = std::env::var_os("SVG_INPUT_ROOT") {
+        output = output.with_input_root(input_root);
+    }
+    if let Some(s3) = s3_config()? {
+        info!("Writing s3:// outputs to MinIO bucket {} at {}", s3.bucket, s3.endpoint);
+        output = output.with_s3(s3);
+    }
+    let max_jobs_per_document: Option<usize> = std::env::var("MAX_JOBS_PER_DOCUMENT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&limit| limit > 0);
+    let config = WorkerConfig {
+        concurrency,
+        conversion_timeout,
+        shutdown_grace,
+        output,
+    };
+    let defaults = QueueConfig::default();
+    let queue_config = QueueConfig {
+        blpop_timeout: env_secs_f64("BLPOP_TIMEOUT_SECS").unwrap_or(defaults.blpop_timeout),
+        error_backoff: env_secs_f64("ERROR_BACKOFF_SECS").unwrap_or(defaults.error_backoff),
+        max_svg_bytes: max_svg_bytes(),
+        max_pages: max_pages(),
+        serialization: std::env::var("QUEUE_SERIALIZATION")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default(),
+        // 0 keeps every dead-lettered payload
+        dead_letter_max_len: match std::env::var("DEAD_LETTER_MAX_LEN").ok().and_then(|v| v.parse().ok()) {
+            Some(0) => None,
+            Some(max_len) => Some(max_len),
+            None => defaults.dead_letter_max_len,
+        },
+        dead_letter_ttl: env_secs_f64("DEAD_LETTER_TTL_SECS").or(defaults.dead_letter_ttl),
+        max_dlq_replays: std::env::var("MAX_DLQ_REPLAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.max_dlq_replays),
+        scope_priorities: match std::env::var("SCOPE_PRIORITIES") {
+            Ok(v) => v.parse().context("Invalid SCOPE_PRIORITIES")?,
+            Err(_) => defaults.scope_priorities.clone(),
+        },
+    };
+
+    let metrics_port: u16 = std::env::var("METRICS_PORT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(9090);
+    let stats_interval = env_secs_f64("STATS_INTERVAL_SECS")
+        .filter(|interval| !interval.is_zero())
+        .unwrap_or(Duration::from_secs(15));
 
     info!(
-        "Configuration: redis_url={}, concurrency={}",
-        redis_url, concurrency
+        "Configuration: redis_url={}, namespace={}, instance_id={}, config={:?}, queue_config={:?}, font_dirs={:?}, metrics_port={}",
+        redis_url, namespace.prefix(), instance_id, config, queue_config, font_dirs(), metrics_port
     );
 
+    // Serve Prometheus metrics
+    tokio::spawn(async move {
+        if let Err(e) = metrics::serve(metrics_port).await {
+            warn!("Metrics endpoint stopped: {:#}", e);
+        }
+    });
+
     // Connect to Redis
     let client = Client::open(redis_url.as_str())
         .context("Failed to create Redis client")?;
@@ -73,162 +444,228 @@ async fn main() -> Result<()> {
         .context("Failed to connect to Redis")?;
 
     info!("Connected to Redis");
+    metrics::set_redis_connected(true);
+
+    // Recover jobs a previous incarnation of this instance never acknowledged
+    let mut queue = JobQueue::new(conn)
+        .with_namespace(namespace)
+        .with_consumer_id(&instance_id)
+        .with_config(queue_config);
+    let recovered = queue
+        .recover_processing(&instance_id)
+        .await
+        .context("Failed to recover processing list")?;
+    info!("Recovered {} job(s) from processing list", recovered);
+
+    // Refresh queue depth gauges in the background
+    tokio::spawn(metrics::poll_queue_stats(queue.clone(), stats_interval));
 
     // Create shared resources
-    let semaphore = Arc::new(Semaphore::new(concurrency));
-    let converter = Arc::new(SvgToPdfConverter::new());
+    let limit = ConcurrencyLimit::new(concurrency, max_concurrency);
+    let dry_run = std::env::var("DRY_RUN").is_ok_and(|v| matches!(v.as_str(), "1" | "true"));
+    if dry_run {
+        warn!("DRY_RUN is set: jobs are converted but no output is written");
+    }
+    let converter = Arc::new(build_converter().with_dry_run(dry_run));
+    let shutdown = CancellationToken::new();
+    let in_flight = InFlightJobs::new().with_document_limit(max_jobs_per_document);
+
+    // Move scheduled and retrying jobs onto their queues once due
+    let promoter = tokio::spawn(promoter_loop(queue.clone(), PROMOTE_INTERVAL, shutdown.clone()));
+
+    // Follow the concurrency target set in Redis
+    let concurrency_control = tokio::spawn(concurrency_loop(
+        queue.clone(),
+        limit.clone(),
+        CONCURRENCY_POLL_INTERVAL,
+        shutdown.clone(),
+    ));
+
+    // Announce this worker before taking jobs, and take back the jobs of
+    // workers that stopped announcing themselves. Beats continue until the
+    // in-flight jobs are drained, so they aren't taken while finishing.
+    if let Err(e) = queue.heartbeat(HEARTBEAT_TTL).await {
+        warn!("Failed to write worker heartbeat: {}", e);
+    }
+    let heartbeat_stop = CancellationToken::new();
+    let heartbeat = tokio::spawn(heartbeat_loop(
+        queue.clone(),
+        in_flight.clone(),
+        HEARTBEAT_INTERVAL,
+        HEARTBEAT_TTL,
+        config.stall_timeout(),
+        heartbeat_stop.clone(),
+    ));
 
     // Spawn worker tasks
     let mut handles = vec![];
     for worker_id in 0..concurrency {
-        let conn = conn.clone();
-        let semaphore = semaphore.clone();
-        let converter = converter.clone();
-
-        let handle = tokio::spawn(async move {
-            worker_loop(worker_id, conn, semaphore, converter).await
-        });
+        let handle = tokio::spawn(worker_loop(
+            worker_id,
+            queue.clone(),
+            limit.clone(),
+            converter.clone(),
+            config.clone(),
+            shutdown.clone(),
+            in_flight.clone(),
+        ));
 
         handles.push(handle);
     }
 
     // Wait for shutdown signal
     info!("Worker service ready, press Ctrl+C to shutdown");
-    signal::ctrl_c().await.context("Failed to listen for Ctrl+C")?;
+    let received = shutdown_signal().await?;
 
-    info!("Received shutdown signal, waiting for workers to finish...");
+    info!("Received {}, waiting for workers to finish...", received);
+    shutdown.cancel();
 
-    // Wait for all workers to complete
+    // Wait for all workers to stop dequeueing
     for handle in handles {
         let _ = handle.await;
     }
+    let _ = promoter.await;
+    let _ = concurrency_control.await;
+
+    // Let in-flight jobs finish, re-queueing any that outlive the grace period
+    let drain_timeout = config.drain_timeout();
+    info!(
+        "Draining {} in-flight job(s) (waiting up to {:?})",
+        in_flight.len(),
+        drain_timeout
+    );
+    let requeued = in_flight.drain(drain_timeout, &mut queue).await;
+    if requeued > 0 {
+        warn!("Re-queued {} unfinished job(s)", requeued);
+    }
+    heartbeat_stop.cancel();
+    let _ = heartbeat.await;
+
+    // Flush spans and metrics from the last jobs before exiting
+    telemetry::shutdown_telemetry();
 
     info!("Worker service shutdown complete");
     Ok(())
 }
 
-/// Main worker loop that processes jobs from the queue.
-///
-/// This function runs indefinitely until the process is terminated.
-/// It uses a semaphore to limit concurrent job processing.
-async fn worker_loop(
-    worker_id: usize,
-    conn: redis::aio::ConnectionManager,
-    semaphore: Arc<Semaphore>,
-    converter: Arc<SvgToPdfConverter>,
-) {
-    let mut queue = JobQueue::new(conn);
-
-    info!("Worker {} started", worker_id);
-
-    loop {
-        // Dequeue next job (blocks with timeout)
-        let job = match queue.dequeue().await {
-            Ok(Some(job)) => job,
-            Ok(None) => {
-                // Timeout, no job available
-                continue;
-            }
-            Err(e) => {
-                error!("Worker {} failed to dequeue job: {}", worker_id, e);
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-                continue;
-            }
-        };
-
-        // Acquire semaphore permit
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
+/// Waits for SIGTERM (sent by container orchestrators on stop) or SIGINT
+/// and returns the signal's name.
+#[cfg(unix)]
+async fn shutdown_signal() -> Result<&'static str> {
+    use signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+    let mut sigint = signal(SignalKind::interrupt()).context("Failed to listen for SIGINT")?;
+    let received = tokio::select! {
+        _ = sigterm.recv() => "SIGTERM",
+        _ = sigint.recv() => "SIGINT",
+    };
+    Ok(received)
+}
 
-        // Spawn job processing task
-        let mut queue_clone = JobQueue::new(queue.conn.clone());
-        let converter = converter.clone();
+/// Waits for Ctrl+C, the only shutdown signal outside unix.
+#[cfg(not(unix))]
+async fn shutdown_signal() -> Result<&'static str> {
+    signal::ctrl_c().await.context("Failed to listen for Ctrl+C")?;
+    Ok("Ctrl+C")
+}
 
-        tokio::spawn(async move {
-            process_job(job, &mut queue_clone, &converter).await;
-            drop(permit); // Release semaphore
-        });
+/// Installs the log subscriber, honoring `RUST_LOG` over `default_filter`.
+fn init_tracing(default_filter: &str) {
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| default_filter.into()),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+}
 
-        // Record heartbeat every 10 jobs
-        if let Ok(queue_len) = queue.queue_length().await {
-            if queue_len % 10 == 0 {
-                telemetry::record_worker_heartbeat(queue_len);
-            }
-        }
+/// Builds the converter from `FONT_DIRS`, `MAX_SVG_BYTES`, `MAX_PAGES`,
+/// `MAX_IMAGE_PIXELS` and `REMOTE_IMAGE_HOSTS`.
+fn build_converter() -> SvgToPdfConverter {
+    let max_image_pixels: Option<u64> = std::env::var("MAX_IMAGE_PIXELS")
+        .ok()
+        .and_then(|v| v.parse().ok());
+
+    let mut converter = SvgToPdfConverter::new()
+        .with_font_dirs(font_dirs())
+        .with_max_svg_bytes(max_svg_bytes())
+        .with_max_pages(max_pages())
+        .with_resource_policy(resource_policy());
+    if let Some(max_pixels) = max_image_pixels {
+        converter = converter.with_max_image_pixels(max_pixels);
     }
+    converter
 }
 
-/// Processes a single PDF export job.
-///
-/// This function handles the complete job lifecycle:
-/// 1. Mark job as processing
-/// 2. Convert SVG to PDF
-/// 3. Mark job as complete or failed
-/// 4. Record telemetry
-/// 5. Retry on failure (up to 3 times)
-async fn process_job(
-    mut job: PdfExportJob,
-    queue: &mut JobQueue,
-    converter: &SvgToPdfConverter,
-) {
-    info!(
-        "Processing job: job_id={}, document_id={}",
-        job.job_id, job.document_id
-    );
+/// Image loading policy: data URIs, plus http fetches from the hosts in
+/// `REMOTE_IMAGE_HOSTS` if any are listed.
+fn resource_policy() -> ResourcePolicy {
+    let allowlist: Vec<String> = std::env::var("REMOTE_IMAGE_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|host| !host.is_empty())
+        .map(String::from)
+        .collect();
+    if allowlist.is_empty() {
+        ResourcePolicy::DataUriOnly
+    } else {
+        ResourcePolicy::AllowRemote { allowlist }
+    }
+}
 
-    // Mark as processing
-    job.start_processing();
-    if let Err(e) = queue.update_status(&job).await {
-        error!("Failed to update job status: {}", e);
+/// MinIO storage for `s3://` output paths, configured when `MINIO_BUCKET`
+/// is set.
+fn s3_config() -> Result<Option<S3Config>> {
+    let Ok(bucket) = std::env::var("MINIO_BUCKET") else {
+        return Ok(None);
+    };
+    let endpoint =
+        std::env::var("MINIO_ENDPOINT").context("MINIO_BUCKET is set without MINIO_ENDPOINT")?;
+    let access_key_id = std::env::var("MINIO_ACCESS_KEY_ID")
+        .context("MINIO_BUCKET is set without MINIO_ACCESS_KEY_ID")?;
+    let secret_access_key = std::env::var("MINIO_SECRET_ACCESS_KEY")
+        .context("MINIO_BUCKET is set without MINIO_SECRET_ACCESS_KEY")?;
+
+    let mut config = S3Config::new(endpoint, bucket, access_key_id, secret_access_key);
+    if let Ok(region) = std::env::var("MINIO_REGION") {
+        config = config.with_region(region);
+    }
+    if let Ok(prefix) = std::env::var("MINIO_KEY_PREFIX") {
+        config = config.with_key_prefix(prefix);
     }
+    Ok(Some(config))
+}
 
-    // Convert SVG to PDF
-    let result = converter.convert(&job.svg_content, &job.output_path);
+/// Extra font directories from `FONT_DIRS`.
+fn font_dirs() -> Vec<PathBuf> {
+    std::env::var_os("FONT_DIRS")
+        .map(|v| std::env::split_paths(&v).collect())
+        .unwrap_or_default()
+}
 
-    match result {
-        Ok(()) => {
-            // Mark as complete
-            job.mark_complete();
-            if let Err(e) = queue.update_status(&job).await {
-                error!("Failed to update job status: {}", e);
-            }
+/// Largest SVG accepted, from `MAX_SVG_BYTES`.
+fn max_svg_bytes() -> usize {
+    std::env::var("MAX_SVG_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_SVG_BYTES)
+}
 
-            info!(
-                "Job completed: job_id={}, duration_ms={:?}",
-                job.job_id,
-                job.processing_duration_ms()
-            );
-        }
-        Err(e) => {
-            // Mark as failed
-            let error_msg = format!("{:#}", e);
-            error!(
-                "Job failed: job_id={}, error={}",
-                job.job_id, error_msg
-            );
-
-            job.mark_failed(error_msg);
-
-            // Attempt retry
-            match queue.retry_job(job.clone()).await {
-                Ok(true) => {
-                    info!(
-                        "Job re-queued for retry: job_id={}, retry_count={}",
-                        job.job_id, job.retry_count
-                    );
-                }
-                Ok(false) => {
-                    warn!(
-                        "Job failed permanently: job_id={}, max retries exceeded",
-                        job.job_id
-                    );
-                }
-                Err(e) => {
-                    error!("Failed to retry job: {}", e);
-                }
-            }
-        }
-    }
+/// Most pages per job, from `MAX_PAGES`.
+fn max_pages() -> usize {
+    std::env::var("MAX_PAGES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_PAGES)
+}
 
-    // Record telemetry
-    telemetry::record_job_telemetry(&job);
+/// Reads a duration in (possibly fractional) seconds from the environment.
+fn env_secs_f64(name: &str) -> Option<Duration> {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
 }
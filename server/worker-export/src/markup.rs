@@ -0,0 +1,132 @@
+//! Scanning raw SVG markup.
+//!
+//! The passes that rewrite the SVG before usvg parses it (sizing, cropping
+//! and link tagging) share one [`parse`] of the caller's text and return
+//! [`Edit`]s to it by byte offset, which are applied together. Parse error
+//! reporting works on the same offsets, so tags are scanned here rather
+//! than by each pass its own way.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Replaces a byte range of the source text; an empty range inserts.
+pub(crate) type Edit = (Range<usize>, String);
+
+/// Parses `svg` for the rewriting passes, or `None` if it isn't well-formed
+/// XML, which is left for usvg to report.
+pub(crate) fn parse(svg: &str) -> Option<roxmltree::Document<'_>> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    roxmltree::Document::parse_with_options(svg, options).ok()
+}
+
+/// Applies `edits`, which must not overlap, to `svg`. Edits at the same
+/// offset are applied in the order given.
+pub(crate) fn apply_edits(svg: &str, mut edits: Vec<Edit>) -> Cow<'_, str> {
+    if edits.is_empty() {
+        return Cow::Borrowed(svg);
+    }
+    edits.sort_by_key(|(range, _)| range.start);
+
+    let added: usize = edits.iter().map(|(_, text)| text.len()).sum();
+    let mut edited = String::with_capacity(svg.len() + added);
+    let mut last = 0;
+    for (range, text) in edits {
+        edited.push_str(&svg[last..range.start]);
+        edited.push_str(&text);
+        last = range.end;
+    }
+    edited.push_str(&svg[last..]);
+    Cow::Owned(edited)
+}
+
+/// Returns the byte offset just past the tag name of the tag starting at
+/// `start`.
+pub(crate) fn tag_name_end(svg: &str, start: usize) -> usize {
+    let name_start = (start + 1).min(svg.len());
+    svg[name_start..]
+        .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '<' | '=' | '"' | '\''))
+        .map_or(svg.len(), |offset| name_start + offset)
+}
+
+/// Edits removing the attributes of `element` named in `names` and adding
+/// `attributes` right after its tag name.
+pub(crate) fn replace_attributes(
+    element: roxmltree::Node,
+    names: &[&str],
+    attributes: &str,
+) -> Vec<Edit> {
+    let name_end = tag_name_end(element.document().input_text(), element.range().start);
+    let mut edits = vec![(name_end..name_end, format!(" {}", attributes))];
+    edits.extend(
+        element
+            .attributes()
+            .filter(|attr| attr.namespace().is_none() && names.contains(&attr.name()))
+            .map(|attr| (attr.range(), String::new())),
+    );
+    edits
+}
+
+/// Name of the start tag whose markup spans byte `offset` of `svg`, if
+/// any. Quoted attribute values, comments, CDATA sections and processing
+/// instructions are skipped over rather than searched for tags.
+pub(crate) fn enclosing_tag(svg: &str, offset: usize) -> Option<String> {
+    let mut tag_start = None;
+    let mut quote = None;
+    let mut i = 0;
+    'scan: while i <= offset && i < svg.len() {
+        let rest = &svg[i..];
+        match (tag_start, quote) {
+            (Some(_), Some(q)) if rest.starts_with(q) => quote = None,
+            (Some(_), None) if rest.starts_with(['"', '\'']) => quote = rest.chars().next(),
+            (Some(_), None) if rest.starts_with('>') => tag_start = None,
+            (None, _) => {
+                for (open, close) in [("<!--", "-->"), ("<![CDATA[", "]]>"), ("<?", "?>")] {
+                    if rest.starts_with(open) {
+                        i += rest.find(close).map_or(rest.len(), |end| end + close.len());
+                        continue 'scan;
+                    }
+                }
+                if rest.starts_with('<') {
+                    tag_start = Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += rest.chars().next().map_or(1, char::len_utf8);
+    }
+
+    let start = tag_start?;
+    let name = &svg[start + 1..tag_name_end(svg, start)];
+    (!name.is_empty() && !name.starts_with('!')).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enclosing_tag_skips_quoted_values_and_comments() {
+        let svg = r#"<svg><!-- <g> --><text font-family="a>b" x="1">hi</text></svg>"#;
+        assert_eq!(
+            enclosing_tag(svg, svg.find("x=").unwrap()),
+            Some("text".to_string())
+        );
+        assert_eq!(enclosing_tag(svg, svg.find("hi").unwrap()), None);
+        assert_eq!(enclosing_tag(svg, svg.find("<g>").unwrap() + 1), None);
+        assert_eq!(enclosing_tag(svg, svg.find("</text").unwrap() + 2), None);
+    }
+
+    #[test]
+    fn test_replace_attributes_after_tag_name() {
+        let svg = r#"<svg width="1" id="a" height="2"><rect/></svg>"#;
+        let doc = parse(svg).unwrap();
+        let edits = replace_attributes(doc.root_element(), &["width", "height"], r#"width="3""#);
+        assert_eq!(
+            apply_edits(svg, edits),
+            r#"<svg width="3"  id="a" ><rect/></svg>"#
+        );
+    }
+}
@@ -0,0 +1,444 @@
+//! Prometheus metrics for the export worker.
+//!
+//! Metrics are kept in process-wide atomics and rendered in the Prometheus
+//! text exposition format by a small HTTP server on `/metrics`.
+
+use crate::job::JobStatus;
+use crate::queue::{JobQueue, QueueStats};
+use crate::telemetry::{self, WorkerStats};
+use axum::{http::StatusCode, routing::get, Router};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Upper bounds (seconds) of the conversion duration histogram buckets.
+const DURATION_BUCKETS: [f64; 10] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+
+/// Job outcomes counted by `pdf_export_jobs_total`, in label order.
+const JOB_STATUSES: [JobStatus; 4] = [
+    JobStatus::Complete,
+    JobStatus::Failed,
+    JobStatus::Cancelled,
+    JobStatus::Expired,
+];
+
+/// Queues and lists reported by `pdf_export_queue_jobs`, in label order.
+const QUEUE_LABELS: [&str; 5] = ["high", "normal", "low", "delayed", "processing"];
+
+/// States reported by `pdf_export_job_statuses`, in label order.
+const STATUS_LABELS: [JobStatus; 6] = [
+    JobStatus::Queued,
+    JobStatus::Processing,
+    JobStatus::Complete,
+    JobStatus::Failed,
+    JobStatus::Cancelled,
+    JobStatus::Expired,
+];
+
+static JOBS_TOTAL: [AtomicU64; 4] = [const { AtomicU64::new(0) }; 4];
+static QUEUE_LENGTH: AtomicU64 = AtomicU64::new(0);
+static OLDEST_JOB_AGE_MS: AtomicU64 = AtomicU64::new(0);
+static QUEUE_JOBS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static JOB_STATUSES_GAUGE: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+static CONVERSION_DURATION: Histogram = Histogram::new();
+static QUEUE_WAIT: Histogram = Histogram::new();
+static REDIS_CONNECTED: AtomicBool = AtomicBool::new(false);
+static REDIS_RECONNECT_ATTEMPTS: AtomicU64 = AtomicU64::new(0);
+static DISK_FULL_AT: Mutex<Option<Instant>> = Mutex::new(None);
+static DISK_FULL_TOTAL: AtomicU64 = AtomicU64::new(0);
+static SLOW_JOBS_TOTAL: AtomicU64 = AtomicU64::new(0);
+static CONVERSION_WARNINGS_TOTAL: AtomicU64 = AtomicU64::new(0);
+static ACTIVE_JOBS: AtomicU64 = AtomicU64::new(0);
+static AVAILABLE_PERMITS: AtomicU64 = AtomicU64::new(0);
+static WORKER_JOBS_PROCESSED: Mutex<BTreeMap<usize, u64>> = Mutex::new(BTreeMap::new());
+
+/// Cumulative histogram over [`DURATION_BUCKETS`].
+struct Histogram {
+    buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [const { AtomicU64::new(0) }; DURATION_BUCKETS.len()],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&self.buckets) {
+            if seconds <= *bound {
+                count.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str) {
+        for (bound, count) in DURATION_BUCKETS.iter().zip(&self.buckets) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound,
+                count.load(Ordering::Relaxed)
+            );
+        }
+        let total = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, total);
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            name,
+            self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "{}_count {}", name, total);
+    }
+}
+
+/// Counts a job that finished processing with the given status.
+///
+/// Statuses other than complete, failed, cancelled and expired are ignored.
+pub fn record_job(status: JobStatus) {
+    if let Some(index) = JOB_STATUSES.iter().position(|s| *s == status) {
+        JOBS_TOTAL[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Counts a job that took longer than the slow-job threshold.
+pub fn record_slow_job() {
+    SLOW_JOBS_TOTAL.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Counts skipped or degraded content reported by a completed conversion.
+pub fn record_conversion_warnings(count: usize) {
+    CONVERSION_WARNINGS_TOTAL.fetch_add(count as u64, Ordering::Relaxed);
+}
+
+/// Records how long a single conversion took.
+pub fn observe_conversion_duration(duration: Duration) {
+    CONVERSION_DURATION.observe(duration);
+}
+
+/// Records how long a job waited in the queue before processing started.
+pub fn observe_queue_wait(duration: Duration) {
+    QUEUE_WAIT.observe(duration);
+}
+
+/// Sets the current queue length gauge.
+pub fn set_queue_length(length: usize) {
+    QUEUE_LENGTH.store(length as u64, Ordering::Relaxed);
+}
+
+/// Sets the age of the oldest waiting job; `None` (empty queues) reads as
+/// zero.
+pub fn set_oldest_job_age(age: Option<Duration>) {
+    let age_ms = age.map_or(0, |age| age.as_millis() as u64);
+    OLDEST_JOB_AGE_MS.store(age_ms, Ordering::Relaxed);
+}
+
+/// Sets the utilization gauges and the reporting worker's job counter.
+pub fn set_worker_stats(stats: &WorkerStats) {
+    ACTIVE_JOBS.store(stats.active_jobs as u64, Ordering::Relaxed);
+    AVAILABLE_PERMITS.store(stats.available_permits as u64, Ordering::Relaxed);
+    WORKER_JOBS_PROCESSED
+        .lock()
+        .unwrap()
+        .insert(stats.worker_id, stats.jobs_processed);
+}
+
+/// Sets the per-queue and per-status gauges from a stats snapshot.
+pub fn set_queue_stats(stats: &QueueStats) {
+    let depths = [stats.high, stats.normal, stats.low, stats.delayed, stats.processing];
+    for (gauge, depth) in QUEUE_JOBS.iter().zip(depths) {
+        gauge.store(depth as u64, Ordering::Relaxed);
+    }
+    for (gauge, status) in JOB_STATUSES_GAUGE.iter().zip(&STATUS_LABELS) {
+        let count = stats.by_status.get(status).copied().unwrap_or(0);
+        gauge.store(count as u64, Ordering::Relaxed);
+    }
+    QUEUE_LENGTH.store(stats.queued() as u64, Ordering::Relaxed);
+}
+
+/// Records whether the worker can currently reach Redis; reported by the
+/// `pdf_export_redis_connected` gauge and [`ready_handler`].
+pub fn set_redis_connected(connected: bool) {
+    REDIS_CONNECTED.store(connected, Ordering::Relaxed);
+}
+
+/// Returns `true` if the worker last reached Redis successfully.
+pub fn redis_connected() -> bool {
+    REDIS_CONNECTED.load(Ordering::Relaxed)
+}
+
+/// Counts a job whose output could not be written because the disk is
+/// full, and reports the worker not ready until [`set_disk_full`] clears it.
+pub fn record_disk_full() {
+    DISK_FULL_TOTAL.fetch_add(1, Ordering::Relaxed);
+    set_disk_full(true);
+}
+
+/// Records whether the output disk is full; reported by the
+/// `pdf_export_disk_full` gauge and [`ready_handler`].
+pub fn set_disk_full(full: bool) {
+    *DISK_FULL_AT.lock().unwrap_or_else(|e| e.into_inner()) = full.then(Instant::now);
+}
+
+/// Returns `true` if the last write of job output failed with a full disk.
+pub fn disk_full() -> bool {
+    disk_full_since().is_some()
+}
+
+/// Returns when a write of job output last failed with a full disk, unless
+/// a later write succeeded.
+pub fn disk_full_since() -> Option<Instant> {
+    *DISK_FULL_AT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Counts an attempt to reach Redis again after a connection failure.
+pub fn record_reconnect_attempt() {
+    REDIS_RECONNECT_ATTEMPTS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Refreshes the queue gauges from [`JobQueue::queue_stats`] and
+/// [`JobQueue::oldest_job_age`] every `interval`, until the process exits.
+pub async fn poll_queue_stats(mut queue: JobQueue, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        match queue.queue_stats().await {
+            Ok(stats) => set_queue_stats(&stats),
+            Err(e) => warn!("Failed to collect queue stats: {}", e),
+        }
+        match queue.oldest_job_age().await {
+            Ok(age) => telemetry::record_oldest_job_age(age),
+            Err(e) => warn!("Failed to read oldest job age: {}", e),
+        }
+    }
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP pdf_export_jobs_total PDF export jobs processed, by outcome.\n");
+    out.push_str("# TYPE pdf_export_jobs_total counter\n");
+    for (status, count) in JOB_STATUSES.iter().zip(&JOBS_TOTAL) {
+        let _ = writeln!(
+            out,
+            "pdf_export_jobs_total{{status=\"{}\"}} {}",
+            status,
+            count.load(Ordering::Relaxed)
+        );
+    }
+
+    out.push_str("# HELP pdf_export_conversion_duration_seconds Time spent converting SVG to PDF.\n");
+    out.push_str("# TYPE pdf_export_conversion_duration_seconds histogram\n");
+    CONVERSION_DURATION.render(&mut out, "pdf_export_conversion_duration_seconds");
+
+    out.push_str("# HELP pdf_export_queue_wait_seconds Time jobs waited in the queue before processing.\n");
+    out.push_str("# TYPE pdf_export_queue_wait_seconds histogram\n");
+    QUEUE_WAIT.render(&mut out, "pdf_export_queue_wait_seconds");
+
+    out.push_str("# HELP pdf_export_slow_jobs_total Jobs slower than the slow-job threshold.\n");
+    out.push_str("# TYPE pdf_export_slow_jobs_total counter\n");
+    let _ = writeln!(out, "pdf_export_slow_jobs_total {}", SLOW_JOBS_TOTAL.load(Ordering::Relaxed));
+
+    out.push_str("# HELP pdf_export_conversion_warnings_total Content skipped or degraded by completed conversions.\n");
+    out.push_str("# TYPE pdf_export_conversion_warnings_total counter\n");
+    let _ = writeln!(
+        out,
+        "pdf_export_conversion_warnings_total {}",
+        CONVERSION_WARNINGS_TOTAL.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# HELP pdf_export_queue_length Jobs waiting in the export queues.\n");
+    out.push_str("# TYPE pdf_export_queue_length gauge\n");
+    let _ = writeln!(out, "pdf_export_queue_length {}", QUEUE_LENGTH.load(Ordering::Relaxed));
+
+    out.push_str("# HELP pdf_export_oldest_job_age_seconds Age of the oldest job waiting at the head of a queue.\n");
+    out.push_str("# TYPE pdf_export_oldest_job_age_seconds gauge\n");
+    let _ = writeln!(
+        out,
+        "pdf_export_oldest_job_age_seconds {:.3}",
+        OLDEST_JOB_AGE_MS.load(Ordering::Relaxed) as f64 / 1000.0
+    );
+
+    out.push_str("# HELP pdf_export_queue_jobs Jobs in each queue, the retry set and processing lists.\n");
+    out.push_str("# TYPE pdf_export_queue_jobs gauge\n");
+    for (queue, gauge) in QUEUE_LABELS.iter().zip(&QUEUE_JOBS) {
+        let _ = writeln!(
+            out,
+            "pdf_export_queue_jobs{{queue=\"{}\"}} {}",
+            queue,
+            gauge.load(Ordering::Relaxed)
+        );
+    }
+
+    out.push_str("# HELP pdf_export_job_statuses Tracked jobs (unexpired status keys) by status.\n");
+    out.push_str("# TYPE pdf_export_job_statuses gauge\n");
+    for (status, gauge) in STATUS_LABELS.iter().zip(&JOB_STATUSES_GAUGE) {
+        let _ = writeln!(
+            out,
+            "pdf_export_job_statuses{{status=\"{}\"}} {}",
+            status,
+            gauge.load(Ordering::Relaxed)
+        );
+    }
+
+    out.push_str("# HELP pdf_export_active_jobs Jobs holding a concurrency permit.\n");
+    out.push_str("# TYPE pdf_export_active_jobs gauge\n");
+    let _ = writeln!(out, "pdf_export_active_jobs {}", ACTIVE_JOBS.load(Ordering::Relaxed));
+
+    out.push_str("# HELP pdf_export_available_permits Free concurrency slots.\n");
+    out.push_str("# TYPE pdf_export_available_permits gauge\n");
+    let _ = writeln!(
+        out,
+        "pdf_export_available_permits {}",
+        AVAILABLE_PERMITS.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# HELP pdf_export_worker_jobs_processed_total Jobs finished, by worker loop.\n");
+    out.push_str("# TYPE pdf_export_worker_jobs_processed_total counter\n");
+    for (worker_id, processed) in WORKER_JOBS_PROCESSED.lock().unwrap().iter() {
+        let _ = writeln!(
+            out,
+            "pdf_export_worker_jobs_processed_total{{worker=\"{}\"}} {}",
+            worker_id, processed
+        );
+    }
+
+    out.push_str("# HELP pdf_export_redis_connected Whether the worker can reach Redis.\n");
+    out.push_str("# TYPE pdf_export_redis_connected gauge\n");
+    let _ = writeln!(out, "pdf_export_redis_connected {}", u8::from(redis_connected()));
+
+    out.push_str("# HELP pdf_export_redis_reconnect_attempts_total Attempts to reach Redis after a connection failure.\n");
+    out.push_str("# TYPE pdf_export_redis_reconnect_attempts_total counter\n");
+    let _ = writeln!(
+        out,
+        "pdf_export_redis_reconnect_attempts_total {}",
+        REDIS_RECONNECT_ATTEMPTS.load(Ordering::Relaxed)
+    );
+
+    out.push_str("# HELP pdf_export_disk_full Whether the last output write failed with a full disk.\n");
+    out.push_str("# TYPE pdf_export_disk_full gauge\n");
+    let _ = writeln!(out, "pdf_export_disk_full {}", u8::from(disk_full()));
+
+    out.push_str("# HELP pdf_export_disk_full_total Jobs put back because the output disk was full.\n");
+    out.push_str("# TYPE pdf_export_disk_full_total counter\n");
+    let _ = writeln!(
+        out,
+        "pdf_export_disk_full_total {}",
+        DISK_FULL_TOTAL.load(Ordering::Relaxed)
+    );
+
+    out
+}
+
+/// Handler for `GET /metrics`.
+pub async fn metrics_handler() -> String {
+    render()
+}
+
+/// Handler for `GET /ready`: 200 while Redis is reachable and the output
+/// disk has space, 503 otherwise.
+pub async fn ready_handler() -> (StatusCode, &'static str) {
+    if !redis_connected() {
+        (StatusCode::SERVICE_UNAVAILABLE, "redis unavailable\n")
+    } else if disk_full() {
+        (StatusCode::SERVICE_UNAVAILABLE, "output disk full\n")
+    } else {
+        (StatusCode::OK, "ready\n")
+    }
+}
+
+/// Serves `/metrics` and the `/ready` readiness probe on the given port
+/// until the process exits.
+pub async fn serve(port: u16) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .route("/ready", get(ready_handler));
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+
+    info!("Serving Prometheus metrics on {}", addr);
+    axum::Server::try_bind(&addr)?
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_handler_exposition() {
+        record_job(JobStatus::Complete);
+        observe_conversion_duration(Duration::from_millis(300));
+        set_queue_length(7);
+
+        // Other tests touch the same globals, so only check the format
+        let body = metrics_handler().await;
+
+        assert!(body.contains("# TYPE pdf_export_jobs_total counter"));
+        assert!(body.contains("pdf_export_jobs_total{status=\"complete\"}"));
+        assert!(body.contains("pdf_export_conversion_duration_seconds_bucket{le=\"0.5\"}"));
+        assert!(body.contains("pdf_export_conversion_duration_seconds_count"));
+        assert!(body.contains("# TYPE pdf_export_queue_wait_seconds histogram"));
+        assert!(body.contains("# TYPE pdf_export_slow_jobs_total counter"));
+        assert!(body.contains("# TYPE pdf_export_conversion_warnings_total counter"));
+        assert!(body.contains("# TYPE pdf_export_queue_length gauge"));
+        assert!(body.contains("pdf_export_queue_jobs{queue=\"delayed\"}"));
+        assert!(body.contains("pdf_export_job_statuses{status=\"processing\"}"));
+        assert!(body.contains("# TYPE pdf_export_redis_connected gauge"));
+        assert!(body.contains("pdf_export_redis_reconnect_attempts_total"));
+        assert!(body.contains("# TYPE pdf_export_disk_full gauge"));
+        assert!(body.contains("# TYPE pdf_export_disk_full_total counter"));
+    }
+
+    #[tokio::test]
+    async fn test_ready_handler_follows_redis_connection() {
+        set_redis_connected(false);
+        assert_eq!(ready_handler().await.0, StatusCode::SERVICE_UNAVAILABLE);
+
+        set_redis_connected(true);
+        assert_eq!(ready_handler().await.0, StatusCode::OK);
+
+        record_disk_full();
+        assert_eq!(
+            ready_handler().await,
+            (StatusCode::SERVICE_UNAVAILABLE, "output disk full\n")
+        );
+
+        set_disk_full(false);
+        assert_eq!(ready_handler().await.0, StatusCode::OK);
+    }
+
+    #[test]
+    fn test_duration_histogram_is_cumulative() {
+        let histogram = Histogram::new();
+        histogram.observe(Duration::from_secs(3));
+        histogram.observe(Duration::from_millis(200));
+
+        let counts: Vec<u64> = histogram
+            .buckets
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        assert_eq!(counts, vec![0, 0, 1, 1, 1, 1, 2, 2, 2, 2]);
+
+        let mut out = String::new();
+        histogram.render(&mut out, "test_seconds");
+        assert!(out.contains("test_seconds_bucket{le=\"+Inf\"} 2"));
+        assert!(out.contains("test_seconds_sum 3.2"));
+    }
+}
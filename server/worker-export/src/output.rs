@@ -0,0 +1,640 @@
+//! Output location policy for exported files.
+//!
+//! Jobs name their own `output_path`, so every path is resolved against a
+//! configured root directory before anything is written. Paths that would
+//! land outside the root (`../` components, absolute paths elsewhere, or
+//! symlinks pointing out of it) are refused.
+//!
+//! A server-side [`OutputTemplate`] can replace the job's path altogether,
+//! naming files from job fields instead.
+//!
+//! Paths of the form `s3://bucket/key` are uploaded to object storage
+//! instead (see [`OutputConfig::sink_for`]), provided that bucket is the one
+//! configured with [`OutputConfig::with_s3`].
+//!
+//! Jobs that reference their SVG by file path
+//! ([`SvgSource::FilePath`](crate::job::SvgSource::FilePath)) are confined
+//! the same way, to an input root that must be configured for them to be
+//! read at all ([`OutputConfig::with_input_root`]).
+
+use crate::job::PdfExportJob;
+use crate::sink::{self, LocalSink, OutputSink, S3Config, S3Sink};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::warn;
+
+/// Default directory exports are written under.
+pub const DEFAULT_OUTPUT_ROOT: &str = "/exports";
+
+/// Errors produced while resolving an output path.
+#[derive(Debug, Error)]
+pub enum OutputPathError {
+    /// The output root does not exist or cannot be resolved.
+    #[error("Output root {path} is unavailable: {source}")]
+    Root {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The requested path resolves to somewhere outside the output root.
+    #[error("Output path {0} is outside the output root")]
+    OutsideRoot(String),
+
+    /// The `s3://` path isn't an object in the configured bucket, or no
+    /// object storage is configured.
+    #[error("Output path {0} is not in the configured object storage bucket")]
+    UnknownBucket(String),
+
+    /// A job references an SVG file, but no input root is configured.
+    #[error("SVG file {0} can't be read: file sources are disabled")]
+    NoInputRoot(String),
+
+    /// A referenced SVG file resolves to somewhere outside the input root.
+    #[error("SVG file {0} is outside the input root")]
+    OutsideInputRoot(String),
+}
+
+/// Error returned when an output template can't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum OutputTemplateError {
+    #[error("Unknown output template token {{{0}}}")]
+    UnknownToken(String),
+
+    #[error("Unclosed {{ in output template")]
+    Unclosed,
+
+    #[error("Output template must end in .{{ext}}")]
+    MissingExtension,
+}
+
+/// A value an [`OutputTemplate`] fills in from the job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    JobId,
+    DocumentId,
+    ArtboardId,
+    UserId,
+    Timestamp,
+    Date,
+    Ext,
+}
+
+impl Token {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "job_id" => Some(Token::JobId),
+            "document_id" => Some(Token::DocumentId),
+            "artboard_id" => Some(Token::ArtboardId),
+            "user_id" => Some(Token::UserId),
+            "timestamp" => Some(Token::Timestamp),
+            "date" => Some(Token::Date),
+            "ext" => Some(Token::Ext),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Token::JobId => "job_id",
+            Token::DocumentId => "document_id",
+            Token::ArtboardId => "artboard_id",
+            Token::UserId => "user_id",
+            Token::Timestamp => "timestamp",
+            Token::Date => "date",
+            Token::Ext => "ext",
+        }
+    }
+
+    fn value(self, job: &PdfExportJob) -> String {
+        match self {
+            Token::JobId => job.job_id.clone(),
+            Token::DocumentId => job.document_id.clone(),
+            Token::ArtboardId => job
+                .metadata
+                .artboard_ids
+                .first()
+                .or(job.artboards.first().map(|artboard| &artboard.artboard_id))
+                .unwrap_or(&job.metadata.export_scope)
+                .clone(),
+            Token::UserId => job
+                .metadata
+                .user_id
+                .clone()
+                .unwrap_or_else(|| "anonymous".to_string()),
+            Token::Timestamp => job.created_at.format("%Y%m%dT%H%M%SZ").to_string(),
+            Token::Date => job.created_at.format("%Y-%m-%d").to_string(),
+            Token::Ext => job.output_format.extension().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Token(Token),
+}
+
+/// Server-side naming for output files, e.g.
+/// `{document_id}/{artboard_id}-{timestamp}.{ext}`.
+///
+/// Tokens are `{job_id}`, `{document_id}`, `{artboard_id}` (the first
+/// artboard, or the export scope when the job names none), `{user_id}`
+/// (`anonymous` without one), `{timestamp}` (`20240131T235959Z`) and
+/// `{date}` (`2024-01-31`), both from when the job was created so retries
+/// write the same file, and `{ext}` (the output format's extension).
+///
+/// Filled-in values are sanitized to a single path component (anything but
+/// ASCII letters, digits, `-`, `_` and `.` becomes `_`, and `.`/`..` are
+/// replaced), so only the template's own `/`s create directories. The
+/// worker creates those directories before writing. Templates must end in
+/// `.{ext}` so the file always matches its format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputTemplate {
+    parts: Vec<Part>,
+}
+
+impl OutputTemplate {
+    /// Renders the output path for `job`.
+    pub fn render(&self, job: &PdfExportJob) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.clone(),
+                Part::Token(token) => sanitize_component(&token.value(job)),
+            })
+            .collect()
+    }
+}
+
+impl FromStr for OutputTemplate {
+    type Err = OutputTemplateError;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or(OutputTemplateError::Unclosed)? + start;
+            let name = &rest[start + 1..end];
+            let token = Token::parse(name)
+                .ok_or_else(|| OutputTemplateError::UnknownToken(name.to_string()))?;
+            parts.push(Part::Token(token));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        match parts.as_slice() {
+            [.., Part::Literal(text), Part::Token(Token::Ext)] if text.ends_with('.') => {
+                Ok(Self { parts })
+            }
+            _ => Err(OutputTemplateError::MissingExtension),
+        }
+    }
+}
+
+impl fmt::Display for OutputTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => f.write_str(text)?,
+                Part::Token(token) => write!(f, "{{{}}}", token.name())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Makes `value` safe to use as one path component.
+fn sanitize_component(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    match sanitized.as_str() {
+        "" | "." | ".." => "_".to_string(),
+        _ => sanitized,
+    }
+}
+
+/// Where exported files may be written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputConfig {
+    /// Directory every output file must live under. Relative job paths are
+    /// resolved against it.
+    pub root: PathBuf,
+    /// Naming that replaces each job's own `output_path`, if set.
+    pub template: Option<OutputTemplate>,
+    /// Object storage `s3://` paths are uploaded to, if any.
+    pub s3: Option<S3Config>,
+    /// Directory SVG files referenced by jobs must live under. Without one,
+    /// file sources are refused.
+    pub input_root: Option<PathBuf>,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_OUTPUT_ROOT)
+    }
+}
+
+impl OutputConfig {
+    /// Creates a config that confines output to `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            template: None,
+            s3: None,
+            input_root: None,
+        }
+    }
+
+    /// Names every output file with `template` (relative to the root)
+    /// instead of the job's `output_path`.
+    pub fn with_template(mut self, template: OutputTemplate) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    /// Lets jobs write to the bucket described by `config` with
+    /// `s3://bucket/key` output paths.
+    pub fn with_s3(mut self, config: S3Config) -> Self {
+        self.s3 = Some(config);
+        self
+    }
+
+    /// Lets jobs reference SVG files under `root`. Relative paths are
+    /// resolved against it.
+    pub fn with_input_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.input_root = Some(root.into());
+        self
+    }
+
+    /// Returns the sink output written to `output_path` goes through:
+    /// object storage for `s3://` paths and the local filesystem otherwise.
+    ///
+    /// Local paths still need confining to the root with
+    /// [`resolve`](Self::resolve); `s3://` paths are written as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OutputPathError::UnknownBucket`] for an `s3://` path
+    /// without a key, or outside the configured bucket.
+    pub fn sink_for(&self, output_path: &str) -> Result<Arc<dyn OutputSink>, OutputPathError> {
+        if !sink::is_s3_url(output_path) {
+            return Ok(Arc::new(LocalSink));
+        }
+        match (&self.s3, sink::parse_s3_url(output_path)) {
+            (Some(config), Some((bucket, _))) if bucket == config.bucket => {
+                Ok(Arc::new(S3Sink::new(config.clone())))
+            }
+            _ => Err(OutputPathError::UnknownBucket(output_path.to_string())),
+        }
+    }
+
+    /// Returns the path `job` should be written to before it is resolved
+    /// against the root: the rendered template if one is configured, and
+    /// otherwise the job's own `output_path`.
+    pub fn output_path(&self, job: &PdfExportJob) -> String {
+        match &self.template {
+            Some(template) => template.render(job),
+            None => job.output_path.clone(),
+        }
+    }
+
+    /// Resolves `output_path` to an absolute path inside the root.
+    ///
+    /// Relative paths are taken relative to the root; absolute paths are
+    /// accepted only if they already point inside it. `.` and `..`
+    /// components are resolved, as are symlinks in the part of the path that
+    /// already exists, before the containment check.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OutputPathError::OutsideRoot`] if the path escapes the root
+    /// or names the root itself, and [`OutputPathError::Root`] if the root
+    /// cannot be resolved.
+    pub fn validate(&self, output_path: &str) -> Result<PathBuf, OutputPathError> {
+        let root = canonicalize_root(&self.root)?;
+        confine(&root, Path::new(output_path))
+            .ok_or_else(|| OutputPathError::OutsideRoot(output_path.to_string()))
+    }
+
+    /// Resolves the path of an SVG file a job references to an absolute
+    /// path inside the input root, as [`validate`](Self::validate) does for
+    /// output paths.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OutputPathError::NoInputRoot`] if no input root is
+    /// configured, [`OutputPathError::OutsideInputRoot`] if the path escapes
+    /// it, and [`OutputPathError::Root`] if it cannot be resolved.
+    pub fn validate_input(&self, svg_path: &Path) -> Result<PathBuf, OutputPathError> {
+        let display = || svg_path.display().to_string();
+        let root = self
+            .input_root
+            .as_deref()
+            .ok_or_else(|| OutputPathError::NoInputRoot(display()))?;
+        let root = canonicalize_root(root)?;
+        confine(&root, svg_path).ok_or_else(|| OutputPathError::OutsideInputRoot(display()))
+    }
+
+    /// Returns where `job`'s output should be written.
+    ///
+    /// This is the job's own `output_path` when it is valid, and otherwise
+    /// `<root>/<job_id>.<ext>`, so a bad path never fails the export.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`OutputPathError::Root`] if the root cannot be resolved.
+    pub fn resolve(&self, job: &PdfExportJob) -> Result<PathBuf, OutputPathError> {
+        match self.validate(&job.output_path) {
+            Ok(path) => Ok(path),
+            Err(OutputPathError::OutsideRoot(path)) => {
+                let fallback = canonicalize_root(&self.root)?.join(format!(
+                    "{}.{}",
+                    job.job_id,
+                    job.output_format.extension()
+                ));
+                warn!(
+                    "Rejected output path outside {}: job_id={}, output_path={}, using {}",
+                    self.root.display(),
+                    job.job_id,
+                    path,
+                    fallback.display()
+                );
+                Ok(fallback)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+}
+
+fn canonicalize_root(root: &Path) -> Result<PathBuf, OutputPathError> {
+    root.canonicalize().map_err(|source| OutputPathError::Root {
+        path: root.to_path_buf(),
+        source,
+    })
+}
+
+/// Resolves `path` (relative to `root` unless absolute) with its `.`, `..`
+/// and existing symlinks followed, if it lands strictly inside the
+/// canonical `root`.
+fn confine(root: &Path, path: &Path) -> Option<PathBuf> {
+    let joined = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        root.join(path)
+    };
+    let resolved = resolve_symlinks(&normalize(&joined));
+    (resolved.starts_with(root) && resolved != root).then_some(resolved)
+}
+
+/// Lexically resolves `.` and `..` components of an absolute path. `..` at
+/// the filesystem root stays at the root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and re-appends the
+/// rest, so symlinked directories are followed even though the output file
+/// itself does not exist yet.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut existing = path;
+    let mut rest = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return rest.iter().rev().fold(canonical, |acc, part| acc.join(part));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name.to_owned());
+                existing = parent;
+            }
+            _ => return path.to_path_buf(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::JobMetadata;
+
+    fn job_with_path(output_path: &str) -> PdfExportJob {
+        PdfExportJob::new(
+            "doc-123".to_string(),
+            "<svg></svg>".to_string(),
+            output_path.to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_relative_path_resolves_inside_root() {
+        let root = tempfile::tempdir().unwrap();
+        let config = OutputConfig::new(root.path());
+        let canonical_root = root.path().canonicalize().unwrap();
+
+        let path = config.validate("docs/doc-123.pdf").unwrap();
+        assert_eq!(path, canonical_root.join("docs/doc-123.pdf"));
+
+        // `..` that stays inside the root is fine
+        let path = config.validate("docs/../doc-123.pdf").unwrap();
+        assert_eq!(path, canonical_root.join("doc-123.pdf"));
+
+        // So is an absolute path under the root
+        let absolute = canonical_root.join("doc-123.pdf");
+        assert_eq!(config.validate(absolute.to_str().unwrap()).unwrap(), absolute);
+    }
+
+    #[test]
+    fn test_traversal_outside_root_is_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let config = OutputConfig::new(root.path());
+
+        for path in ["../../etc/passwd", "docs/../../escape.pdf", "/etc/cron.d/job", "", "."] {
+            assert!(
+                matches!(config.validate(path), Err(OutputPathError::OutsideRoot(_))),
+                "{:?} was accepted",
+                path
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_out_of_root_is_rejected() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("link")).unwrap();
+
+        let config = OutputConfig::new(root.path());
+        assert!(matches!(
+            config.validate("link/doc.pdf"),
+            Err(OutputPathError::OutsideRoot(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_input_paths_are_confined_to_input_root() {
+        let root = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join("art.svg"), "<svg/>").unwrap();
+        std::os::unix::fs::symlink(outside.path(), root.path().join("link")).unwrap();
+
+        let disabled = OutputConfig::new(root.path());
+        assert!(matches!(
+            disabled.validate_input(&root.path().join("art.svg")),
+            Err(OutputPathError::NoInputRoot(_))
+        ));
+
+        let config = OutputConfig::new(outside.path()).with_input_root(root.path());
+        let canonical_root = root.path().canonicalize().unwrap();
+        assert_eq!(
+            config.validate_input(Path::new("art.svg")).unwrap(),
+            canonical_root.join("art.svg")
+        );
+        for path in ["/etc/passwd", "../../etc/passwd", "link/secret.svg"] {
+            assert!(
+                matches!(
+                    config.validate_input(Path::new(path)),
+                    Err(OutputPathError::OutsideInputRoot(_))
+                ),
+                "{:?} was accepted",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_falls_back_to_job_id() {
+        let root = tempfile::tempdir().unwrap();
+        let config = OutputConfig::new(root.path());
+        let job = job_with_path("../../etc/passwd");
+
+        let path = config.resolve(&job).unwrap();
+        assert_eq!(
+            path,
+            root.path()
+                .canonicalize()
+                .unwrap()
+                .join(format!("{}.pdf", job.job_id))
+        );
+    }
+
+    #[test]
+    fn test_template_renders_sanitized_job_fields() {
+        let root = tempfile::tempdir().unwrap();
+        let template: OutputTemplate = "{document_id}/{artboard_id}-{timestamp}.{ext}".parse().unwrap();
+        let config = OutputConfig::new(root.path()).with_template(template.clone());
+
+        let mut job = job_with_path("client-chosen.pdf");
+        job.document_id = "../team plans/q3".to_string();
+        job.metadata.artboard_ids = vec!["ab-1".to_string()];
+        job.created_at = "2024-01-31T23:59:59Z".parse().unwrap();
+
+        // Document ids can't add directories or climb out
+        let path = config.output_path(&job);
+        assert_eq!(path, ".._team_plans_q3/ab-1-20240131T235959Z.pdf");
+        job.output_path = path;
+        assert_eq!(
+            config.resolve(&job).unwrap(),
+            root.path()
+                .canonicalize()
+                .unwrap()
+                .join(".._team_plans_q3/ab-1-20240131T235959Z.pdf")
+        );
+
+        job.metadata.artboard_ids.clear();
+        job.document_id = "..".to_string();
+        let template: OutputTemplate =
+            "{user_id}/{date}/{document_id}-{artboard_id}-{job_id}.{ext}".parse().unwrap();
+        assert_eq!(
+            template.render(&job),
+            format!("anonymous/2024-01-31/_-all-{}.pdf", job.job_id)
+        );
+        assert_eq!(
+            template.to_string(),
+            "{user_id}/{date}/{document_id}-{artboard_id}-{job_id}.{ext}"
+        );
+
+        // Without a template the job's path is kept
+        assert_eq!(OutputConfig::new(root.path()).output_path(&job), job.output_path);
+    }
+
+    #[test]
+    fn test_invalid_template_is_rejected() {
+        assert_eq!(
+            "{document_id}/{page}.pdf".parse::<OutputTemplate>(),
+            Err(OutputTemplateError::UnknownToken("page".to_string()))
+        );
+        assert_eq!(
+            "{document_id.pdf".parse::<OutputTemplate>(),
+            Err(OutputTemplateError::Unclosed)
+        );
+        // The extension always comes from the output format
+        for template in ["{document_id}/{job_id}", "{document_id}.pdf", "{job_id}{ext}", "{ext}"] {
+            assert_eq!(
+                template.parse::<OutputTemplate>(),
+                Err(OutputTemplateError::MissingExtension),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sink_is_chosen_by_output_path_scheme() {
+        let config = OutputConfig::new("/exports");
+        assert!(config.sink_for("docs/doc.pdf").is_ok());
+        // Object storage paths need object storage
+        assert!(matches!(
+            config.sink_for("s3://exports/doc.pdf"),
+            Err(OutputPathError::UnknownBucket(_))
+        ));
+
+        let config = config.with_s3(S3Config::new("http://minio:9000", "exports", "key", "secret"));
+        assert!(config.sink_for("/exports/doc.pdf").is_ok());
+        assert!(config.sink_for("s3://exports/docs/doc.pdf").is_ok());
+        for path in ["s3://other/doc.pdf", "s3://exports/", "s3://exports"] {
+            assert!(
+                matches!(config.sink_for(path), Err(OutputPathError::UnknownBucket(_))),
+                "{:?} was accepted",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn test_missing_root_is_an_error() {
+        let config = OutputConfig::new("/nonexistent-output-root");
+        let job = job_with_path("doc.pdf");
+        assert!(matches!(config.resolve(&job), Err(OutputPathError::Root { .. })));
+    }
+}
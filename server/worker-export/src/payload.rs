@@ -0,0 +1,172 @@
+//! Encoding of queued job payloads.
+//!
+//! Jobs waiting on the queues are stored as JSON by default, or as
+//! MessagePack, which is smaller and quicker to parse for jobs carrying
+//! large inline SVG. Status keys stay JSON either way, since clients poll
+//! them. [`decode`] tells the formats apart by their first byte (a JSON job
+//! is an object and starts with `{`, a MessagePack job is a map and never
+//! does), so a worker reads both whatever format it writes.
+
+use crate::job::PdfExportJob;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Format jobs are written to the queues in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Serialization {
+    /// Plain JSON, readable with `redis-cli`.
+    #[default]
+    Json,
+    /// MessagePack with named fields, so jobs written by older or newer
+    /// versions still decode.
+    MessagePack,
+}
+
+impl Serialization {
+    /// Encodes `job` in this format.
+    pub fn encode(self, job: &PdfExportJob) -> Result<Vec<u8>, PayloadError> {
+        match self {
+            Serialization::Json => Ok(serde_json::to_vec(job)?),
+            Serialization::MessagePack => Ok(rmp_serde::to_vec_named(job)?),
+        }
+    }
+}
+
+impl fmt::Display for Serialization {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Serialization::Json => write!(f, "json"),
+            Serialization::MessagePack => write!(f, "msgpack"),
+        }
+    }
+}
+
+/// Error returned when a string is not a recognized serialization.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid serialization: {0:?} (expected json or msgpack)")]
+pub struct ParseSerializationError(String);
+
+impl FromStr for Serialization {
+    type Err = ParseSerializationError;
+
+    /// Parses `json` or `msgpack` (also `messagepack`), case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "json" => Ok(Serialization::Json),
+            "msgpack" | "messagepack" => Ok(Serialization::MessagePack),
+            _ => Err(ParseSerializationError(s.to_string())),
+        }
+    }
+}
+
+/// Errors produced while encoding or decoding a job payload.
+#[derive(Debug, Error)]
+pub enum PayloadError {
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    #[error(transparent)]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+/// Returns the format `payload` was written in.
+pub fn detect(payload: &[u8]) -> Serialization {
+    match payload.iter().find(|byte| !byte.is_ascii_whitespace()) {
+        Some(b'{') | None => Serialization::Json,
+        Some(_) => Serialization::MessagePack,
+    }
+}
+
+/// Decodes a job written in either format.
+pub fn decode(payload: &[u8]) -> Result<PdfExportJob, PayloadError> {
+    match detect(payload) {
+        Serialization::Json => Ok(serde_json::from_slice(payload)?),
+        Serialization::MessagePack => Ok(rmp_serde::from_slice(payload)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{ArtboardSvg, OutputFormat, SvgSource};
+    use std::collections::HashMap;
+
+    fn large_job() -> PdfExportJob {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">{}</svg>"#,
+            r#"<rect x="1" y="1" width="5" height="5" fill="red"/>"#.repeat(200)
+        );
+        let mut job = PdfExportJob::builder()
+            .document_id("doc-123")
+            .svg(svg)
+            .output_path("/tmp/test.png")
+            .output_format(OutputFormat::Png)
+            .user_id("user-42")
+            .build()
+            .unwrap();
+        job.metadata.trace_context = Some(HashMap::from([
+            ("traceparent".to_string(), "00-4bf92f3577b34da6a3ce929b0e0e4736-00f067aa0ba902b7-01".to_string()),
+            ("tracestate".to_string(), "vendor=value".to_string()),
+        ]));
+        job.checksum = Some(job.content_checksum());
+        job
+    }
+
+    #[test]
+    fn test_message_pack_round_trip() {
+        let job = large_job();
+        let packed = Serialization::MessagePack.encode(&job).unwrap();
+        assert_eq!(detect(&packed), Serialization::MessagePack);
+
+        let decoded = decode(&packed).unwrap();
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(&job).unwrap()
+        );
+        assert!(decoded.verify_checksum());
+        // Re-encoding gives the same bytes, so entries can be found by value
+        assert_eq!(Serialization::MessagePack.encode(&decoded).unwrap(), packed);
+
+        let json = Serialization::Json.encode(&job).unwrap();
+        assert_eq!(detect(&json), Serialization::Json);
+        assert!(packed.len() < json.len());
+        assert_eq!(decode(&json).unwrap().job_id, job.job_id);
+
+        // Enum variants with data survive too
+        let mut job = PdfExportJob::new_multi_page(
+            "doc-123".to_string(),
+            vec![ArtboardSvg {
+                artboard_id: "ab-1".to_string(),
+                svg_content: "<svg/>".to_string(),
+                name: None,
+            }],
+            "/tmp/test.pdf".to_string(),
+            job.metadata.clone(),
+        );
+        job.svg_source = Some(SvgSource::RedisKey("svg:doc-123".to_string()));
+        let decoded = decode(&Serialization::MessagePack.encode(&job).unwrap()).unwrap();
+        assert_eq!(decoded.svg_source, job.svg_source);
+        assert_eq!(decoded.artboards.len(), 1);
+    }
+
+    #[test]
+    fn test_corrupted_payloads_fail_to_decode() {
+        let packed = Serialization::MessagePack.encode(&large_job()).unwrap();
+        assert!(decode(&packed[..packed.len() / 2]).is_err());
+        assert!(decode(b"{\"job_id\":").is_err());
+        assert!(decode(b"").is_err());
+    }
+
+    #[test]
+    fn test_parse_serialization() {
+        assert_eq!("json".parse(), Ok(Serialization::Json));
+        assert_eq!(" MsgPack ".parse(), Ok(Serialization::MessagePack));
+        assert_eq!("messagepack".parse(), Ok(Serialization::MessagePack));
+        assert!("xml".parse::<Serialization>().is_err());
+        assert_eq!(Serialization::MessagePack.to_string(), "msgpack");
+    }
+}
@@ -1,40 +1,677 @@
 //! Redis-based job queue for PDF export tasks.
 
-use crate::job::PdfExportJob;
-use anyhow::{Context, Result};
-use redis::{aio::ConnectionManager, AsyncCommands};
-use serde_json;
-use tracing::{debug, error, info};
+use crate::converter::{ConvertError, DEFAULT_MAX_PAGES, DEFAULT_MAX_SVG_BYTES};
+use crate::events::{EventSink, JobEvent, JobEventKind, TracingSink};
+use crate::job::{
+    ErrorKind, JobStatus, OutputExtensionError, PdfExportJob, PRIORITY_HIGH, PRIORITY_LOW,
+    PRIORITY_NORMAL,
+};
+use crate::payload::{self, PayloadError, Serialization};
+use crate::rate_limit::{RateLimit, RateLimits, RATE_LIMIT_SCRIPT};
+use crate::telemetry;
+use chrono::{DateTime, Utc};
+use futures_util::future;
+use futures_util::{Stream, StreamExt};
+use redis::{aio::{ConnectionLike, ConnectionManager}, AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{debug, error, info, warn};
 
-/// Queue name for PDF export jobs.
-const QUEUE_KEY: &str = "wiretuner:export:pdf:queue";
+/// Prefix every queue key is derived from unless a [`KeyNamespace`] is
+/// configured.
+pub const DEFAULT_KEY_NAMESPACE: &str = "wiretuner:export:pdf";
 
-/// Status key prefix for job status tracking.
-const STATUS_KEY_PREFIX: &str = "wiretuner:export:pdf:status";
+/// Most payloads kept on the dead-letter list unless
+/// [`QueueConfig::dead_letter_max_len`] says otherwise.
+pub const DEFAULT_DEAD_LETTER_MAX_LEN: usize = 10_000;
 
-/// Job TTL in seconds (24 hours).
+/// Manual replays from the dead-letter list allowed per job unless
+/// [`QueueConfig::max_dlq_replays`] says otherwise.
+pub const DEFAULT_MAX_DLQ_REPLAYS: u8 = 3;
+
+/// Maximum number of ready delayed jobs promoted per dequeue attempt.
+const PROMOTE_BATCH_SIZE: isize = 100;
+
+/// Moves a delayed job onto its queue only if this caller removed it from
+/// the delayed set, so concurrent workers never promote the same job twice.
+/// ARGV[2] is the job's tenant (`''` for the shared queue), given a turn in
+/// the KEYS[3] rotation at ARGV[3] unless it has one; a token is pushed
+/// onto the KEYS[4] notification list, which keeps at most ARGV[4].
+const PROMOTE_SCRIPT: &str = r#"
+if redis.call('ZREM', KEYS[1], ARGV[1]) == 1 then
+    redis.call('RPUSH', KEYS[2], ARGV[1])
+    redis.call('ZADD', KEYS[3], 'NX', ARGV[3], ARGV[2])
+    redis.call('RPUSH', KEYS[4], 1)
+    redis.call('LTRIM', KEYS[4], 0, tonumber(ARGV[4]) - 1)
+    return 1
+end
+return 0
+"#;
+
+/// Pops the next job of tenant ARGV[1] from its queue KEYS[1] onto the
+/// processing list KEYS[3], and moves the tenant to the back of the KEYS[2]
+/// rotation.
+///
+/// Tenants (`user_id`s) with waiting jobs take turns in a sorted set scored
+/// by when their turn comes; the shared queue, holding jobs without a user,
+/// takes its turn as the tenant `''`. The caller reads whose turn is next
+/// and passes that tenant's queue, so every key is declared in KEYS. A
+/// served tenant is scored ARGV[2] (the caller's clock, in milliseconds) or
+/// just past the last turn, whichever is later; one left with nothing
+/// waiting leaves the rotation until a job of its is pushed again.
+const FAIR_DEQUEUE_SCRIPT: &str = r#"
+local job = redis.call('LMOVE', KEYS[1], KEYS[3], 'LEFT', 'RIGHT')
+if job and redis.call('LLEN', KEYS[1]) > 0 then
+    local turn = tonumber(ARGV[2])
+    local last = redis.call('ZRANGE', KEYS[2], -1, -1, 'WITHSCORES')
+    if last[2] and tonumber(last[2]) >= turn then
+        turn = tonumber(last[2]) + 1
+    end
+    redis.call('ZADD', KEYS[2], turn, ARGV[1])
+else
+    redis.call('ZREM', KEYS[2], ARGV[1])
+end
+return job
+"#;
+
+/// Tenants found with nothing waiting that one dequeue skips past at a
+/// priority before moving on; the next dequeue carries on from there.
+const MAX_TENANT_PROBES: usize = 8;
+
+/// Most wake-up tokens kept on a priority's notification list (see
+/// [`KeyNamespace::notify`]), so tokens pushed while every worker was busy
+/// cost idle workers only a few empty polls.
+const NOTIFY_BACKLOG: isize = 32;
+
+/// Every Nth dequeue services queues lowest-priority first so batch work
+/// is never starved by a steady stream of urgent jobs.
+const STARVATION_INTERVAL: u64 = 8;
+
+/// Priority levels in the order they are normally serviced.
+const PRIORITIES: [u8; 3] = [PRIORITY_HIGH, PRIORITY_NORMAL, PRIORITY_LOW];
+
+/// Longest a blocked dequeue waits before checking for due delayed jobs.
+const PRIORITY_POLL_SLICE: Duration = Duration::from_secs(1);
+
+/// Consumer id used when none is configured.
+const DEFAULT_CONSUMER_ID: &str = "default";
+
+/// Default job TTL in seconds (24 hours), used unless the job sets
+/// `status_ttl`.
 const JOB_TTL_SECONDS: u64 = 86400;
 
+/// Errors produced by queue operations.
+#[derive(Debug, Error)]
+pub enum QueueError {
+    /// A Redis command failed.
+    #[error("{context}: {source}")]
+    Redis {
+        context: &'static str,
+        #[source]
+        source: redis::RedisError,
+    },
+
+    /// A job could not be converted to or from JSON or MessagePack.
+    #[error("{context}: {source}")]
+    Serialization {
+        context: &'static str,
+        #[source]
+        source: PayloadError,
+    },
+
+    /// A job was refused at enqueue time and never reached the queue.
+    #[error("Job {job_id} rejected: {source}")]
+    InvalidJob {
+        job_id: String,
+        #[source]
+        source: ConvertError,
+    },
+
+    /// A job's `output_path` doesn't end in its output format's extension.
+    #[error("Job {job_id} rejected: {source}")]
+    InvalidOutputPath {
+        job_id: String,
+        #[source]
+        source: OutputExtensionError,
+    },
+
+    /// The job's document (or user) has used up its enqueue rate limit.
+    #[error("Job {job_id} rate limited: more than {} jobs per {:?} for {key}", .limit.max_jobs, .limit.window)]
+    RateLimited {
+        job_id: String,
+        key: String,
+        limit: RateLimit,
+    },
+
+    /// A dequeued payload was truncated or failed its checksum. It has been
+    /// moved to the dead-letter list instead of being processed or retried.
+    #[error("Corrupted job payload ({}): {reason}", .job_id.as_deref().unwrap_or("unknown job"))]
+    CorruptedPayload {
+        job_id: Option<String>,
+        reason: String,
+    },
+
+    /// A dead-lettered job has already been replayed as often as
+    /// [`QueueConfig::max_dlq_replays`] allows.
+    #[error("Job {job_id} not replayed: already replayed {replays} time(s) from the dead-letter list, the limit is {limit}")]
+    ReplayLimitExceeded {
+        job_id: String,
+        replays: u8,
+        limit: u8,
+    },
+}
+
+impl QueueError {
+    /// Returns `true` if the error means Redis is unreachable (connection
+    /// refused, dropped or timed out) rather than a problem with a command
+    /// or job.
+    pub fn is_connection_error(&self) -> bool {
+        match self {
+            QueueError::Redis { source, .. } => source.kind() == redis::ErrorKind::IoError,
+            QueueError::Serialization { .. }
+            | QueueError::InvalidJob { .. }
+            | QueueError::InvalidOutputPath { .. }
+            | QueueError::RateLimited { .. }
+            | QueueError::CorruptedPayload { .. }
+            | QueueError::ReplayLimitExceeded { .. } => false,
+        }
+    }
+}
+
+type Result<T, E = QueueError> = std::result::Result<T, E>;
+
+/// Attaches a description of the failed operation to a Redis,
+/// JSON or MessagePack error.
+trait ResultExt<T> {
+    fn context(self, context: &'static str) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for redis::RedisResult<T> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|source| QueueError::Redis { context, source })
+    }
+}
+
+impl<T> ResultExt<T> for serde_json::Result<T> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|source| QueueError::Serialization {
+            context,
+            source: source.into(),
+        })
+    }
+}
+
+impl<T> ResultExt<T> for std::result::Result<T, PayloadError> {
+    fn context(self, context: &'static str) -> Result<T> {
+        self.map_err(|source| QueueError::Serialization { context, source })
+    }
+}
+
+/// Attempts at writing a job in [`JobQueue::enqueue`] before a connection
+/// error is returned to the caller.
+const ENQUEUE_ATTEMPTS: u32 = 3;
+
+/// Wait before the first enqueue retry; doubled before each further one.
+const ENQUEUE_RETRY_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Number of status keys fetched per MGET when counting jobs by status.
+const STATUS_SCAN_BATCH: usize = 100;
+
+/// Snapshot of queue depths and job states, from [`JobQueue::queue_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Jobs waiting in the high-priority queue.
+    pub high: usize,
+    /// Jobs waiting in the normal-priority queue.
+    pub normal: usize,
+    /// Jobs waiting in the low-priority queue.
+    pub low: usize,
+    /// Jobs waiting out a retry backoff.
+    pub delayed: usize,
+    /// Jobs on any worker's processing list.
+    pub processing: usize,
+    /// Unexpired status keys, by job status.
+    pub by_status: HashMap<JobStatus, usize>,
+}
+
+impl QueueStats {
+    /// Total jobs waiting in the priority queues.
+    pub fn queued(&self) -> usize {
+        self.high + self.normal + self.low
+    }
+}
+
+/// Priorities given to jobs enqueued without one, by their
+/// `metadata.export_scope`. Scopes without an entry get
+/// [`PRIORITY_NORMAL`].
+///
+/// The default sends `current` exports, which a user is waiting on, to the
+/// high-priority queue and leaves `all` (batch) exports at normal priority,
+/// so interactive exports jump ahead without clients picking a number.
+/// Jobs that set `priority` themselves keep it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopePriorities {
+    scopes: HashMap<String, u8>,
+}
+
+impl ScopePriorities {
+    /// Creates a mapping without entries, giving every job
+    /// [`PRIORITY_NORMAL`].
+    pub fn empty() -> Self {
+        Self {
+            scopes: HashMap::new(),
+        }
+    }
+
+    /// Gives jobs of `scope` the priority `priority`.
+    pub fn with_scope(mut self, scope: impl Into<String>, priority: u8) -> Self {
+        self.scopes.insert(scope.into(), priority);
+        self
+    }
+
+    /// Returns the priority of a job of `scope` enqueued without one.
+    pub fn priority_for(&self, scope: &str) -> u8 {
+        self.scopes.get(scope).copied().unwrap_or(PRIORITY_NORMAL)
+    }
+}
+
+impl Default for ScopePriorities {
+    fn default() -> Self {
+        Self::empty()
+            .with_scope("current", PRIORITY_HIGH)
+            .with_scope("all", PRIORITY_NORMAL)
+    }
+}
+
+/// Error returned when a string is not a valid scope-to-priority mapping.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid scope priorities: {0:?} (expected scope=low|normal|high, comma-separated)")]
+pub struct ParseScopePrioritiesError(String);
+
+impl FromStr for ScopePriorities {
+    type Err = ParseScopePrioritiesError;
+
+    /// Parses comma-separated `scope=priority` pairs such as
+    /// `current=high,all=normal,selected=high`, with priorities given as
+    /// `low`, `normal` or `high` (or their numbers). Scopes not listed get
+    /// normal priority, as with [`empty`](Self::empty).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseScopePrioritiesError(s.to_string());
+        let mut priorities = Self::empty();
+        for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (scope, priority) = pair.split_once('=').ok_or_else(invalid)?;
+            let priority = match priority.trim().to_ascii_lowercase().as_str() {
+                "low" => PRIORITY_LOW,
+                "normal" => PRIORITY_NORMAL,
+                "high" => PRIORITY_HIGH,
+                number => number.parse().map_err(|_| invalid())?,
+            };
+            priorities = priorities.with_scope(scope.trim(), priority);
+        }
+        Ok(priorities)
+    }
+}
+
+/// Timing settings for queue polling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueConfig {
+    /// How long a single [`JobQueue::dequeue`] blocks waiting for a job.
+    pub blpop_timeout: Duration,
+    /// How long a worker waits before retrying after a dequeue error.
+    pub error_backoff: Duration,
+    /// Largest SVG document accepted by [`JobQueue::enqueue`], in bytes.
+    pub max_svg_bytes: usize,
+    /// Most pages (artboards) a job accepted by [`JobQueue::enqueue`] may
+    /// have.
+    pub max_pages: usize,
+    /// Format jobs are written to the queues in. Jobs in either format are
+    /// read regardless.
+    pub serialization: Serialization,
+    /// Most payloads kept on the dead-letter list; the oldest are trimmed
+    /// beyond it. `None` keeps every payload.
+    pub dead_letter_max_len: Option<usize>,
+    /// How long the dead-letter list is kept after the last payload was
+    /// added to it. `None` keeps it until it is deleted by hand.
+    pub dead_letter_ttl: Option<Duration>,
+    /// Most times [`JobQueue::requeue_from_dlq`] replays one job without
+    /// being forced, so a job that is broken for good can't cycle between
+    /// the queue and the dead-letter list forever.
+    pub max_dlq_replays: u8,
+    /// Priorities of jobs enqueued without one, by export scope.
+    pub scope_priorities: ScopePriorities,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            blpop_timeout: Duration::from_secs(5),
+            error_backoff: Duration::from_secs(5),
+            max_svg_bytes: DEFAULT_MAX_SVG_BYTES,
+            max_pages: DEFAULT_MAX_PAGES,
+            serialization: Serialization::default(),
+            dead_letter_max_len: Some(DEFAULT_DEAD_LETTER_MAX_LEN),
+            dead_letter_ttl: None,
+            max_dlq_replays: DEFAULT_MAX_DLQ_REPLAYS,
+            scope_priorities: ScopePriorities::default(),
+        }
+    }
+}
+
+/// Prefix that every Redis key used by a [`JobQueue`] is derived from.
+///
+/// Queues with different namespaces share nothing, so several environments
+/// (or tenants) can run against one Redis. The default namespace,
+/// `wiretuner:export:pdf`, gives the key names documented in the README.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyNamespace {
+    prefix: String,
+}
+
+impl KeyNamespace {
+    /// Creates a namespace whose keys all start with `prefix:`.
+    pub fn new(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Returns the namespace prefix.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Queue for normal-priority jobs.
+    pub fn queue(&self) -> String {
+        format!("{}:queue", self.prefix)
+    }
+
+    /// Queue for high-priority jobs.
+    pub fn high_priority_queue(&self) -> String {
+        format!("{}:queue:high", self.prefix)
+    }
+
+    /// Queue for low-priority jobs.
+    pub fn low_priority_queue(&self) -> String {
+        format!("{}:queue:low", self.prefix)
+    }
+
+    /// Queue a job of the given priority is pushed to.
+    pub fn queue_for_priority(&self, priority: u8) -> String {
+        match priority {
+            PRIORITY_LOW => self.low_priority_queue(),
+            p if p >= PRIORITY_HIGH => self.high_priority_queue(),
+            _ => self.queue(),
+        }
+    }
+
+    /// Queue holding one tenant's jobs of the given priority.
+    pub fn tenant_queue(&self, priority: u8, user_id: &str) -> String {
+        format!("{}{}", self.tenant_queue_prefix(priority), user_id)
+    }
+
+    fn tenant_queue_prefix(&self, priority: u8) -> String {
+        format!("{}:tenant:", self.queue_for_priority(priority))
+    }
+
+    /// Sorted set of tenants with jobs waiting at the given priority, in
+    /// the order their turns come; the shared queue's turn is the tenant
+    /// `''`.
+    pub fn tenants(&self, priority: u8) -> String {
+        format!("{}:tenants", self.queue_for_priority(priority))
+    }
+
+    /// List a token is pushed onto whenever a job becomes ready at the given
+    /// priority, for idle workers to block on.
+    pub fn notify(&self, priority: u8) -> String {
+        format!("{}:notify", self.queue_for_priority(priority))
+    }
+
+    /// Queue a job waits on: its tenant's queue if it has a `user_id`,
+    /// otherwise the shared queue for its priority.
+    pub fn ready_queue(&self, job: &PdfExportJob) -> String {
+        match tenant(job) {
+            Some(user_id) => self.tenant_queue(job.effective_priority(), user_id),
+            None => self.queue_for_priority(job.effective_priority()),
+        }
+    }
+
+    /// Sorted set of jobs waiting out a retry backoff or scheduled for
+    /// later, scored by the Unix time (milliseconds) at which they become
+    /// ready.
+    pub fn delayed(&self) -> String {
+        format!("{}:delayed", self.prefix)
+    }
+
+    /// Status key clients poll for a job.
+    pub fn status(&self, job_id: &str) -> String {
+        format!("{}:status:{}", self.prefix, job_id)
+    }
+
+    /// Key mapping an idempotency key to the job it created.
+    pub fn idempotency(&self, key: &str) -> String {
+        format!("{}:idem:{}", self.prefix, key)
+    }
+
+    /// Cancellation flag for a job.
+    pub fn cancel(&self, job_id: &str) -> String {
+        format!("{}:cancel:{}", self.prefix, job_id)
+    }
+
+    /// Processing list holding a consumer's unacknowledged jobs.
+    /// `consumer_id` may be a Redis glob when scanning.
+    pub fn processing(&self, consumer_id: &str) -> String {
+        format!("{}:processing:{}", self.prefix, consumer_id)
+    }
+
+    /// Heartbeat key a live worker keeps refreshing, expiring when it stops.
+    pub fn worker(&self, consumer_id: &str) -> String {
+        format!("{}:worker:{}", self.prefix, consumer_id)
+    }
+
+    /// Concurrency limit requested for every worker, overriding
+    /// `WORKER_CONCURRENCY` while set.
+    pub fn concurrency(&self) -> String {
+        format!("{}:concurrency", self.prefix)
+    }
+
+    /// Flag that, while set, stops workers from taking new jobs.
+    pub fn paused(&self) -> String {
+        format!("{}:paused", self.prefix)
+    }
+
+    /// Dead-letter list holding payloads that could not be decoded or
+    /// failed their checksum, kept verbatim for inspection.
+    pub fn dead_letter(&self) -> String {
+        format!("{}:dead", self.prefix)
+    }
+
+    /// Sliding-window counter for an enqueue rate limit, e.g.
+    /// `rate_limit("document:doc-123")`.
+    pub fn rate_limit(&self, scope: &str) -> String {
+        format!("{}:ratelimit:{}", self.prefix, scope)
+    }
+
+    /// Pub/sub channel carrying a [`StatusEvent`] for every status update.
+    pub fn events(&self) -> String {
+        format!("{}:events", self.prefix)
+    }
+}
+
+impl Default for KeyNamespace {
+    fn default() -> Self {
+        Self::new(DEFAULT_KEY_NAMESPACE)
+    }
+}
+
+/// A job status transition, published on [`KeyNamespace::events`] by
+/// [`JobQueue::update_status`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusEvent {
+    pub job_id: String,
+    pub status: JobStatus,
+    pub progress: Option<u8>,
+}
+
+impl StatusEvent {
+    fn for_job(job: &PdfExportJob) -> Self {
+        Self {
+            job_id: job.job_id.clone(),
+            status: job.status,
+            progress: job.progress,
+        }
+    }
+}
+
+/// Consumers [`reap_dead_workers`](JobQueue::reap_dead_workers) found
+/// without a heartbeat, and when it first did.
+///
+/// A missing heartbeat key alone doesn't prove a worker dead: the key may
+/// have been lost (e.g. in a Redis failover) while the worker is still
+/// beating on its interval. Keep one of these across calls so a worker is
+/// only reaped once its heartbeat has stayed missing for a grace period.
+#[derive(Debug, Default)]
+pub struct MissingHeartbeats {
+    since: HashMap<String, Instant>,
+}
+
 /// Redis-based job queue manager.
 ///
 /// Provides async job enqueue/dequeue operations with job status tracking.
-/// Jobs are stored as JSON in Redis lists, with separate status keys for
+/// Jobs are stored in Redis lists, as JSON or MessagePack (see
+/// [`QueueConfig::serialization`]), with separate JSON status keys for
 /// client polling.
+///
+/// Dequeued jobs are atomically moved to a processing list owned by the
+/// queue's consumer id and stay there until [`ack`](Self::ack)ed, so a
+/// worker crash never loses a job: [`recover_processing`](Self::recover_processing)
+/// moves unacknowledged jobs back onto the queue.
+#[derive(Clone)]
 pub struct JobQueue {
     /// Redis connection manager for async operations.
     pub conn: ConnectionManager,
+    /// Prefix all keys are derived from.
+    keys: KeyNamespace,
+    /// Consumer id that owns `processing_key`.
+    consumer_id: String,
+    /// Processing list holding this consumer's unacknowledged jobs.
+    processing_key: String,
+    /// Jobs dequeued so far, used to bound starvation of low priorities.
+    dequeue_count: u64,
+    /// Polling timeouts.
+    config: QueueConfig,
+    /// Enqueue rate limits, if any.
+    rate_limits: Option<Arc<RateLimits>>,
+    /// Receives lifecycle events of the jobs passing through this queue.
+    events: Arc<dyn EventSink>,
 }
 
 impl JobQueue {
     /// Creates a new job queue with the given Redis connection.
     pub fn new(conn: ConnectionManager) -> Self {
-        Self { conn }
+        let keys = KeyNamespace::default();
+        Self {
+            conn,
+            processing_key: keys.processing(DEFAULT_CONSUMER_ID),
+            keys,
+            consumer_id: DEFAULT_CONSUMER_ID.to_string(),
+            dequeue_count: 0,
+            config: QueueConfig::default(),
+            rate_limits: None,
+            events: Arc::new(TracingSink),
+        }
+    }
+
+    /// Sets the polling timeouts.
+    pub fn with_config(mut self, config: QueueConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the polling timeouts.
+    pub fn config(&self) -> &QueueConfig {
+        &self.config
+    }
+
+    /// Rejects enqueues beyond `rate_limits` with [`QueueError::RateLimited`].
+    ///
+    /// Only the producer side needs this; workers re-enqueueing jobs on
+    /// shutdown should not be limited.
+    pub fn with_rate_limits(mut self, rate_limits: RateLimits) -> Self {
+        self.rate_limits = Some(Arc::new(rate_limits));
+        self
+    }
+
+    /// Sends job lifecycle events to `sink` instead of logging them with
+    /// [`TracingSink`]. Clones of the queue share the sink.
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.events = sink;
+        self
+    }
+
+    /// Reports a lifecycle event of `kind` for `job` to the event sink.
+    pub fn emit_event(&self, kind: JobEventKind, job: &PdfExportJob) {
+        self.events.emit(&JobEvent::new(kind, job));
+    }
+
+    /// Sets the consumer id that owns this queue's processing list.
+    ///
+    /// The id should be stable across restarts of the same worker instance
+    /// (e.g. the pod hostname) so a restarted worker can recover the jobs
+    /// its previous incarnation was processing.
+    pub fn with_consumer_id(mut self, consumer_id: &str) -> Self {
+        self.consumer_id = consumer_id.to_string();
+        self.processing_key = self.keys.processing(consumer_id);
+        self
+    }
+
+    /// Returns the consumer id that owns this queue's processing list.
+    pub fn consumer_id(&self) -> &str {
+        &self.consumer_id
+    }
+
+    /// Derives every key this queue uses from `keys` instead of the default
+    /// `wiretuner:export:pdf` prefix.
+    ///
+    /// Producers and workers must agree on the namespace; jobs enqueued in
+    /// one namespace are invisible to queues in another.
+    pub fn with_namespace(mut self, keys: KeyNamespace) -> Self {
+        self.processing_key = keys.processing(&self.consumer_id);
+        self.keys = keys;
+        self
+    }
+
+    /// Returns the namespace this queue's keys are derived from.
+    pub fn namespace(&self) -> &KeyNamespace {
+        &self.keys
     }
 
     /// Enqueues a new PDF export job.
     ///
-    /// The job is added to the Redis list and a status key is created
-    /// for client polling. The status key expires after 24 hours.
+    /// The job is added to the Redis list for its priority and a status key
+    /// is created for client polling, in one transaction. The status key
+    /// expires after 24 hours. Connection errors are retried a couple of
+    /// times with backoff before being returned.
+    ///
+    /// If the job carries an idempotency key that an earlier job already
+    /// claimed (within the same 24 hours), nothing is enqueued and the
+    /// earlier job's id is returned instead.
+    ///
+    /// Jobs with an SVG larger than the configured `max_svg_bytes` are
+    /// rejected with [`QueueError::InvalidJob`], and jobs whose
+    /// `output_path` doesn't end in their format's extension with
+    /// [`QueueError::InvalidOutputPath`], so they never reach a worker.
+    ///
+    /// Jobs without a `trace_context` are stored with the caller's current
+    /// trace context, so the worker's job span continues the same trace.
+    /// Jobs without a `priority` get the one configured for their export
+    /// scope (see [`ScopePriorities`]). Every job is stamped with a content
+    /// checksum that [`dequeue`](Self::dequeue) verifies.
     ///
     /// # Arguments
     ///
@@ -42,189 +679,2507 @@ impl JobQueue {
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` on success, or an error if Redis operations fail.
-    pub async fn enqueue(&mut self, job: &PdfExportJob) -> Result<()> {
-        let job_json = serde_json::to_string(job)
-            .context("Failed to serialize job")?;
+    /// Returns the id of the enqueued (or previously enqueued) job, or an
+    /// error if Redis operations fail.
+    pub async fn enqueue(&mut self, job: &PdfExportJob) -> Result<String> {
+        self.check_job(job)?;
 
-        // Push to queue (RPUSH for FIFO order)
-        self.conn
-            .rpush::<_, _, ()>(QUEUE_KEY, &job_json)
-            .await
-            .context("Failed to push job to queue")?;
+        if let Some(existing) = self.claim_idempotency_key(job).await? {
+            return Ok(existing);
+        }
+        self.admit_or_release(job).await?;
 
-        // Set status key with TTL
-        let status_key = format!("{}:{}", STATUS_KEY_PREFIX, job.job_id);
-        self.conn
-            .set_ex::<_, _, ()>(&status_key, &job_json, JOB_TTL_SECONDS)
-            .await
-            .context("Failed to set job status")?;
+        let job = prepare_for_enqueue(&self.config, job);
+        let job_json = serde_json::to_string(&job)
+            .context("Failed to serialize job")?;
+        let payload = self.payload(&job, &job_json)?;
+
+        let pipe = self.write_pipeline(&job, &payload, &job_json);
+        write_job(&mut self.conn, &pipe, &self.keys.status(&job.job_id), &job_json).await?;
 
         info!(
             "Enqueued job: job_id={}, document_id={}",
             job.job_id, job.document_id
         );
+        self.emit_event(JobEventKind::Enqueued, &job);
 
-        Ok(())
+        Ok(job.job_id.clone())
     }
 
-    /// Dequeues the next job from the queue (blocking with timeout).
+    /// Enqueues a job to run no earlier than `when`, e.g. a nightly batch
+    /// export.
     ///
-    /// Uses BLPOP to wait for jobs with a 5-second timeout. Returns `None`
-    /// if no jobs are available within the timeout window.
+    /// The job waits in the delayed set (status `queued`) until it is due,
+    /// then is moved onto its priority queue by
+    /// [`promote_delayed`](Self::promote_delayed), which every dequeue and
+    /// the worker's promoter task call. A `when` in the past makes the job
+    /// ready immediately. Size limits and idempotency keys are handled as in
+    /// [`enqueue`](Self::enqueue).
     ///
     /// # Returns
     ///
-    /// Returns `Ok(Some(job))` if a job was dequeued, `Ok(None)` if timeout,
-    /// or an error if Redis operations fail.
-    pub async fn dequeue(&mut self) -> Result<Option<PdfExportJob>> {
-        // BLPOP with 5-second timeout
-        let result: Option<(String, String)> = self.conn
-            .blpop(QUEUE_KEY, 5.0)
-            .await
-            .context("Failed to pop job from queue")?;
-
-        match result {
-            Some((_key, job_json)) => {
-                let job: PdfExportJob = serde_json::from_str(&job_json)
-                    .context("Failed to deserialize job")?;
+    /// Returns the id of the scheduled (or previously enqueued) job.
+    pub async fn enqueue_at(&mut self, job: &PdfExportJob, when: DateTime<Utc>) -> Result<String> {
+        self.check_job(job)?;
 
-                debug!("Dequeued job: job_id={}", job.job_id);
-                Ok(Some(job))
-            }
-            None => {
-                // Timeout, no job available
-                Ok(None)
-            }
+        if let Some(existing) = self.claim_idempotency_key(job).await? {
+            return Ok(existing);
         }
+        self.admit_or_release(job).await?;
+
+        let mut job = prepare_for_enqueue(&self.config, job).into_owned();
+        job.next_attempt_at = Some(when);
+        job.enqueued_at = Some(when);
+        self.schedule(&job, None).await?;
+
+        info!(
+            "Scheduled job: job_id={}, document_id={}, run_at={}",
+            job.job_id, job.document_id, when
+        );
+        self.emit_event(JobEventKind::Enqueued, &job);
+
+        Ok(job.job_id)
     }
 
-    /// Updates the status of a job.
+    /// Enqueues several jobs at once, e.g. one per artboard of a document.
     ///
-    /// This writes the updated job state to the status key, which clients
-    /// poll to track progress.
+    /// All queue pushes and status writes go out in a single atomic
+    /// pipeline, so a batch of N jobs costs one Redis round-trip instead of
+    /// the 2N made by calling [`enqueue`](Self::enqueue) in a loop (a
+    /// 50-artboard export drops from 100 round-trips to 1). Jobs carrying an
+    /// idempotency key need a conditional check first and are enqueued
+    /// individually.
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `job` - The job with updated status
-    pub async fn update_status(&mut self, job: &PdfExportJob) -> Result<()> {
-        let status_key = format!("{}:{}", STATUS_KEY_PREFIX, job.job_id);
-        let job_json = serde_json::to_string(job)
-            .context("Failed to serialize job status")?;
+    /// Returns the job id for each input job, in order, with the same
+    /// deduplication semantics as `enqueue`.
+    pub async fn enqueue_batch(&mut self, jobs: &[PdfExportJob]) -> Result<Vec<String>> {
+        // Reject the whole batch before anything is enqueued
+        for job in jobs {
+            self.check_job(job)?;
+        }
+        let pipelined_jobs: Vec<&PdfExportJob> = jobs
+            .iter()
+            .filter(|job| job.metadata.idempotency_key.is_none())
+            .collect();
+        self.admit(&pipelined_jobs).await?;
 
-        self.conn
-            .set_ex::<_, _, ()>(&status_key, &job_json, JOB_TTL_SECONDS)
-            .await
-            .context("Failed to update job status")?;
+        let mut job_ids = Vec::with_capacity(jobs.len());
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        let mut pipelined = Vec::new();
 
-        debug!("Updated job status: job_id={}, status={}", job.job_id, job.status);
-        Ok(())
+        for job in jobs {
+            if job.metadata.idempotency_key.is_some() {
+                job_ids.push(self.enqueue(job).await?);
+                continue;
+            }
+
+            let prepared = prepare_for_enqueue(&self.config, job);
+            let job_json = serde_json::to_string(&prepared)
+                .context("Failed to serialize job")?;
+            let payload = self.payload(&prepared, &job_json)?;
+            let status_key = self.keys.status(&job.job_id);
+            self.push_ready(&mut pipe, &prepared, &payload);
+            pipe.set_ex(&status_key, &job_json, status_ttl(job))
+                .ignore();
+            job_ids.push(job.job_id.clone());
+            pipelined.push(job);
+        }
+
+        if !pipelined.is_empty() {
+            pipe.query_async::<_, ()>(&mut self.conn)
+                .await
+                .context("Failed to enqueue job batch")?;
+        }
+        for job in pipelined {
+            self.emit_event(JobEventKind::Enqueued, job);
+        }
+
+        info!("Enqueued batch of {} job(s)", jobs.len());
+        Ok(job_ids)
     }
 
-    /// Gets the current status of a job by ID.
+    /// Dequeues the next job from the queue (blocking with timeout).
     ///
-    /// # Arguments
+    /// Priorities are checked in order (high, normal, low); every few
+    /// dequeues the order is reversed so low priorities are never starved.
+    /// Within a priority, tenants (jobs' `user_id`s) are served round-robin,
+    /// with jobs that have no user sharing one turn, so a tenant's large
+    /// batch doesn't block everyone else's jobs. When all queues are empty,
+    /// blocks with BLPOP on every priority's notification list (see
+    /// [`KeyNamespace::notify`]) and checks again once woken, in slices that
+    /// also pick up due delayed jobs, for up to the configured
+    /// [`blpop_timeout`](QueueConfig::blpop_timeout) in total.
     ///
-    /// * `job_id` - The job ID to query
+    /// The job is atomically moved onto this consumer's processing list.
+    /// Returns `None` if no jobs are available within the timeout window.
+    /// Callers must [`ack`](Self::ack) the job once it has been handled.
     ///
     /// # Returns
     ///
-    /// Returns `Ok(Some(job))` if the job exists, `Ok(None)` if not found,
-    /// or an error if Redis operations fail.
-    pub async fn get_status(&mut self, job_id: &str) -> Result<Option<PdfExportJob>> {
-        let status_key = format!("{}:{}", STATUS_KEY_PREFIX, job_id);
+    /// Returns `Ok(Some(job))` if a job was dequeued, `Ok(None)` if timeout,
+    /// [`QueueError::CorruptedPayload`] if the popped payload was unreadable
+    /// or failed its checksum (it is dead-lettered, and the next call moves
+    /// on), or an error if Redis operations fail.
+    pub async fn dequeue(&mut self) -> Result<Option<PdfExportJob>> {
+        let deadline = Instant::now() + self.config.blpop_timeout;
 
-        let job_json: Option<String> = self.conn
-            .get(&status_key)
-            .await
-            .context("Failed to get job status")?;
+        loop {
+            if let Some(job) = self.try_dequeue().await? {
+                return Ok(Some(job));
+            }
 
-        match job_json {
-            Some(json) => {
-                let job: PdfExportJob = serde_json::from_str(&json)
-                    .context("Failed to deserialize job status")?;
-                Ok(Some(job))
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                // Timeout, no job available
+                return Ok(None);
             }
-            None => Ok(None),
+
+            // Whichever priority was notified, the next pass checks them
+            // all in order
+            let wait = remaining.min(PRIORITY_POLL_SLICE);
+            let notify: Vec<String> = PRIORITIES.iter().map(|&p| self.keys.notify(p)).collect();
+            self.conn
+                .blpop::<_, Option<(String, String)>>(notify, wait.as_secs_f64())
+                .await
+                .context("Failed to wait for jobs")?;
         }
     }
 
-    /// Retries a failed job by re-enqueueing it.
-    ///
-    /// This increments the retry count and pushes the job back to the queue
-    /// if retries are available.
-    ///
-    /// # Arguments
+    /// Dequeues the next job if one is ready, without waiting.
     ///
-    /// * `job` - The job to retry
+    /// Makes a single pass over the queues in the same order as
+    /// [`dequeue`](Self::dequeue), popping with a non-blocking move onto this
+    /// consumer's processing list, and returns `Ok(None)` straight away if
+    /// every queue is empty. Useful for polling from a scheduler tick or a
+    /// test; the worker loop uses the blocking [`dequeue`](Self::dequeue).
+    /// Callers must [`ack`](Self::ack) the job once it has been handled.
     ///
-    /// # Returns
+    /// # Errors
     ///
-    /// Returns `Ok(true)` if retry was enqueued, `Ok(false)` if max retries
-    /// exceeded, or an error if operations fail.
-    pub async fn retry_job(&mut self, mut job: PdfExportJob) -> Result<bool> {
-        if job.retry() {
-            self.enqueue(&job).await?;
-            Ok(true)
-        } else {
-            // Max retries exceeded, update status to failed
-            self.update_status(&job).await?;
-            error!(
-                "Job failed after max retries: job_id={}, error={:?}",
-                job.job_id, job.error
-            );
-            Ok(false)
+    /// The same as [`dequeue`](Self::dequeue).
+    pub async fn try_dequeue(&mut self) -> Result<Option<PdfExportJob>> {
+        if let Err(e) = self.promote_delayed().await {
+            warn!("Failed to promote delayed jobs: {:#}", e);
         }
+
+        for priority in service_order(PRIORITIES, self.dequeue_count) {
+            if let Some(payload) = self.pop_fair(priority).await? {
+                return self.decode_dequeued(payload).await.map(Some);
+            }
+        }
+        Ok(None)
     }
 
-    /// Returns the current queue length.
-    pub async fn queue_length(&mut self) -> Result<usize> {
-        let len: usize = self.conn
-            .llen(QUEUE_KEY)
-            .await
-            .context("Failed to get queue length")?;
-        Ok(len)
+    /// Pops the next job at `priority` from the tenant whose turn it is (see
+    /// [`FAIR_DEQUEUE_SCRIPT`]) onto this consumer's processing list.
+    ///
+    /// Tenants found with nothing waiting leave the rotation and the next
+    /// one is tried, up to [`MAX_TENANT_PROBES`]. With the rotation empty,
+    /// the shared queue is still tried, so jobs pushed onto it without a
+    /// turn aren't stranded.
+    async fn pop_fair(&mut self, priority: u8) -> Result<Option<Vec<u8>>> {
+        let tenants_key = self.keys.tenants(priority);
+        let fair_dequeue = redis::Script::new(FAIR_DEQUEUE_SCRIPT);
+        for _ in 0..MAX_TENANT_PROBES {
+            let next: Vec<String> = self.conn
+                .zrange(&tenants_key, 0, 0)
+                .await
+                .context("Failed to read tenant rotation")?;
+            let rotation_empty = next.is_empty();
+            let tenant = next.into_iter().next().unwrap_or_default();
+            let queue_key = if tenant.is_empty() {
+                self.keys.queue_for_priority(priority)
+            } else {
+                self.keys.tenant_queue(priority, &tenant)
+            };
+            let result: Option<Vec<u8>> = fair_dequeue
+                .key(queue_key)
+                .key(&tenants_key)
+                .key(&self.processing_key)
+                .arg(&tenant)
+                .arg(Utc::now().timestamp_millis())
+                .invoke_async(&mut self.conn)
+                .await
+                .context("Failed to pop job from queue")?;
+            if result.is_some() || rotation_empty {
+                return Ok(result);
+            }
+        }
+        Ok(None)
     }
-}
+
+    /// Moves delayed jobs whose backoff has elapsed onto their queues.
+    ///
+    /// Returns the number of jobs promoted.
+    pub async fn promote_delayed(&mut self) -> Result<usize> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let delayed_key = self.keys.delayed();
+        let ready: Vec<Vec<u8>> = self.conn
+            .zrangebyscore_limit(&delayed_key, "-inf", now, 0, PROMOTE_BATCH_SIZE)
+            .await
+            .context("Failed to read delayed jobs")?;
+
+        let script = redis::Script::new(PROMOTE_SCRIPT);
+        let mut promoted = 0;
+        for payload in ready {
+            // Unreadable payloads fall back to the normal queue, where
+            // dequeue discards them
+            let job = payload::decode(&payload).ok();
+            let (queue_key, priority, user_id) = match &job {
+                Some(job) => (self.keys.ready_queue(job), job.effective_priority(), tenant(job)),
+                None => (self.keys.queue(), PRIORITY_NORMAL, None),
+            };
+            let moved: i32 = script
+                .key(&delayed_key)
+                .key(queue_key)
+                .key(self.keys.tenants(priority))
+                .key(self.keys.notify(priority))
+                .arg(&payload)
+                .arg(user_id.unwrap_or_default())
+                .arg(now)
+                .arg(NOTIFY_BACKLOG)
+                .invoke_async(&mut self.conn)
+                .await
+                .context("Failed to promote delayed job")?;
+            promoted += moved as usize;
+        }
+
+        if promoted > 0 {
+            debug!("Promoted {} delayed job(s)", promoted);
+        }
+        Ok(promoted)
+    }
+
+    /// Deserializes a job that was just moved onto the processing list and
+    /// verifies its checksum.
+    ///
+    /// Payloads that fail either check are moved to the dead-letter list and
+    /// reported as [`QueueError::CorruptedPayload`]; if the job id is still
+    /// readable its status is set to failed.
+    async fn decode_dequeued(&mut self, payload: Vec<u8>) -> Result<PdfExportJob> {
+        let mut job = match payload::decode(&payload) {
+            Ok(job) => job,
+            Err(e) => {
+                self.dead_letter(&payload).await?;
+                return Err(QueueError::CorruptedPayload {
+                    job_id: None,
+                    reason: format!("Failed to deserialize job: {}", e),
+                });
+            }
+        };
+
+        if !job.verify_checksum() {
+            self.dead_letter(&payload).await?;
+            let reason = "SVG content does not match its checksum".to_string();
+            job.mark_failed(format!("Corrupted job payload: {}", reason));
+            job.error_kind = Some(ErrorKind::Corrupted);
+            self.update_status(&job).await?;
+            self.emit_event(JobEventKind::Failed, &job);
+            return Err(QueueError::CorruptedPayload {
+                job_id: Some(job.job_id),
+                reason,
+            });
+        }
+
+        self.dequeue_count += 1;
+        debug!("Dequeued job: job_id={}, priority={}", job.job_id, job.effective_priority());
+        Ok(job)
+    }
+
+    /// Moves a payload from this consumer's processing list to the
+    /// dead-letter list, so it is neither processed nor recovered again.
+    ///
+    /// The list is then trimmed to [`QueueConfig::dead_letter_max_len`],
+    /// dropping the oldest payloads, and its expiry refreshed from
+    /// [`QueueConfig::dead_letter_ttl`].
+    async fn dead_letter(&mut self, payload: &[u8]) -> Result<()> {
+        let dead_letter_key = self.keys.dead_letter();
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .lrem(&self.processing_key, 1, payload)
+            .ignore()
+            .rpush(&dead_letter_key, payload)
+            .ignore();
+        if let Some(max_len) = self.config.dead_letter_max_len {
+            let keep = max_len.min(isize::MAX as usize) as isize;
+            pipe.ltrim(&dead_letter_key, -keep, -1).ignore();
+        }
+        if let Some(ttl) = self.config.dead_letter_ttl {
+            let millis = ttl.as_millis().clamp(1, i64::MAX as u128) as i64;
+            pipe.pexpire(&dead_letter_key, millis).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut self.conn)
+            .await
+            .context("Failed to dead-letter corrupted job")?;
+        warn!("Moved corrupted job payload to {}", dead_letter_key);
+        Ok(())
+    }
+
+    /// Moves a job from the dead-letter list back onto its ready queue, e.g.
+    /// once whatever corrupted it has been fixed. Its status returns to
+    /// `queued` and its [`dlq_replay_count`](PdfExportJob::dlq_replay_count)
+    /// goes up by one.
+    ///
+    /// The payload is replayed as it was dead-lettered, so one that still
+    /// fails its checksum lands back on the list at the next dequeue.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the job was requeued, `Ok(false)` if no
+    /// readable payload with that id is on the dead-letter list, and
+    /// [`QueueError::ReplayLimitExceeded`] if the job was already replayed
+    /// [`max_dlq_replays`](QueueConfig::max_dlq_replays) times, unless
+    /// `force` is set.
+    pub async fn requeue_from_dlq(&mut self, job_id: &str, force: bool) -> Result<bool> {
+        let dead_letter_key = self.keys.dead_letter();
+        let payloads: Vec<Vec<u8>> = self.conn
+            .lrange(&dead_letter_key, 0, -1)
+            .await
+            .context("Failed to read dead-letter list")?;
+        let Some((payload, mut job)) = payloads.into_iter().find_map(|payload| {
+            let job = payload::decode(&payload).ok().filter(|job| job.job_id == job_id)?;
+            Some((payload, job))
+        }) else {
+            return Ok(false);
+        };
+
+        let limit = self.config.max_dlq_replays;
+        if job.dlq_replay_count >= limit {
+            if !force {
+                return Err(QueueError::ReplayLimitExceeded {
+                    job_id: job.job_id,
+                    replays: job.dlq_replay_count,
+                    limit,
+                });
+            }
+            warn!(
+                "Forcing dead-letter replay past the limit: job_id={}, replays={}, limit={}",
+                job.job_id, job.dlq_replay_count, limit
+            );
+        }
+
+        job.requeue_from_dead_letter();
+        let job_json = serde_json::to_string(&job)
+            .context("Failed to serialize job")?;
+        let replayed = self.payload(&job, &job_json)?;
+
+        // Remove and requeue in one transaction, so the job can't be lost
+        // between the two
+        let mut pipe = redis::pipe();
+        pipe.atomic().lrem(&dead_letter_key, 1, &payload);
+        self.push_ready(&mut pipe, &job, &replayed);
+        pipe.set_ex(self.keys.status(&job.job_id), &job_json, status_ttl(&job))
+            .ignore();
+        let (removed,): (usize,) = pipe
+            .query_async(&mut self.conn)
+            .await
+            .context("Failed to replay job from dead-letter list")?;
+        if removed == 0 {
+            // A concurrent replay removed the payload first and queued the
+            // same job; take back this copy so it runs once
+            self.conn
+                .lrem::<_, _, ()>(self.keys.ready_queue(&job), 1, &*replayed)
+                .await
+                .context("Failed to undo duplicate dead-letter replay")?;
+            return Ok(false);
+        }
+
+        info!(
+            "Replayed job from dead-letter list: job_id={}, replays={}",
+            job.job_id, job.dlq_replay_count
+        );
+        self.emit_event(JobEventKind::Enqueued, &job);
+        Ok(true)
+    }
+
+    /// Acknowledges a dequeued job, removing it from the processing list.
+    ///
+    /// Returns `Ok(true)` if the job was found and removed.
+    pub async fn ack(&mut self, job_id: &str) -> Result<bool> {
+        let Some(payload) = self.processing_payload(job_id).await? else {
+            return Ok(false);
+        };
+        let removed: usize = self.conn
+            .lrem(&self.processing_key, 1, &payload)
+            .await
+            .context("Failed to acknowledge job")?;
+        debug!("Acknowledged job: job_id={}", job_id);
+        Ok(removed > 0)
+    }
+
+    /// Returns the payload of `job_id` on this consumer's processing list,
+    /// as it was dequeued.
+    async fn processing_payload(&mut self, job_id: &str) -> Result<Option<Vec<u8>>> {
+        let payloads: Vec<Vec<u8>> = self.conn
+            .lrange(&self.processing_key, 0, -1)
+            .await
+            .context("Failed to read processing list")?;
+        Ok(payloads.into_iter().find(|payload| {
+            payload::decode(payload).is_ok_and(|job| job.job_id == job_id)
+        }))
+    }
+
+    /// Moves jobs from stale processing lists back onto the queue.
+    ///
+    /// Scans for processing lists whose consumer id matches
+    /// `consumer_pattern` (a Redis glob) and pushes their contents back onto
+    /// each job's ready queue, keeping its priority and tenant. Call this on
+    /// startup with the worker's own consumer id, before any worker loop
+    /// starts dequeuing; only pass a wider pattern when the matching
+    /// consumers are known to be dead.
+    ///
+    /// Returns the number of jobs recovered.
+    pub async fn recover_processing(&mut self, consumer_pattern: &str) -> Result<usize> {
+        let pattern = self.keys.processing(consumer_pattern);
+        let keys = self.scan_keys(&pattern).await?;
+
+        let mut recovered = 0;
+        for key in keys {
+            recovered += self.recover_list(&key).await?.len();
+        }
+
+        if recovered > 0 {
+            warn!("Recovered {} unacknowledged job(s) from processing lists", recovered);
+        }
+        Ok(recovered)
+    }
+
+    /// Moves every job on the processing list `key` back onto its ready
+    /// queue (see [`KeyNamespace::ready_queue`]), returning their payloads.
+    ///
+    /// Payloads that can't be decoded go to the normal-priority queue, to be
+    /// dead-lettered when next dequeued.
+    async fn recover_list(&mut self, key: &str) -> Result<Vec<Vec<u8>>> {
+        let payloads: Vec<Vec<u8>> = self.conn
+            .lrange(key, 0, -1)
+            .await
+            .context("Failed to read processing list")?;
+
+        let mut moved = Vec::new();
+        for payload in payloads {
+            // Remove and requeue in one transaction, so the job can't be
+            // lost between the two
+            let mut pipe = redis::pipe();
+            pipe.atomic().lrem(key, 1, &payload);
+            let ready_queue = match payload::decode(&payload) {
+                Ok(job) => {
+                    self.push_ready(&mut pipe, &job, &payload);
+                    self.keys.ready_queue(&job)
+                }
+                Err(_) => {
+                    pipe.rpush(self.keys.queue(), &payload).ignore();
+                    self.announce(&mut pipe, PRIORITY_NORMAL, "");
+                    self.keys.queue()
+                }
+            };
+            let (removed,): (usize,) = pipe
+                .query_async(&mut self.conn)
+                .await
+                .context("Failed to recover job")?;
+            if removed == 0 {
+                // The owner acknowledged it meanwhile; take back this copy
+                self.conn
+                    .lrem::<_, _, ()>(&ready_queue, -1, &payload)
+                    .await
+                    .context("Failed to undo duplicate recovery")?;
+                continue;
+            }
+            moved.push(payload);
+        }
+        Ok(moved)
+    }
+
+    /// Returns the concurrency limit requested for every worker, if any.
+    ///
+    /// Values that aren't a positive integer are treated as unset.
+    pub async fn concurrency_target(&mut self) -> Result<Option<usize>> {
+        let target: Option<String> = self.conn
+            .get(self.keys.concurrency())
+            .await
+            .context("Failed to get concurrency target")?;
+        Ok(target
+            .and_then(|target| target.trim().parse().ok())
+            .filter(|&target| target > 0))
+    }
+
+    /// Asks every worker to process `target` jobs at once, or with `None`
+    /// to return to its configured concurrency. Workers pick the change up
+    /// on their next poll (see
+    /// [`concurrency_loop`](crate::worker::concurrency_loop)).
+    pub async fn set_concurrency_target(&mut self, target: Option<usize>) -> Result<()> {
+        let key = self.keys.concurrency();
+        match target {
+            Some(target) => self.conn.set::<_, _, ()>(key, target).await,
+            None => self.conn.del::<_, ()>(key).await,
+        }
+        .context("Failed to set concurrency target")
+    }
+
+    /// Stops every worker on this namespace from taking new jobs, e.g. for
+    /// maintenance. Jobs already running finish, and producers can still
+    /// enqueue; workers idle until [`resume`](Self::resume) is called.
+    pub async fn pause(&mut self) -> Result<()> {
+        self.conn
+            .set::<_, _, ()>(self.keys.paused(), Utc::now().to_rfc3339())
+            .await
+            .context("Failed to pause queue")?;
+        info!("Queue paused: namespace={}", self.keys.prefix());
+        Ok(())
+    }
+
+    /// Lets workers take jobs again after [`pause`](Self::pause).
+    pub async fn resume(&mut self) -> Result<()> {
+        self.conn
+            .del::<_, ()>(self.keys.paused())
+            .await
+            .context("Failed to resume queue")?;
+        info!("Queue resumed: namespace={}", self.keys.prefix());
+        Ok(())
+    }
+
+    /// Returns `true` while the queue is [`pause`](Self::pause)d.
+    pub async fn is_paused(&mut self) -> Result<bool> {
+        let paused: bool = self.conn
+            .exists(self.keys.paused())
+            .await
+            .context("Failed to read pause flag")?;
+        Ok(paused)
+    }
+
+    /// Marks this queue's consumer alive for `ttl`.
+    ///
+    /// Workers refresh the heartbeat well within `ttl`; once it expires,
+    /// [`reap_dead_workers`](Self::reap_dead_workers) on any other worker
+    /// takes back the consumer's unacknowledged jobs.
+    pub async fn heartbeat(&mut self, ttl: Duration) -> Result<()> {
+        self.conn
+            .set_ex::<_, _, ()>(
+                self.keys.worker(&self.consumer_id),
+                Utc::now().to_rfc3339(),
+                ttl.as_secs().max(1),
+            )
+            .await
+            .context("Failed to write worker heartbeat")
+    }
+
+    /// Re-queues the jobs of workers whose heartbeat has expired.
+    ///
+    /// Finds processing lists whose consumer has no live
+    /// [`heartbeat`](Self::heartbeat), other than this queue's own, and
+    /// moves their jobs back onto their ready queues as in
+    /// [`recover_processing`](Self::recover_processing), setting their
+    /// status back to `queued`. A consumer whose heartbeat is missing counts
+    /// as unknown rather than dead until it has been missing for `grace`,
+    /// tracked in `missing` across calls. A worker that hung rather than
+    /// crashed may still finish such a job, in which case it runs twice.
+    ///
+    /// Returns the number of jobs re-queued.
+    pub async fn reap_dead_workers(
+        &mut self,
+        missing: &mut MissingHeartbeats,
+        grace: Duration,
+    ) -> Result<usize> {
+        let prefix = self.keys.processing("");
+        let keys = self.scan_keys(&self.keys.processing("*")).await?;
+
+        let mut reaped = 0;
+        let mut still_missing = HashMap::new();
+        for key in keys {
+            let Some(consumer_id) = key.strip_prefix(&prefix) else {
+                continue;
+            };
+            if consumer_id == self.consumer_id {
+                continue;
+            }
+            let alive: bool = self.conn
+                .exists(self.keys.worker(consumer_id))
+                .await
+                .context("Failed to read worker heartbeat")?;
+            if alive {
+                continue;
+            }
+
+            // Give a lost heartbeat key time to be written again
+            let since = missing
+                .since
+                .get(consumer_id)
+                .copied()
+                .unwrap_or_else(Instant::now);
+            if since.elapsed() < grace {
+                still_missing.insert(consumer_id.to_string(), since);
+                continue;
+            }
+
+            let payloads = self.recover_list(&key).await?;
+            if payloads.is_empty() {
+                continue;
+            }
+            warn!(
+                "Worker {} stopped heartbeating, re-queued {} job(s)",
+                consumer_id,
+                payloads.len()
+            );
+            reaped += payloads.len();
+
+            for payload in payloads {
+                let Ok(job) = payload::decode(&payload) else {
+                    continue;
+                };
+                if let Some(mut status) = self.get_status(&job.job_id).await? {
+                    if status.status == JobStatus::Processing {
+                        status.requeue();
+                        self.update_status(&status).await?;
+                    }
+                }
+            }
+        }
+        missing.since = still_missing;
+        Ok(reaped)
+    }
+
+    /// Collects every key matching `pattern` with cursor-based SCAN.
+    async fn scan_keys(&mut self, pattern: &str) -> Result<Vec<String>> {
+        let mut iter = self.conn
+            .scan_match::<_, String>(pattern)
+            .await
+            .context("Failed to scan keys")?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    /// Updates the status of a job.
+    ///
+    /// This writes the updated job state to the status key, which clients
+    /// poll to track progress.
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - The job with updated status
+    pub async fn update_status(&mut self, job: &PdfExportJob) -> Result<()> {
+        let mut pipe = redis::pipe();
+        self.write_status(&mut pipe, job)?;
+        pipe.query_async::<_, ()>(&mut self.conn)
+            .await
+            .context("Failed to update job status")?;
+
+        debug!("Updated job status: job_id={}, status={}", job.job_id, job.status);
+        Ok(())
+    }
+
+    /// Adds the commands writing `job`'s status key and publishing the
+    /// change to [`KeyNamespace::events`] to `pipe`.
+    fn write_status(&self, pipe: &mut redis::Pipeline, job: &PdfExportJob) -> Result<()> {
+        let job_json = serde_json::to_string(job)
+            .context("Failed to serialize job status")?;
+        let event_json = serde_json::to_string(&StatusEvent::for_job(job))
+            .context("Failed to serialize status event")?;
+        pipe.set_ex(self.keys.status(&job.job_id), &job_json, status_ttl(job))
+            .ignore()
+            .publish(self.keys.events(), &event_json)
+            .ignore();
+        Ok(())
+    }
+
+    /// Records conversion progress (0-100) on a job's status key.
+    ///
+    /// Progress is only written while the job is processing, so a late
+    /// update never overwrites a terminal status.
+    pub async fn update_progress(&mut self, job_id: &str, pct: u8) -> Result<()> {
+        let Some(mut job) = self.get_status(job_id).await? else {
+            warn!("Progress update for unknown job: job_id={}", job_id);
+            return Ok(());
+        };
+        if job.status != JobStatus::Processing {
+            return Ok(());
+        }
+
+        job.progress = Some(pct.min(100));
+        self.update_status(&job).await?;
+        debug!("Updated job progress: job_id={}, progress={}%", job_id, pct);
+        Ok(())
+    }
+
+    /// Gets the current status of a job by ID.
+    ///
+    /// # Arguments
+    ///
+    /// * `job_id` - The job ID to query
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Some(job))` if the job exists, `Ok(None)` if not found,
+    /// or an error if Redis operations fail.
+    pub async fn get_status(&mut self, job_id: &str) -> Result<Option<PdfExportJob>> {
+        let status_key = self.keys.status(job_id);
+
+        let job_json: Option<String> = self.conn
+            .get(&status_key)
+            .await
+            .context("Failed to get job status")?;
+
+        match job_json {
+            Some(json) => {
+                let job: PdfExportJob = serde_json::from_str(&json)
+                    .context("Failed to deserialize job status")?;
+                Ok(Some(job))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Gets the status of several jobs in one round trip.
+    ///
+    /// Results are in the same order as `job_ids`; jobs with no status
+    /// (unknown or expired) are `None`.
+    pub async fn get_statuses(&mut self, job_ids: &[String]) -> Result<Vec<Option<PdfExportJob>>> {
+        if job_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let status_keys: Vec<String> = job_ids
+            .iter()
+            .map(|job_id| self.keys.status(job_id))
+            .collect();
+
+        let jobs_json: Vec<Option<String>> = self.conn
+            .mget(&status_keys)
+            .await
+            .context("Failed to get job statuses")?;
+
+        jobs_json
+            .into_iter()
+            .map(|json| {
+                json.map(|json| serde_json::from_str(&json))
+                    .transpose()
+                    .context("Failed to deserialize job status")
+            })
+            .collect()
+    }
+
+    /// Subscribes to status updates for one job, as an alternative to
+    /// polling [`get_status`](Self::get_status).
+    ///
+    /// Pub/sub needs a dedicated connection, so one is opened from `client`
+    /// and held by the returned stream. Only updates made after the
+    /// subscription is established are delivered; check the current status
+    /// with `get_status` afterwards to avoid missing an earlier transition.
+    pub async fn subscribe_status(
+        &self,
+        client: &redis::Client,
+        job_id: &str,
+    ) -> Result<impl Stream<Item = StatusEvent>> {
+        let mut pubsub = client
+            .get_async_connection()
+            .await
+            .context("Failed to open pub/sub connection")?
+            .into_pubsub();
+        pubsub
+            .subscribe(self.keys.events())
+            .await
+            .context("Failed to subscribe to status events")?;
+
+        let job_id = job_id.to_string();
+        Ok(pubsub.into_on_message().filter_map(move |msg| {
+            let event = msg
+                .get_payload::<String>()
+                .ok()
+                .and_then(|payload| serde_json::from_str::<StatusEvent>(&payload).ok())
+                .filter(|event| event.job_id == job_id);
+            future::ready(event)
+        }))
+    }
+
+    /// Reads SVG content stored under `key`, for jobs whose
+    /// [`svg_source`](PdfExportJob::svg_source) is a Redis key.
+    ///
+    /// The content is returned as stored, which may be gzip-compressed; see
+    /// [`decode_svg`](crate::converter::decode_svg). Returns `Ok(None)` if
+    /// the key doesn't exist.
+    pub async fn fetch_svg(&mut self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.conn
+            .get(key)
+            .await
+            .context("Failed to fetch SVG content")
+    }
+
+    /// Requests cancellation of a job.
+    ///
+    /// Sets a cancellation flag that workers check before and during
+    /// conversion. If the job is still waiting in a queue (or for a retry
+    /// backoff) it is removed outright and marked cancelled.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if the job was removed before any worker picked it
+    /// up, `Ok(false)` if only the flag was set (the job is running, already
+    /// finished, or unknown).
+    pub async fn cancel_job(&mut self, job_id: &str) -> Result<bool> {
+        let cancel_key = self.keys.cancel(job_id);
+        self.conn
+            .set_ex::<_, _, ()>(&cancel_key, 1, JOB_TTL_SECONDS)
+            .await
+            .context("Failed to set cancellation flag")?;
+
+        // A queued job's status holds the job as it was queued
+        let status_key = self.keys.status(job_id);
+        let job_json: Option<String> = self.conn
+            .get(&status_key)
+            .await
+            .context("Failed to get job status")?;
+        let Some(job_json) = job_json else {
+            return Ok(false);
+        };
+        let mut job: PdfExportJob = serde_json::from_str(&job_json)
+            .context("Failed to deserialize job status")?;
+        if job.status != JobStatus::Queued {
+            info!("Cancellation requested for running job: job_id={}", job_id);
+            return Ok(false);
+        }
+
+        if !self.unqueue(&job, &job_json).await? {
+            // A worker dequeued it in the meantime; it will see the flag
+            return Ok(false);
+        }
+
+        job.mark_cancelled();
+        self.update_status(&job).await?;
+        info!("Cancelled queued job: job_id={}", job_id);
+        Ok(true)
+    }
+
+    /// Removes a queued job's entry from its ready queue or the delayed set,
+    /// whichever format it was enqueued in (a producer may write another
+    /// format than this queue). The entry is first looked for as the
+    /// status JSON and its MessagePack encoding, then by decoding each
+    /// entry, for producers whose bytes differ from ours.
+    ///
+    /// Returns `true` if an entry was removed.
+    async fn unqueue(&mut self, job: &PdfExportJob, job_json: &str) -> Result<bool> {
+        let ready_queue = self.keys.ready_queue(job);
+        let delayed_key = self.keys.delayed();
+        let packed = Serialization::MessagePack
+            .encode(job)
+            .context("Failed to serialize job")?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for payload in [job_json.as_bytes(), &packed] {
+            pipe.lrem(&ready_queue, 1, payload)
+                .zrem(&delayed_key, payload);
+        }
+        let removed: Vec<usize> = pipe
+            .query_async(&mut self.conn)
+            .await
+            .context("Failed to remove job from queue")?;
+        if removed.iter().sum::<usize>() > 0 {
+            return Ok(true);
+        }
+
+        let matches = |payload: &Vec<u8>| {
+            payload::decode(payload).is_ok_and(|queued| queued.job_id == job.job_id)
+        };
+        let queued: Vec<Vec<u8>> = self.conn
+            .lrange(&ready_queue, 0, -1)
+            .await
+            .context("Failed to read queue")?;
+        if let Some(payload) = queued.iter().find(|payload| matches(payload)) {
+            let removed: usize = self.conn
+                .lrem(&ready_queue, 1, payload)
+                .await
+                .context("Failed to remove job from queue")?;
+            return Ok(removed > 0);
+        }
+        let scheduled: Vec<Vec<u8>> = self.conn
+            .zrange(&delayed_key, 0, -1)
+            .await
+            .context("Failed to read delayed set")?;
+        if let Some(payload) = scheduled.iter().find(|payload| matches(payload)) {
+            let removed: usize = self.conn
+                .zrem(&delayed_key, payload)
+                .await
+                .context("Failed to remove job from delayed set")?;
+            return Ok(removed > 0);
+        }
+        Ok(false)
+    }
+
+    /// Returns `true` if cancellation has been requested for the job.
+    pub async fn is_cancelled(&mut self, job_id: &str) -> Result<bool> {
+        let cancel_key = self.keys.cancel(job_id);
+        self.conn
+            .exists(&cancel_key)
+            .await
+            .context("Failed to check cancellation flag")
+    }
+
+    /// Retries a failed job by re-enqueueing it.
+    ///
+    /// This increments the retry count and, if retries are available,
+    /// schedules the job on the delayed set with an exponential backoff
+    /// rather than re-enqueueing it immediately. A retried job is
+    /// acknowledged in the same transaction, as in [`defer`](Self::defer).
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - The job to retry
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(true)` if retry was enqueued, `Ok(false)` if max retries
+    /// exceeded, or an error if operations fail.
+    pub async fn retry_job(&mut self, mut job: PdfExportJob) -> Result<bool> {
+        if job.retry() {
+            let dequeued = self.processing_payload(&job.job_id).await?;
+            self.schedule(&job, dequeued.as_deref()).await?;
+            info!(
+                "Scheduled job retry: job_id={}, next_attempt_at={:?}",
+                job.job_id, job.next_attempt_at
+            );
+            self.emit_event(JobEventKind::Retried, &job);
+            Ok(true)
+        } else {
+            // Not retryable or max retries exceeded, update status to failed
+            self.update_status(&job).await?;
+            self.emit_event(JobEventKind::Failed, &job);
+            error!(
+                "Job failed permanently: job_id={}, retry_count={}, error={:?}",
+                job.job_id, job.retry_count, job.error
+            );
+            Ok(false)
+        }
+    }
+
+    /// Puts a dequeued job back in the delayed set to run again after
+    /// `delay`, without counting a retry, e.g. when the worker can't take it
+    /// yet.
+    ///
+    /// The job is acknowledged in the same transaction, so it is never both
+    /// scheduled and left on the processing list for recovery to queue a
+    /// second time.
+    pub async fn defer(&mut self, job: &PdfExportJob, delay: Duration) -> Result<()> {
+        let mut job = job.clone();
+        let when = Utc::now()
+            + chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+        job.next_attempt_at = Some(when);
+        job.enqueued_at = Some(when);
+        let dequeued = self.processing_payload(&job.job_id).await?;
+        self.schedule(&job, dequeued.as_deref()).await?;
+
+        debug!("Deferred job: job_id={}, until={}", job.job_id, when);
+        Ok(())
+    }
+
+    /// Counts `jobs` against their rate limits, admitting all of them or
+    /// none per limit key.
+    ///
+    /// Jobs sharing a key are admitted together; when a batch spans several
+    /// keys and a later one is full, the earlier keys keep counting the jobs
+    /// they admitted.
+    async fn admit(&mut self, jobs: &[&PdfExportJob]) -> Result<()> {
+        let Some(rate_limits) = self.rate_limits.clone() else {
+            return Ok(());
+        };
+
+        let mut groups: Vec<(String, RateLimit, Vec<&PdfExportJob>)> = Vec::new();
+        for job in jobs {
+            let Some((key, limit)) = rate_limits.limit_for(job, &self.keys) else {
+                continue;
+            };
+            match groups.iter_mut().find(|(k, l, _)| *k == key && *l == limit) {
+                Some((_, _, members)) => members.push(job),
+                None => groups.push((key, limit, vec![job])),
+            }
+        }
+
+        let now = Utc::now().timestamp_millis();
+        let script = redis::Script::new(RATE_LIMIT_SCRIPT);
+        for (key, limit, members) in groups {
+            let mut invocation = script.key(&key);
+            invocation
+                .arg(now)
+                .arg(limit.window.as_millis() as u64)
+                .arg(limit.max_jobs);
+            for job in &members {
+                invocation.arg(&job.job_id);
+            }
+            let admitted: i32 = invocation
+                .invoke_async(&mut self.conn)
+                .await
+                .context("Failed to check rate limit")?;
+            if admitted == 0 {
+                warn!(
+                    "Rate limited enqueue: key={}, jobs={}, max_jobs={}, window={:?}",
+                    key,
+                    members.len(),
+                    limit.max_jobs,
+                    limit.window
+                );
+                return Err(QueueError::RateLimited {
+                    job_id: members[0].job_id.clone(),
+                    key,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts `job` against its rate limit, releasing the idempotency key it
+    /// just claimed if the limit is reached, so a later retry isn't mistaken
+    /// for a duplicate of a job that was never enqueued.
+    async fn admit_or_release(&mut self, job: &PdfExportJob) -> Result<()> {
+        let Err(e) = self.admit(std::slice::from_ref(&job)).await else {
+            return Ok(());
+        };
+        if let Some(key) = &job.metadata.idempotency_key {
+            let idem_key = self.keys.idempotency(key);
+            self.conn
+                .del::<_, ()>(&idem_key)
+                .await
+                .context("Failed to release idempotency key")?;
+        }
+        Err(e)
+    }
+
+    /// Claims `job`'s idempotency key, if it has one.
+    ///
+    /// Returns the id of the job that already holds the key when this is a
+    /// duplicate enqueue, or `None` if `job` may be enqueued.
+    async fn claim_idempotency_key(&mut self, job: &PdfExportJob) -> Result<Option<String>> {
+        let Some(key) = &job.metadata.idempotency_key else {
+            return Ok(None);
+        };
+
+        let idem_key = self.keys.idempotency(key);
+        let claimed: Option<String> = self.conn
+            .set_options(
+                &idem_key,
+                &job.job_id,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(status_ttl(job) as usize)),
+            )
+            .await
+            .context("Failed to claim idempotency key")?;
+
+        if claimed.is_none() {
+            let existing: Option<String> = self.conn
+                .get(&idem_key)
+                .await
+                .context("Failed to read idempotency key")?;
+            // Re-enqueueing the job that owns the key (e.g. on shutdown)
+            // is not a duplicate
+            if let Some(existing) = existing.filter(|id| *id != job.job_id) {
+                info!(
+                    "Duplicate enqueue ignored: idempotency_key={}, job_id={}",
+                    key, existing
+                );
+                return Ok(Some(existing));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Refuses jobs a worker should never be handed.
+    fn check_job(&self, job: &PdfExportJob) -> Result<()> {
+        check_job(&self.config, job)
+    }
+
+    /// Adds a job to the delayed set until its `next_attempt_at`, then
+    /// [`dequeue`](Self::dequeue) promotes it onto its queue. Jobs without
+    /// a `next_attempt_at` become ready immediately.
+    ///
+    /// `dequeued`, the payload a job was dequeued as, is removed from the
+    /// processing list in the same transaction, so the job is never both
+    /// scheduled and left there for recovery to queue a second time.
+    async fn schedule(&mut self, job: &PdfExportJob, dequeued: Option<&[u8]>) -> Result<()> {
+        let payload = self.config.serialization.encode(job)
+            .context("Failed to serialize job")?;
+        let ready_at = job
+            .next_attempt_at
+            .unwrap_or_else(chrono::Utc::now)
+            .timestamp_millis();
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if let Some(dequeued) = dequeued {
+            pipe.lrem(&self.processing_key, 1, dequeued).ignore();
+        }
+        pipe.zadd(self.keys.delayed(), &payload, ready_at).ignore();
+        self.write_status(&mut pipe, job)?;
+        pipe.query_async::<_, ()>(&mut self.conn)
+            .await
+            .context("Failed to schedule job")?;
+
+        debug!(
+            "Scheduled job: job_id={}, next_attempt_at={:?}",
+            job.job_id, job.next_attempt_at
+        );
+        Ok(())
+    }
+
+    /// Counts jobs in each queue and list, and status keys by state.
+    ///
+    /// Processing lists and status keys are found with SCAN rather than
+    /// KEYS, so this never blocks Redis, but it does walk the keyspace and
+    /// should be polled periodically rather than per job.
+    pub async fn queue_stats(&mut self) -> Result<QueueStats> {
+        let mut stats = QueueStats {
+            high: self.priority_length(PRIORITY_HIGH).await?,
+            normal: self.priority_length(PRIORITY_NORMAL).await?,
+            low: self.priority_length(PRIORITY_LOW).await?,
+            delayed: self.conn
+                .zcard(self.keys.delayed())
+                .await
+                .context("Failed to count delayed jobs")?,
+            ..QueueStats::default()
+        };
+
+        let processing_pattern = self.keys.processing("*");
+        for key in self.scan_keys(&processing_pattern).await? {
+            let len: usize = self.conn
+                .llen(&key)
+                .await
+                .context("Failed to get processing list length")?;
+            stats.processing += len;
+        }
+
+        /// Just the status field, so large SVG payloads aren't materialized.
+        #[derive(serde::Deserialize)]
+        struct StatusOnly {
+            status: JobStatus,
+        }
+
+        let status_pattern = self.keys.status("*");
+        let status_keys = self.scan_keys(&status_pattern).await?;
+        for batch in status_keys.chunks(STATUS_SCAN_BATCH) {
+            let values: Vec<Option<String>> = self.conn
+                .mget(batch)
+                .await
+                .context("Failed to read job statuses")?;
+            // Keys can expire between SCAN and MGET
+            for json in values.into_iter().flatten() {
+                if let Ok(job) = serde_json::from_str::<StatusOnly>(&json) {
+                    *stats.by_status.entry(job.status).or_default() += 1;
+                }
+            }
+        }
+
+        Ok(stats)
+    }
+
+    /// Returns the current queue length across all priorities.
+    pub async fn queue_length(&mut self) -> Result<usize> {
+        let mut total = 0;
+        for priority in PRIORITIES {
+            total += self.priority_length(priority).await?;
+        }
+        Ok(total)
+    }
+
+    /// Returns how long the oldest job waiting at the head of any ready
+    /// queue has existed (now minus its `created_at`), or `None` if the
+    /// queues are empty.
+    ///
+    /// Only heads are read (`LINDEX 0`), one per priority and tenant queue,
+    /// so this stays cheap however long the queues are. A short queue with
+    /// an old head points at a stalled worker pool.
+    pub async fn oldest_job_age(&mut self) -> Result<Option<Duration>> {
+        let mut queues = Vec::new();
+        for priority in PRIORITIES {
+            let tenants = self.waiting_tenants(priority).await?;
+            queues.push(self.keys.queue_for_priority(priority));
+            queues.extend(tenants.iter().map(|user_id| self.keys.tenant_queue(priority, user_id)));
+        }
+
+        let mut oldest: Option<DateTime<Utc>> = None;
+        for key in queues {
+            let head: Option<Vec<u8>> = self.conn
+                .lindex(&key, 0)
+                .await
+                .context("Failed to read queue head")?;
+            let Some(head) = head else {
+                continue;
+            };
+            match payload::decode(&head) {
+                Ok(job) => {
+                    oldest = Some(oldest.map_or(job.created_at, |at| at.min(job.created_at)));
+                }
+                // The worker that pops it dead-letters it
+                Err(e) => debug!("Skipped unreadable job at head of {}: {}", key, e),
+            }
+        }
+
+        Ok(oldest.map(|created_at| {
+            Utc::now()
+                .signed_duration_since(created_at)
+                .to_std()
+                .unwrap_or_default()
+        }))
+    }
+
+    /// Returns the number of payloads on the dead-letter list.
+    pub async fn dlq_length(&mut self) -> Result<usize> {
+        let len: usize = self.conn
+            .llen(self.keys.dead_letter())
+            .await
+            .context("Failed to get dead-letter list length")?;
+        Ok(len)
+    }
+
+    /// Returns the tenants with a turn at `priority`, other than the shared
+    /// queue's.
+    async fn waiting_tenants(&mut self, priority: u8) -> Result<Vec<String>> {
+        let mut tenants: Vec<String> = self.conn
+            .zrange(self.keys.tenants(priority), 0, -1)
+            .await
+            .context("Failed to list tenants")?;
+        tenants.retain(|user_id| !user_id.is_empty());
+        Ok(tenants)
+    }
+
+    /// Counts the jobs waiting at `priority`, in the shared queue and every
+    /// tenant's queue.
+    async fn priority_length(&mut self, priority: u8) -> Result<usize> {
+        let tenants = self.waiting_tenants(priority).await?;
+        let mut total: usize = self.conn
+            .llen(self.keys.queue_for_priority(priority))
+            .await
+            .context("Failed to get queue length")?;
+        for user_id in tenants {
+            let len: usize = self.conn
+                .llen(self.keys.tenant_queue(priority, &user_id))
+                .await
+                .context("Failed to get queue length")?;
+            total += len;
+        }
+        Ok(total)
+    }
+
+    /// Returns `job`, already serialized to `job_json`, as its queue entry
+    /// in the configured format.
+    fn payload<'a>(&self, job: &PdfExportJob, job_json: &'a str) -> Result<Cow<'a, [u8]>> {
+        match self.config.serialization {
+            Serialization::Json => Ok(Cow::Borrowed(job_json.as_bytes())),
+            serialization => serialization
+                .encode(job)
+                .map(Cow::Owned)
+                .context("Failed to serialize job"),
+        }
+    }
+
+    /// Builds the transaction [`enqueue`](Self::enqueue) writes a job with:
+    /// the push of `payload` onto its queue (RPUSH for FIFO order) and its
+    /// status key, so a job is never queued without a status to poll.
+    fn write_pipeline(&self, job: &PdfExportJob, payload: &[u8], job_json: &str) -> redis::Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        self.push_ready(&mut pipe, job, payload);
+        pipe.set_ex(self.keys.status(&job.job_id), job_json, status_ttl(job))
+            .ignore();
+        pipe
+    }
+
+    /// Adds the commands pushing `payload` onto `job`'s ready queue to
+    /// `pipe`, giving its tenant a turn for fair scheduling.
+    fn push_ready(&self, pipe: &mut redis::Pipeline, job: &PdfExportJob, payload: &[u8]) {
+        pipe.rpush(self.keys.ready_queue(job), payload).ignore();
+        self.announce(pipe, job.effective_priority(), tenant(job).unwrap_or_default());
+    }
+
+    /// Adds the commands giving `tenant` (`''` for the shared queue) a turn
+    /// at `priority`, unless it already has one, and waking a worker blocked
+    /// in [`dequeue`](Self::dequeue), to `pipe`.
+    fn announce(&self, pipe: &mut redis::Pipeline, priority: u8, tenant: &str) {
+        pipe.cmd("ZADD")
+            .arg(self.keys.tenants(priority))
+            .arg("NX")
+            .arg(Utc::now().timestamp_millis())
+            .arg(tenant)
+            .ignore();
+        let notify = self.keys.notify(priority);
+        pipe.rpush(&notify, 1).ignore();
+        pipe.ltrim(&notify, 0, NOTIFY_BACKLOG - 1).ignore();
+    }
+}
+
+/// Runs `pipe`, the transaction writing a job, retrying connection errors
+/// up to [`ENQUEUE_ATTEMPTS`] times with exponential backoff.
+///
+/// A dropped connection can lose the reply to a transaction that did run,
+/// so before each retry the job's status key is read back: if it already
+/// holds `job_json`, the job was written and isn't pushed a second time.
+async fn write_job<C: ConnectionLike + Send>(
+    conn: &mut C,
+    pipe: &redis::Pipeline,
+    status_key: &str,
+    job_json: &str,
+) -> Result<()> {
+    let mut attempt = 1;
+    let mut backoff = ENQUEUE_RETRY_BACKOFF;
+    loop {
+        let result = async {
+            if attempt > 1 {
+                let stored: Option<String> = conn
+                    .get(status_key)
+                    .await
+                    .context("Failed to read job status")?;
+                if stored.as_deref() == Some(job_json) {
+                    return Ok(());
+                }
+            }
+            pipe.query_async::<_, ()>(conn)
+                .await
+                .context("Failed to push job to queue")
+        }
+        .await;
+
+        match result {
+            Err(e) if e.is_connection_error() && attempt < ENQUEUE_ATTEMPTS => {
+                warn!(
+                    "Failed to enqueue job (attempt {}/{}), retrying in {:?}: {}",
+                    attempt, ENQUEUE_ATTEMPTS, backoff, e
+                );
+                tokio::time::sleep(backoff).await;
+                attempt += 1;
+                backoff *= 2;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Returns the tenant a job is scheduled under: its non-empty `user_id`.
+fn tenant(job: &PdfExportJob) -> Option<&str> {
+    job.metadata
+        .user_id
+        .as_deref()
+        .filter(|user_id| !user_id.is_empty())
+}
+
+/// The queue operations that code enqueueing or processing jobs builds on.
+///
+/// Implemented by [`JobQueue`] and, with the `testing` feature, by the
+/// in-memory `testing::MockQueue`, so such code can be written against
+/// `impl Queue` and unit-tested without Redis.
+pub trait Queue: Send {
+    /// Adds a job to the queue. See [`JobQueue::enqueue`].
+    fn enqueue(&mut self, job: &PdfExportJob) -> impl Future<Output = Result<String>> + Send;
+
+    /// Takes the next job off the queue, or `None` if there is none. See
+    /// [`JobQueue::dequeue`].
+    fn dequeue(&mut self) -> impl Future<Output = Result<Option<PdfExportJob>>> + Send;
+
+    /// Returns the stored status of a job. See [`JobQueue::get_status`].
+    fn get_status(
+        &mut self,
+        job_id: &str,
+    ) -> impl Future<Output = Result<Option<PdfExportJob>>> + Send;
+
+    /// Stores a job's status. See [`JobQueue::update_status`].
+    fn update_status(&mut self, job: &PdfExportJob) -> impl Future<Output = Result<()>> + Send;
+}
+
+impl Queue for JobQueue {
+    async fn enqueue(&mut self, job: &PdfExportJob) -> Result<String> {
+        JobQueue::enqueue(self, job).await
+    }
+
+    async fn dequeue(&mut self) -> Result<Option<PdfExportJob>> {
+        JobQueue::dequeue(self).await
+    }
+
+    async fn get_status(&mut self, job_id: &str) -> Result<Option<PdfExportJob>> {
+        JobQueue::get_status(self, job_id).await
+    }
+
+    async fn update_status(&mut self, job: &PdfExportJob) -> Result<()> {
+        JobQueue::update_status(self, job).await
+    }
+}
+
+/// Refuses jobs a worker should never be handed: a wrong output
+/// extension, too many pages or an oversized SVG.
+pub(crate) fn check_job(config: &QueueConfig, job: &PdfExportJob) -> Result<()> {
+    job.check_output_extension()
+        .map_err(|source| QueueError::InvalidOutputPath {
+            job_id: job.job_id.clone(),
+            source,
+        })?;
+    job.check_page_count(config.max_pages)
+        .and_then(|()| job.check_svg_size(config.max_svg_bytes))
+        .map_err(|source| QueueError::InvalidJob {
+            job_id: job.job_id.clone(),
+            source,
+        })
+}
+
+/// Returns `job` as it is stored in Redis: stamped with its content
+/// checksum, given the priority of its export scope if it has none, and
+/// with the current trace context if it doesn't carry one so the worker's
+/// span joins the trace of the request that enqueued it.
+pub(crate) fn prepare_for_enqueue<'a>(
+    config: &QueueConfig,
+    job: &'a PdfExportJob,
+) -> Cow<'a, PdfExportJob> {
+    let checksum = job.content_checksum();
+    let carrier = match job.metadata.trace_context {
+        Some(_) => HashMap::new(),
+        None => telemetry::current_trace_context(),
+    };
+    if job.checksum.as_ref() == Some(&checksum) && job.priority.is_some() && carrier.is_empty() {
+        return Cow::Borrowed(job);
+    }
+
+    let mut job = job.clone();
+    job.checksum = Some(checksum);
+    job.priority = job
+        .priority
+        .or_else(|| Some(config.scope_priorities.priority_for(&job.metadata.export_scope)));
+    if !carrier.is_empty() {
+        job.metadata.trace_context = Some(carrier);
+    }
+    Cow::Owned(job)
+}
+
+/// Returns how long `job`'s status and idempotency keys live, in seconds. A
+/// zero TTL, which Redis would reject, falls back to the default.
+fn status_ttl(job: &PdfExportJob) -> u64 {
+    job.status_ttl
+        .filter(|&ttl| ttl > 0)
+        .unwrap_or(JOB_TTL_SECONDS)
+}
+
+/// Returns the order in which `keys`, given in priority order, are checked
+/// for the next dequeue.
+fn service_order<T>(mut keys: [T; 3], dequeue_count: u64) -> impl Iterator<Item = T> {
+    let starving = dequeue_count % STARVATION_INTERVAL == STARVATION_INTERVAL - 1;
+    if starving {
+        keys.reverse();
+    }
+    keys.into_iter()
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::job::JobMetadata;
+    use crate::job::{ArtboardSvg, JobMetadata, JobStatus, PRIORITY_NORMAL};
+
+    #[test]
+    fn test_queue_error_variants() {
+        let redis_error: redis::RedisResult<()> =
+            Err(redis::RedisError::from((redis::ErrorKind::IoError, "connection reset")));
+        let err = redis_error.context("Failed to push job to queue").unwrap_err();
+        assert!(matches!(err, QueueError::Redis { .. }));
+        assert!(err.to_string().starts_with("Failed to push job to queue"));
+        assert!(err.is_connection_error());
+
+        let redis_error: redis::RedisResult<()> =
+            Err(redis::RedisError::from((redis::ErrorKind::TypeError, "WRONGTYPE")));
+        let err = redis_error.context("Failed to read job status").unwrap_err();
+        assert!(!err.is_connection_error());
+
+        let err = serde_json::from_str::<PdfExportJob>("{")
+            .context("Failed to deserialize job")
+            .unwrap_err();
+        assert!(matches!(err, QueueError::Serialization { .. }));
+        assert!(!err.is_connection_error());
+
+        let err = QueueError::CorruptedPayload {
+            job_id: None,
+            reason: "EOF while parsing".to_string(),
+        };
+        assert_eq!(err.to_string(), "Corrupted job payload (unknown job): EOF while parsing");
+        assert!(!err.is_connection_error());
+    }
+
+    /// Connection that fails its next calls with an I/O error, either
+    /// before sending them or after they ran (a lost reply).
+    struct FlakyConnection {
+        inner: ConnectionManager,
+        fail_before: usize,
+        lose_replies: usize,
+    }
+
+    impl FlakyConnection {
+        fn dropped() -> redis::RedisError {
+            redis::RedisError::from((redis::ErrorKind::IoError, "connection dropped"))
+        }
+    }
+
+    impl ConnectionLike for FlakyConnection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a redis::Cmd) -> redis::RedisFuture<'a, redis::Value> {
+            Box::pin(async move {
+                if self.fail_before > 0 {
+                    self.fail_before -= 1;
+                    return Err(Self::dropped());
+                }
+                self.inner.req_packed_command(cmd).await
+            })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            cmd: &'a redis::Pipeline,
+            offset: usize,
+            count: usize,
+        ) -> redis::RedisFuture<'a, Vec<redis::Value>> {
+            Box::pin(async move {
+                if self.fail_before > 0 {
+                    self.fail_before -= 1;
+                    return Err(Self::dropped());
+                }
+                let reply = self.inner.req_packed_commands(cmd, offset, count).await?;
+                if self.lose_replies > 0 {
+                    self.lose_replies -= 1;
+                    return Err(Self::dropped());
+                }
+                Ok(reply)
+            })
+        }
+
+        fn get_db(&self) -> i64 {
+            self.inner.get_db()
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_retries_connection_errors() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn.clone())
+            .with_namespace(KeyNamespace::new(format!("test-{}:flaky", uuid::Uuid::new_v4())));
+        let job = |n: u32| {
+            PdfExportJob::builder()
+                .document_id(format!("doc-flaky-{n}"))
+                .svg("<svg></svg>")
+                .output_path("/tmp/flaky.pdf")
+                .build()
+                .unwrap()
+        };
+        let write = |flaky: FlakyConnection, queue: &JobQueue, job: &PdfExportJob| {
+            let job_json = serde_json::to_string(&prepare_for_enqueue(queue.config(), job)).unwrap();
+            let pipe = queue.write_pipeline(job, job_json.as_bytes(), &job_json);
+            let status_key = queue.keys.status(&job.job_id);
+            async move {
+                let mut flaky = flaky;
+                write_job(&mut flaky, &pipe, &status_key, &job_json).await
+            }
+        };
+
+        // Two failed attempts, then the job and its status land together
+        let first = job(1);
+        let flaky = FlakyConnection { inner: conn.clone(), fail_before: 2, lose_replies: 0 };
+        write(flaky, &queue, &first).await.unwrap();
+        assert_eq!(queue.queue_length().await.unwrap(), 1);
+        let status = queue.get_status(&first.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Queued);
+
+        // A transaction whose reply was lost isn't pushed twice
+        let second = job(2);
+        let flaky = FlakyConnection { inner: conn.clone(), fail_before: 0, lose_replies: 1 };
+        write(flaky, &queue, &second).await.unwrap();
+        assert_eq!(queue.queue_length().await.unwrap(), 2);
+        assert!(queue.get_status(&second.job_id).await.unwrap().is_some());
+
+        // Attempts are bounded
+        let third = job(3);
+        let flaky = FlakyConnection { inner: conn.clone(), fail_before: usize::MAX, lose_replies: 0 };
+        let err = write(flaky, &queue, &third).await.unwrap_err();
+        assert!(err.is_connection_error());
+        assert!(queue.get_status(&third.job_id).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_prepare_for_enqueue_stamps_checksum() {
+        let job = PdfExportJob::new(
+            "doc-checksum".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/checksum.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let config = QueueConfig::default();
+        let prepared = prepare_for_enqueue(&config, &job);
+        assert_eq!(prepared.checksum, Some(job.content_checksum()));
+
+        // Already stamped jobs are stored as-is
+        let stamped = prepared.into_owned();
+        assert!(matches!(prepare_for_enqueue(&config, &stamped), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_queue_key_for_priority() {
+        let keys = KeyNamespace::default();
+        assert_eq!(keys.queue_for_priority(PRIORITY_LOW), "wiretuner:export:pdf:queue:low");
+        assert_eq!(keys.queue_for_priority(PRIORITY_NORMAL), "wiretuner:export:pdf:queue");
+        assert_eq!(keys.queue_for_priority(PRIORITY_HIGH), "wiretuner:export:pdf:queue:high");
+        assert_eq!(keys.queue_for_priority(u8::MAX), "wiretuner:export:pdf:queue:high");
+    }
+
+    #[test]
+    fn test_key_namespace_default_names_unchanged() {
+        let keys = KeyNamespace::default();
+        assert_eq!(keys.delayed(), "wiretuner:export:pdf:delayed");
+        assert_eq!(keys.status("job-1"), "wiretuner:export:pdf:status:job-1");
+        assert_eq!(keys.idempotency("idem-1"), "wiretuner:export:pdf:idem:idem-1");
+        assert_eq!(keys.cancel("job-1"), "wiretuner:export:pdf:cancel:job-1");
+        assert_eq!(keys.processing("default"), "wiretuner:export:pdf:processing:default");
+        assert_eq!(keys.dead_letter(), "wiretuner:export:pdf:dead");
+        assert_eq!(keys.events(), "wiretuner:export:pdf:events");
+        assert_eq!(keys.paused(), "wiretuner:export:pdf:paused");
+
+        assert_eq!(keys.tenant_queue(PRIORITY_HIGH, "alice"), "wiretuner:export:pdf:queue:high:tenant:alice");
+        assert_eq!(keys.tenants(PRIORITY_NORMAL), "wiretuner:export:pdf:queue:tenants");
+        assert_eq!(keys.notify(PRIORITY_LOW), "wiretuner:export:pdf:queue:low:notify");
+
+        let staging = KeyNamespace::new("staging:export:pdf");
+        assert_eq!(staging.queue(), "staging:export:pdf:queue");
+        assert_eq!(staging.status("job-1"), "staging:export:pdf:status:job-1");
+    }
+
+    #[test]
+    fn test_service_order_bounds_starvation() {
+        let orders: Vec<Vec<u8>> = (0..STARVATION_INTERVAL)
+            .map(|n| service_order(PRIORITIES, n).collect())
+            .collect();
+
+        // Mostly high first...
+        assert_eq!(orders[0], PRIORITIES.to_vec());
+        // ...but once per interval, low priority gets first pick
+        let low_first = orders
+            .iter()
+            .filter(|o| o[0] == PRIORITY_LOW)
+            .count();
+        assert_eq!(low_first, 1);
+    }
+
+    // Note: These tests require a running Redis instance.
+    // Run with: docker run -d -p 6379:6379 redis:7-alpine
+    // Skip in CI: cargo test --lib -- --skip queue::tests
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_dequeue() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let job = PdfExportJob::new(
+            "doc-123".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                artboard_ids: vec!["ab-1".to_string()],
+                export_scope: "current".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+
+        // Enqueue
+        queue.enqueue(&job).await.unwrap();
+
+        // Dequeue
+        let dequeued = queue.dequeue().await.unwrap();
+        assert!(dequeued.is_some());
+
+        let dequeued_job = dequeued.unwrap();
+        assert_eq!(dequeued_job.job_id, job.job_id);
+        assert_eq!(dequeued_job.status, JobStatus::Queued);
+
+        // Acknowledge
+        assert!(queue.ack(&job.job_id).await.unwrap());
+    }
+
+    #[test]
+    fn test_queue_config_defaults() {
+        let config = QueueConfig::default();
+        assert_eq!(config.blpop_timeout, Duration::from_secs(5));
+        assert_eq!(config.error_backoff, Duration::from_secs(5));
+        assert_eq!(config.max_svg_bytes, DEFAULT_MAX_SVG_BYTES);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_queue_with_custom_config() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let config = QueueConfig {
+            blpop_timeout: Duration::from_millis(250),
+            error_backoff: Duration::from_secs(1),
+            ..QueueConfig::default()
+        };
+        let mut queue = JobQueue::new(conn)
+            .with_consumer_id("test-config")
+            .with_config(config.clone());
+
+        assert_eq!(*queue.config(), config);
+
+        // An empty queue returns after roughly the configured timeout
+        let started = Instant::now();
+        let _ = queue.dequeue().await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_reap_dead_workers_requeues_their_jobs() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let namespace = KeyNamespace::new(format!("test-{}:reap", uuid::Uuid::new_v4()));
+        let worker = |consumer_id: &str| {
+            JobQueue::new(conn.clone())
+                .with_namespace(namespace.clone())
+                .with_consumer_id(consumer_id)
+                .with_config(QueueConfig {
+                    blpop_timeout: Duration::from_secs(1),
+                    ..QueueConfig::default()
+                })
+        };
+        let mut hung = worker("hung");
+        let mut alive = worker("alive");
+        let mut reaper = worker("reaper");
+
+        // Each worker takes a job and starts it, heartbeating as it goes
+        for (queue, document_id) in [(&mut hung, "doc-hung"), (&mut alive, "doc-alive")] {
+            let job = PdfExportJob::builder()
+                .document_id(document_id)
+                .svg("<svg></svg>")
+                .output_path("/tmp/reap.pdf")
+                .priority(PRIORITY_HIGH)
+                .build()
+                .unwrap();
+            queue.enqueue(&job).await.unwrap();
+            let mut job = queue.dequeue().await.unwrap().unwrap();
+            job.worker_id = Some(queue.consumer_id().to_string());
+            job.start_processing();
+            queue.update_status(&job).await.unwrap();
+        }
+        hung.heartbeat(Duration::from_secs(1)).await.unwrap();
+        alive.heartbeat(Duration::from_secs(30)).await.unwrap();
+
+        // Only the worker whose heartbeat expired loses its job, once it
+        // has been missing for the grace period
+        let mut missing = MissingHeartbeats::default();
+        let grace = Duration::from_secs(1);
+        assert_eq!(reaper.reap_dead_workers(&mut missing, grace).await.unwrap(), 0);
+        tokio::time::sleep(Duration::from_millis(2100)).await;
+        assert_eq!(reaper.reap_dead_workers(&mut missing, grace).await.unwrap(), 0);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_eq!(reaper.reap_dead_workers(&mut missing, grace).await.unwrap(), 1);
+        assert_eq!(reaper.reap_dead_workers(&mut missing, grace).await.unwrap(), 0);
+
+        // Back on its own priority queue, not the normal one
+        assert_eq!(
+            reaper.conn.llen::<_, usize>(namespace.high_priority_queue()).await.unwrap(),
+            1
+        );
+        let requeued = reaper.dequeue().await.unwrap().unwrap();
+        assert_eq!(requeued.document_id, "doc-hung");
+        let status = reaper.get_status(&requeued.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Queued);
+        assert_eq!(status.worker_id, None);
+        assert!(reaper.dequeue().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_try_dequeue_returns_immediately() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:try", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-try");
+
+        // An empty queue doesn't wait out the blocking timeout
+        let started = Instant::now();
+        assert!(queue.try_dequeue().await.unwrap().is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let job = PdfExportJob::builder()
+            .document_id("doc-try")
+            .svg("<svg></svg>")
+            .output_path("/tmp/try.pdf")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+
+        let dequeued = queue.try_dequeue().await.unwrap().unwrap();
+        assert_eq!(dequeued.job_id, job.job_id);
+        queue.ack(&job.job_id).await.unwrap();
+        assert!(queue.try_dequeue().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_rejects_oversized_svg() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_config(QueueConfig {
+            max_svg_bytes: 1024,
+            ..QueueConfig::default()
+        });
+
+        let job = PdfExportJob::new(
+            "doc-oversized".to_string(),
+            format!("<svg>{}</svg>", "<g/>".repeat(1024)),
+            "/tmp/oversized.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let err = queue.enqueue(&job).await.unwrap_err();
+        assert!(matches!(err, QueueError::InvalidJob { .. }));
+        assert!(err.to_string().contains("SVG too large"));
+        assert!(queue.get_status(&job.job_id).await.unwrap().is_none());
+
+        let err = queue.enqueue_batch(std::slice::from_ref(&job)).await.unwrap_err();
+        assert!(matches!(err, QueueError::InvalidJob { .. }));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_rejects_too_many_pages() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:pages", uuid::Uuid::new_v4())))
+            .with_config(QueueConfig {
+                max_pages: 2,
+                ..QueueConfig::default()
+            });
+
+        let mut job = PdfExportJob::new(
+            "doc-many-pages".to_string(),
+            String::new(),
+            "/tmp/many-pages.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.artboards = (0..3)
+            .map(|i| ArtboardSvg {
+                artboard_id: format!("ab-{}", i),
+                svg_content: "<svg/>".to_string(),
+                name: None,
+            })
+            .collect();
+
+        let err = queue.enqueue(&job).await.unwrap_err();
+        assert!(matches!(
+            err,
+            QueueError::InvalidJob {
+                source: ConvertError::TooManyPages { pages: 3, limit: 2 },
+                ..
+            }
+        ));
+        assert!(err.to_string().contains("Too many pages"));
+        assert!(queue.get_status(&job.job_id).await.unwrap().is_none());
+
+        job.artboards.pop();
+        queue.enqueue(&job).await.unwrap();
+        assert_eq!(queue.try_dequeue().await.unwrap().unwrap().job_id, job.job_id);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_oldest_job_age() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:age", uuid::Uuid::new_v4())));
+        assert_eq!(queue.oldest_job_age().await.unwrap(), None);
+
+        let job = PdfExportJob::new(
+            "doc-age".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/age.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+
+        let age = queue.oldest_job_age().await.unwrap().unwrap();
+        assert!(age >= Duration::from_millis(1200), "age {:?}", age);
+        assert!(age < Duration::from_secs(10), "age {:?}", age);
+
+        queue.try_dequeue().await.unwrap().unwrap();
+        queue.ack(&job.job_id).await.unwrap();
+        assert_eq!(queue.oldest_job_age().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_dlq_replays_are_capped() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:replay", uuid::Uuid::new_v4())))
+            .with_config(QueueConfig {
+                max_dlq_replays: 2,
+                ..QueueConfig::default()
+            });
+
+        // A payload whose checksum will never match goes straight to the DLQ
+        let mut job = PdfExportJob::new(
+            "doc-replay".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/replay.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.checksum = Some("0".repeat(64));
+        let ready_queue = queue.namespace().ready_queue(&job);
+        queue.conn
+            .rpush::<_, _, ()>(&ready_queue, serde_json::to_string(&job).unwrap())
+            .await
+            .unwrap();
+        async fn dead_letter(queue: &mut JobQueue, job_id: &str) {
+            let err = queue.try_dequeue().await.unwrap_err();
+            assert!(matches!(err, QueueError::CorruptedPayload { job_id: Some(ref id), .. } if id == job_id));
+        }
+        dead_letter(&mut queue, &job.job_id).await;
+
+        assert!(!queue.requeue_from_dlq("no-such-job", false).await.unwrap());
+        for replays in 1..=2 {
+            assert!(queue.requeue_from_dlq(&job.job_id, false).await.unwrap());
+            let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+            assert_eq!(status.status, JobStatus::Queued);
+            assert_eq!(status.dlq_replay_count, replays);
+            assert_eq!(queue.dlq_length().await.unwrap(), 0);
+            dead_letter(&mut queue, &job.job_id).await;
+        }
+
+        let err = queue.requeue_from_dlq(&job.job_id, false).await.unwrap_err();
+        assert!(matches!(
+            err,
+            QueueError::ReplayLimitExceeded { replays: 2, limit: 2, .. }
+        ));
+        assert_eq!(queue.dlq_length().await.unwrap(), 1);
+
+        // Forcing the replay bypasses the limit
+        assert!(queue.requeue_from_dlq(&job.job_id, true).await.unwrap());
+        assert_eq!(queue.dlq_length().await.unwrap(), 0);
+        let replayed = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(replayed.dlq_replay_count, 3);
+        assert!(replayed.error.is_none());
+
+        // Racing replays queue the job once
+        dead_letter(&mut queue, &job.job_id).await;
+        let (mut first, mut second) = (queue.clone(), queue.clone());
+        let (a, b) = tokio::join!(
+            first.requeue_from_dlq(&job.job_id, true),
+            second.requeue_from_dlq(&job.job_id, true)
+        );
+        assert!(a.unwrap() ^ b.unwrap());
+        assert_eq!(queue.conn.llen::<_, usize>(&ready_queue).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_queue_stats() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-stats");
+        let before = queue.queue_stats().await.unwrap();
+
+        let new_job = |priority| {
+            PdfExportJob::new(
+                "doc-stats".to_string(),
+                "<svg></svg>".to_string(),
+                "/tmp/stats.pdf".to_string(),
+                JobMetadata {
+                    export_scope: "all".to_string(),
+                    client_version: "0.1.0".to_string(),
+                    ..Default::default()
+                },
+            )
+            .with_priority(priority)
+        };
+        queue.enqueue(&new_job(PRIORITY_HIGH)).await.unwrap();
+        queue.enqueue(&new_job(PRIORITY_NORMAL)).await.unwrap();
+        queue.enqueue(&new_job(PRIORITY_LOW)).await.unwrap();
+        queue.enqueue(&new_job(PRIORITY_LOW)).await.unwrap();
+
+        // Move the high-priority job into processing
+        let mut processing = queue.dequeue().await.unwrap().unwrap();
+        processing.start_processing();
+        queue.update_status(&processing).await.unwrap();
+
+        let after = queue.queue_stats().await.unwrap();
+        assert_eq!(after.high, before.high);
+        assert_eq!(after.normal, before.normal + 1);
+        assert_eq!(after.low, before.low + 2);
+        assert_eq!(after.processing, before.processing + 1);
+        let count = |stats: &QueueStats, status| stats.by_status.get(&status).copied().unwrap_or(0);
+        assert_eq!(count(&after, JobStatus::Queued), count(&before, JobStatus::Queued) + 3);
+        assert_eq!(count(&after, JobStatus::Processing), count(&before, JobStatus::Processing) + 1);
+
+        queue.ack(&processing.job_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_high_priority_dequeued_first() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let metadata = JobMetadata {
+            export_scope: "all".to_string(),
+            client_version: "0.1.0".to_string(),
+            ..Default::default()
+        };
+        let normal = PdfExportJob::new(
+            "doc-batch".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/batch.pdf".to_string(),
+            metadata.clone(),
+        );
+        let urgent = PdfExportJob::new(
+            "doc-urgent".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/urgent.pdf".to_string(),
+            metadata,
+        )
+        .with_priority(PRIORITY_HIGH);
+
+        queue.enqueue(&normal).await.unwrap();
+        queue.enqueue(&urgent).await.unwrap();
+
+        let first = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(first.job_id, urgent.job_id);
+        let second = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(second.job_id, normal.job_id);
+
+        queue.ack(&first.job_id).await.unwrap();
+        queue.ack(&second.job_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_current_scope_dequeued_before_earlier_batch() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:scope", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-scope");
+
+        let job = |document_id: &str, scope: &str| {
+            PdfExportJob::builder()
+                .document_id(document_id)
+                .svg("<svg></svg>")
+                .output_path("/tmp/scope.pdf")
+                .export_scope(scope)
+                .build()
+                .unwrap()
+        };
+        let batch = job("doc-batch", "all");
+        let interactive = job("doc-interactive", "current");
+        queue.enqueue(&batch).await.unwrap();
+        queue.enqueue(&interactive).await.unwrap();
+
+        // Neither job set a priority; the interactive one still goes first
+        let first = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(first.job_id, interactive.job_id);
+        assert_eq!(first.priority, Some(PRIORITY_HIGH));
+        let second = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(second.job_id, batch.job_id);
+        assert_eq!(second.priority, Some(PRIORITY_NORMAL));
+    }
+
+    #[test]
+    fn test_scope_priorities() {
+        let defaults = ScopePriorities::default();
+        assert_eq!(defaults.priority_for("current"), PRIORITY_HIGH);
+        assert_eq!(defaults.priority_for("all"), PRIORITY_NORMAL);
+        assert_eq!(defaults.priority_for("selected"), PRIORITY_NORMAL);
+
+        let parsed: ScopePriorities = "current=high, all=low,selected=2".parse().unwrap();
+        assert_eq!(
+            parsed,
+            ScopePriorities::empty()
+                .with_scope("current", PRIORITY_HIGH)
+                .with_scope("all", PRIORITY_LOW)
+                .with_scope("selected", PRIORITY_HIGH)
+        );
+        assert_eq!("".parse(), Ok(ScopePriorities::empty()));
+        assert!("current".parse::<ScopePriorities>().is_err());
+        assert!("current=urgent".parse::<ScopePriorities>().is_err());
+
+        // Only jobs without a priority are given their scope's
+        let config = QueueConfig {
+            scope_priorities: parsed,
+            ..QueueConfig::default()
+        };
+        let job = PdfExportJob::builder()
+            .document_id("doc-scope")
+            .svg("<svg></svg>")
+            .output_path("/tmp/scope.pdf")
+            .export_scope("all")
+            .build()
+            .unwrap();
+        assert_eq!(job.priority, None);
+        assert_eq!(prepare_for_enqueue(&config, &job).priority, Some(PRIORITY_LOW));
+        let explicit = job.with_priority(PRIORITY_HIGH);
+        assert_eq!(prepare_for_enqueue(&config, &explicit).priority, Some(PRIORITY_HIGH));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_retry_is_delayed_until_backoff_elapses() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let mut job = PdfExportJob::new(
+            "doc-retry".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/retry.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.mark_failed("transient".to_string());
+        assert!(queue.retry_job(job.clone()).await.unwrap());
+
+        // Not ready yet: the first backoff is at least one second
+        assert_eq!(queue.promote_delayed().await.unwrap(), 0);
+
+        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        let retried = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(retried.job_id, job.job_id);
+        assert_eq!(retried.retry_count, 1);
+        queue.ack(&retried.job_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_scheduled_job_waits_until_due() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_consumer_id("test-scheduled")
+            .with_config(QueueConfig {
+                blpop_timeout: Duration::from_millis(500),
+                ..QueueConfig::default()
+            });
+
+        let job = PdfExportJob::new(
+            "doc-scheduled".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/scheduled.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+        .with_priority(PRIORITY_HIGH);
+        let run_at = chrono::Utc::now() + chrono::Duration::seconds(3);
+        queue.enqueue_at(&job, run_at).await.unwrap();
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Queued);
+        assert_eq!(status.next_attempt_at, Some(run_at));
+
+        // Not handed out before it is due
+        while chrono::Utc::now() < run_at - chrono::Duration::milliseconds(600) {
+            if let Some(other) = queue.dequeue().await.unwrap() {
+                assert_ne!(other.job_id, job.job_id, "scheduled job ran early");
+                queue.ack(&other.job_id).await.unwrap();
+            }
+        }
 
-    // Note: These tests require a running Redis instance.
-    // Run with: docker run -d -p 6379:6379 redis:7-alpine
-    // Skip in CI: cargo test --lib -- --skip queue::tests
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(dequeued.job_id, job.job_id);
+        assert!(chrono::Utc::now() >= run_at);
+        queue.ack(&dequeued.job_id).await.unwrap();
+    }
 
     #[tokio::test]
     #[ignore]
-    async fn test_enqueue_dequeue() {
+    async fn test_cancel_queued_job_removes_it() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-cancel");
+
+        let job = PdfExportJob::new(
+            "doc-cancel".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/cancel.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+        .with_priority(PRIORITY_LOW);
+        queue.enqueue(&job).await.unwrap();
+
+        assert!(queue.cancel_job(&job.job_id).await.unwrap());
+        assert!(queue.is_cancelled(&job.job_id).await.unwrap());
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Cancelled);
+        let queued: Vec<String> = queue.conn.lrange(queue.namespace().low_priority_queue(), 0, -1).await.unwrap();
+        assert!(!queued.iter().any(|j| j.contains(&job.job_id)));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_message_pack_queue_round_trip() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let namespace = KeyNamespace::new(format!("test-{}:msgpack", uuid::Uuid::new_v4()));
+        let config = QueueConfig {
+            blpop_timeout: Duration::from_millis(100),
+            serialization: Serialization::MessagePack,
+            ..QueueConfig::default()
+        };
+        let mut queue = JobQueue::new(conn.clone())
+            .with_namespace(namespace.clone())
+            .with_consumer_id("test-msgpack")
+            .with_config(config);
+        let mut json_queue = JobQueue::new(conn).with_namespace(namespace.clone());
+        let job = |n: u32| {
+            let mut job = PdfExportJob::builder()
+                .document_id(format!("doc-msgpack-{n}"))
+                .svg("<svg></svg>")
+                .output_path("/tmp/msgpack.pdf")
+                .build()
+                .unwrap();
+            job.metadata.trace_context = Some(HashMap::from([
+                ("traceparent".to_string(), "00-4bf92f3577b34da6a3ce929b0e0e4736-00f067aa0ba902b7-01".to_string()),
+                ("tracestate".to_string(), "vendor=value".to_string()),
+            ]));
+            job
+        };
+
+        // Queue entries are MessagePack; status keys stay JSON
+        let cancelled = job(0);
+        queue.enqueue(&cancelled).await.unwrap();
+        let queued: Vec<Vec<u8>> = queue.conn.lrange(namespace.queue(), 0, -1).await.unwrap();
+        assert_eq!(payload::detect(&queued[0]), Serialization::MessagePack);
+        assert_eq!(queue.get_status(&cancelled.job_id).await.unwrap().unwrap().job_id, cancelled.job_id);
+
+        // Cancellation finds the entry from the JSON status
+        assert!(queue.cancel_job(&cancelled.job_id).await.unwrap());
+        assert_eq!(queue.queue_length().await.unwrap(), 0);
+
+        // Whichever format the producer wrote, and however it laid it out
+        let foreign = job(3);
+        json_queue.enqueue(&foreign).await.unwrap();
+        assert!(queue.cancel_job(&foreign.job_id).await.unwrap());
+        let reformatted = job(4);
+        queue.enqueue(&reformatted).await.unwrap();
+        queue.conn.del::<_, ()>(namespace.queue()).await.unwrap();
+        queue.conn
+            .rpush::<_, _, ()>(namespace.queue(), serde_json::to_string_pretty(&reformatted).unwrap())
+            .await
+            .unwrap();
+        assert!(json_queue.cancel_job(&reformatted.job_id).await.unwrap());
+        assert_eq!(queue.queue_length().await.unwrap(), 0);
+
+        // Either format is dequeued, whichever the queue writes
+        let packed = job(1);
+        let plain = job(2);
+        queue.enqueue(&packed).await.unwrap();
+        json_queue.enqueue(&plain).await.unwrap();
+        let first = queue.dequeue().await.unwrap().unwrap();
+        let second = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(first.job_id, packed.job_id);
+        assert_eq!(first.metadata.trace_context, packed.metadata.trace_context);
+        assert_eq!(second.job_id, plain.job_id);
+        assert!(queue.ack(&first.job_id).await.unwrap());
+        assert!(queue.ack(&second.job_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cancel_after_dequeue_sets_flag_only() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-cancel-race");
+
+        let job = PdfExportJob::new(
+            "doc-cancel-race".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/cancel-race.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+        .with_priority(PRIORITY_HIGH);
+        queue.enqueue(&job).await.unwrap();
+
+        // A worker takes the job before the cancel reaches the queue, so
+        // there is nothing left to remove and the flag must be honored
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(dequeued.job_id, job.job_id);
+
+        assert!(!queue.cancel_job(&job.job_id).await.unwrap());
+        assert!(queue.is_cancelled(&job.job_id).await.unwrap());
+        queue.ack(&job.job_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_progress_update() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let mut job = PdfExportJob::new(
+            "doc-progress".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/progress.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        job.start_processing();
+        queue.update_status(&job).await.unwrap();
+
+        queue.update_progress(&job.job_id, 50).await.unwrap();
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.progress, Some(50));
+        assert_eq!(status.status, JobStatus::Processing);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_idempotent_enqueue() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let idempotency_key = uuid::Uuid::new_v4().to_string();
+        let new_job = || {
+            PdfExportJob::new(
+                "doc-idem".to_string(),
+                "<svg></svg>".to_string(),
+                "/tmp/idem.pdf".to_string(),
+                JobMetadata {
+                    export_scope: "all".to_string(),
+                    client_version: "0.1.0".to_string(),
+                    idempotency_key: Some(idempotency_key.clone()),
+                    ..Default::default()
+                },
+            )
+            .with_priority(PRIORITY_LOW)
+        };
+        let first = new_job();
+        let second = new_job();
+
+        let first_id = queue.enqueue(&first).await.unwrap();
+        let second_id = queue.enqueue(&second).await.unwrap();
+        assert_eq!(first_id, first.job_id);
+        assert_eq!(second_id, first.job_id);
+
+        let queued: Vec<String> = queue.conn.lrange(queue.namespace().low_priority_queue(), 0, -1).await.unwrap();
+        assert_eq!(queued.iter().filter(|j| j.contains(&idempotency_key)).count(), 1);
+        assert!(queue.get_status(&second.job_id).await.unwrap().is_none());
+
+        queue.cancel_job(&first.job_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_batch() {
         let client = redis::Client::open("redis://127.0.0.1/").unwrap();
         let conn = ConnectionManager::new(client).await.unwrap();
         let mut queue = JobQueue::new(conn);
+        let initial = queue.queue_length().await.unwrap();
+
+        let jobs: Vec<PdfExportJob> = (0..20)
+            .map(|i| {
+                PdfExportJob::new(
+                    format!("doc-batch-{}", i),
+                    "<svg></svg>".to_string(),
+                    format!("/tmp/batch-{}.pdf", i),
+                    JobMetadata {
+                        export_scope: "all".to_string(),
+                        client_version: "0.1.0".to_string(),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        let ids = queue.enqueue_batch(&jobs).await.unwrap();
+        assert_eq!(ids.len(), 20);
+        assert_eq!(queue.queue_length().await.unwrap(), initial + 20);
+        assert!(queue.get_status(&ids[19]).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_crashed_worker_job_is_recovered() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let consumer_id = format!("test-crash-{}", uuid::Uuid::new_v4());
 
         let job = PdfExportJob::new(
-            "doc-123".to_string(),
+            "doc-crash".to_string(),
             "<svg></svg>".to_string(),
-            "/tmp/test.pdf".to_string(),
+            "/tmp/crash.pdf".to_string(),
             JobMetadata {
-                artboard_ids: vec!["ab-1".to_string()],
-                export_scope: "current".to_string(),
+                export_scope: "all".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
-        // Enqueue
-        queue.enqueue(&job).await.unwrap();
+        // A "worker" dequeues the job and dies before acknowledging it
+        {
+            let conn = ConnectionManager::new(client.clone()).await.unwrap();
+            let mut crashed = JobQueue::new(conn).with_consumer_id(&consumer_id);
+            crashed.enqueue(&job).await.unwrap();
+            let dequeued = crashed.dequeue().await.unwrap().unwrap();
+            assert_eq!(dequeued.job_id, job.job_id);
+        }
 
-        // Dequeue
-        let dequeued = queue.dequeue().await.unwrap();
-        assert!(dequeued.is_some());
+        // The restarted worker recovers it
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id(&consumer_id);
+        assert_eq!(queue.recover_processing(&consumer_id).await.unwrap(), 1);
 
-        let dequeued_job = dequeued.unwrap();
-        assert_eq!(dequeued_job.job_id, job.job_id);
-        assert_eq!(dequeued_job.status, JobStatus::Queued);
+        let recovered = queue.dequeue().await.unwrap().unwrap();
+        assert_eq!(recovered.job_id, job.job_id);
+        assert!(queue.ack(&job.job_id).await.unwrap());
     }
 
     #[tokio::test]
@@ -239,10 +3194,9 @@ mod tests {
             "<svg></svg>".to_string(),
             "/tmp/test2.pdf".to_string(),
             JobMetadata {
-                artboard_ids: vec![],
                 export_scope: "all".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
@@ -262,4 +3216,429 @@ mod tests {
         let updated_status = queue.get_status(&job.job_id).await.unwrap();
         assert_eq!(updated_status.unwrap().status, JobStatus::Processing);
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_subscribe_status_receives_updates() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client.clone()).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let mut job = PdfExportJob::new(
+            "doc-events".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/events.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        let mut events = Box::pin(queue.subscribe_status(&client, &job.job_id).await.unwrap());
+
+        job.start_processing();
+        queue.update_status(&job).await.unwrap();
+
+        let event = tokio::time::timeout(Duration::from_secs(5), events.next())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            event,
+            StatusEvent {
+                job_id: job.job_id.clone(),
+                status: JobStatus::Processing,
+                progress: Some(0),
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_get_statuses_preserves_order() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let mut job_ids = Vec::new();
+        for i in 0..3 {
+            let job = PdfExportJob::new(
+                format!("doc-bulk-{}", i),
+                "<svg></svg>".to_string(),
+                format!("/tmp/bulk-{}.pdf", i),
+                JobMetadata {
+                    export_scope: "all".to_string(),
+                    client_version: "0.1.0".to_string(),
+                    ..Default::default()
+                },
+            );
+            queue.enqueue(&job).await.unwrap();
+            job_ids.push(job.job_id);
+        }
+        // An unknown ID in the middle comes back as None
+        job_ids.insert(1, "no-such-job".to_string());
+
+        let statuses = queue.get_statuses(&job_ids).await.unwrap();
+        assert_eq!(statuses.len(), 4);
+        assert!(statuses[1].is_none());
+        for (job_id, status) in job_ids.iter().zip(&statuses) {
+            if let Some(job) = status {
+                assert_eq!(&job.job_id, job_id);
+            }
+        }
+        assert_eq!(statuses.iter().filter(|s| s.is_some()).count(), 3);
+    }
+
+    #[test]
+    fn test_status_ttl_defaults_to_24h() {
+        let job = PdfExportJob::new(
+            "doc-ttl".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/ttl.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(status_ttl(&job), JOB_TTL_SECONDS);
+        assert_eq!(status_ttl(&job.clone().with_status_ttl(300)), 300);
+        assert_eq!(status_ttl(&job.with_status_ttl(0)), JOB_TTL_SECONDS);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_applies_job_status_ttl() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let job = PdfExportJob::new(
+            "doc-preview".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/preview.pdf".to_string(),
+            JobMetadata {
+                export_scope: "current".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+        .with_status_ttl(120);
+        queue.enqueue(&job).await.unwrap();
+
+        let status_key = queue.namespace().status(&job.job_id);
+        let ttl: i64 = queue.conn.ttl(&status_key).await.unwrap();
+        assert!(ttl > 0 && ttl <= 120, "unexpected TTL {}", ttl);
+
+        // Status updates keep the job's TTL rather than resetting to 24h
+        queue.update_status(&job).await.unwrap();
+        let ttl: i64 = queue.conn.ttl(&status_key).await.unwrap();
+        assert!(ttl > 0 && ttl <= 120, "unexpected TTL {}", ttl);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_corrupted_payload_is_dead_lettered() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-corrupted");
+
+        let job = PdfExportJob::new(
+            "doc-corrupted".to_string(),
+            "<svg><rect width=\"10\" height=\"10\"/></svg>".to_string(),
+            "/tmp/corrupted.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        )
+        .with_priority(PRIORITY_HIGH);
+        queue.enqueue(&job).await.unwrap();
+
+        // Tamper with the SVG in the stored payload, keeping the checksum
+        let high_queue = queue.namespace().high_priority_queue();
+        let queued: Vec<String> = queue.conn.lrange(&high_queue, 0, -1).await.unwrap();
+        let index = queued.iter().position(|json| json.contains(&job.job_id)).unwrap();
+        let tampered = queued[index].replace("width", "w1dth");
+        queue.conn
+            .lset::<_, _, ()>(&high_queue, index as isize, &tampered)
+            .await
+            .unwrap();
+        // Make it the next job out
+        queue.conn.lrem::<_, _, ()>(&high_queue, 1, &tampered).await.unwrap();
+        queue.conn.lpush::<_, _, ()>(&high_queue, &tampered).await.unwrap();
+
+        let err = queue.dequeue().await.unwrap_err();
+        assert!(
+            matches!(&err, QueueError::CorruptedPayload { job_id: Some(id), .. } if *id == job.job_id),
+            "unexpected error: {}",
+            err
+        );
+
+        // Dead-lettered rather than left for recovery, and not retried
+        let dead: Vec<String> = queue.conn.lrange(queue.namespace().dead_letter(), 0, -1).await.unwrap();
+        assert!(dead.contains(&tampered));
+        assert!(!queue.ack(&job.job_id).await.unwrap());
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Failed);
+        assert_eq!(status.error_kind, Some(ErrorKind::Corrupted));
+
+        // A truncated payload takes the same path, without a job id
+        let truncated = &tampered[..tampered.len() / 2];
+        queue.conn.lpush::<_, _, ()>(&high_queue, truncated).await.unwrap();
+        let err = queue.dequeue().await.unwrap_err();
+        assert!(matches!(err, QueueError::CorruptedPayload { job_id: None, .. }));
+        let dead: Vec<String> = queue.conn.lrange(queue.namespace().dead_letter(), 0, -1).await.unwrap();
+        assert!(dead.iter().any(|json| json == truncated));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_dead_letter_list_is_trimmed() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:dlq", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-dlq")
+            .with_config(QueueConfig {
+                dead_letter_max_len: Some(3),
+                dead_letter_ttl: Some(Duration::from_secs(60)),
+                ..QueueConfig::default()
+            });
+        assert_eq!(queue.dlq_length().await.unwrap(), 0);
+
+        let queue_key = queue.namespace().queue();
+        for n in 0..5 {
+            let payload = format!("{{\"truncated\": {n}");
+            queue.conn.rpush::<_, _, ()>(&queue_key, &payload).await.unwrap();
+            let err = queue.try_dequeue().await.unwrap_err();
+            assert!(matches!(err, QueueError::CorruptedPayload { .. }), "unexpected error: {}", err);
+        }
+
+        // Only the newest payloads are kept
+        assert_eq!(queue.dlq_length().await.unwrap(), 3);
+        let dead: Vec<String> = queue.conn.lrange(queue.namespace().dead_letter(), 0, -1).await.unwrap();
+        assert_eq!(dead, ["{\"truncated\": 2", "{\"truncated\": 3", "{\"truncated\": 4"]);
+        let ttl: i64 = queue.conn.ttl(queue.namespace().dead_letter()).await.unwrap();
+        assert!(ttl > 0 && ttl <= 60, "unexpected TTL {}", ttl);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_enqueue_rate_limited_per_document() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_rate_limits(RateLimits::new(RateLimit::new(
+            3,
+            Duration::from_secs(1),
+        )));
+
+        let document_id = format!("doc-flood-{}", uuid::Uuid::new_v4());
+        let job = || {
+            PdfExportJob::new(
+                document_id.clone(),
+                "<svg></svg>".to_string(),
+                "/tmp/flood.pdf".to_string(),
+                JobMetadata {
+                    export_scope: "all".to_string(),
+                    client_version: "0.1.0".to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+
+        for _ in 0..3 {
+            queue.enqueue(&job()).await.unwrap();
+        }
+        let err = queue.enqueue(&job()).await.unwrap_err();
+        assert!(matches!(err, QueueError::RateLimited { .. }), "unexpected error: {}", err);
+
+        // Other documents have their own budget
+        let mut other = job();
+        other.document_id = format!("{}-other", document_id);
+        queue.enqueue(&other).await.unwrap();
+
+        // The window slides past the earlier enqueues
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        queue.enqueue(&job()).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_namespaces_are_isolated() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let run = uuid::Uuid::new_v4();
+        let mut staging = JobQueue::new(conn.clone())
+            .with_namespace(KeyNamespace::new(format!("test-{}:staging", run)))
+            .with_config(QueueConfig {
+                blpop_timeout: Duration::from_secs(1),
+                ..QueueConfig::default()
+            });
+        let mut production = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:production", run)))
+            .with_config(staging.config().clone());
+
+        let job = PdfExportJob::new(
+            "doc-isolated".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/isolated.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        let job_id = staging.enqueue(&job).await.unwrap();
+
+        assert_eq!(production.queue_length().await.unwrap(), 0);
+        assert!(production.get_status(&job_id).await.unwrap().is_none());
+        assert!(production.dequeue().await.unwrap().is_none());
+
+        assert_eq!(staging.queue_length().await.unwrap(), 1);
+        let dequeued = staging.dequeue().await.unwrap().unwrap();
+        assert_eq!(dequeued.job_id, job_id);
+        assert!(staging.ack(&job_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_dequeue_alternates_between_tenants() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:fair", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-fair");
+
+        let job = |user_id: &str| {
+            PdfExportJob::builder()
+                .document_id(format!("doc-{}", user_id))
+                .svg("<svg></svg>")
+                .output_path("/tmp/fair.pdf")
+                .user_id(user_id)
+                .build()
+                .unwrap()
+        };
+        // Alice's whole batch is queued before Bob's first job
+        for user_id in ["alice", "alice", "alice", "bob", "bob", "bob"] {
+            queue.enqueue(&job(user_id)).await.unwrap();
+        }
+        assert_eq!(queue.queue_length().await.unwrap(), 6);
+
+        let mut order = Vec::new();
+        while let Some(dequeued) = queue.dequeue().await.unwrap() {
+            order.push(dequeued.metadata.user_id.clone().unwrap());
+            queue.ack(&dequeued.job_id).await.unwrap();
+            if order.len() == 6 {
+                break;
+            }
+        }
+        assert_eq!(order, ["alice", "bob", "alice", "bob", "alice", "bob"]);
+
+        // Served tenants left the rotation once their queues emptied
+        let tenants: Vec<String> = queue
+            .conn
+            .zrange(queue.namespace().tenants(PRIORITY_NORMAL), 0, -1)
+            .await
+            .unwrap();
+        assert!(tenants.is_empty(), "left in rotation: {:?}", tenants);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_defer_acknowledges_in_the_same_transaction() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:defer", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-defer");
+
+        let job = PdfExportJob::builder()
+            .document_id("doc-defer")
+            .svg("<svg></svg>")
+            .output_path("/tmp/defer.pdf")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        queue.defer(&dequeued, Duration::from_secs(60)).await.unwrap();
+
+        // Off the processing list, so recovery can't queue it a second time
+        assert!(!queue.ack(&job.job_id).await.unwrap());
+        assert_eq!(queue.recover_processing("test-defer").await.unwrap(), 0);
+        let stats = queue.queue_stats().await.unwrap();
+        assert_eq!((stats.delayed, stats.processing, stats.queued()), (1, 0, 0));
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.retry_count, 0);
+        assert!(status.next_attempt_at.unwrap() > chrono::Utc::now());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_retry_acknowledges_in_the_same_transaction() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:retry", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-retry-ack");
+
+        let job = PdfExportJob::builder()
+            .document_id("doc-retry-ack")
+            .svg("<svg></svg>")
+            .output_path("/tmp/retry-ack.pdf")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+        let mut dequeued = queue.dequeue().await.unwrap().unwrap();
+        dequeued.mark_failed("transient".to_string());
+        assert!(queue.retry_job(dequeued).await.unwrap());
+
+        // Off the processing list, so recovery can't queue it a second time
+        assert!(!queue.ack(&job.job_id).await.unwrap());
+        assert_eq!(queue.recover_processing("test-retry-ack").await.unwrap(), 0);
+        let stats = queue.queue_stats().await.unwrap();
+        assert_eq!((stats.delayed, stats.processing, stats.queued()), (1, 0, 0));
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.retry_count, 1);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_blocked_dequeue_wakes_for_any_priority() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client.clone()).await.unwrap();
+        let namespace = KeyNamespace::new(format!("test-{}:notify", uuid::Uuid::new_v4()));
+        let mut waiting = JobQueue::new(conn)
+            .with_namespace(namespace.clone())
+            .with_consumer_id("test-notify")
+            .with_config(QueueConfig {
+                blpop_timeout: Duration::from_secs(10),
+                ..QueueConfig::default()
+            });
+        // Blocking commands hold their connection, so enqueue on another
+        let mut producer = JobQueue::new(ConnectionManager::new(client).await.unwrap())
+            .with_namespace(namespace);
+
+        let blocked = tokio::spawn(async move {
+            let started = Instant::now();
+            let job = waiting.dequeue().await.unwrap();
+            (job, started.elapsed())
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let job = PdfExportJob::builder()
+            .document_id("doc-notify")
+            .svg("<svg></svg>")
+            .output_path("/tmp/notify.pdf")
+            .priority(PRIORITY_LOW)
+            .build()
+            .unwrap();
+        producer.enqueue(&job).await.unwrap();
+
+        // Woken by the low-priority notification, not the polling slice
+        let (dequeued, elapsed) = blocked.await.unwrap();
+        assert_eq!(dequeued.unwrap().job_id, job.job_id);
+        assert!(elapsed < Duration::from_millis(900), "took {:?}", elapsed);
+    }
 }
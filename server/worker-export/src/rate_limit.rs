@@ -0,0 +1,166 @@
+//! Enqueue rate limiting.
+//!
+//! Caps how many jobs a single document (or user) may enqueue within a
+//! sliding time window, so one misbehaving client can't flood the queue.
+//! Limits can differ per user tier, taken from
+//! [`JobMetadata::user_tier`](crate::job::JobMetadata::user_tier).
+
+use crate::job::PdfExportJob;
+use crate::queue::KeyNamespace;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Admits `#ARGV - 3` jobs into a sliding window if they fit under the limit.
+///
+/// KEYS[1] is a sorted set of admitted job ids scored by enqueue time (ms).
+/// ARGV is `now_ms, window_ms, limit, job_id...`. Returns 1 if the jobs were
+/// admitted, 0 if the window is full.
+pub(crate) const RATE_LIMIT_SCRIPT: &str = r#"
+local now = tonumber(ARGV[1])
+local window = tonumber(ARGV[2])
+redis.call('ZREMRANGEBYSCORE', KEYS[1], '-inf', now - window)
+if redis.call('ZCARD', KEYS[1]) + #ARGV - 3 > tonumber(ARGV[3]) then
+    return 0
+end
+for i = 4, #ARGV do
+    redis.call('ZADD', KEYS[1], now, ARGV[i])
+end
+redis.call('PEXPIRE', KEYS[1], window)
+return 1
+"#;
+
+/// At most `max_jobs` enqueues per `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_jobs: u32,
+    pub window: Duration,
+}
+
+impl RateLimit {
+    /// Creates a limit of `max_jobs` per `window`.
+    pub fn new(max_jobs: u32, window: Duration) -> Self {
+        Self { max_jobs, window }
+    }
+}
+
+/// What a rate limit counts jobs against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLimitKey {
+    /// Each document has its own budget.
+    #[default]
+    Document,
+    /// Each user has one budget across their documents. Jobs without a
+    /// `user_id` are counted per document instead.
+    User,
+}
+
+/// Rate limits applied by [`JobQueue::enqueue`](crate::queue::JobQueue::enqueue).
+///
+/// Jobs whose `user_tier` names an entry in `tiers` get that limit; all
+/// others get `default`, and are unlimited if it is `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    /// What jobs are counted against.
+    pub key: RateLimitKey,
+    /// Limit for jobs without a configured tier.
+    pub default: Option<RateLimit>,
+    /// Limits by `JobMetadata::user_tier`.
+    pub tiers: HashMap<String, RateLimit>,
+}
+
+impl RateLimits {
+    /// Limits every document (or user) to `limit`.
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            default: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    /// Counts jobs per user rather than per document.
+    pub fn per_user(mut self) -> Self {
+        self.key = RateLimitKey::User;
+        self
+    }
+
+    /// Sets the limit for jobs whose `user_tier` is `tier`.
+    pub fn with_tier(mut self, tier: impl Into<String>, limit: RateLimit) -> Self {
+        self.tiers.insert(tier.into(), limit);
+        self
+    }
+
+    /// Returns the Redis counter key (within `keys`) and limit that apply to
+    /// `job`, or `None` if it is unlimited.
+    pub(crate) fn limit_for(
+        &self,
+        job: &PdfExportJob,
+        keys: &KeyNamespace,
+    ) -> Option<(String, RateLimit)> {
+        let limit = job
+            .metadata
+            .user_tier
+            .as_ref()
+            .and_then(|tier| self.tiers.get(tier))
+            .or(self.default.as_ref())?;
+
+        let key = match (self.key, &job.metadata.user_id) {
+            (RateLimitKey::User, Some(user_id)) => keys.rate_limit(&format!("user:{}", user_id)),
+            _ => keys.rate_limit(&format!("document:{}", job.document_id)),
+        };
+        Some((key, *limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::JobMetadata;
+
+    fn job(user_id: Option<&str>, user_tier: Option<&str>) -> PdfExportJob {
+        PdfExportJob::new(
+            "doc-123".to_string(),
+            "<svg></svg>".to_string(),
+            "doc-123.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                user_id: user_id.map(str::to_string),
+                user_tier: user_tier.map(str::to_string),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_limit_for_uses_tier_then_default() {
+        let free = RateLimit::new(10, Duration::from_secs(60));
+        let pro = RateLimit::new(100, Duration::from_secs(60));
+        let limits = RateLimits::new(free).with_tier("pro", pro);
+        let keys = KeyNamespace::default();
+
+        let (key, limit) = limits.limit_for(&job(None, None), &keys).unwrap();
+        assert_eq!(key, "wiretuner:export:pdf:ratelimit:document:doc-123");
+        assert_eq!(limit, free);
+
+        assert_eq!(limits.limit_for(&job(None, Some("pro")), &keys).unwrap().1, pro);
+        // Unknown tiers get the default
+        assert_eq!(limits.limit_for(&job(None, Some("gold")), &keys).unwrap().1, free);
+
+        // Without a default only listed tiers are limited
+        let tiers_only = RateLimits::default().with_tier("free", free);
+        assert!(tiers_only.limit_for(&job(None, None), &keys).is_none());
+        assert_eq!(tiers_only.limit_for(&job(None, Some("free")), &keys).unwrap().1, free);
+    }
+
+    #[test]
+    fn test_per_user_key_falls_back_to_document() {
+        let limits = RateLimits::new(RateLimit::new(10, Duration::from_secs(60))).per_user();
+        let keys = KeyNamespace::default();
+
+        let (key, _) = limits.limit_for(&job(Some("user-42"), None), &keys).unwrap();
+        assert_eq!(key, "wiretuner:export:pdf:ratelimit:user:user-42");
+
+        let (key, _) = limits.limit_for(&job(None, None), &keys).unwrap();
+        assert_eq!(key, "wiretuner:export:pdf:ratelimit:document:doc-123");
+    }
+}
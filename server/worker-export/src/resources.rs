@@ -0,0 +1,207 @@
+//! Loading of the external resources an SVG references.
+//!
+//! `<image>` elements point at their content with an `href` that is either
+//! a `data:` URI carrying the image inline or a string naming somewhere to
+//! load it from. usvg resolves such strings as local file paths anywhere on
+//! the worker, and never fetches URLs. The resolver built here replaces
+//! that with a [`ResourcePolicy`]: data URIs (PNG, JPEG, GIF and nested
+//! SVG) are decoded as usvg would, `http://` URLs are fetched only from
+//! allowlisted hosts, and relative paths are read only from inside the
+//! resources directory (usvg's `resources_dir`), when one is set.
+
+use hyper::body::HttpBody;
+use hyper::client::HttpConnector;
+use hyper::Uri;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// How long fetching one remote image may take, connection included.
+const REMOTE_IMAGE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest remote or local image loaded, in bytes.
+const MAX_REMOTE_IMAGE_BYTES: usize = 20 * 1024 * 1024;
+
+/// Which `<image>` hrefs a converter loads.
+///
+/// Hrefs the policy refuses are skipped with a warning, leaving the image
+/// out of the output as usvg does for images that fail to load. Every
+/// policy but [`Deny`](ResourcePolicy::Deny) also loads relative paths
+/// from inside the resources directory, if the converter has one.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ResourcePolicy {
+    /// Load no images at all, not even inline ones.
+    Deny,
+    /// Load only images embedded as `data:` URIs.
+    #[default]
+    DataUriOnly,
+    /// Load `data:` URIs, and fetch `http://` URLs whose host is in
+    /// `allowlist` (an exact, case-insensitive match on the host name or
+    /// `host:port`).
+    ///
+    /// Redirects are not followed, so a response can't lead off the
+    /// allowlist. HTTPS URLs are refused: the worker is built without a
+    /// TLS stack.
+    AllowRemote { allowlist: Vec<String> },
+}
+
+impl ResourcePolicy {
+    /// Returns `true` if this policy lets `uri` be fetched.
+    fn allows(&self, uri: &Uri) -> bool {
+        let ResourcePolicy::AllowRemote { allowlist } = self else {
+            return false;
+        };
+        let (Some(host), Some(authority)) = (uri.host(), uri.authority()) else {
+            return false;
+        };
+        allowlist.iter().any(|allowed| {
+            allowed.eq_ignore_ascii_case(host) || allowed.eq_ignore_ascii_case(authority.as_str())
+        })
+    }
+}
+
+/// Builds an `<image>` resolver that loads what `policy` allows.
+pub(crate) fn image_resolver(policy: ResourcePolicy) -> usvg::ImageHrefResolver<'static> {
+    let resolve_data = usvg::ImageHrefResolver::default_data_resolver();
+    let inline = policy != ResourcePolicy::Deny;
+    let fetch_data = usvg::ImageHrefResolver::default_data_resolver();
+    usvg::ImageHrefResolver {
+        resolve_data: Box::new(move |mime, data, options| {
+            if !inline {
+                warn!("Skipped inline {} image: the resource policy denies all images", mime);
+                return None;
+            }
+            resolve_data(mime, data, options)
+        }),
+        resolve_string: Box::new(move |href, options| {
+            if policy == ResourcePolicy::Deny {
+                warn!("Skipped image {}: the resource policy denies all images", href);
+                return None;
+            }
+            if !has_scheme(href) {
+                let Some(dir) = options.resources_dir.as_deref() else {
+                    warn!("Skipped image {}: no resources directory to read it from", href);
+                    return None;
+                };
+                return match read_local(dir, href) {
+                    Ok(data) => {
+                        debug!("Loaded image {}: {} bytes", href, data.len());
+                        fetch_data("text/plain", Arc::new(data), options)
+                    }
+                    Err(e) => {
+                        warn!("Skipped image {}: {}", href, e);
+                        None
+                    }
+                };
+            }
+            let uri = match href.parse::<Uri>() {
+                Ok(uri) if uri.scheme().is_some() && policy.allows(&uri) => uri,
+                _ => {
+                    warn!("Skipped image {}: not allowed by the resource policy", href);
+                    return None;
+                }
+            };
+            if uri.scheme_str() != Some("http") {
+                warn!("Skipped image {}: only http:// images can be fetched", href);
+                return None;
+            }
+            match fetch(uri) {
+                Ok(data) => {
+                    debug!("Fetched image {}: {} bytes", href, data.len());
+                    // Sniffed from the content, like a data URI without a type
+                    fetch_data("text/plain", Arc::new(data), options)
+                }
+                Err(e) => {
+                    warn!("Failed to fetch image {}: {}", href, e);
+                    None
+                }
+            }
+        }),
+    }
+}
+
+/// Returns `true` if `href` starts with a URI scheme such as `http:`,
+/// rather than being a path.
+fn has_scheme(href: &str) -> bool {
+    href.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.len() > 1
+            && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+/// Reads the image at relative path `href` inside `dir`, refusing paths
+/// that are absolute or lead outside it, through `..` or symlinks.
+fn read_local(dir: &Path, href: &str) -> Result<Vec<u8>, String> {
+    let relative = Path::new(href);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err("only relative paths inside the resources directory are read".to_string());
+    }
+    let dir = dir.canonicalize().map_err(|e| format!("resources directory: {}", e))?;
+    let path = dir.join(relative).canonicalize().map_err(|e| e.to_string())?;
+    if !path.starts_with(&dir) {
+        return Err("outside the resources directory".to_string());
+    }
+
+    let mut data = Vec::new();
+    fs::File::open(&path)
+        .and_then(|file| file.take(MAX_REMOTE_IMAGE_BYTES as u64 + 1).read_to_end(&mut data))
+        .map_err(|e| e.to_string())?;
+    if data.len() > MAX_REMOTE_IMAGE_BYTES {
+        return Err(format!("larger than {} bytes", MAX_REMOTE_IMAGE_BYTES));
+    }
+    Ok(data)
+}
+
+/// Fetches `uri` on the process's shared HTTP client, blocking until it
+/// has been read.
+fn fetch(uri: Uri) -> Result<Vec<u8>, String> {
+    crate::http::block_on(REMOTE_IMAGE_TIMEOUT, |client| get(client, uri))?
+}
+
+/// Reads the body of a successful response to a GET of `uri`.
+async fn get(client: hyper::Client<HttpConnector>, uri: Uri) -> Result<Vec<u8>, String> {
+    let response = client.get(uri).await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("server responded {}", response.status()));
+    }
+
+    let mut body = response.into_body();
+    let mut data = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        if data.len() + chunk.len() > MAX_REMOTE_IMAGE_BYTES {
+            return Err(format!("larger than {} bytes", MAX_REMOTE_IMAGE_BYTES));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist_matches_host() {
+        let policy = ResourcePolicy::AllowRemote {
+            allowlist: vec!["assets.example.com".to_string(), "cdn.example.com:8080".to_string()],
+        };
+        let allows = |uri: &str| policy.allows(&uri.parse().unwrap());
+
+        assert!(allows("http://assets.example.com/logo.png"));
+        assert!(allows("http://ASSETS.example.com:8000/logo.png"));
+        assert!(allows("http://cdn.example.com:8080/logo.png"));
+        assert!(!allows("http://cdn.example.com/logo.png"));
+        assert!(!allows("http://evil.example.com/assets.example.com/logo.png"));
+        assert!(!allows("http://assets.example.com.evil.test/logo.png"));
+        assert!(!allows("logo.png"));
+
+        let uri = "http://assets.example.com/logo.png".parse().unwrap();
+        assert!(!ResourcePolicy::DataUriOnly.allows(&uri));
+        assert!(!ResourcePolicy::Deny.allows(&uri));
+    }
+}
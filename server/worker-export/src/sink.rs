@@ -0,0 +1,497 @@
+//! Destinations exported files are written to.
+//!
+//! A converter hands every finished file to an [`OutputSink`]. The default,
+//! [`LocalSink`], writes it to the local filesystem atomically. [`S3Sink`]
+//! uploads it over plain http to a MinIO (or other S3-compatible) server
+//! for deployments without persistent disk; jobs select it with an `s3://`
+//! output path (see [`OutputConfig::sink_for`](crate::output::OutputConfig::sink_for)).
+
+use chrono::{DateTime, Utc};
+use hyper::body::HttpBody;
+use hyper::client::HttpConnector;
+use hyper::{Body, Method, Request, StatusCode, Uri};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::debug;
+
+/// Scheme of output paths stored in object storage.
+pub const S3_SCHEME: &str = "s3://";
+
+/// Region requests are signed for unless configured otherwise. MinIO
+/// accepts it whatever its own region setting.
+pub const DEFAULT_S3_REGION: &str = "us-east-1";
+
+/// How long one upload may take, connection included.
+const S3_UPLOAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Most of an error response body kept for the error message.
+const MAX_ERROR_BODY_BYTES: usize = 1024;
+
+/// Headers covered by the request signature, in canonical order.
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+/// Somewhere exported files can be written.
+///
+/// `write` is called from the blocking thread a conversion runs on, so
+/// implementations may block.
+pub trait OutputSink: Send + Sync {
+    /// Stores `bytes` under `path`, replacing anything already there.
+    fn write(&self, path: &str, bytes: &[u8]) -> io::Result<()>;
+}
+
+/// Writes files to the local filesystem, atomically: readers never see a
+/// partial file, and a failed write leaves any previous file in place.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalSink;
+
+impl OutputSink for LocalSink {
+    fn write(&self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        crate::converter::write_atomic(Path::new(path), |file| file.write_all(bytes))
+    }
+}
+
+/// Errors produced while uploading to object storage.
+#[derive(Debug, Error)]
+pub enum S3Error {
+    /// The output path isn't of the form `s3://bucket/key`, or its key has
+    /// an empty, `.` or `..` segment.
+    #[error("Invalid S3 URL {0}: expected s3://bucket/key")]
+    InvalidUrl(String),
+
+    /// The output path names a bucket other than the configured one.
+    #[error("Bucket {bucket} is not the configured bucket {configured}")]
+    WrongBucket { bucket: String, configured: String },
+
+    /// The endpoint isn't an `http://` URL.
+    #[error("Invalid MinIO endpoint {0}: expected http://host[:port] (TLS is not supported)")]
+    InvalidEndpoint(String),
+
+    /// The request could not be sent or its response read.
+    #[error("S3 request failed: {0}")]
+    Request(String),
+
+    /// The storage service refused the upload.
+    #[error("S3 responded {status}: {body}")]
+    Status { status: u16, body: String },
+}
+
+impl S3Error {
+    /// Returns `true` if retrying the upload can't succeed: the output path
+    /// or configuration is wrong, or the server refused the request with a
+    /// 4xx status other than a timeout or throttling.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            S3Error::InvalidUrl(_) | S3Error::WrongBucket { .. } | S3Error::InvalidEndpoint(_) => true,
+            S3Error::Request(_) => false,
+            S3Error::Status { status, .. } => {
+                let status = StatusCode::from_u16(*status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+                status.is_client_error()
+                    && status != StatusCode::REQUEST_TIMEOUT
+                    && status != StatusCode::TOO_MANY_REQUESTS
+            }
+        }
+    }
+}
+
+impl From<S3Error> for io::Error {
+    fn from(error: S3Error) -> Self {
+        io::Error::other(error)
+    }
+}
+
+/// Returns `true` if `error` is an upload failure that retrying won't fix
+/// (see [`S3Error::is_permanent`]).
+pub fn is_permanent_failure(error: &io::Error) -> bool {
+    error
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<S3Error>())
+        .is_some_and(S3Error::is_permanent)
+}
+
+/// Returns `true` if `path` names an object in object storage rather than
+/// a local file.
+pub fn is_s3_url(path: &str) -> bool {
+    path.starts_with(S3_SCHEME)
+}
+
+/// Splits an `s3://bucket/key` output path into its bucket and key.
+///
+/// Keys with an empty, `.` or `..` segment are refused: servers resolve
+/// them like paths, so they could name an object other than the one
+/// written.
+pub fn parse_s3_url(url: &str) -> Option<(&str, &str)> {
+    let (bucket, key) = url.strip_prefix(S3_SCHEME)?.split_once('/')?;
+    if bucket.is_empty() || !is_valid_key(key) {
+        return None;
+    }
+    Some((bucket, key))
+}
+
+/// Returns `true` if every `/`-separated segment of `key` is a name.
+fn is_valid_key(key: &str) -> bool {
+    key.split('/')
+        .all(|segment| !matches!(segment, "" | "." | ".."))
+}
+
+/// Where and as whom [`S3Sink`] uploads.
+#[derive(Clone, PartialEq, Eq)]
+pub struct S3Config {
+    /// Server URL, e.g. `http://minio:9000`; plain http only. Objects are
+    /// addressed path-style (`<endpoint>/<bucket>/<key>`).
+    pub endpoint: String,
+    /// The one bucket jobs may write to.
+    pub bucket: String,
+    /// Prepended to every key written, e.g. `exports/` to keep the worker's
+    /// objects under one folder of a shared bucket. Empty by default.
+    pub key_prefix: String,
+    /// Region requests are signed for.
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+impl S3Config {
+    /// Creates a config for `bucket` at `endpoint`, signing requests for
+    /// [`DEFAULT_S3_REGION`].
+    pub fn new(
+        endpoint: impl Into<String>,
+        bucket: impl Into<String>,
+        access_key_id: impl Into<String>,
+        secret_access_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            bucket: bucket.into(),
+            key_prefix: String::new(),
+            region: DEFAULT_S3_REGION.to_string(),
+            access_key_id: access_key_id.into(),
+            secret_access_key: secret_access_key.into(),
+        }
+    }
+
+    /// Sets the region requests are signed for.
+    pub fn with_region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    /// Writes every object under `prefix`. A `/` is added between the
+    /// prefix and the key unless the prefix ends in one.
+    pub fn with_key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.key_prefix = prefix.into();
+        self
+    }
+
+    /// Returns the object key a job's `key` is stored under.
+    fn object_key(&self, key: &str) -> String {
+        match self.key_prefix.trim_start_matches('/') {
+            "" => key.to_string(),
+            prefix if prefix.ends_with('/') => format!("{}{}", prefix, key),
+            prefix => format!("{}/{}", prefix, key),
+        }
+    }
+}
+
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("key_prefix", &self.key_prefix)
+            .field("region", &self.region)
+            .field("access_key_id", &self.access_key_id)
+            .field("secret_access_key", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Uploads files to a MinIO server (or another S3-compatible service
+/// reachable over plain http) with a single signed (AWS Signature Version
+/// 4) `PUT` per file.
+///
+/// Paths are `s3://bucket/key` URLs. The worker is built without a TLS
+/// stack, so the endpoint must be `http://`, such as MinIO inside the
+/// cluster; AWS S3 itself, which requires https, can't be written to.
+#[derive(Debug, Clone)]
+pub struct S3Sink {
+    config: S3Config,
+}
+
+impl S3Sink {
+    pub fn new(config: S3Config) -> Self {
+        Self { config }
+    }
+
+    /// Builds the signed upload of `bytes` to `key`, as of `now`.
+    fn request(&self, key: &str, bytes: &[u8], now: DateTime<Utc>) -> Result<Request<Body>, S3Error> {
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        let invalid_endpoint = || S3Error::InvalidEndpoint(self.config.endpoint.clone());
+        let base: Uri = endpoint.parse().map_err(|_| invalid_endpoint())?;
+        let host = match (base.scheme_str(), base.authority()) {
+            (Some("http"), Some(authority)) => authority.as_str().to_string(),
+            _ => return Err(invalid_endpoint()),
+        };
+
+        let path = format!(
+            "{}/{}/{}",
+            base.path().trim_end_matches('/'),
+            self.config.bucket,
+            uri_encode(&self.config.object_key(key))
+        );
+        let uri = format!("http://{}{}", host, path);
+        let payload_hash = hex(&Sha256::digest(bytes));
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+
+        let canonical_request = format!(
+            "PUT\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            path, host, payload_hash, amz_date, SIGNED_HEADERS, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let key = signing_key(&self.config.secret_access_key, &date, &self.config.region, "s3");
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key_id, scope, SIGNED_HEADERS, signature
+        );
+
+        Request::builder()
+            .method(Method::PUT)
+            .uri(uri)
+            .header("host", host)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+            .header("content-length", bytes.len())
+            .body(Body::from(bytes.to_vec()))
+            .map_err(|e| S3Error::Request(e.to_string()))
+    }
+}
+
+impl OutputSink for S3Sink {
+    fn write(&self, path: &str, bytes: &[u8]) -> io::Result<()> {
+        let (bucket, key) = parse_s3_url(path).ok_or_else(|| S3Error::InvalidUrl(path.to_string()))?;
+        if bucket != self.config.bucket {
+            return Err(S3Error::WrongBucket {
+                bucket: bucket.to_string(),
+                configured: self.config.bucket.clone(),
+            }
+            .into());
+        }
+
+        let request = self.request(key, bytes, Utc::now())?;
+        send(request)?;
+        debug!("Uploaded {} bytes to {}", bytes.len(), path);
+        Ok(())
+    }
+}
+
+/// Sends `request` on the process's shared HTTP client, blocking until the
+/// response has been read.
+fn send(request: Request<Body>) -> Result<(), S3Error> {
+    crate::http::block_on(S3_UPLOAD_TIMEOUT, |client| put(client, request))
+        .unwrap_or_else(|e| Err(S3Error::Request(e)))
+}
+
+/// Sends an upload, failing with the start of the body of an error
+/// response.
+async fn put(client: hyper::Client<HttpConnector>, request: Request<Body>) -> Result<(), S3Error> {
+    let response = client
+        .request(request)
+        .await
+        .map_err(|e| S3Error::Request(e.to_string()))?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let mut body = response.into_body();
+    let mut data = Vec::new();
+    while let Some(Ok(chunk)) = body.data().await {
+        data.extend_from_slice(&chunk);
+        if data.len() >= MAX_ERROR_BODY_BYTES {
+            data.truncate(MAX_ERROR_BODY_BYTES);
+            break;
+        }
+    }
+    Err(S3Error::Status {
+        status: status.as_u16(),
+        body: String::from_utf8_lossy(&data).trim().to_string(),
+    })
+}
+
+/// Percent-encodes an object key for the request path, keeping `/` and the
+/// characters SigV4 leaves unreserved.
+fn uri_encode(key: &str) -> String {
+    let mut encoded = String::with_capacity(key.len());
+    for byte in key.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Derives the SigV4 signing key for `date` (`YYYYMMDD`), `region` and
+/// `service`.
+fn signing_key(secret_access_key: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let key = hmac_sha256(format!("AWS4{}", secret_access_key).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let mut inner = Sha256::new();
+    inner.update(block.map(|byte| byte ^ 0x36));
+    inner.update(data);
+    let mut outer = Sha256::new();
+    outer.update(block.map(|byte| byte ^ 0x5c));
+    outer.update(inner.finalize());
+    outer.finalize().into()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_signing_primitives_match_reference_vectors() {
+        // RFC 4231 test cases 1 and 6
+        assert_eq!(
+            hex(&hmac_sha256(&[0x0b; 20], b"Hi There")),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+        assert_eq!(
+            hex(&hmac_sha256(&[0xaa; 131], b"Test Using Larger Than Block-Size Key - Hash Key First")),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+        // The signing key example from the AWS SigV4 documentation
+        assert_eq!(
+            hex(&signing_key("wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY", "20120215", "us-east-1", "iam")),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+
+        assert_eq!(uri_encode("doc 1/ab+c~.pdf"), "doc%201/ab%2Bc~.pdf");
+        assert_eq!(parse_s3_url("s3://exports/doc/1.pdf"), Some(("exports", "doc/1.pdf")));
+        assert_eq!(parse_s3_url("s3://exports/"), None);
+        assert_eq!(parse_s3_url("/exports/1.pdf"), None);
+        for key in ["doc//1.pdf", "doc/./1.pdf", "../1.pdf", "doc/..", "doc/"] {
+            assert_eq!(parse_s3_url(&format!("s3://exports/{key}")), None, "{key}");
+        }
+        assert_eq!(parse_s3_url("s3://exports/doc/..1.pdf"), Some(("exports", "doc/..1.pdf")));
+
+        let config = S3Config::new("http://minio:9000", "exports", "key", "secret");
+        assert_eq!(config.object_key("doc/1.pdf"), "doc/1.pdf");
+        let prefixed = config.clone().with_key_prefix("worker");
+        assert_eq!(prefixed.object_key("doc/1.pdf"), "worker/doc/1.pdf");
+        assert_eq!(config.with_key_prefix("/worker/").object_key("1.pdf"), "worker/1.pdf");
+    }
+
+    #[test]
+    fn test_s3_errors_are_permanent_unless_transient() {
+        let status = |status| S3Error::Status { status, body: String::new() };
+        for error in [status(400), status(403), status(404), S3Error::InvalidUrl("s3://".to_string())] {
+            assert!(error.is_permanent(), "{error}");
+            assert!(is_permanent_failure(&error.into()));
+        }
+        for error in [status(408), status(429), status(500), status(503), S3Error::Request("reset".to_string())] {
+            assert!(!error.is_permanent(), "{error}");
+            assert!(!is_permanent_failure(&error.into()));
+        }
+        assert!(!is_permanent_failure(&io::Error::from(io::ErrorKind::StorageFull)));
+
+        // Refused uploads fail the job without a retry
+        let error = crate::converter::ConvertError::Io {
+            path: "s3://exports/doc.pdf".to_string(),
+            source: status(403).into(),
+        };
+        assert_eq!(crate::job::ErrorKind::from(&error), crate::job::ErrorKind::OutputLocation);
+        assert!(!crate::job::ErrorKind::from(&error).is_retryable());
+    }
+
+    #[test]
+    fn test_s3_sink_uploads_signed_put() {
+        // A minimal S3 endpoint that records the one request it gets
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = Vec::new();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                head.push(line.trim_end().to_string());
+                line.clear();
+            }
+            let length: usize = head
+                .iter()
+                .find_map(|h| h.to_ascii_lowercase().strip_prefix("content-length: ").map(str::to_string))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            tx.send((head, body)).unwrap();
+        });
+
+        let sink = S3Sink::new(S3Config::new(
+            format!("http://127.0.0.1:{}", port),
+            "exports",
+            "AKIDEXAMPLE",
+            "secret",
+        ));
+        sink.write("s3://exports/doc-1/page 1.pdf", b"%PDF-1.7 test").unwrap();
+
+        let (head, body) = rx.recv().unwrap();
+        assert_eq!(head[0], "PUT /exports/doc-1/page%201.pdf HTTP/1.1");
+        assert_eq!(body, b"%PDF-1.7 test");
+        let header = |name: &str| {
+            head.iter()
+                .find_map(|h| h.split_once(": ").filter(|(n, _)| n.eq_ignore_ascii_case(name)))
+                .map(|(_, value)| value.to_string())
+                .unwrap()
+        };
+        assert_eq!(header("x-amz-content-sha256"), hex(&Sha256::digest(b"%PDF-1.7 test")));
+        let authorization = header("authorization");
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/"));
+        assert!(authorization.contains("/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="));
+
+        // Other buckets are refused without a request
+        let err = sink.write("s3://elsewhere/doc.pdf", b"%PDF").unwrap_err();
+        assert!(err.to_string().contains("not the configured bucket"));
+    }
+}
@@ -1,14 +1,174 @@
 //! Telemetry and structured logging for export worker.
 
 use crate::job::{PdfExportJob, JobStatus};
+use crate::metrics;
+use opentelemetry::metrics::{Counter, Histogram, Unit, UpDownCounter};
+use opentelemetry::propagation::TextMapPropagator;
 use opentelemetry::trace::{Span, Tracer};
-use opentelemetry::{global, KeyValue};
-use tracing::{info, warn};
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tracing::{error, info, warn};
+
+/// Instrumentation scope name for spans and metrics.
+const INSTRUMENTATION_NAME: &str = "pdf-export-worker";
+
+/// Default duration above which a job is logged as slow.
+const DEFAULT_SLOW_JOB_THRESHOLD_MS: u64 = 5000;
+
+/// Duration above which a job is logged and counted as slow, set from
+/// `PDF_EXPORT_SLOW_THRESHOLD_MS` by [`init_telemetry`].
+static SLOW_JOB_THRESHOLD_MS: AtomicU64 = AtomicU64::new(DEFAULT_SLOW_JOB_THRESHOLD_MS);
+
+/// Queue depth last reported to the up-down counter, so heartbeats can
+/// record the change since then.
+static LAST_QUEUE_DEPTH: AtomicI64 = AtomicI64::new(0);
+
+/// Oldest job age last reported, likewise.
+static LAST_OLDEST_JOB_AGE_MS: AtomicI64 = AtomicI64::new(0);
+
+/// Active jobs and free permits last reported, for the same reason.
+static LAST_ACTIVE_JOBS: AtomicI64 = AtomicI64::new(0);
+static LAST_AVAILABLE_PERMITS: AtomicI64 = AtomicI64::new(0);
+
+/// Jobs processed by each worker as of its last heartbeat, so the counter
+/// only receives the increase.
+static REPORTED_JOBS_PROCESSED: Mutex<BTreeMap<usize, u64>> = Mutex::new(BTreeMap::new());
+
+static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+
+/// Providers installed by [`init_telemetry`], kept so
+/// [`shutdown_telemetry`] can flush them. `None` until telemetry is
+/// initialized and again after shutdown.
+static PROVIDERS: Mutex<Option<Providers>> = Mutex::new(None);
+
+struct Providers {
+    tracer: opentelemetry_sdk::trace::TracerProvider,
+    meter: opentelemetry_sdk::metrics::MeterProvider,
+}
+
+/// OpenTelemetry metric instruments for the export pipeline.
+struct Instruments {
+    /// `pdf_export.jobs`: finished jobs, labeled by status.
+    jobs: Counter<u64>,
+    /// `pdf_export.duration_ms`: job processing time.
+    duration_ms: Histogram<f64>,
+    /// `pdf_export.queue_wait_ms`: time jobs waited before processing.
+    queue_wait_ms: Histogram<f64>,
+    /// `pdf_export.render_duration_ms`: time spent converting completed
+    /// jobs.
+    render_duration_ms: Histogram<f64>,
+    /// `pdf_export.slow_jobs`: jobs slower than the slow-job threshold.
+    slow_jobs: Counter<u64>,
+    /// `pdf_export.conversion_warnings`: skipped or degraded content in
+    /// completed jobs.
+    conversion_warnings: Counter<u64>,
+    /// `pdf_export.disk_full`: jobs put back because the output disk was full.
+    disk_full: Counter<u64>,
+    /// `pdf_export.queue_depth`: jobs waiting in the queues.
+    queue_depth: UpDownCounter<i64>,
+    /// `pdf_export.oldest_job_age_ms`: age of the oldest waiting job.
+    oldest_job_age_ms: UpDownCounter<i64>,
+    /// `pdf_export.active_jobs`: jobs holding a concurrency permit.
+    active_jobs: UpDownCounter<i64>,
+    /// `pdf_export.available_permits`: free concurrency slots.
+    available_permits: UpDownCounter<i64>,
+    /// `pdf_export.worker.jobs_processed`: jobs finished, by worker.
+    worker_jobs_processed: Counter<u64>,
+}
+
+/// Returns the metric instruments, creating them from the global meter
+/// provider on first use.
+fn instruments() -> &'static Instruments {
+    INSTRUMENTS.get_or_init(|| {
+        let meter = global::meter(INSTRUMENTATION_NAME);
+        Instruments {
+            jobs: meter
+                .u64_counter("pdf_export.jobs")
+                .with_description("PDF export jobs processed, by status")
+                .init(),
+            duration_ms: meter
+                .f64_histogram("pdf_export.duration_ms")
+                .with_description("PDF export job processing time")
+                .with_unit(Unit::new("ms"))
+                .init(),
+            queue_wait_ms: meter
+                .f64_histogram("pdf_export.queue_wait_ms")
+                .with_description("Time PDF export jobs waited in the queue before processing")
+                .with_unit(Unit::new("ms"))
+                .init(),
+            render_duration_ms: meter
+                .f64_histogram("pdf_export.render_duration_ms")
+                .with_description("Time PDF export jobs spent converting, excluding queueing and retries")
+                .with_unit(Unit::new("ms"))
+                .init(),
+            slow_jobs: meter
+                .u64_counter("pdf_export.slow_jobs")
+                .with_description("PDF export jobs slower than the slow-job threshold")
+                .init(),
+            conversion_warnings: meter
+                .u64_counter("pdf_export.conversion_warnings")
+                .with_description("Content skipped or degraded by completed PDF exports")
+                .init(),
+            disk_full: meter
+                .u64_counter("pdf_export.disk_full")
+                .with_description("PDF export jobs put back because the output disk was full")
+                .init(),
+            queue_depth: meter
+                .i64_up_down_counter("pdf_export.queue_depth")
+                .with_description("Jobs waiting in the export queues")
+                .init(),
+            oldest_job_age_ms: meter
+                .i64_up_down_counter("pdf_export.oldest_job_age_ms")
+                .with_description("Age of the oldest export job waiting at the head of a queue")
+                .with_unit(Unit::new("ms"))
+                .init(),
+            active_jobs: meter
+                .i64_up_down_counter("pdf_export.active_jobs")
+                .with_description("Export jobs currently holding a concurrency permit")
+                .init(),
+            available_permits: meter
+                .i64_up_down_counter("pdf_export.available_permits")
+                .with_description("Free concurrency slots across the worker process")
+                .init(),
+            worker_jobs_processed: meter
+                .u64_counter("pdf_export.worker.jobs_processed")
+                .with_description("PDF export jobs finished, by worker loop")
+                .init(),
+        }
+    })
+}
+
+/// Returns the W3C trace context of the current OpenTelemetry context, for
+/// storing on a job at enqueue time. Empty when no span is active.
+pub fn current_trace_context() -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Context::current(), &mut carrier);
+    carrier
+}
+
+/// Returns the context to parent a job's spans on: the trace context the
+/// job was enqueued with, or the current context if it has none.
+pub fn parent_context(job: &PdfExportJob) -> Context {
+    match &job.metadata.trace_context {
+        Some(carrier) => TraceContextPropagator::new().extract(carrier),
+        None => Context::current(),
+    }
+}
+
+/// Returns the duration above which a job is logged and counted as slow.
+pub fn slow_job_threshold_ms() -> u64 {
+    SLOW_JOB_THRESHOLD_MS.load(Ordering::Relaxed)
+}
 
 /// Records telemetry for a completed or failed job.
 ///
-/// This function emits structured logs and OpenTelemetry spans for monitoring
-/// export pipeline health. Metrics include:
+/// This function emits structured logs, OpenTelemetry spans and metrics,
+/// and Prometheus job counters for monitoring export pipeline health. Metrics include:
 /// - Job duration (ms)
 /// - Success/failure status
 /// - Retry count
@@ -18,8 +178,20 @@ use tracing::{info, warn};
 ///
 /// * `job` - The completed or failed job
 pub fn record_job_telemetry(job: &PdfExportJob) {
-    let tracer = global::tracer("pdf-export-worker");
-    let mut span = tracer.start("pdf_export_job");
+    record_job_telemetry_with_threshold(job, slow_job_threshold_ms());
+}
+
+/// Records telemetry for a completed or failed job, treating it as slow if
+/// it took longer than `slow_threshold_ms`.
+pub fn record_job_telemetry_with_threshold(job: &PdfExportJob, slow_threshold_ms: u64) {
+    metrics::record_job(job.status);
+
+    let instruments = instruments();
+    let attributes = [KeyValue::new("status", job.status.to_string())];
+    instruments.jobs.add(1, &attributes);
+
+    let tracer = global::tracer(INSTRUMENTATION_NAME);
+    let mut span = tracer.start_with_context("pdf_export_job", &parent_context(job));
 
     // Add span attributes
     span.set_attribute(KeyValue::new("job_id", job.job_id.clone()));
@@ -29,6 +201,7 @@ pub fn record_job_telemetry(job: &PdfExportJob) {
 
     if let Some(duration_ms) = job.processing_duration_ms() {
         span.set_attribute(KeyValue::new("duration_ms", duration_ms));
+        instruments.duration_ms.record(duration_ms as f64, &attributes);
 
         // Log performance metrics
         info!(
@@ -39,16 +212,23 @@ pub fn record_job_telemetry(job: &PdfExportJob) {
             "PDF export job completed"
         );
 
-        // Warn if exceeding performance threshold (5 seconds)
-        if duration_ms > 5000 {
-            warn!(
-                job_id = %job.job_id,
-                duration_ms = duration_ms,
-                "PDF export exceeded performance threshold (5000ms)"
-            );
+        // An expired job spent its time waiting, not converting
+        if job.status != JobStatus::Expired && record_if_slow(job, duration_ms, slow_threshold_ms) {
+            span.set_attribute(KeyValue::new("slow", true));
         }
     }
 
+    if let Some(queue_wait_ms) = job.queue_wait_ms {
+        span.set_attribute(KeyValue::new("queue_wait_ms", queue_wait_ms));
+        instruments.queue_wait_ms.record(queue_wait_ms as f64, &attributes);
+        metrics::observe_queue_wait(Duration::from_millis(queue_wait_ms as u64));
+    }
+
+    if let Some(render_duration_ms) = job.render_duration_ms {
+        span.set_attribute(KeyValue::new("render_duration_ms", render_duration_ms));
+        instruments.render_duration_ms.record(render_duration_ms as f64, &attributes);
+    }
+
     // Record error details if job failed
     if job.status == JobStatus::Failed {
         if let Some(ref error) = job.error {
@@ -62,6 +242,14 @@ pub fn record_job_telemetry(job: &PdfExportJob) {
         }
     }
 
+    if !job.warnings.is_empty() {
+        span.set_attribute(KeyValue::new("warning_count", job.warnings.len() as i64));
+        instruments
+            .conversion_warnings
+            .add(job.warnings.len() as u64, &[]);
+        metrics::record_conversion_warnings(job.warnings.len());
+    }
+
     // Record metadata
     span.set_attribute(KeyValue::new(
         "export_scope",
@@ -79,23 +267,136 @@ pub fn record_job_telemetry(job: &PdfExportJob) {
     span.end();
 }
 
+/// Warns about and counts a job that took longer than `threshold_ms`.
+///
+/// Returns `true` if the job was slow.
+fn record_if_slow(job: &PdfExportJob, duration_ms: i64, threshold_ms: u64) -> bool {
+    if duration_ms <= threshold_ms as i64 {
+        return false;
+    }
+
+    warn!(
+        job_id = %job.job_id,
+        duration_ms = duration_ms,
+        threshold_ms = threshold_ms,
+        "PDF export exceeded performance threshold ({}ms over {}ms)",
+        duration_ms - threshold_ms as i64,
+        threshold_ms
+    );
+    metrics::record_slow_job();
+    instruments()
+        .slow_jobs
+        .add(1, &[KeyValue::new("status", job.status.to_string())]);
+    true
+}
+
+/// Reports a job whose output couldn't be written because the disk is
+/// full. The worker stays unready (see [`metrics::ready_handler`]) and takes
+/// no new jobs until a later job writes its output successfully.
+pub fn record_disk_full(job: &PdfExportJob) {
+    error!(
+        job_id = %job.job_id,
+        output_path = %job.output_path,
+        disk_full = true,
+        "Output disk full, deferring job without using a retry"
+    );
+    metrics::record_disk_full();
+    instruments().disk_full.add(1, &[]);
+}
+
+/// Utilization of a worker process, sampled for a heartbeat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkerStats {
+    /// Worker loop sending the heartbeat.
+    pub worker_id: usize,
+    /// Jobs holding a concurrency permit, across all worker loops.
+    pub active_jobs: usize,
+    /// Concurrency permits not currently held.
+    pub available_permits: usize,
+    /// Jobs this worker loop has finished since it started.
+    pub jobs_processed: u64,
+}
+
+impl WorkerStats {
+    /// Samples `semaphore`, which was created with `concurrency` permits.
+    pub fn sample(
+        worker_id: usize,
+        semaphore: &Semaphore,
+        concurrency: usize,
+        jobs_processed: u64,
+    ) -> Self {
+        let available_permits = semaphore.available_permits();
+        Self {
+            worker_id,
+            active_jobs: concurrency.saturating_sub(available_permits),
+            available_permits,
+            jobs_processed,
+        }
+    }
+}
+
+/// Records the age of the oldest waiting job, `None` when the queues are
+/// empty, to both OpenTelemetry and Prometheus. Refreshed by
+/// [`metrics::poll_queue_stats`] along with the other queue gauges.
+pub fn record_oldest_job_age(age: Option<Duration>) {
+    metrics::set_oldest_job_age(age);
+    let age_ms = age.map_or(0, |age| age.as_millis() as i64);
+    let previous = LAST_OLDEST_JOB_AGE_MS.swap(age_ms, Ordering::Relaxed);
+    instruments().oldest_job_age_ms.add(age_ms - previous, &[]);
+}
+
 /// Records a worker heartbeat for monitoring worker health.
 ///
 /// This should be called periodically by the worker loop to signal
-/// that the worker is alive and processing jobs.
+/// that the worker is alive and processing jobs. Queue length and worker
+/// utilization are reported to both OpenTelemetry and Prometheus.
 ///
 /// # Arguments
 ///
 /// * `queue_length` - Current number of jobs in the queue
-pub fn record_worker_heartbeat(queue_length: usize) {
-    let tracer = global::tracer("pdf-export-worker");
+/// * `stats` - Concurrency usage and this worker's job count
+pub fn record_worker_heartbeat(queue_length: usize, stats: WorkerStats) {
+    metrics::set_queue_length(queue_length);
+    metrics::set_worker_stats(&stats);
+
+    let instruments = instruments();
+    let depth = queue_length as i64;
+    let previous = LAST_QUEUE_DEPTH.swap(depth, Ordering::Relaxed);
+    instruments.queue_depth.add(depth - previous, &[]);
+
+    let active = stats.active_jobs as i64;
+    let previous = LAST_ACTIVE_JOBS.swap(active, Ordering::Relaxed);
+    instruments.active_jobs.add(active - previous, &[]);
+
+    let available = stats.available_permits as i64;
+    let previous = LAST_AVAILABLE_PERMITS.swap(available, Ordering::Relaxed);
+    instruments.available_permits.add(available - previous, &[]);
+
+    let previous = REPORTED_JOBS_PROCESSED
+        .lock()
+        .unwrap()
+        .insert(stats.worker_id, stats.jobs_processed)
+        .unwrap_or(0);
+    instruments.worker_jobs_processed.add(
+        stats.jobs_processed.saturating_sub(previous),
+        &[KeyValue::new("worker_id", stats.worker_id as i64)],
+    );
+
+    let tracer = global::tracer(INSTRUMENTATION_NAME);
     let mut span = tracer.start("worker_heartbeat");
 
     span.set_attribute(KeyValue::new("queue_length", queue_length as i64));
+    span.set_attribute(KeyValue::new("worker_id", stats.worker_id as i64));
+    span.set_attribute(KeyValue::new("active_jobs", active));
+    span.set_attribute(KeyValue::new("available_permits", available));
     span.end();
 
     info!(
         queue_length = queue_length,
+        worker_id = stats.worker_id,
+        active_jobs = stats.active_jobs,
+        available_permits = stats.available_permits,
+        jobs_processed = stats.jobs_processed,
         "Worker heartbeat"
     );
 }
@@ -106,6 +407,11 @@ pub fn record_worker_heartbeat(queue_length: usize) {
 /// from environment variables:
 /// - `OTEL_EXPORTER_OTLP_ENDPOINT` - Collector endpoint (default: http://localhost:4317)
 /// - `OTEL_SERVICE_NAME` - Service name (default: pdf-export-worker)
+/// - `PDF_EXPORT_SLOW_THRESHOLD_MS` - Job duration logged and counted as
+///   slow (default: 5000; `SLOW_JOB_THRESHOLD_MS` is accepted as a fallback)
+///
+/// Traces and metrics are both exported over OTLP; the metric instruments
+/// are created here so they bind to the OTLP meter provider.
 ///
 /// # Returns
 ///
@@ -120,7 +426,20 @@ pub fn init_telemetry() -> Result<(), Box<dyn std::error::Error>> {
     let service_name = std::env::var("OTEL_SERVICE_NAME")
         .unwrap_or_else(|_| "pdf-export-worker".to_string());
 
-    // Initialize OTLP exporter
+    if let Some(threshold_ms) = std::env::var("PDF_EXPORT_SLOW_THRESHOLD_MS")
+        .or_else(|_| std::env::var("SLOW_JOB_THRESHOLD_MS"))
+        .ok()
+        .and_then(|v| v.parse().ok())
+    {
+        SLOW_JOB_THRESHOLD_MS.store(threshold_ms, Ordering::Relaxed);
+    }
+
+    let resource = opentelemetry_sdk::Resource::new(vec![
+        KeyValue::new("service.name", service_name),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ]);
+
+    // Initialize OTLP exporters
     let tracer = opentelemetry_otlp::new_pipeline()
         .tracing()
         .with_exporter(
@@ -128,27 +447,71 @@ pub fn init_telemetry() -> Result<(), Box<dyn std::error::Error>> {
                 .tonic()
                 .with_endpoint(&endpoint),
         )
-        .with_trace_config(Config::default().with_resource(
-            opentelemetry_sdk::Resource::new(vec![
-                KeyValue::new("service.name", service_name),
-                KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
-            ]),
-        ))
+        .with_trace_config(Config::default().with_resource(resource.clone()))
         .install_batch(opentelemetry_sdk::runtime::Tokio)?;
 
-    global::set_tracer_provider(tracer.provider().unwrap());
+    let tracer_provider = tracer.provider().unwrap();
+
+    let meter_provider = opentelemetry_otlp::new_pipeline()
+        .metrics(opentelemetry_sdk::runtime::Tokio)
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&endpoint),
+        )
+        .with_resource(resource)
+        .build()?;
+
+    global::set_tracer_provider(tracer_provider.clone());
+    global::set_meter_provider(meter_provider.clone());
+    instruments();
+
+    *PROVIDERS.lock().unwrap() = Some(Providers {
+        tracer: tracer_provider,
+        meter: meter_provider,
+    });
 
     info!("Telemetry initialized: endpoint={}", endpoint);
     Ok(())
 }
 
+/// Flushes pending spans and metrics and shuts down the exporters.
+///
+/// Call once at shutdown, after in-flight jobs have finished, so their
+/// spans reach the collector. Does nothing if [`init_telemetry`] failed or
+/// was never called. Export failures are logged rather than returned, as
+/// there is nothing left to retry them.
+pub fn shutdown_telemetry() {
+    let Some(providers) = PROVIDERS.lock().unwrap().take() else {
+        return;
+    };
+
+    for result in providers.tracer.force_flush() {
+        if let Err(e) = result {
+            warn!("Failed to flush spans: {}", e);
+        }
+    }
+    // Dropping the last provider handle shuts down the batch span processor
+    drop(providers.tracer);
+    global::shutdown_tracer_provider();
+
+    // The periodic reader's own shutdown skips the final collection, so
+    // flush explicitly first; its shutdown result only reports that.
+    if let Err(e) = providers.meter.force_flush() {
+        warn!("Failed to flush metrics: {}", e);
+    }
+    let _ = providers.meter.shutdown();
+
+    info!("Telemetry shut down");
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::job::JobMetadata;
 
-    #[test]
-    fn test_record_job_telemetry() {
+    #[tokio::test]
+    async fn test_record_job_telemetry() {
         // Initialize no-op telemetry for testing
         let _ = init_telemetry();
 
@@ -160,18 +523,18 @@ mod tests {
                 artboard_ids: vec!["ab-1".to_string()],
                 export_scope: "current".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
-        job.mark_complete();
+        job.mark_complete(1024, 1, None);
 
         // Should not panic
         record_job_telemetry(&job);
     }
 
-    #[test]
-    fn test_record_failed_job() {
+    #[tokio::test]
+    async fn test_record_failed_job() {
         let _ = init_telemetry();
 
         let mut job = PdfExportJob::new(
@@ -179,10 +542,9 @@ mod tests {
             "<svg></svg>".to_string(),
             "/tmp/test.pdf".to_string(),
             JobMetadata {
-                artboard_ids: vec![],
                 export_scope: "all".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
@@ -191,4 +553,128 @@ mod tests {
         // Should not panic and should log error
         record_job_telemetry(&job);
     }
+
+    #[tokio::test]
+    async fn test_slow_job_over_configured_threshold() {
+        let mut job = PdfExportJob::new(
+            "doc-slow".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.start_processing();
+        job.mark_complete(1024, 1, None);
+
+        assert!(record_if_slow(&job, 50, 10));
+        assert!(!record_if_slow(&job, 50, 50));
+        assert!(!record_if_slow(&job, 50, DEFAULT_SLOW_JOB_THRESHOLD_MS));
+        assert!(crate::metrics::render().contains("pdf_export_slow_jobs_total "));
+        assert!(!crate::metrics::render().contains("pdf_export_slow_jobs_total 0\n"));
+
+        // The whole recording path takes the threshold it is given
+        job.created_at -= chrono::Duration::milliseconds(20);
+        record_job_telemetry_with_threshold(&job, 1);
+    }
+
+    #[test]
+    fn test_trace_context_round_trip_sets_parent() {
+        use opentelemetry::trace::{TraceContextExt, TraceId};
+
+        let traceparent = "00-4bf92f3577b34da6a3ce929b0e0e4736-00f067aa0ba902b7-01";
+        let job = PdfExportJob::new(
+            "doc-789".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                trace_context: Some(HashMap::from([(
+                    "traceparent".to_string(),
+                    traceparent.to_string(),
+                )])),
+                ..Default::default()
+            },
+        );
+
+        let json = serde_json::to_string(&job).unwrap();
+        let decoded: PdfExportJob = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.metadata.trace_context, job.metadata.trace_context);
+
+        let parent = parent_context(&decoded);
+        let span_context = parent.span().span_context().clone();
+        assert!(span_context.is_remote());
+        assert_eq!(
+            span_context.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929b0e0e4736").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_no_trace_context_outside_a_span() {
+        assert!(current_trace_context().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_record_job_metrics() {
+        let _ = init_telemetry();
+
+        let mut job = PdfExportJob::new(
+            "doc-321".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.start_processing();
+        job.mark_complete(1024, 1, None);
+
+        // Should create the instruments and record without panicking
+        record_job_telemetry(&job);
+
+        assert!(INSTRUMENTS.get().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_reports_worker_utilization() {
+        let _ = init_telemetry();
+
+        let semaphore = Semaphore::new(4);
+        let _permits = semaphore.acquire_many(3).await.unwrap();
+        let stats = WorkerStats::sample(7, &semaphore, 4, 12);
+        assert_eq!(stats.active_jobs, 3);
+        assert_eq!(stats.available_permits, 1);
+
+        record_worker_heartbeat(3, stats);
+        record_worker_heartbeat(1, WorkerStats { jobs_processed: 15, ..stats });
+        record_oldest_job_age(Some(Duration::from_secs(90)));
+        record_oldest_job_age(Some(Duration::from_millis(2500)));
+
+        assert_eq!(LAST_QUEUE_DEPTH.load(Ordering::Relaxed), 1);
+        assert_eq!(LAST_OLDEST_JOB_AGE_MS.load(Ordering::Relaxed), 2500);
+        assert_eq!(LAST_ACTIVE_JOBS.load(Ordering::Relaxed), 3);
+        assert_eq!(LAST_AVAILABLE_PERMITS.load(Ordering::Relaxed), 1);
+        assert_eq!(REPORTED_JOBS_PROCESSED.lock().unwrap()[&7], 15);
+
+        let body = metrics::render();
+        assert!(body.contains("pdf_export_active_jobs 3\n"));
+        assert!(body.contains("pdf_export_oldest_job_age_seconds 2.500\n"));
+        assert!(body.contains("pdf_export_available_permits 1\n"));
+        assert!(body.contains("pdf_export_worker_jobs_processed_total{worker=\"7\"} 15\n"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_init_then_shutdown_telemetry() {
+        init_telemetry().unwrap();
+        shutdown_telemetry();
+
+        // A second shutdown has nothing left to flush
+        shutdown_telemetry();
+    }
 }
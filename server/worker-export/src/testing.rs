@@ -0,0 +1,182 @@
+//! Test doubles for code built on the job queue.
+//!
+//! Compiled with the `testing` feature (and in this crate's own tests).
+//! [`MockQueue`] implements [`Queue`] in memory, so code that enqueues or
+//! processes jobs through the trait can be unit-tested without Redis.
+
+use crate::job::{Clock, PdfExportJob, PRIORITY_HIGH};
+use crate::queue::{self, Queue, QueueConfig, QueueError};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// [`Clock`] that only moves when told to, for pinning job timestamps and
+/// PDF creation dates.
+#[derive(Debug)]
+pub struct ManualClock(Mutex<DateTime<Utc>>);
+
+impl ManualClock {
+    /// Creates a clock stopped at `start`.
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self(Mutex::new(start))
+    }
+
+    /// Moves the clock forward by `by`.
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.0.lock().unwrap() += by;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// In-memory [`Queue`].
+///
+/// Jobs are validated against the [`QueueConfig`] limits as
+/// [`JobQueue::enqueue`](crate::queue::JobQueue::enqueue) validates them,
+/// stamped with their checksum, and dequeued highest priority first, oldest
+/// first within a priority. Idempotency keys, rate limits, scheduling and
+/// acknowledgement are not modelled: a dequeued job is simply gone from the
+/// queue, and its status stays whatever was last stored.
+#[derive(Debug, Default)]
+pub struct MockQueue {
+    config: QueueConfig,
+    ready: VecDeque<PdfExportJob>,
+    statuses: HashMap<String, PdfExportJob>,
+}
+
+impl MockQueue {
+    /// Creates an empty queue with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the limits enqueued jobs are checked against.
+    pub fn with_config(mut self, config: QueueConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the jobs waiting to be dequeued, in enqueue order.
+    pub fn pending(&self) -> impl Iterator<Item = &PdfExportJob> {
+        self.ready.iter()
+    }
+
+    /// Returns the number of jobs waiting to be dequeued.
+    pub fn len(&self) -> usize {
+        self.ready.len()
+    }
+
+    /// Returns `true` if no job is waiting to be dequeued.
+    pub fn is_empty(&self) -> bool {
+        self.ready.is_empty()
+    }
+}
+
+impl Queue for MockQueue {
+    async fn enqueue(&mut self, job: &PdfExportJob) -> Result<String, QueueError> {
+        queue::check_job(&self.config, job)?;
+
+        let job = queue::prepare_for_enqueue(&self.config, job).into_owned();
+        self.statuses.insert(job.job_id.clone(), job.clone());
+        let job_id = job.job_id.clone();
+        self.ready.push_back(job);
+        Ok(job_id)
+    }
+
+    async fn dequeue(&mut self) -> Result<Option<PdfExportJob>, QueueError> {
+        let priority = |job: &PdfExportJob| job.effective_priority().min(PRIORITY_HIGH);
+        let Some(highest) = self.ready.iter().map(priority).max() else {
+            return Ok(None);
+        };
+        let index = self
+            .ready
+            .iter()
+            .position(|job| priority(job) == highest)
+            .expect("a job has the highest priority");
+        Ok(self.ready.remove(index))
+    }
+
+    async fn get_status(&mut self, job_id: &str) -> Result<Option<PdfExportJob>, QueueError> {
+        Ok(self.statuses.get(job_id).cloned())
+    }
+
+    async fn update_status(&mut self, job: &PdfExportJob) -> Result<(), QueueError> {
+        self.statuses.insert(job.job_id.clone(), job.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::{JobMetadata, JobStatus, PRIORITY_LOW};
+
+    fn test_job(document_id: &str, priority: u8) -> PdfExportJob {
+        let mut job = PdfExportJob::new(
+            document_id.to_string(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#.to_string(),
+            format!("/tmp/{}.pdf", document_id),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.priority = Some(priority);
+        job
+    }
+
+    /// Processes one job the way a worker does, through the trait only.
+    async fn process_next(queue: &mut impl Queue) -> Option<String> {
+        let mut job = queue.dequeue().await.unwrap()?;
+        job.start_processing();
+        queue.update_status(&job).await.unwrap();
+        job.mark_complete(128, 1, None);
+        queue.update_status(&job).await.unwrap();
+        Some(job.job_id)
+    }
+
+    #[tokio::test]
+    async fn test_mock_queue_enqueue_dequeue_status() {
+        let mut queue = MockQueue::new().with_config(QueueConfig {
+            max_svg_bytes: 1024,
+            ..QueueConfig::default()
+        });
+        let low = test_job("doc-low", PRIORITY_LOW);
+        let high = test_job("doc-high", PRIORITY_HIGH);
+
+        assert_eq!(queue.enqueue(&low).await.unwrap(), low.job_id);
+        assert_eq!(queue.enqueue(&high).await.unwrap(), high.job_id);
+        assert_eq!(queue.len(), 2);
+
+        let queued = queue.get_status(&low.job_id).await.unwrap().unwrap();
+        assert_eq!(queued.status, JobStatus::Queued);
+        assert!(queued.checksum.is_some());
+
+        // Higher priorities are served first
+        assert_eq!(process_next(&mut queue).await.unwrap(), high.job_id);
+        let done = queue.get_status(&high.job_id).await.unwrap().unwrap();
+        assert_eq!(done.status, JobStatus::Complete);
+        assert_eq!(done.output_bytes, Some(128));
+        assert_eq!(
+            queue.get_status(&low.job_id).await.unwrap().unwrap().status,
+            JobStatus::Queued
+        );
+
+        assert_eq!(process_next(&mut queue).await.unwrap(), low.job_id);
+        assert!(queue.is_empty());
+        assert!(process_next(&mut queue).await.is_none());
+        assert!(queue.get_status("missing").await.unwrap().is_none());
+
+        // Jobs JobQueue would refuse are refused too
+        let mut oversized = test_job("doc-oversized", PRIORITY_LOW);
+        oversized.svg_content = "<svg/>".repeat(200);
+        let err = queue.enqueue(&oversized).await.unwrap_err();
+        assert!(matches!(err, QueueError::InvalidJob { .. }));
+        assert!(queue.get_status(&oversized.job_id).await.unwrap().is_none());
+    }
+}
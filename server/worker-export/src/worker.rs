@@ -0,0 +1,2359 @@
+//! Worker loop and job processing for the export service.
+
+use crate::converter::{
+    decode_svg, AbandonedConversion, ConvertError, PdfMetadata, SvgToPdfConverter,
+};
+use crate::events::JobEventKind;
+use crate::job::{ErrorKind, OutputFormat, PdfExportJob, SvgSource};
+use crate::metrics;
+use crate::output::OutputConfig;
+use crate::queue::{JobQueue, MissingHeartbeats, QueueError};
+use crate::sink;
+use crate::telemetry::{self, WorkerStats};
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+/// Pixels per SVG user unit for PNG output.
+const PNG_SCALE: f32 = 1.0;
+
+/// How often the promoter task moves due scheduled jobs onto their queues.
+pub const PROMOTE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often an idle worker checks whether a paused queue was resumed.
+pub const PAUSE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often a worker refreshes its heartbeat and looks for dead workers.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a heartbeat lasts; a worker silent for this long is presumed
+/// dead and its jobs re-queued.
+pub const HEARTBEAT_TTL: Duration = Duration::from_secs(30);
+
+/// How often a running conversion checks whether its job was cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Upper bound on the wait between attempts to reach an unavailable Redis.
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How often a worker checks for a new concurrency target.
+pub const CONCURRENCY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a job is put back for when its document already has as many
+/// jobs running as [`InFlightJobs::with_document_limit`] allows.
+const DOCUMENT_DEFER_DELAY: Duration = Duration::from_secs(1);
+
+/// How long a worker takes no jobs after an output write fails with a full
+/// disk, and how long the job that hit it is put back for. The next job
+/// dequeued after that finds out whether the disk has room again.
+pub const DISK_FULL_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Runtime configuration shared by all worker loops.
+#[derive(Debug, Clone)]
+pub struct WorkerConfig {
+    /// Maximum number of jobs processed concurrently.
+    pub concurrency: usize,
+    /// Deadline for a single job's conversion.
+    pub conversion_timeout: Duration,
+    /// How long shutdown waits for in-flight jobs before re-queueing them,
+    /// capped at `conversion_timeout` (see [`drain_timeout`](Self::drain_timeout)).
+    pub shutdown_grace: Duration,
+    /// Directory job output is confined to.
+    pub output: OutputConfig,
+}
+
+impl Default for WorkerConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            conversion_timeout: Duration::from_secs(60),
+            shutdown_grace: Duration::from_secs(30),
+            output: OutputConfig::default(),
+        }
+    }
+}
+
+impl WorkerConfig {
+    /// How long shutdown waits for in-flight jobs: the grace period, but no
+    /// longer than a conversion is allowed to take, since a job still
+    /// running by then is stuck and better re-queued.
+    pub fn drain_timeout(&self) -> Duration {
+        self.shutdown_grace.min(self.conversion_timeout)
+    }
+
+    /// How long a job may stay in flight before the worker counts as stuck:
+    /// the conversion deadline, plus a heartbeat's lifetime to record the
+    /// outcome. See [`heartbeat_loop`].
+    pub fn stall_timeout(&self) -> Duration {
+        self.conversion_timeout + HEARTBEAT_TTL
+    }
+}
+
+/// Escalating wait between dequeue attempts while Redis is unreachable.
+///
+/// Each consecutive connection failure doubles the wait, starting from the
+/// queue's error backoff and capped at [`MAX_RECONNECT_BACKOFF`], so a long
+/// outage isn't met with a steady stream of reconnects. The first
+/// successful call resets it.
+#[derive(Debug, Clone)]
+pub struct ReconnectBackoff {
+    base: Duration,
+    failures: u32,
+}
+
+impl ReconnectBackoff {
+    /// Creates a backoff whose first wait is `base`.
+    pub fn new(base: Duration) -> Self {
+        Self { base, failures: 0 }
+    }
+
+    /// Returns the number of consecutive connection failures.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Records a connection failure and returns how long to wait before
+    /// trying again.
+    pub fn on_failure(&mut self) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        let factor = 2u32.saturating_pow(self.failures - 1);
+        self.base.saturating_mul(factor).min(MAX_RECONNECT_BACKOFF)
+    }
+
+    /// Records a successful call, returning how many failures it ended.
+    pub fn on_success(&mut self) -> u32 {
+        std::mem::take(&mut self.failures)
+    }
+}
+
+/// The number of jobs processed at once, shared by every worker loop in the
+/// process and adjustable while they run.
+///
+/// Raising the limit frees slots immediately. Lowering it retires free
+/// slots at once and busy ones as their jobs finish, so running jobs are
+/// never interrupted.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    state: Arc<Mutex<LimitState>>,
+    initial: usize,
+    max: usize,
+}
+
+#[derive(Debug)]
+struct LimitState {
+    limit: usize,
+    /// Busy slots to retire when their jobs finish.
+    owed: usize,
+}
+
+impl ConcurrencyLimit {
+    /// Creates a limit of `initial` slots that
+    /// [`set_limit`](Self::set_limit) can move between 1 and `max`.
+    pub fn new(initial: usize, max: usize) -> Self {
+        let max = max.max(1);
+        let initial = initial.clamp(1, max);
+        Self {
+            semaphore: Arc::new(Semaphore::new(initial)),
+            state: Arc::new(Mutex::new(LimitState { limit: initial, owed: 0 })),
+            initial,
+            max,
+        }
+    }
+
+    /// Returns the limit the process started with.
+    pub fn initial(&self) -> usize {
+        self.initial
+    }
+
+    /// Returns the current limit.
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit
+    }
+
+    /// Returns the number of slots in existence, which exceeds
+    /// [`limit`](Self::limit) while a reduction waits for jobs to finish.
+    pub fn permits(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.limit + state.owed
+    }
+
+    /// Returns the semaphore the slots are drawn from.
+    pub fn semaphore(&self) -> &Semaphore {
+        &self.semaphore
+    }
+
+    /// Waits for a free slot.
+    pub async fn acquire(&self) -> ConcurrencyPermit {
+        let permit = self.semaphore.clone().acquire_owned().await.unwrap();
+        ConcurrencyPermit {
+            permit: Some(permit),
+            state: self.state.clone(),
+        }
+    }
+
+    /// Changes the limit to `limit`, clamped to `1..=max`, and returns the
+    /// limit applied.
+    pub fn set_limit(&self, limit: usize) -> usize {
+        let limit = limit.clamp(1, self.max);
+        let mut state = self.state.lock().unwrap();
+        if limit > state.limit {
+            // Keep slots that were due to be retired before adding new ones
+            let added = limit - state.limit;
+            let kept = added.min(state.owed);
+            state.owed -= kept;
+            self.semaphore.add_permits(added - kept);
+        } else {
+            let removed = state.limit - limit;
+            let retired = self.semaphore.forget_permits(removed);
+            state.owed += removed - retired;
+        }
+        state.limit = limit;
+        limit
+    }
+}
+
+/// A slot taken from a [`ConcurrencyLimit`], returned on drop unless the
+/// limit was lowered meanwhile.
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    state: Arc<Mutex<LimitState>>,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        // Released under the lock so a concurrent reduction can't miss it
+        let mut state = self.state.lock().unwrap();
+        if state.owed > 0 {
+            state.owed -= 1;
+            permit.forget();
+        } else {
+            drop(permit);
+        }
+    }
+}
+
+/// Per-document concurrency caps, shared by every worker loop in the
+/// process.
+///
+/// Each document with jobs running gets a semaphore of `limit` permits.
+/// Semaphores are dropped as soon as their last permit is returned, so the
+/// map only ever holds documents that are currently being processed.
+#[derive(Clone, Default)]
+pub struct DocumentLimiter {
+    limit: Option<usize>,
+    semaphores: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl DocumentLimiter {
+    /// Creates a limiter allowing `limit` concurrent jobs per document, or
+    /// any number when `None`.
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            semaphores: Arc::default(),
+        }
+    }
+
+    /// Takes a slot for `document_id` without waiting, or returns `None` if
+    /// the document is at its cap. Always succeeds without a cap.
+    pub fn try_acquire(&self, document_id: &str) -> Option<DocumentPermit> {
+        let Some(limit) = self.limit else {
+            return Some(DocumentPermit {
+                permit: None,
+                document_id: document_id.to_string(),
+                semaphores: self.semaphores.clone(),
+            });
+        };
+
+        let mut semaphores = self.semaphores.lock().unwrap();
+        let semaphore = semaphores
+            .entry(document_id.to_string())
+            .or_insert_with(|| Arc::new(Semaphore::new(limit)))
+            .clone();
+        let permit = semaphore.try_acquire_owned().ok()?;
+        Some(DocumentPermit {
+            permit: Some(permit),
+            document_id: document_id.to_string(),
+            semaphores: self.semaphores.clone(),
+        })
+    }
+
+    /// Returns the number of documents with jobs holding a slot.
+    pub fn len(&self) -> usize {
+        self.semaphores.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no document holds a slot.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A slot taken from a [`DocumentLimiter`], returned on drop.
+pub struct DocumentPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    document_id: String,
+    semaphores: Arc<Mutex<HashMap<String, Arc<Semaphore>>>>,
+}
+
+impl Drop for DocumentPermit {
+    fn drop(&mut self) {
+        let Some(permit) = self.permit.take() else {
+            return;
+        };
+        // Permits are only taken under the lock, so once the map holds the
+        // last reference no other job is using or about to use the semaphore
+        let mut semaphores = self.semaphores.lock().unwrap();
+        drop(permit);
+        if semaphores
+            .get(&self.document_id)
+            .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+        {
+            semaphores.remove(&self.document_id);
+        }
+    }
+}
+
+/// Tracks jobs that have been dequeued and are currently being processed.
+///
+/// On shutdown, [`drain`](Self::drain) waits for these jobs to finish and
+/// re-queues any that are still running once the grace period elapses, so
+/// no job is left stuck in `Processing`.
+#[derive(Clone, Default)]
+pub struct InFlightJobs {
+    tracker: TaskTracker,
+    jobs: Arc<Mutex<HashMap<String, (PdfExportJob, Instant)>>>,
+    documents: DocumentLimiter,
+}
+
+impl InFlightJobs {
+    /// Creates an empty in-flight job registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the jobs of any one document in flight at once, so a large
+    /// document's artboards can't take every slot. `None` (the default)
+    /// means no cap.
+    pub fn with_document_limit(mut self, limit: Option<usize>) -> Self {
+        self.documents = DocumentLimiter::new(limit);
+        self
+    }
+
+    /// Takes a slot for a job of `document_id`, or returns `None` if the
+    /// document is at its cap.
+    pub fn try_acquire_document(&self, document_id: &str) -> Option<DocumentPermit> {
+        self.documents.try_acquire(document_id)
+    }
+
+    /// Spawns `future` as the processing task for `job`.
+    ///
+    /// The job is registered until the task completes.
+    pub fn spawn<F>(&self, job: PdfExportJob, future: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        let job_id = job.job_id.clone();
+        self.jobs.lock().unwrap().insert(job_id.clone(), (job, Instant::now()));
+
+        let jobs = self.jobs.clone();
+        self.tracker.spawn(async move {
+            future.await;
+            jobs.lock().unwrap().remove(&job_id);
+        });
+    }
+
+    /// Stops counting `job_id` as in flight while its task runs on, once
+    /// the job is finished and only its slot is still held, so it is
+    /// neither re-queued on shutdown nor taken for a stall.
+    pub fn release(&self, job_id: &str) {
+        self.jobs.lock().unwrap().remove(job_id);
+    }
+
+    /// Returns the number of jobs currently in flight.
+    pub fn len(&self) -> usize {
+        self.jobs.lock().unwrap().len()
+    }
+
+    /// Returns `true` if no jobs are in flight.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns how long the longest-running job in flight has been running,
+    /// or `None` if there are none.
+    pub fn longest_running(&self) -> Option<Duration> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|(_, started)| started.elapsed())
+            .max()
+    }
+
+    /// Returns `true` if a job has been in flight longer than
+    /// `stall_timeout`, so the worker is stuck rather than busy.
+    pub fn is_stalled(&self, stall_timeout: Duration) -> bool {
+        self.longest_running().is_some_and(|running| running > stall_timeout)
+    }
+
+    /// Waits up to `grace` for in-flight jobs to finish, then re-queues any
+    /// that are still running.
+    ///
+    /// Returns the number of jobs that were re-queued.
+    pub async fn drain(&self, grace: Duration, queue: &mut JobQueue) -> usize {
+        self.tracker.close();
+
+        if tokio::time::timeout(grace, self.tracker.wait()).await.is_ok() {
+            info!("All in-flight jobs finished");
+            return 0;
+        }
+
+        let remaining: Vec<PdfExportJob> =
+            self.jobs.lock().unwrap().drain().map(|(_, (job, _))| job).collect();
+        warn!(
+            "Shutdown grace period ({:?}) elapsed with {} job(s) in flight, re-queueing",
+            grace,
+            remaining.len()
+        );
+
+        let mut requeued = 0;
+        for mut job in remaining {
+            job.requeue();
+            match queue.enqueue(&job).await {
+                Ok(_) => requeued += 1,
+                Err(e) => {
+                    // Leave it on the processing list for startup recovery
+                    error!("Failed to re-queue job {}: {}", job.job_id, e);
+                    continue;
+                }
+            }
+            if let Err(e) = queue.ack(&job.job_id).await {
+                error!("Failed to acknowledge re-queued job {}: {}", job.job_id, e);
+            }
+        }
+        requeued
+    }
+}
+
+/// Main worker loop that processes jobs from the queue.
+///
+/// `queue` should carry the worker instance's consumer id so dequeued jobs
+/// land on its processing list. Runs until `shutdown` is cancelled. Cancellation is checked between
+/// dequeue attempts, so a worker stops within one blocking-pop timeout and
+/// never abandons a job it has already popped.
+///
+/// `limit` is shared by every worker loop in the process and bounds the
+/// jobs in flight: a slot is taken before dequeueing and held until the
+/// job finishes, so no job is popped off the queue while every slot is
+/// busy. `in_flight` is shared the same way and
+/// enforces its per-document cap (see
+/// [`with_document_limit`](InFlightJobs::with_document_limit)): a job whose
+/// document is at its cap is put back on the queue for a moment and its
+/// slot given to the next job.
+///
+/// While Redis is unreachable, dequeue attempts back off exponentially
+/// (see [`ReconnectBackoff`]) and the readiness probe reports the worker
+/// unavailable until a call succeeds again.
+///
+/// While the queue is paused (see [`JobQueue::pause`]) the loop takes no
+/// jobs and checks the flag every [`PAUSE_POLL_INTERVAL`] instead; jobs
+/// already running are unaffected. Likewise, no jobs are taken for
+/// [`DISK_FULL_BACKOFF`] after an output write fails with a full disk, or
+/// while a job is stuck in flight (see [`InFlightJobs::is_stalled`]), since
+/// other workers take this one's jobs back once its heartbeat lapses.
+pub async fn worker_loop(
+    worker_id: usize,
+    mut queue: JobQueue,
+    limit: ConcurrencyLimit,
+    converter: Arc<SvgToPdfConverter>,
+    config: WorkerConfig,
+    shutdown: CancellationToken,
+    in_flight: InFlightJobs,
+) {
+    info!("Worker {} started", worker_id);
+    let mut backoff = ReconnectBackoff::new(queue.config().error_backoff);
+    let jobs_processed = Arc::new(AtomicU64::new(0));
+    let mut paused = false;
+
+    while !shutdown.is_cancelled() {
+        // Take no new jobs while the queue is paused; running ones finish
+        match queue.is_paused().await {
+            Ok(true) => {
+                if !paused {
+                    info!("Worker {} paused", worker_id);
+                    paused = true;
+                }
+                tokio::select! {
+                    () = tokio::time::sleep(PAUSE_POLL_INTERVAL) => {}
+                    () = shutdown.cancelled() => {}
+                }
+                continue;
+            }
+            Ok(false) if paused => {
+                info!("Worker {} resumed", worker_id);
+                paused = false;
+            }
+            Ok(false) => {}
+            // Dequeueing will fail too and back off
+            Err(e) => warn!("Worker {} failed to read the pause flag: {}", worker_id, e),
+        }
+
+        // A stuck worker stops heartbeating; its jobs would be taken back
+        if in_flight.is_stalled(config.stall_timeout()) {
+            tokio::select! {
+                () = tokio::time::sleep(PAUSE_POLL_INTERVAL) => {}
+                () = shutdown.cancelled() => {}
+            }
+            continue;
+        }
+
+        // Leave jobs on the queue while the output disk is full
+        if let Some(since) = metrics::disk_full_since() {
+            let remaining = DISK_FULL_BACKOFF.saturating_sub(since.elapsed());
+            if !remaining.is_zero() {
+                tokio::select! {
+                    () = tokio::time::sleep(remaining) => {}
+                    () = shutdown.cancelled() => {}
+                }
+                continue;
+            }
+        }
+
+        // Wait for a free slot before taking a job off the queue
+        let permit = tokio::select! {
+            permit = limit.acquire() => permit,
+            () = shutdown.cancelled() => break,
+        };
+
+        // Dequeue next job (blocks with timeout); an idle attempt releases
+        // the permit
+        let dequeued = queue.dequeue().await;
+        if dequeued.is_ok() {
+            let failures = backoff.on_success();
+            if failures > 0 {
+                info!(
+                    "Worker {} reconnected to Redis after {} failed attempt(s)",
+                    worker_id, failures
+                );
+            }
+            metrics::set_redis_connected(true);
+        }
+
+        let job = match dequeued {
+            Ok(Some(job)) => job,
+            Ok(None) => {
+                // Timeout, no job available
+                continue;
+            }
+            Err(e) if e.is_connection_error() => {
+                // Don't hold a slot while backing off
+                drop(permit);
+                let delay = backoff.on_failure();
+                metrics::set_redis_connected(false);
+                metrics::record_reconnect_attempt();
+                warn!(
+                    "Worker {} lost its Redis connection: {}; retrying in {:?} (attempt {})",
+                    worker_id,
+                    e,
+                    delay,
+                    backoff.failures()
+                );
+                tokio::select! {
+                    () = tokio::time::sleep(delay) => {}
+                    () = shutdown.cancelled() => {}
+                }
+                continue;
+            }
+            Err(e @ QueueError::CorruptedPayload { .. }) => {
+                // Already dead-lettered; Redis is fine, so move straight on
+                error!("Worker {} discarded a job: {}", worker_id, e);
+                continue;
+            }
+            Err(e) => {
+                error!("Worker {} failed to dequeue job: {}", worker_id, e);
+                drop(permit);
+                tokio::select! {
+                    () = tokio::time::sleep(queue.config().error_backoff) => {}
+                    () = shutdown.cancelled() => {}
+                }
+                continue;
+            }
+        };
+
+        // Let other documents have the slot while this one is at its cap
+        let Some(document_permit) = in_flight.try_acquire_document(&job.document_id) else {
+            defer_for_document(&job, &mut queue).await;
+            continue;
+        };
+
+        // Spawn job processing task
+        let mut queue_clone = queue.clone();
+        let converter = converter.clone();
+        let conversion_timeout = config.conversion_timeout;
+        let output = config.output.clone();
+        let processed = jobs_processed.clone();
+
+        let jobs = in_flight.clone();
+        in_flight.spawn(job.clone(), async move {
+            let mut process_queue = queue_clone.clone();
+            let process = {
+                let job = job.clone();
+                async move {
+                    let job_id = job.job_id.clone();
+                    let abandoned =
+                        process_job(job, &mut process_queue, &converter, conversion_timeout, &output)
+                            .await;
+                    if let Some(abandoned) = abandoned {
+                        // The job is finished, but its conversion still has
+                        // the CPU the slot stands for
+                        jobs.release(&job_id);
+                        abandoned.finished().await;
+                    }
+                }
+            };
+            run_isolated(job, &mut queue_clone, process).await;
+            processed.fetch_add(1, Ordering::Relaxed);
+            drop(document_permit);
+            drop(permit); // Release the slot
+        });
+
+        // Record heartbeat every 10 jobs
+        if let Ok(queue_len) = queue.queue_length().await {
+            metrics::set_queue_length(queue_len);
+            if queue_len % 10 == 0 {
+                let stats = WorkerStats::sample(
+                    worker_id,
+                    limit.semaphore(),
+                    limit.permits(),
+                    jobs_processed.load(Ordering::Relaxed),
+                );
+                telemetry::record_worker_heartbeat(queue_len, stats);
+            }
+        }
+    }
+
+    info!("Worker {} stopped", worker_id);
+}
+
+/// Puts back a job whose document is at its concurrency cap, to be picked
+/// up again after [`DOCUMENT_DEFER_DELAY`] without using up a retry.
+async fn defer_for_document(job: &PdfExportJob, queue: &mut JobQueue) {
+    info!(
+        "Document at its concurrency cap, deferring job: job_id={}, document_id={}",
+        job.job_id, job.document_id
+    );
+    if let Err(e) = queue.defer(job, DOCUMENT_DEFER_DELAY).await {
+        // Left on the processing list for startup recovery
+        error!("Failed to defer job {}: {}", job.job_id, e);
+    }
+}
+
+/// Puts back a job whose output didn't fit on the disk, to run again after
+/// [`DISK_FULL_BACKOFF`] without using up a retry.
+async fn defer_for_disk_full(job: &PdfExportJob, queue: &mut JobQueue) {
+    if let Err(e) = queue.defer(job, DISK_FULL_BACKOFF).await {
+        // Left on the processing list for startup recovery
+        error!("Failed to defer job {}: {}", job.job_id, e);
+    }
+}
+
+/// Runs `process` (the processing of `job`) on its own task so a panic
+/// can't take down the caller.
+///
+/// A panic is turned into a job failure: the job is marked failed, retried
+/// if it has retries left, and acknowledged, so it never stays stuck in
+/// `Processing`.
+pub async fn run_isolated<F>(mut job: PdfExportJob, queue: &mut JobQueue, process: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let Err(e) = tokio::spawn(process).await else {
+        return;
+    };
+    let reason = if e.is_panic() {
+        panic_message(&*e.into_panic())
+    } else {
+        e.to_string()
+    };
+    error!("Job processing panicked: job_id={}, panic={}", job.job_id, reason);
+
+    job.mark_failed(format!("Worker panicked: {}", reason));
+    retry_or_fail(&mut job, queue).await;
+    telemetry::record_job_telemetry(&job);
+    if let Err(e) = queue.ack(&job.job_id).await {
+        error!("Failed to acknowledge job {}: {}", job.job_id, e);
+    }
+}
+
+/// The message a panic was raised with, from its payload.
+pub fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Moves due delayed and scheduled jobs onto their queues every
+/// `interval` until `shutdown` is cancelled.
+///
+/// Dequeues promote due jobs too, but only when a worker is free to ask;
+/// this task keeps promotion timely (and queue depth metrics accurate) while
+/// every worker is busy. Promotion is atomic per job, so any number of
+/// promoters and workers can run against the same queue.
+pub async fn promoter_loop(mut queue: JobQueue, interval: Duration, shutdown: CancellationToken) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            () = shutdown.cancelled() => break,
+        }
+        if let Err(e) = queue.promote_delayed().await {
+            warn!("Failed to promote delayed jobs: {}", e);
+        }
+    }
+}
+
+/// Refreshes the worker's heartbeat every `interval` (lasting `ttl`) and
+/// re-queues the jobs of workers whose heartbeat has been missing for
+/// `ttl`, until `shutdown` is cancelled.
+///
+/// The heartbeat follows job progress: it lapses while a job in
+/// `in_flight` has been running longer than `stall_timeout` (see
+/// [`WorkerConfig::stall_timeout`]), so the jobs of a worker that hung
+/// mid-job are taken back even though the process is still alive, and
+/// resumes once the job ends.
+pub async fn heartbeat_loop(
+    mut queue: JobQueue,
+    in_flight: InFlightJobs,
+    interval: Duration,
+    ttl: Duration,
+    stall_timeout: Duration,
+    shutdown: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    let mut missing = MissingHeartbeats::default();
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            () = shutdown.cancelled() => break,
+        }
+        if let Some(running) = in_flight.longest_running().filter(|&r| r > stall_timeout) {
+            warn!(
+                "A job has been running for {:?}, past {:?}; letting the heartbeat lapse",
+                running, stall_timeout
+            );
+        } else if let Err(e) = queue.heartbeat(ttl).await {
+            warn!("Failed to write worker heartbeat: {}", e);
+            continue;
+        }
+        if let Err(e) = queue.reap_dead_workers(&mut missing, ttl).await {
+            warn!("Failed to re-queue jobs of dead workers: {}", e);
+        }
+    }
+}
+
+/// Applies the concurrency target set with
+/// [`JobQueue::set_concurrency_target`] to `limit`, checking every
+/// `interval` until `shutdown` is cancelled. Clearing the target returns
+/// `limit` to its initial value.
+pub async fn concurrency_loop(
+    mut queue: JobQueue,
+    limit: ConcurrencyLimit,
+    interval: Duration,
+    shutdown: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            () = shutdown.cancelled() => break,
+        }
+        let target = match queue.concurrency_target().await {
+            Ok(target) => target.unwrap_or(limit.initial()),
+            Err(e) => {
+                warn!("Failed to read concurrency target: {}", e);
+                continue;
+            }
+        };
+        let current = limit.limit();
+        if target != current {
+            let applied = limit.set_limit(target);
+            if applied != current {
+                info!("Concurrency limit changed from {} to {}", current, applied);
+            }
+        }
+    }
+}
+
+/// Processes a single PDF export job.
+///
+/// This function handles the complete job lifecycle:
+/// 1. Mark job as processing, unless it was cancelled while queued or its
+///    `expires_at` has passed (see [`PdfExportJob::is_expired`]), with
+///    its output path named by `output`'s template, if any, and confined to
+///    its root (see [`OutputConfig::resolve`]) unless it is an `s3://`
+///    object (see [`OutputConfig::sink_for`]), and any referenced SVG
+///    loaded (see [`load_pages`])
+/// 2. Convert SVG to PDF, writing the output through the chosen sink
+///    (bounded by `conversion_timeout`, abandoned if the job is cancelled
+///    meanwhile)
+/// 3. Mark job as complete, failed or cancelled
+/// 4. Record telemetry
+/// 5. Retry transient failures (up to the job's `max_retries`); invalid
+///    SVGs fail immediately
+/// 6. Acknowledge the dequeued job
+///
+/// Each step is reported to the queue's event sink (see
+/// [`JobQueue::with_event_sink`]), and everything logged along the way,
+/// conversion included, is recorded in the job's `process_job` span (see
+/// [`job_span`]).
+///
+/// A conversion abandoned when the job is cancelled or times out writes no
+/// output, but can't be interrupted; its thread is returned so the caller
+/// can hold the job's slot until it is free.
+pub async fn process_job(
+    job: PdfExportJob,
+    queue: &mut JobQueue,
+    converter: &SvgToPdfConverter,
+    conversion_timeout: Duration,
+    output: &OutputConfig,
+) -> Option<AbandonedConversion> {
+    let span = job_span(&job);
+    process_job_in_span(job, queue, converter, conversion_timeout, output)
+        .instrument(span)
+        .await
+}
+
+/// Returns the span a job is processed in, carrying the job's identity,
+/// attempt and size so every log line of the job can be correlated.
+///
+/// The SVG itself is never recorded, only its length: `svg_bytes` is the
+/// inline content at first, and is updated once referenced SVG is loaded.
+pub fn job_span(job: &PdfExportJob) -> Span {
+    info_span!(
+        "process_job",
+        job_id = %job.job_id,
+        document_id = %job.document_id,
+        retry_count = job.retry_count,
+        priority = job.effective_priority(),
+        export_scope = %job.metadata.export_scope,
+        svg_bytes = job.pages().iter().map(|page| page.len()).sum::<usize>(),
+    )
+}
+
+async fn process_job_in_span(
+    mut job: PdfExportJob,
+    queue: &mut JobQueue,
+    converter: &SvgToPdfConverter,
+    conversion_timeout: Duration,
+    output: &OutputConfig,
+) -> Option<AbandonedConversion> {
+    info!(
+        "Processing job: job_id={}, document_id={}",
+        job.job_id, job.document_id
+    );
+
+    // Skip jobs cancelled before a worker picked them up
+    if queue.is_cancelled(&job.job_id).await.unwrap_or(false) {
+        finish_cancelled(job, queue).await;
+        return None;
+    }
+
+    // Skip jobs nobody is waiting for anymore
+    if job.is_expired() {
+        finish_expired(job, queue).await;
+        return None;
+    }
+
+    // Server-side naming replaces the client's path
+    job.output_path = output.output_path(&job);
+
+    // Never write one format under another's extension
+    if let Err(e) = job.check_output_extension() {
+        fail_before_conversion(job, queue, e.to_string(), ErrorKind::OutputExtension).await;
+        return None;
+    }
+
+    // Upload s3:// paths to object storage, write the rest locally
+    let sink = match output.sink_for(&job.output_path) {
+        Ok(sink) => sink,
+        Err(e) => {
+            fail_before_conversion(job, queue, e.to_string(), ErrorKind::OutputLocation).await;
+            return None;
+        }
+    };
+
+    // Never write outside the output root
+    if !sink::is_s3_url(&job.output_path) {
+        match output.resolve(&job) {
+            Ok(path) => {
+                // Templates can name directories that don't exist yet
+                if let Some(parent) = path.parent() {
+                    if let Err(e) = tokio::fs::create_dir_all(parent).await {
+                        let message = format!("Failed to create {}: {}", parent.display(), e);
+                        fail_before_conversion(job, queue, message, ErrorKind::Io).await;
+                        return None;
+                    }
+                }
+                job.output_path = path.to_string_lossy().into_owned();
+            }
+            Err(e) => {
+                // The root itself is missing, e.g. an unmounted volume
+                fail_before_conversion(job, queue, e.to_string(), ErrorKind::Io).await;
+                return None;
+            }
+        }
+    }
+
+    // Fetch SVG held outside the job
+    let pages = match load_pages(&job, queue, output).await {
+        Ok(pages) => {
+            let svg_bytes: usize = pages.iter().map(String::len).sum();
+            Span::current().record("svg_bytes", svg_bytes);
+            pages
+        }
+        Err(e) => {
+            fail_before_conversion(job, queue, e.to_string(), e.kind).await;
+            return None;
+        }
+    };
+
+    // Mark as processing
+    job.worker_id = Some(queue.consumer_id().to_string());
+    job.start_processing();
+    if let Err(e) = queue.update_status(&job).await {
+        error!("Failed to update job status: {}", e);
+    }
+    queue.emit_event(JobEventKind::Started, &job);
+
+    // Convert SVG to PDF
+    let converter = converter.clone().with_output_sink(sink);
+    let options = converter
+        .options()
+        .with_metadata(PdfMetadata {
+            title: Some(job.document_id.clone()),
+            author: job.metadata.user_id.clone(),
+            creator: Some(format!("WireTuner {}", job.metadata.client_version)),
+            ..Default::default()
+        })
+        .with_page_labels(job.page_labels());
+    let output_path = job.output_path.clone();
+    let output_format = job.output_format;
+    let (progress_tx, progress_rx) = tokio::sync::watch::channel(0u8);
+    let progress = tokio::spawn(
+        report_progress(queue.clone(), job.job_id.clone(), progress_rx).in_current_span(),
+    );
+    let mut conversion = converter.spawn_conversion(move |converter| {
+        let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+        let render_started = Instant::now();
+        let (output_bytes, page_count, output_sha256, warnings) = match output_format {
+            OutputFormat::Pdf => {
+                let report = converter.convert_multi_with_report(
+                    &pages,
+                    &output_path,
+                    &options,
+                    |done, total| {
+                        let pct = (done * 100 / total) as u8;
+                        // The last page is reported as complete, not as progress
+                        if pct < 100 {
+                            progress_tx.send_replace(pct);
+                        }
+                    },
+                )?;
+                (
+                    report.output_bytes as u64,
+                    pages.len() as u32,
+                    Some(report.output_sha256),
+                    report.warnings,
+                )
+            }
+            OutputFormat::Png => {
+                if pages.len() > 1 {
+                    warn!("PNG output renders only the first of {} pages", pages.len());
+                }
+                let output_bytes = converter.convert_to_png(pages[0], &output_path, PNG_SCALE)?;
+                (output_bytes as u64, 1, None, Vec::new())
+            }
+            OutputFormat::Eps => {
+                if pages.len() > 1 {
+                    warn!("EPS output renders only the first of {} pages", pages.len());
+                }
+                let output_bytes = converter.convert_to_eps(pages[0], &output_path)?;
+                (output_bytes as u64, 1, None, Vec::new())
+            }
+        };
+        Ok((output_bytes, page_count, output_sha256, warnings, render_started.elapsed()))
+    });
+    let mut cancel_queue = queue.clone();
+    let started = Instant::now();
+    let result = tokio::select! {
+        result = conversion.wait(conversion_timeout) => Some(result),
+        () = wait_for_cancellation(&mut cancel_queue, &job.job_id) => None,
+    };
+
+    // Stop reporting before writing the final status
+    progress.abort();
+    let _ = progress.await;
+
+    let Some(result) = result else {
+        // The blocking conversion can't be interrupted; stop it writing and
+        // leave its thread to the caller
+        let abandoned = conversion.abandon();
+        finish_cancelled(job, queue).await;
+        return abandoned;
+    };
+    metrics::observe_conversion_duration(started.elapsed());
+
+    match result {
+        Ok((output_bytes, page_count, output_sha256, warnings, render_duration)) => {
+            // Mark as complete
+            job.warnings = warnings.iter().map(ToString::to_string).collect();
+            job.render_duration_ms = Some(render_duration.as_millis() as i64);
+            job.mark_complete(output_bytes, page_count, output_sha256);
+            // Output was written, so the disk has room again
+            metrics::set_disk_full(false);
+            if let Err(e) = queue.update_status(&job).await {
+                error!("Failed to update job status: {}", e);
+            }
+            queue.emit_event(JobEventKind::Completed, &job);
+
+            info!(
+                "Job completed: job_id={}, duration_ms={:?}, render_duration_ms={}, output_bytes={}, page_count={}",
+                job.job_id,
+                job.processing_duration_ms(),
+                render_duration.as_millis(),
+                output_bytes,
+                page_count
+            );
+        }
+        Err(e) if e.is_disk_full() => {
+            // The disk won't clear by retrying; keep the job until it does
+            telemetry::record_disk_full(&job);
+            job.requeue();
+            defer_for_disk_full(&job, queue).await;
+            return None;
+        }
+        Err(e) => {
+            // Mark as failed
+            error!("Job failed: job_id={}, error={}", job.job_id, e);
+
+            job.mark_conversion_failed(&e);
+
+            retry_or_fail(&mut job, queue).await;
+        }
+    }
+
+    // Record telemetry
+    telemetry::record_job_telemetry(&job);
+
+    // Remove from the processing list; retries were acknowledged as they
+    // were scheduled
+    if let Err(e) = queue.ack(&job.job_id).await {
+        error!("Failed to acknowledge job {}: {}", job.job_id, e);
+    }
+
+    // Still running only if it timed out
+    conversion.abandon()
+}
+
+/// Referenced SVG that couldn't be loaded, with the kind of failure to
+/// record on the job.
+#[derive(Debug, Error)]
+#[error("{message}")]
+pub struct LoadError {
+    pub message: String,
+    pub kind: ErrorKind,
+}
+
+impl LoadError {
+    fn new(message: impl Into<String>, kind: ErrorKind) -> Self {
+        Self {
+            message: message.into(),
+            kind,
+        }
+    }
+}
+
+impl From<ConvertError> for LoadError {
+    fn from(error: ConvertError) -> Self {
+        Self::new(error.to_string(), ErrorKind::from(&error))
+    }
+}
+
+/// Returns the SVG for each page of `job`, loading it from Redis or disk
+/// when the job references its SVG via [`SvgSource`] instead of carrying
+/// it. Referenced SVG may be gzip-compressed (`.svgz`).
+///
+/// Files are only read from under `output`'s input root, and no more of
+/// them than the SVG size limit allows.
+///
+/// # Errors
+///
+/// Returns a [`LoadError`] if referenced SVG can't be read or decoded. A
+/// missing Redis key or a file outside the input root is
+/// [`ErrorKind::SourceUnavailable`], and not retried; a Redis or file read
+/// error is [`ErrorKind::Io`].
+pub async fn load_pages(
+    job: &PdfExportJob,
+    queue: &mut JobQueue,
+    output: &OutputConfig,
+) -> Result<Vec<String>, LoadError> {
+    let source = match &job.svg_source {
+        // Artboards always carry their own SVG
+        Some(source) if job.artboards.is_empty() => source,
+        _ => return Ok(job.pages().into_iter().map(str::to_string).collect()),
+    };
+
+    let max_svg_bytes = queue.config().max_svg_bytes;
+    let data = match source {
+        SvgSource::Inline(svg) => return Ok(vec![svg.clone()]),
+        SvgSource::RedisKey(key) => queue
+            .fetch_svg(key)
+            .await
+            .map_err(|e| LoadError::new(e.to_string(), ErrorKind::Io))?
+            .ok_or_else(|| {
+                LoadError::new(format!("SVG key {} not found", key), ErrorKind::SourceUnavailable)
+            })?,
+        SvgSource::FilePath(path) => {
+            let path = output
+                .validate_input(path)
+                .map_err(|e| LoadError::new(e.to_string(), ErrorKind::SourceUnavailable))?;
+            read_svg_file(&path, max_svg_bytes).await?
+        }
+    };
+    let svg = decode_svg(&data, max_svg_bytes)?;
+    Ok(vec![svg.into_owned()])
+}
+
+/// Reads an SVG file, refusing one larger than `max_svg_bytes` before
+/// reading it.
+async fn read_svg_file(path: &Path, max_svg_bytes: usize) -> Result<Vec<u8>, LoadError> {
+    let read_error = |e: std::io::Error| {
+        LoadError::new(
+            format!("Failed to read SVG from {}: {}", path.display(), e),
+            ErrorKind::Io,
+        )
+    };
+    let file = tokio::fs::File::open(path).await.map_err(read_error)?;
+    let size = file.metadata().await.map_err(read_error)?.len();
+    if size > max_svg_bytes as u64 {
+        return Err(ConvertError::TooLarge {
+            size: size as usize,
+            limit: max_svg_bytes,
+        }
+        .into());
+    }
+
+    // The file may grow after the size check
+    let mut data = Vec::with_capacity(size as usize);
+    file.take(max_svg_bytes as u64 + 1)
+        .read_to_end(&mut data)
+        .await
+        .map_err(read_error)?;
+    Ok(data)
+}
+
+/// Fails a job that never reached conversion (no output root, unreadable
+/// SVG reference, mismatched output extension) with `kind`, retrying it if
+/// the kind is retryable.
+async fn fail_before_conversion(
+    mut job: PdfExportJob,
+    queue: &mut JobQueue,
+    error: String,
+    kind: ErrorKind,
+) {
+    error!("Job failed: job_id={}, error={}", job.job_id, error);
+    job.mark_failed(error);
+    job.error_kind = Some(kind);
+    retry_or_fail(&mut job, queue).await;
+    telemetry::record_job_telemetry(&job);
+    if let Err(e) = queue.ack(&job.job_id).await {
+        error!("Failed to acknowledge job {}: {}", job.job_id, e);
+    }
+}
+
+/// Retries a failed job, or records it as permanently failed if its error
+/// isn't retryable or it has no retries left.
+async fn retry_or_fail(job: &mut PdfExportJob, queue: &mut JobQueue) {
+    // The SVG itself is bad; retrying would fail the same way
+    if job.error_kind.is_some_and(|kind| !kind.is_retryable()) {
+        warn!(
+            "Job failed permanently: job_id={}, error_kind={:?}, not retrying",
+            job.job_id, job.error_kind
+        );
+        if let Err(e) = queue.update_status(job).await {
+            error!("Failed to update job status: {}", e);
+        }
+        queue.emit_event(JobEventKind::Failed, job);
+        return;
+    }
+
+    // Attempt retry
+    match queue.retry_job(job.clone()).await {
+        Ok(true) => {
+            info!(
+                "Job re-queued for retry: job_id={}, retry_count={}",
+                job.job_id, job.retry_count
+            );
+        }
+        Ok(false) => {
+            warn!(
+                "Job failed permanently: job_id={}, max retries exceeded",
+                job.job_id
+            );
+        }
+        Err(e) => {
+            error!("Failed to retry job: {}", e);
+        }
+    }
+}
+
+/// Writes each progress value received on `progress` to the job's status.
+async fn report_progress(
+    mut queue: JobQueue,
+    job_id: String,
+    mut progress: tokio::sync::watch::Receiver<u8>,
+) {
+    while progress.changed().await.is_ok() {
+        let pct = *progress.borrow_and_update();
+        if let Err(e) = queue.update_progress(&job_id, pct).await {
+            warn!("Failed to update job progress: {}", e);
+        }
+    }
+}
+
+/// Resolves once cancellation is requested for `job_id`.
+async fn wait_for_cancellation(queue: &mut JobQueue, job_id: &str) {
+    loop {
+        tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+        if queue.is_cancelled(job_id).await.unwrap_or(false) {
+            return;
+        }
+    }
+}
+
+/// Marks a job cancelled, records telemetry and acknowledges it.
+async fn finish_cancelled(job: PdfExportJob, queue: &mut JobQueue) {
+    info!("Job cancelled: job_id={}", job.job_id);
+    finish_unstarted(job, queue, PdfExportJob::mark_cancelled).await;
+}
+
+/// Marks a job that passed its deadline while queued as expired, without
+/// converting it, and acknowledges it.
+async fn finish_expired(job: PdfExportJob, queue: &mut JobQueue) {
+    info!(
+        "Job expired before processing: job_id={}, expires_at={:?}",
+        job.job_id, job.expires_at
+    );
+    finish_unstarted(job, queue, PdfExportJob::mark_expired).await;
+}
+
+/// Ends a job that won't be (or stops being) converted: applies `mark`,
+/// saves the status, records telemetry and acknowledges it.
+async fn finish_unstarted(
+    mut job: PdfExportJob,
+    queue: &mut JobQueue,
+    mark: fn(&mut PdfExportJob),
+) {
+    mark(&mut job);
+    if let Err(e) = queue.update_status(&job).await {
+        error!("Failed to update job status: {}", e);
+    }
+    telemetry::record_job_telemetry(&job);
+    if let Err(e) = queue.ack(&job.job_id).await {
+        error!("Failed to acknowledge job {}: {}", job.job_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::ConverterOptions;
+    use crate::events::{EventSink, JobEvent};
+    use crate::job::{ArtboardSvg, JobMetadata, JobStatus};
+    use crate::queue::{KeyNamespace, QueueConfig};
+    use crate::sink::S3Config;
+    use redis::aio::ConnectionManager;
+
+    #[test]
+    fn test_drain_timeout_is_capped_by_conversion_timeout() {
+        let config = WorkerConfig::default();
+        assert_eq!(config.drain_timeout(), Duration::from_secs(30));
+
+        let config = WorkerConfig {
+            conversion_timeout: Duration::from_secs(10),
+            ..WorkerConfig::default()
+        };
+        assert_eq!(config.drain_timeout(), Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_document_limiter_caps_jobs_per_document() {
+        let semaphore = Arc::new(Semaphore::new(4));
+        let documents = DocumentLimiter::new(Some(1));
+        let queue: Arc<Mutex<Vec<&str>>> = Arc::new(Mutex::new(vec![
+            "doc-a", "doc-a", "doc-a", "doc-a", "doc-a", "doc-a", "doc-b", "doc-c", "doc-b", "doc-c",
+        ]));
+        let running: Arc<Mutex<HashMap<&str, usize>>> = Arc::default();
+        let peaks: Arc<Mutex<(usize, HashMap<&str, usize>)>> = Arc::default();
+
+        // Four loops sharing four slots, as worker_loop does, with deferred
+        // jobs going to the back of the queue
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let (semaphore, documents) = (semaphore.clone(), documents.clone());
+                let (queue, running, peaks) = (queue.clone(), running.clone(), peaks.clone());
+                tokio::spawn(async move {
+                    loop {
+                        let permit = semaphore.clone().acquire_owned().await.unwrap();
+                        let Some(document) = queue.lock().unwrap().pop() else {
+                            break;
+                        };
+                        let Some(document_permit) = documents.try_acquire(document) else {
+                            queue.lock().unwrap().insert(0, document);
+                            drop(permit);
+                            tokio::time::sleep(Duration::from_millis(5)).await;
+                            continue;
+                        };
+
+                        {
+                            let mut running = running.lock().unwrap();
+                            *running.entry(document).or_default() += 1;
+                            let mut peaks = peaks.lock().unwrap();
+                            peaks.0 = peaks.0.max(running.values().sum());
+                            let peak = peaks.1.entry(document).or_default();
+                            *peak = (*peak).max(running[document]);
+                        }
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                        *running.lock().unwrap().get_mut(document).unwrap() -= 1;
+                        drop(document_permit);
+                        drop(permit);
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.await.unwrap();
+        }
+
+        let (total_peak, document_peaks) = &*peaks.lock().unwrap();
+        assert_eq!(document_peaks.values().copied().max(), Some(1));
+        assert_eq!(document_peaks.len(), 3);
+        // Other documents ran alongside doc-a rather than waiting behind it
+        assert!(*total_peak > 1);
+        // Idle documents don't keep a semaphore around
+        assert!(documents.is_empty());
+
+        // Without a cap every job gets a slot
+        let unlimited = DocumentLimiter::new(None);
+        let permits: Vec<_> = (0..8).map(|_| unlimited.try_acquire("doc-a").unwrap()).collect();
+        assert!(unlimited.is_empty());
+        drop(permits);
+    }
+
+    #[tokio::test]
+    async fn test_concurrency_limit_adjusts_at_runtime() {
+        let limit = ConcurrencyLimit::new(1, 4);
+        let running = Arc::new(AtomicU64::new(0));
+        let peak = Arc::new(AtomicU64::new(0));
+        let run_jobs = |count: usize| {
+            let (limit, running, peak) = (limit.clone(), running.clone(), peak.clone());
+            async move {
+                peak.store(0, Ordering::SeqCst);
+                let jobs: Vec<_> = (0..count)
+                    .map(|_| {
+                        let (limit, running, peak) = (limit.clone(), running.clone(), peak.clone());
+                        tokio::spawn(async move {
+                            let _permit = limit.acquire().await;
+                            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                            peak.fetch_max(now, Ordering::SeqCst);
+                            tokio::time::sleep(Duration::from_millis(20)).await;
+                            running.fetch_sub(1, Ordering::SeqCst);
+                        })
+                    })
+                    .collect();
+                for job in jobs {
+                    job.await.unwrap();
+                }
+                peak.load(Ordering::SeqCst)
+            }
+        };
+
+        assert_eq!(run_jobs(6).await, 1);
+
+        // Raising the limit lets more jobs run at once, up to the bound
+        assert_eq!(limit.set_limit(3), 3);
+        assert_eq!(run_jobs(6).await, 3);
+        assert_eq!(limit.set_limit(10), 4);
+        assert_eq!(limit.semaphore().available_permits(), 4);
+
+        // Lowering it waits for busy slots to be given back
+        let mut busy = vec![];
+        for _ in 0..3 {
+            busy.push(limit.acquire().await);
+        }
+        assert_eq!(limit.set_limit(1), 1);
+        assert_eq!(limit.semaphore().available_permits(), 0);
+        assert_eq!(limit.permits(), 3);
+        drop(busy);
+        assert_eq!(limit.permits(), 1);
+        assert_eq!(limit.semaphore().available_permits(), 1);
+        assert_eq!(run_jobs(4).await, 1);
+
+        // Raising it again before busy slots are given back keeps them
+        assert_eq!(limit.set_limit(0), 1);
+        limit.set_limit(2);
+        let busy = (limit.acquire().await, limit.acquire().await);
+        limit.set_limit(1);
+        assert_eq!(limit.set_limit(2), 2);
+        assert_eq!(limit.permits(), 2);
+        drop(busy);
+        assert_eq!(limit.semaphore().available_permits(), 2);
+    }
+
+    fn slow_svg() -> String {
+        let mut svg = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1000" height="1000">"#);
+        for i in 0..50_000 {
+            svg.push_str(&format!(
+                r#"<g><rect x="{}" y="{}" width="5" height="5" fill="red"/></g>"#,
+                i % 1000,
+                i / 50
+            ));
+        }
+        svg.push_str("</svg>");
+        svg
+    }
+
+    #[test]
+    fn test_reconnect_backoff_escalates_then_recovers() {
+        let connection_error = || {
+            let result: redis::RedisResult<()> =
+                Err(redis::RedisError::from((redis::ErrorKind::IoError, "connection refused")));
+            result.map_err(|source| crate::queue::QueueError::Redis {
+                context: "Failed to dequeue job",
+                source,
+            })
+        };
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(5));
+
+        // Redis is down for a while...
+        let mut delays = vec![];
+        for _ in 0..6 {
+            let err = connection_error().unwrap_err();
+            assert!(err.is_connection_error());
+            delays.push(backoff.on_failure());
+        }
+        let secs: Vec<u64> = delays.iter().map(Duration::as_secs).collect();
+        assert_eq!(secs, vec![5, 10, 20, 40, 60, 60]);
+        assert_eq!(backoff.failures(), 6);
+
+        // ...then comes back
+        assert_eq!(backoff.on_success(), 6);
+        assert_eq!(backoff.failures(), 0);
+        assert_eq!(backoff.on_failure(), Duration::from_secs(5));
+    }
+
+    /// Records the fields of `process_job` spans, and the span each event
+    /// was logged in.
+    #[derive(Default, Clone)]
+    struct SpanCapture {
+        fields: Arc<Mutex<HashMap<String, String>>>,
+        event_spans: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl<S> tracing_subscriber::Layer<S> for SpanCapture
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            _id: &tracing::span::Id,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if attrs.metadata().name() == "process_job" {
+                attrs.record(&mut FieldVisitor(&mut self.fields.lock().unwrap()));
+            }
+        }
+
+        fn on_record(
+            &self,
+            _id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            values.record(&mut FieldVisitor(&mut self.fields.lock().unwrap()));
+        }
+
+        fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+            if let Some(span) = ctx.event_span(event) {
+                self.event_spans.lock().unwrap().push(span.name().to_string());
+            }
+        }
+    }
+
+    #[test]
+    fn test_job_span_carries_job_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let svg = format!("<svg>{}</svg>", "<g/>".repeat(10));
+        let mut job = PdfExportJob::new(
+            "doc-span".to_string(),
+            svg.clone(),
+            "/tmp/span.pdf".to_string(),
+            JobMetadata {
+                export_scope: "selection".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        job.retry_count = 2;
+        job.priority = Some(crate::job::PRIORITY_HIGH);
+
+        let capture = SpanCapture::default();
+        let subscriber = tracing_subscriber::registry().with(capture.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = job_span(&job);
+            span.in_scope(|| info!("Converting"));
+            span.record("svg_bytes", 4096);
+        });
+
+        let fields = capture.fields.lock().unwrap().clone();
+        assert_eq!(fields["job_id"], job.job_id);
+        assert_eq!(fields["document_id"], "doc-span");
+        assert_eq!(fields["retry_count"], "2");
+        assert_eq!(fields["priority"], "2");
+        assert_eq!(fields["export_scope"], "selection");
+        assert_eq!(fields["svg_bytes"], "4096");
+        assert!(fields.values().all(|value| !value.contains("<svg")));
+        assert_eq!(*capture.event_spans.lock().unwrap(), vec!["process_job"]);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_jobs_tracks_spawned_tasks() {
+        let in_flight = InFlightJobs::new();
+        let job = PdfExportJob::new(
+            "doc-123".to_string(),
+            "<svg></svg>".to_string(),
+            "/tmp/test.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+        in_flight.spawn(job.clone(), async move {
+            let _ = rx.await;
+        });
+        assert_eq!(in_flight.len(), 1);
+        assert!(!in_flight.is_stalled(Duration::from_secs(60)));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(in_flight.is_stalled(Duration::from_millis(10)));
+
+        tx.send(()).unwrap();
+        in_flight.tracker.close();
+        in_flight.tracker.wait().await;
+        assert!(in_flight.is_empty());
+        assert_eq!(in_flight.longest_running(), None);
+        assert!(!in_flight.is_stalled(Duration::ZERO));
+    }
+
+    // Note: These tests require a running Redis instance.
+    // Run with: docker run -d -p 6379:6379 redis:7-alpine
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_process_job_skips_expired_job() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:expired", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-expired");
+
+        let dir = tempfile::tempdir().unwrap();
+        let job = PdfExportJob::builder()
+            .document_id("doc-expired")
+            .svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#)
+            .output_path("expired.pdf")
+            .export_scope("current")
+            .expires_at(chrono::Utc::now() - chrono::Duration::seconds(1))
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        process_job(
+            dequeued,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_secs(5),
+            &OutputConfig::new(dir.path()),
+        )
+        .await;
+
+        // Skipped without rendering, and not left in the processing list
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Expired);
+        assert!(status.history.iter().all(|change| change.status != JobStatus::Processing));
+        assert!(!dir.path().join("expired.pdf").exists());
+        assert!(!queue.ack(&job.job_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_disk_full_job_is_deferred_without_retry() {
+        /// Fails every write as if the disk were full.
+        struct FullDisk;
+
+        impl crate::sink::OutputSink for FullDisk {
+            fn write(&self, _path: &str, _bytes: &[u8]) -> std::io::Result<()> {
+                Err(std::io::Error::from_raw_os_error(28)) // ENOSPC
+            }
+        }
+
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:disk-full", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-disk-full");
+
+        let dir = tempfile::tempdir().unwrap();
+        let job = PdfExportJob::builder()
+            .document_id("doc-disk-full")
+            .svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#)
+            .output_path("full.pdf")
+            .export_scope("current")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        let converter = SvgToPdfConverter::new().with_output_sink(Arc::new(FullDisk));
+        process_job(
+            dequeued,
+            &mut queue,
+            &converter,
+            Duration::from_secs(5),
+            &OutputConfig::new(dir.path()),
+        )
+        .await;
+
+        // Back on the queue for later, with its retries untouched
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Queued);
+        assert_eq!(status.retry_count, 0);
+        assert!(status.next_attempt_at.unwrap() > chrono::Utc::now());
+        assert_eq!(queue.queue_stats().await.unwrap().delayed, 1);
+        assert!(!queue.ack(&job.job_id).await.unwrap());
+        assert!(metrics::disk_full());
+        metrics::set_disk_full(false);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_process_job_skips_cancelled_job() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-cancel-before-start");
+
+        let output_path = std::env::temp_dir()
+            .join(format!("cancelled-{}.pdf", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned();
+        let job = PdfExportJob::new(
+            "doc-cancelled".to_string(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#.to_string(),
+            output_path.clone(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+
+        // Cancelled after dequeue but before processing starts
+        assert!(!queue.cancel_job(&job.job_id).await.unwrap());
+        process_job(
+            dequeued,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_secs(5),
+            &OutputConfig::new(std::env::temp_dir()),
+        )
+        .await;
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap().status;
+        assert_eq!(status, JobStatus::Cancelled);
+        assert!(!std::path::Path::new(&output_path).exists());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_cancelled_conversion_leaves_no_output() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-cancel-during-conversion");
+
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("cancelled.pdf");
+        let job = PdfExportJob::new(
+            "doc-cancelled-during-conversion".to_string(),
+            slow_svg(),
+            output_path.to_string_lossy().into_owned(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+
+        // Cancelled once the conversion is under way
+        let mut cancel_queue = queue.clone();
+        let job_id = job.job_id.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            cancel_queue.cancel_job(&job_id).await.unwrap();
+        });
+        let abandoned = process_job(
+            dequeued,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_secs(60),
+            &OutputConfig::new(dir.path()),
+        )
+        .await
+        .expect("the conversion was still running");
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap().status;
+        assert_eq!(status, JobStatus::Cancelled);
+
+        // Even once its thread is done, nothing was written
+        abandoned.finished().await;
+        assert!(!output_path.exists());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_malformed_svg_is_not_retried() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-malformed");
+
+        let job = PdfExportJob::new(
+            "doc-malformed".to_string(),
+            "<svg><rect".to_string(),
+            "/tmp/malformed.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+
+        process_job(
+            dequeued,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_secs(5),
+            &OutputConfig::new(std::env::temp_dir()),
+        )
+        .await;
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Failed);
+        assert_eq!(status.retry_count, 0);
+        assert_eq!(status.error_kind, Some(crate::job::ErrorKind::Parse));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_timed_out_conversion_leaves_no_output() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-timed-out-conversion");
+
+        let dir = tempfile::tempdir().unwrap();
+        let output_path = dir.path().join("timed-out.pdf");
+        let job = PdfExportJob::new(
+            "doc-timed-out".to_string(),
+            slow_svg(),
+            output_path.to_string_lossy().into_owned(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+
+        let abandoned = process_job(
+            dequeued,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_millis(100),
+            &OutputConfig::new(dir.path()),
+        )
+        .await
+        .expect("the conversion was still running");
+
+        // Retried as a timeout
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.retry_count, 1);
+
+        // Even once its thread is done, nothing was written
+        abandoned.finished().await;
+        assert!(!output_path.exists());
+        queue.cancel_job(&job.job_id).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_template_directories_are_created() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-template-dirs");
+
+        let job = PdfExportJob::new(
+            "doc-template".to_string(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#.to_string(),
+            "ignored.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+
+        let root = tempfile::tempdir().unwrap();
+        let template = "{document_id}/{date}/{job_id}.{ext}".parse().unwrap();
+        process_job(
+            dequeued,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_secs(5),
+            &OutputConfig::new(root.path()).with_template(template),
+        )
+        .await;
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Complete);
+        let expected = root
+            .path()
+            .canonicalize()
+            .unwrap()
+            .join("doc-template")
+            .join(job.created_at.format("%Y-%m-%d").to_string())
+            .join(format!("{}.pdf", job.job_id));
+        assert!(expected.is_file());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_shutdown_drains_or_requeues_slow_job() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-shutdown");
+
+        let job = PdfExportJob::new(
+            "doc-shutdown".to_string(),
+            slow_svg(),
+            "/tmp/shutdown.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+
+        let shutdown = CancellationToken::new();
+        let in_flight = InFlightJobs::new();
+        let worker = tokio::spawn(worker_loop(
+            0,
+            queue.clone(),
+            ConcurrencyLimit::new(1, 1),
+            Arc::new(SvgToPdfConverter::new()),
+            WorkerConfig {
+                output: OutputConfig::new(std::env::temp_dir()),
+                ..WorkerConfig::default()
+            },
+            shutdown.clone(),
+            in_flight.clone(),
+        ));
+
+        // Wait for the worker to pick the job up, then shut down.
+        while in_flight.is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.cancel();
+        worker.await.unwrap();
+        in_flight.drain(Duration::from_millis(1), &mut queue).await;
+
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap().status;
+        assert!(
+            status == JobStatus::Complete || status == JobStatus::Queued,
+            "job left in {}",
+            status
+        );
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_panicking_job_fails_and_releases_permit() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-panic");
+
+        let job = PdfExportJob::new(
+            "doc-panic".to_string(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#.to_string(),
+            "/tmp/panic.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        queue.enqueue(&job).await.unwrap();
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+
+        let semaphore = Arc::new(Semaphore::new(1));
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let in_flight = InFlightJobs::new();
+        let mut task_queue = queue.clone();
+        in_flight.spawn(dequeued.clone(), async move {
+            run_isolated(dequeued, &mut task_queue, async {
+                panic!("converter exploded");
+            })
+            .await;
+            drop(permit);
+        });
+        in_flight.tracker.close();
+        in_flight.tracker.wait().await;
+
+        assert_eq!(semaphore.available_permits(), 1);
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        // Failed with the panic message and scheduled for retry
+        assert_eq!(status.status, JobStatus::Queued);
+        assert_eq!(status.retry_count, 1);
+        assert!(status.error.unwrap().contains("converter exploded"));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_concurrency_limit_runs_jobs_sequentially() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-concurrency");
+
+        let mut job_ids = vec![];
+        for i in 0..2 {
+            let job = PdfExportJob::new(
+                format!("doc-sequential-{}", i),
+                slow_svg(),
+                std::env::temp_dir()
+                    .join(format!("sequential-{}.pdf", uuid::Uuid::new_v4()))
+                    .to_string_lossy()
+                    .into_owned(),
+                JobMetadata {
+                    export_scope: "all".to_string(),
+                    client_version: "0.1.0".to_string(),
+                    ..Default::default()
+                },
+            );
+            queue.enqueue(&job).await.unwrap();
+            job_ids.push(job.job_id);
+        }
+
+        // Two worker loops share a single slot
+        let config = WorkerConfig {
+            concurrency: 1,
+            output: OutputConfig::new(std::env::temp_dir()),
+            ..WorkerConfig::default()
+        };
+        let limit = ConcurrencyLimit::new(config.concurrency, config.concurrency);
+        let converter = Arc::new(SvgToPdfConverter::new());
+        let shutdown = CancellationToken::new();
+        let in_flight = InFlightJobs::new();
+        let workers: Vec<_> = (0..2)
+            .map(|worker_id| {
+                tokio::spawn(worker_loop(
+                    worker_id,
+                    queue.clone(),
+                    limit.clone(),
+                    converter.clone(),
+                    config.clone(),
+                    shutdown.clone(),
+                    in_flight.clone(),
+                ))
+            })
+            .collect();
+
+        let mut finished = vec![];
+        while finished.len() < job_ids.len() {
+            let mut statuses = vec![];
+            for job_id in &job_ids {
+                statuses.push(queue.get_status(job_id).await.unwrap().unwrap());
+            }
+            let running = statuses
+                .iter()
+                .filter(|job| job.status == JobStatus::Processing)
+                .count();
+            assert!(running <= 1, "{} jobs processing at once", running);
+            assert!(in_flight.len() <= 1);
+
+            finished = statuses
+                .into_iter()
+                .filter(|job| job.status == JobStatus::Complete)
+                .collect();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        shutdown.cancel();
+        for worker in workers {
+            worker.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_process_job_records_output_stats() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn).with_consumer_id("test-output-stats");
+
+        let output_path = std::env::temp_dir()
+            .join(format!("stats-{}.pdf", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned();
+        let artboards = ["red", "green"]
+            .iter()
+            .enumerate()
+            .map(|(i, fill)| ArtboardSvg {
+                artboard_id: format!("ab-{}", i),
+                svg_content: format!(
+                    r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100"><rect width="100" height="100" fill="{}"/></svg>"#,
+                    fill
+                ),
+                name: None,
+            })
+            .collect();
+        let job = PdfExportJob::new_multi_page(
+            "doc-stats".to_string(),
+            artboards,
+            output_path.clone(),
+            JobMetadata {
+                artboard_ids: vec!["ab-0".to_string(), "ab-1".to_string()],
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        let job_id = job.job_id.clone();
+
+        process_job(
+            job,
+            &mut queue,
+            &SvgToPdfConverter::new(),
+            Duration::from_secs(30),
+            &OutputConfig::new(std::env::temp_dir()),
+        )
+        .await;
+
+        let status = queue.get_status(&job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Complete);
+        assert_eq!(status.page_count, Some(2));
+        assert_eq!(
+            status.output_bytes,
+            Some(std::fs::metadata(&status.output_path).unwrap().len())
+        );
+        // Rendering is only part of the job's end-to-end time
+        let render_ms = status.render_duration_ms.unwrap();
+        assert!(render_ms >= 0);
+        assert!(render_ms <= status.processing_duration_ms().unwrap());
+        let _ = std::fs::remove_file(&status.output_path);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_svg_sources_resolve_to_same_pdf() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn);
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="120" height="80"><rect width="120" height="80" fill="teal"/></svg>"#;
+        let redis_key = format!("test:svg:{}", uuid::Uuid::new_v4());
+        redis::AsyncCommands::set::<_, _, ()>(&mut queue.conn, &redis_key, svg)
+            .await
+            .unwrap();
+        let input_root = tempfile::tempdir().unwrap();
+        let file = tempfile::NamedTempFile::new_in(input_root.path()).unwrap();
+        std::fs::write(file.path(), svg).unwrap();
+        let output = OutputConfig::new(input_root.path()).with_input_root(input_root.path());
+
+        let options = ConverterOptions::default().with_metadata(PdfMetadata {
+            creation_date: Some(chrono::DateTime::UNIX_EPOCH),
+            ..Default::default()
+        });
+        let mut pdfs = vec![];
+        for source in [
+            SvgSource::Inline(svg.to_string()),
+            SvgSource::RedisKey(redis_key.clone()),
+            SvgSource::FilePath(file.path().to_path_buf()),
+        ] {
+            let job = PdfExportJob::from_source(
+                "doc-sources".to_string(),
+                source,
+                "doc-sources.pdf".to_string(),
+                JobMetadata {
+                    export_scope: "all".to_string(),
+                    client_version: "0.1.0".to_string(),
+                    ..Default::default()
+                },
+            );
+            let pages = load_pages(&job, &mut queue, &output).await.unwrap();
+            assert_eq!(pages, vec![svg.to_string()]);
+            pdfs.push(
+                SvgToPdfConverter::new()
+                    .convert_to_bytes_with_options(&pages[0], &options)
+                    .unwrap(),
+            );
+        }
+        assert_eq!(pdfs[0], pdfs[1]);
+        assert_eq!(pdfs[0], pdfs[2]);
+
+        // A missing reference is reported rather than rendered empty
+        let missing = PdfExportJob::from_source(
+            "doc-sources".to_string(),
+            SvgSource::RedisKey(format!("{}-missing", redis_key)),
+            "doc-sources.pdf".to_string(),
+            JobMetadata {
+                export_scope: "all".to_string(),
+                client_version: "0.1.0".to_string(),
+                ..Default::default()
+            },
+        );
+        let err = load_pages(&missing, &mut queue, &output).await.unwrap_err();
+        assert!(err.message.contains("not found"));
+        assert_eq!(err.kind, ErrorKind::SourceUnavailable);
+
+        // Files outside the input root aren't read
+        for path in ["/etc/passwd", "../../etc/passwd"] {
+            let outside = PdfExportJob::builder()
+                .document_id("doc-sources")
+                .svg_source(SvgSource::FilePath(path.into()))
+                .output_path("doc-sources.pdf")
+                .build()
+                .unwrap();
+            let err = load_pages(&outside, &mut queue, &output).await.unwrap_err();
+            assert_eq!(err.kind, ErrorKind::SourceUnavailable, "{}", err);
+        }
+
+        // Nor are files over the size limit
+        let mut small = JobQueue::new(queue.conn.clone()).with_config(QueueConfig {
+            max_svg_bytes: 16,
+            ..QueueConfig::default()
+        });
+        let job = PdfExportJob::builder()
+            .document_id("doc-sources")
+            .svg_source(SvgSource::FilePath(file.path().to_path_buf()))
+            .output_path("doc-sources.pdf")
+            .build()
+            .unwrap();
+        let err = load_pages(&job, &mut small, &output).await.unwrap_err();
+        assert_eq!(err.kind, ErrorKind::TooLarge, "{}", err);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_paused_queue_is_not_consumed_until_resumed() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:pause", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-pause")
+            .with_config(QueueConfig {
+                blpop_timeout: Duration::from_millis(200),
+                ..QueueConfig::default()
+            });
+        queue.pause().await.unwrap();
+        assert!(queue.is_paused().await.unwrap());
+
+        let dir = tempfile::tempdir().unwrap();
+        let job = PdfExportJob::builder()
+            .document_id("doc-paused")
+            .svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#)
+            .output_path("paused.pdf")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+
+        let config = WorkerConfig {
+            concurrency: 1,
+            output: OutputConfig::new(dir.path()),
+            ..WorkerConfig::default()
+        };
+        let shutdown = CancellationToken::new();
+        let worker = tokio::spawn(worker_loop(
+            0,
+            queue.clone(),
+            ConcurrencyLimit::new(1, 1),
+            Arc::new(SvgToPdfConverter::new()),
+            config,
+            shutdown.clone(),
+            InFlightJobs::new(),
+        ));
+
+        // The job stays queued while paused
+        tokio::time::sleep(PAUSE_POLL_INTERVAL * 2).await;
+        assert_eq!(queue.queue_length().await.unwrap(), 1);
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Queued);
+
+        queue.resume().await.unwrap();
+        assert!(!queue.is_paused().await.unwrap());
+        while queue.get_status(&job.job_id).await.unwrap().unwrap().status != JobStatus::Complete {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(queue.queue_length().await.unwrap(), 0);
+
+        shutdown.cancel();
+        worker.await.unwrap();
+    }
+
+    /// Event sink that keeps every event it receives.
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<JobEvent>>);
+
+    impl EventSink for RecordingSink {
+        fn emit(&self, event: &JobEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_lifecycle_events_for_retried_job() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let sink = Arc::new(RecordingSink::default());
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:events", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-events")
+            .with_event_sink(sink.clone());
+
+        // The SVG file doesn't exist yet, so the first attempt fails
+        let dir = tempfile::tempdir().unwrap();
+        let svg_path = dir.path().join("late.svg");
+        let job = PdfExportJob::builder()
+            .document_id("doc-events")
+            .svg_source(SvgSource::FilePath(svg_path.clone()))
+            .output_path("events.pdf")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+        let output = OutputConfig::new(dir.path()).with_input_root(dir.path());
+        let converter = SvgToPdfConverter::new();
+
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        process_job(dequeued, &mut queue, &converter, Duration::from_secs(5), &output).await;
+
+        std::fs::write(&svg_path, r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#)
+            .unwrap();
+        // Wait out the retry backoff
+        let retried = loop {
+            if let Some(job) = queue.try_dequeue().await.unwrap() {
+                break job;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        };
+        process_job(retried, &mut queue, &converter, Duration::from_secs(5), &output).await;
+
+        let events = sink.0.lock().unwrap().clone();
+        let kinds: Vec<JobEventKind> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                JobEventKind::Enqueued,
+                JobEventKind::Retried,
+                JobEventKind::Started,
+                JobEventKind::Completed,
+            ]
+        );
+        assert!(events.iter().all(|event| event.job_id == job.job_id));
+        assert_eq!(events[1].retry_count, 1);
+        assert_eq!(events[1].error_kind, Some(ErrorKind::Io));
+        assert_eq!(events[3].error_kind, None);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn test_s3_output_needs_configured_bucket() {
+        let client = redis::Client::open("redis://127.0.0.1/").unwrap();
+        let conn = ConnectionManager::new(client).await.unwrap();
+        let mut queue = JobQueue::new(conn)
+            .with_namespace(KeyNamespace::new(format!("test-{}:s3", uuid::Uuid::new_v4())))
+            .with_consumer_id("test-s3");
+
+        let job = PdfExportJob::builder()
+            .document_id("doc-s3")
+            .svg(r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#)
+            .output_path("s3://other-bucket/doc-s3.pdf")
+            .build()
+            .unwrap();
+        queue.enqueue(&job).await.unwrap();
+        let output = OutputConfig::new(std::env::temp_dir()).with_s3(S3Config::new(
+            "http://127.0.0.1:1",
+            "exports",
+            "key",
+            "secret",
+        ));
+
+        let dequeued = queue.dequeue().await.unwrap().unwrap();
+        process_job(dequeued, &mut queue, &SvgToPdfConverter::new(), Duration::from_secs(5), &output).await;
+
+        // Refused before converting, never retried
+        let status = queue.get_status(&job.job_id).await.unwrap().unwrap();
+        assert_eq!(status.status, JobStatus::Failed);
+        assert_eq!(status.error_kind, Some(ErrorKind::OutputLocation));
+        assert_eq!(status.retry_count, 0);
+    }
+}
@@ -0,0 +1,193 @@
+//! End-to-end tests for the `worker-export` binary.
+
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_worker-export");
+
+fn fixture(name: &str) -> String {
+    format!("{}/test/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn convert_writes_pdf_without_redis() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("simple.pdf");
+
+    let status = Command::new(BIN)
+        .args(["convert", &fixture("simple.svg")])
+        .arg(&output)
+        .env("REDIS_URL", "redis://127.0.0.1:1/")
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+    let pdf = std::fs::read(&output).unwrap();
+    assert!(pdf.starts_with(b"%PDF"));
+    assert!(String::from_utf8_lossy(&pdf).contains("/MediaBox [0 0 200 100]"));
+}
+
+#[test]
+fn convert_applies_page_size_and_background() {
+    let dir = tempfile::tempdir().unwrap();
+    let plain = dir.path().join("plain.pdf");
+    let styled = dir.path().join("styled.pdf");
+
+    for (output, extra) in [
+        (&plain, &[][..]),
+        (&styled, &["--page-size", "letter", "--background", "white"][..]),
+    ] {
+        let status = Command::new(BIN)
+            .args(["convert", &fixture("simple.svg")])
+            .arg(output)
+            .args(extra)
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    let styled = std::fs::read(&styled).unwrap();
+    assert_ne!(std::fs::read(&plain).unwrap(), styled);
+    assert!(String::from_utf8_lossy(&styled).contains("/MediaBox [0 0 612 792]"));
+}
+
+#[test]
+fn convert_rejects_bad_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.pdf");
+
+    let missing = Command::new(BIN)
+        .args(["convert", "/nonexistent/input.svg"])
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(!missing.status.success());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("Failed to read"));
+
+    let bad_flag = Command::new(BIN)
+        .args(["convert", &fixture("simple.svg")])
+        .arg(&output)
+        .args(["--background", "not-a-color"])
+        .output()
+        .unwrap();
+    assert!(!bad_flag.status.success());
+    assert!(String::from_utf8_lossy(&bad_flag.stderr).contains("Invalid color"));
+    assert!(!output.exists());
+}
+
+#[test]
+fn batch_converts_every_svg_in_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_dir = dir.path().join("pdfs");
+
+    let output = Command::new(BIN)
+        .args(["batch", &fixture("batch")])
+        .arg(&output_dir)
+        .args(["--concurrency", "2"])
+        .env("REDIS_URL", "redis://127.0.0.1:1/")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let mut written: Vec<String> = std::fs::read_dir(&output_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    written.sort();
+    assert_eq!(written, ["badge.pdf", "banner.pdf", "logo.pdf"]);
+    for name in &written {
+        assert!(std::fs::read(output_dir.join(name)).unwrap().starts_with(b"%PDF"));
+    }
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Converted 3 of 3 files (0 failed)"));
+}
+
+#[test]
+fn batch_reports_failures_and_converts_the_rest() {
+    let input_dir = tempfile::tempdir().unwrap();
+    let output_dir = tempfile::tempdir().unwrap();
+    std::fs::copy(fixture("simple.svg"), input_dir.path().join("good.svg")).unwrap();
+    std::fs::write(input_dir.path().join("broken.svg"), "<svg").unwrap();
+
+    let output = Command::new(BIN)
+        .arg("batch")
+        .args([input_dir.path(), output_dir.path()])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("broken.svg"));
+    assert!(stdout.contains("Converted 1 of 2 files (1 failed)"));
+    assert!(output_dir.path().join("good.pdf").exists());
+    assert!(!output_dir.path().join("broken.pdf").exists());
+}
+
+#[test]
+fn batch_refuses_inputs_writing_the_same_pdf() {
+    let input_dir = tempfile::tempdir().unwrap();
+    let output_dir = tempfile::tempdir().unwrap();
+    std::fs::copy(fixture("simple.svg"), input_dir.path().join("logo.svg")).unwrap();
+    std::fs::copy(fixture("batch/logo.svgz"), input_dir.path().join("logo.svgz")).unwrap();
+    std::fs::copy(fixture("simple.svg"), input_dir.path().join("other.svg")).unwrap();
+
+    let output = Command::new(BIN)
+        .arg("batch")
+        .args([input_dir.path(), output_dir.path()])
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("would also write"), "{}", stdout);
+    assert!(stdout.contains("Converted 1 of 3 files (2 failed)"), "{}", stdout);
+    assert!(!output_dir.path().join("logo.pdf").exists());
+    assert!(output_dir.path().join("other.pdf").exists());
+}
+
+// Requires a running Redis instance:
+// docker run -d -p 6379:6379 redis:7-alpine
+#[cfg(unix)]
+#[test]
+#[ignore]
+fn worker_drains_and_exits_on_sigterm() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let output_root = tempfile::tempdir().unwrap();
+    let mut worker = Command::new(BIN)
+        .arg("worker")
+        .env("RUST_LOG", "info")
+        .env("OUTPUT_ROOT", output_root.path())
+        .env("METRICS_PORT", "0")
+        .env("OTEL_EXPORTER_OTLP_ENDPOINT", "http://127.0.0.1:1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut lines = BufReader::new(worker.stdout.take().unwrap()).lines();
+    lines
+        .by_ref()
+        .map(Result::unwrap)
+        .find(|line| line.contains("Worker service ready"))
+        .expect("worker exited before becoming ready");
+
+    let status = Command::new("kill")
+        .args(["-TERM", &worker.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let started = Instant::now();
+    let exit = loop {
+        if let Some(exit) = worker.try_wait().unwrap() {
+            break exit;
+        }
+        assert!(started.elapsed() < Duration::from_secs(30), "worker ignored SIGTERM");
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert!(exit.success());
+
+    let log: Vec<String> = lines.map(Result::unwrap).collect();
+    assert!(log.iter().any(|line| line.contains("Received SIGTERM")));
+    assert!(log.iter().any(|line| line.contains("Worker service shutdown complete")));
+}
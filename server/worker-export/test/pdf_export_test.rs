@@ -1,18 +1,18 @@
-/// Integration tests for PDF export worker.
-///
-/// These tests verify the complete export pipeline from job enqueue
-/// through SVG conversion to PDF output.
-///
-/// ## Running Tests
-///
-/// ```bash
-/// # Unit tests (no external dependencies)
-/// cargo test --lib
-///
-/// # Integration tests (requires Redis)
-/// docker run -d -p 6379:6379 redis:7-alpine
-/// cargo test --test pdf_export_test
-/// ```
+//! Integration tests for PDF export worker.
+//!
+//! These tests verify the complete export pipeline from job enqueue
+//! through SVG conversion to PDF output.
+//!
+//! ## Running Tests
+//!
+//! ```bash
+//! # Unit tests (no external dependencies)
+//! cargo test --lib
+//!
+//! # Integration tests (requires Redis)
+//! docker run -d -p 6379:6379 redis:7-alpine
+//! cargo test --test pdf_export_test
+//! ```
 
 #[cfg(test)]
 mod tests {
@@ -68,7 +68,7 @@ mod tests {
                 artboard_ids: vec!["ab-1".to_string()],
                 export_scope: "current".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
@@ -85,10 +85,9 @@ mod tests {
             "<svg></svg>".to_string(),
             "/tmp/test.pdf".to_string(),
             JobMetadata {
-                artboard_ids: vec![],
                 export_scope: "all".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
@@ -97,7 +96,7 @@ mod tests {
         assert_eq!(job.status, worker_export::job::JobStatus::Processing);
 
         // Processing → Complete
-        job.mark_complete();
+        job.mark_complete(1024, 1, None);
         assert_eq!(job.status, worker_export::job::JobStatus::Complete);
         assert!(job.processing_duration_ms().is_some());
     }
@@ -110,10 +109,9 @@ mod tests {
             "<svg></svg>".to_string(),
             "/tmp/test.pdf".to_string(),
             JobMetadata {
-                artboard_ids: vec![],
                 export_scope: "all".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
@@ -148,7 +146,7 @@ mod tests {
                 artboard_ids: vec!["ab-1".to_string()],
                 export_scope: "current".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 
@@ -179,10 +177,9 @@ mod tests {
             "<svg></svg>".to_string(),
             "/tmp/status.pdf".to_string(),
             JobMetadata {
-                artboard_ids: vec![],
                 export_scope: "all".to_string(),
                 client_version: "0.1.0".to_string(),
-                user_id: None,
+                ..Default::default()
             },
         );
 